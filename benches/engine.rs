@@ -0,0 +1,82 @@
+#![feature(test)]
+
+extern crate test;
+
+use std::fs;
+
+use paste::paste;
+use test::Bencher;
+use wasm::execution::config::{Config, Engine};
+use wasm::spec::models::{Action, AssertReturn, CommandType, Const, WabtJson};
+use wasm::spec::runner::Runner;
+
+/// 对比两种解释器跑同一个规范测试文件的耗时，测试文件由 tests/walk.py 生成
+macro_rules! bench {
+    ($($name:ident),* $(,)?) => {
+        paste! {
+            $(
+                #[bench]
+                fn [<$name _stack>](b: &mut Bencher) {
+                    run_bench(b, stringify!($name), Engine::Stack);
+                }
+
+                #[bench]
+                fn [<$name _register>](b: &mut Bencher) {
+                    run_bench(b, stringify!($name), Engine::Register);
+                }
+            )*
+        }
+    };
+}
+
+/// 实例化等其他命令只在计时前执行一次，计时的只有 assert_return 中的调用
+fn run_bench(b: &mut Bencher, name: &str, engine: Engine) {
+    let root = format!("./tests/output/{name}");
+    let file = format!("{root}/{name}.json");
+
+    // 没有生成测试文件时跳过
+    let Ok(json) = fs::read_to_string(&file) else {
+        eprintln!(
+            "找不到 {}，跳过 {}，先运行 tests/walk.py 生成测试文件",
+            file, name
+        );
+        return;
+    };
+    let wabt_json: WabtJson = serde_json::from_str(&json).expect("json 读取失败");
+    let config = Config {
+        engine,
+        ..Config::default()
+    };
+    let mut runner = Runner::with_config(&root, config);
+    let mut calls = vec![];
+
+    for command in &wabt_json.commands {
+        match &command.type_ {
+            CommandType::AssertReturn(AssertReturn {
+                action: Action::Invoke(action),
+                ..
+            }) => {
+                let instance = runner.instance(&action.module).expect("找不到模块");
+                let inst = runner.vm_mut().store.get(instance);
+                let args = Const::as_values(&action.args, Some(inst)).expect("参数转换失败");
+
+                calls.push((instance, action.field.clone(), args));
+            }
+            _ => {
+                runner.run_command(command);
+            }
+        }
+    }
+
+    assert!(!calls.is_empty(), "{} 中没有可以计时的调用", file);
+
+    let vm = runner.vm_mut();
+
+    b.iter(|| {
+        for (instance, field, args) in &calls {
+            let _ = vm.call_export(*instance, field, args.clone());
+        }
+    });
+}
+
+bench!(fac, float_exprs, simd_f32x4_arith, simd_i32x4_arith, simd_lane);
@@ -1,10 +1,12 @@
-use super::errors::DecodeErr;
-use super::instruction::{Block, BlockType, BrTableArg, IfBlock, Instruction, MemoryArg};
+use super::errors::{DecodeErr, DecodeError};
+use super::instruction::{
+    Block, BlockType, BrTableArg, Catch, IfBlock, Instruction, MemoryArg, TryTable, MEM_IDX_FLAG,
+};
 use super::reader::{DecodeResult, Reader};
 use super::section::{
     CodeSeg, CustomSeg, DataMode, DataSeg, ElementMode, ElementSeg, ExportDesc, ExportSeg, Expr,
     FuncIdx, GlobalIdx, GlobalSeg, ImportDesc, ImportSeg, LabelIdx, Locals, MaybeU32, MemIdx, TableIdx,
-    TypeIdx,
+    TagIdx, TagType, TypeIdx,
 };
 use super::types::{FuncType, GlobalType, Limits, MemType, Mut, RefType, TableType, ValType};
 
@@ -15,9 +17,8 @@ pub trait Decode<T = Self> {
 
     fn decodes(reader: &mut Reader) -> DecodeResult<Vec<Self::Output>> {
         let total = reader.get_leb_u32()?;
-        let items = (0..total).map(|_| Self::decode(reader)).collect();
 
-        items
+        (0..total).map(|_| Self::decode(reader)).collect()
     }
 }
 
@@ -50,7 +51,7 @@ impl Decode for FuncType {
 
 impl Decode for ValType {
     fn decode(reader: &mut Reader) -> DecodeResult<ValType> {
-        Ok(ValType::from(reader.get_u8()?))
+        Ok(ValType::try_from(reader.get_u8()?)?)
     }
 }
 
@@ -64,6 +65,7 @@ impl Decode for ImportSeg {
             0x01 => ImportDesc::Table(TableType::decode(reader)?),
             0x02 => ImportDesc::Mem(MemType::decode(reader)?),
             0x03 => ImportDesc::Global(GlobalType::decode(reader)?),
+            0x04 => ImportDesc::Tag(TagType::decode(reader)?),
             kind => Err(DecodeErr::InvalidImportKind(kind))?,
         };
 
@@ -80,17 +82,37 @@ impl Decode for TypeIdx {
     }
 }
 
+/// 属性目前只有 0x00，表示异常
+impl Decode for TagType {
+    fn decode(reader: &mut Reader) -> DecodeResult<TagType> {
+        match reader.get_u8()? {
+            0x00 => (),
+            attribute => Err(DecodeErr::InvalidTagAttribute(attribute))?,
+        }
+
+        let tag = TagType {
+            type_idx: TypeIdx::decode(reader)?,
+        };
+
+        Ok(tag)
+    }
+}
+
 impl Decode for TableType {
     fn decode(reader: &mut Reader) -> DecodeResult<TableType> {
         let elem_type = match reader.get_u8()? {
             0x70 => RefType::FuncRef,
             0x6f => RefType::ExternRef,
+            0x69 => RefType::ExnRef,
             elem_type => Err(DecodeErr::InvalidTableElemType(elem_type))?,
         };
-        let table = TableType {
-            elem_type,
-            limits: Limits::decode(reader)?,
-        };
+        let limits = Limits::decode(reader)?;
+
+        if limits.shared {
+            Err(DecodeErr::SharedTable)?
+        }
+
+        let table = TableType { elem_type, limits };
 
         Ok(table)
     }
@@ -98,22 +120,30 @@ impl Decode for TableType {
 
 impl Decode for Limits {
     fn decode(reader: &mut Reader) -> DecodeResult<Limits> {
-        // 0，只指定 min，1 指定 min + max
-        let with_max = match reader.get_u8()? {
-            0 => false,
-            1 => true,
-            val => Err(DecodeErr::InvalidLimitMode(val))?,
-        };
+        // 第 0 位表示指定了 max，第 1 位表示共享，第 2 位表示 64 位地址
+        let flag = reader.get_u8()?;
 
-        let limits = Limits {
-            min: reader.get_leb_u32()?,
-            max: match with_max {
-                true => Some(reader.get_leb_u32()?),
-                false => None,
-            },
+        if flag & !0b111 != 0 {
+            Err(DecodeErr::InvalidLimitMode(flag))?
+        }
+
+        let is64 = flag & 0b100 != 0;
+        let mut size = || match is64 {
+            true => reader.get_leb_u64(),
+            false => reader.get_leb_u32().map(u64::from),
+        };
+        let min = size()?;
+        let max = match flag & 0b1 {
+            0 => None,
+            _ => Some(size()?),
         };
 
-        Ok(limits)
+        Ok(Limits {
+            min,
+            max,
+            is64,
+            shared: flag & 0b10 != 0,
+        })
     }
 }
 
@@ -153,6 +183,7 @@ impl Decode for ExportSeg {
             0x01 => ExportDesc::Table(TableIdx::decode(reader)?),
             0x02 => ExportDesc::Mem(MemIdx::decode(reader)?),
             0x03 => ExportDesc::Global(GlobalIdx::decode(reader)?),
+            0x04 => ExportDesc::Tag(TagIdx::decode(reader)?),
             kind => Err(DecodeErr::InvalidExportKind(kind))?,
         };
         let export = ExportSeg { name, desc };
@@ -190,7 +221,7 @@ impl Decode for ElementSeg {
         };
         let type_ = match flag {
             0..=4 => ValType::FuncRef,
-            _ => ValType::from(reader.get_u8()?),
+            _ => ValType::try_from(reader.get_u8()?)?,
         };
 
         if !type_.is_ref_type() {
@@ -226,17 +257,23 @@ impl Decode for ElementSeg {
 impl Decode for CodeSeg {
     fn decode(reader: &mut Reader) -> DecodeResult<CodeSeg> {
         let size = reader.get_leb_u32()?;
-        let body_bytes = reader.bytes(size as usize)?;
-        let mut body_reader = Reader::new(&body_bytes, reader.data_count);
+        let mut body_reader = reader.sub(size as usize)?;
+        let offset = body_reader.offset();
+        // 函数体用单独的 Reader，出错时在这里记下位置
+        let decoded = Locals::decodes(&mut body_reader)
+            .and_then(|locals| Ok((locals, Vec::<Expr>::decode(&mut body_reader)?)));
+        let (locals, body) = decoded.map_err(|err| DecodeError::at(err, body_reader.last()))?;
 
         let code = CodeSeg {
             size,
-            locals: Locals::decodes(&mut body_reader)?,
-            body: Vec::<Expr>::decode(&mut body_reader)?,
+            locals,
+            body,
+            offset,
+            instr_offsets: body_reader.instr_offsets,
         };
 
         if code.local_size() >= 0x1000_0000 {
-            Err(DecodeErr::LocalsTooLarge)?
+            Err(DecodeError::at(DecodeErr::LocalsTooLarge.into(), offset))?
         }
 
         Ok(code)
@@ -276,6 +313,8 @@ impl Decode for Expr {
         let data_count = reader.data_count;
 
         while reader.not_end()? {
+            reader.instr_offsets.push(reader.offset());
+
             let instr = Instruction::decode(reader)?;
 
             match instr {
@@ -284,12 +323,6 @@ impl Decode for Expr {
 
                     break;
                 }
-                Instruction::MemoryGrow(idx) if idx != 0 => {
-                    Err(DecodeErr::NotZero("MemoryGrow".to_string(), idx))?
-                }
-                Instruction::MemorySize(idx) if idx != 0 => {
-                    Err(DecodeErr::NotZero("MemorySize".to_string(), idx))?
-                }
                 Instruction::MemoryInit(_, _) if data_count.is_none() => {
                     Err(DecodeErr::LossDataCount("MemoryInit".to_string()))?
                 }
@@ -347,6 +380,8 @@ impl Decode for Instruction {
             0x03 => Instruction::Loop(Block::decode(reader)?),
             0x04 => Instruction::If(IfBlock::decode(reader)?),
             0x05 => Instruction::Else,
+            0x08 => Instruction::Throw(reader.get_leb_u32()?),
+            0x0a => Instruction::ThrowRef,
             0x0b => Instruction::End,
             0x0c => Instruction::Br(reader.get_leb_u32()?),
             0x0d => Instruction::BrIf(reader.get_leb_u32()?),
@@ -354,9 +389,12 @@ impl Decode for Instruction {
             0x0f => Instruction::Return,
             0x10 => Instruction::Call(reader.get_leb_u32()?),
             0x11 => Instruction::CallIndirect(reader.get_leb_u32()?, reader.get_leb_u32()?),
+            0x12 => Instruction::ReturnCall(reader.get_leb_u32()?),
+            0x13 => Instruction::ReturnCallIndirect(reader.get_leb_u32()?, reader.get_leb_u32()?),
             0x1a => Instruction::Drop,
             0x1b => Instruction::Select,
             0x1c => Instruction::Select2(reader.get_u8()?, ValType::decode(reader)?),
+            0x1f => Instruction::TryTable(TryTable::decode(reader)?),
             0x20 => Instruction::LocalGet(reader.get_leb_u32()?),
             0x21 => Instruction::LocalSet(reader.get_leb_u32()?),
             0x22 => Instruction::LocalTee(reader.get_leb_u32()?),
@@ -387,8 +425,8 @@ impl Decode for Instruction {
             0x3c => Instruction::I64Store8(MemoryArg::decode(reader)?),
             0x3d => Instruction::I64Store16(MemoryArg::decode(reader)?),
             0x3e => Instruction::I64Store32(MemoryArg::decode(reader)?),
-            0x3f => Instruction::MemorySize(reader.get_u8()?),
-            0x40 => Instruction::MemoryGrow(reader.get_u8()?),
+            0x3f => Instruction::MemorySize(reader.get_leb_u32()?),
+            0x40 => Instruction::MemoryGrow(reader.get_leb_u32()?),
             0x41 => Instruction::I32Const(reader.get_leb_i32()?),
             0x42 => Instruction::I64Const(reader.get_leb_i64()?),
             0x43 => Instruction::F32Const(reader.get_f32()?),
@@ -784,6 +822,79 @@ impl Decode for Instruction {
                 0xff => Instruction::F64x2ConvertLowI32x4U(reader.get_u8()?),
                 opcode => Err(DecodeErr::UnknownOpcode(0xfd, opcode))?,
             },
+            0xfe => match reader.get_leb_u32()? {
+                0x00 => Instruction::MemoryAtomicNotify(MemoryArg::decode(reader)?),
+                0x01 => Instruction::MemoryAtomicWait32(MemoryArg::decode(reader)?),
+                0x02 => Instruction::MemoryAtomicWait64(MemoryArg::decode(reader)?),
+                0x03 => match reader.get_u8()? {
+                    0x00 => Instruction::AtomicFence,
+                    flag => Err(DecodeErr::InvalidFenceFlag(flag))?,
+                },
+                0x10 => Instruction::I32AtomicLoad(MemoryArg::decode(reader)?),
+                0x11 => Instruction::I64AtomicLoad(MemoryArg::decode(reader)?),
+                0x12 => Instruction::I32AtomicLoad8U(MemoryArg::decode(reader)?),
+                0x13 => Instruction::I32AtomicLoad16U(MemoryArg::decode(reader)?),
+                0x14 => Instruction::I64AtomicLoad8U(MemoryArg::decode(reader)?),
+                0x15 => Instruction::I64AtomicLoad16U(MemoryArg::decode(reader)?),
+                0x16 => Instruction::I64AtomicLoad32U(MemoryArg::decode(reader)?),
+                0x17 => Instruction::I32AtomicStore(MemoryArg::decode(reader)?),
+                0x18 => Instruction::I64AtomicStore(MemoryArg::decode(reader)?),
+                0x19 => Instruction::I32AtomicStore8(MemoryArg::decode(reader)?),
+                0x1a => Instruction::I32AtomicStore16(MemoryArg::decode(reader)?),
+                0x1b => Instruction::I64AtomicStore8(MemoryArg::decode(reader)?),
+                0x1c => Instruction::I64AtomicStore16(MemoryArg::decode(reader)?),
+                0x1d => Instruction::I64AtomicStore32(MemoryArg::decode(reader)?),
+                0x1e => Instruction::I32AtomicRmwAdd(MemoryArg::decode(reader)?),
+                0x1f => Instruction::I64AtomicRmwAdd(MemoryArg::decode(reader)?),
+                0x20 => Instruction::I32AtomicRmw8AddU(MemoryArg::decode(reader)?),
+                0x21 => Instruction::I32AtomicRmw16AddU(MemoryArg::decode(reader)?),
+                0x22 => Instruction::I64AtomicRmw8AddU(MemoryArg::decode(reader)?),
+                0x23 => Instruction::I64AtomicRmw16AddU(MemoryArg::decode(reader)?),
+                0x24 => Instruction::I64AtomicRmw32AddU(MemoryArg::decode(reader)?),
+                0x25 => Instruction::I32AtomicRmwSub(MemoryArg::decode(reader)?),
+                0x26 => Instruction::I64AtomicRmwSub(MemoryArg::decode(reader)?),
+                0x27 => Instruction::I32AtomicRmw8SubU(MemoryArg::decode(reader)?),
+                0x28 => Instruction::I32AtomicRmw16SubU(MemoryArg::decode(reader)?),
+                0x29 => Instruction::I64AtomicRmw8SubU(MemoryArg::decode(reader)?),
+                0x2a => Instruction::I64AtomicRmw16SubU(MemoryArg::decode(reader)?),
+                0x2b => Instruction::I64AtomicRmw32SubU(MemoryArg::decode(reader)?),
+                0x2c => Instruction::I32AtomicRmwAnd(MemoryArg::decode(reader)?),
+                0x2d => Instruction::I64AtomicRmwAnd(MemoryArg::decode(reader)?),
+                0x2e => Instruction::I32AtomicRmw8AndU(MemoryArg::decode(reader)?),
+                0x2f => Instruction::I32AtomicRmw16AndU(MemoryArg::decode(reader)?),
+                0x30 => Instruction::I64AtomicRmw8AndU(MemoryArg::decode(reader)?),
+                0x31 => Instruction::I64AtomicRmw16AndU(MemoryArg::decode(reader)?),
+                0x32 => Instruction::I64AtomicRmw32AndU(MemoryArg::decode(reader)?),
+                0x33 => Instruction::I32AtomicRmwOr(MemoryArg::decode(reader)?),
+                0x34 => Instruction::I64AtomicRmwOr(MemoryArg::decode(reader)?),
+                0x35 => Instruction::I32AtomicRmw8OrU(MemoryArg::decode(reader)?),
+                0x36 => Instruction::I32AtomicRmw16OrU(MemoryArg::decode(reader)?),
+                0x37 => Instruction::I64AtomicRmw8OrU(MemoryArg::decode(reader)?),
+                0x38 => Instruction::I64AtomicRmw16OrU(MemoryArg::decode(reader)?),
+                0x39 => Instruction::I64AtomicRmw32OrU(MemoryArg::decode(reader)?),
+                0x3a => Instruction::I32AtomicRmwXor(MemoryArg::decode(reader)?),
+                0x3b => Instruction::I64AtomicRmwXor(MemoryArg::decode(reader)?),
+                0x3c => Instruction::I32AtomicRmw8XorU(MemoryArg::decode(reader)?),
+                0x3d => Instruction::I32AtomicRmw16XorU(MemoryArg::decode(reader)?),
+                0x3e => Instruction::I64AtomicRmw8XorU(MemoryArg::decode(reader)?),
+                0x3f => Instruction::I64AtomicRmw16XorU(MemoryArg::decode(reader)?),
+                0x40 => Instruction::I64AtomicRmw32XorU(MemoryArg::decode(reader)?),
+                0x41 => Instruction::I32AtomicRmwXchg(MemoryArg::decode(reader)?),
+                0x42 => Instruction::I64AtomicRmwXchg(MemoryArg::decode(reader)?),
+                0x43 => Instruction::I32AtomicRmw8XchgU(MemoryArg::decode(reader)?),
+                0x44 => Instruction::I32AtomicRmw16XchgU(MemoryArg::decode(reader)?),
+                0x45 => Instruction::I64AtomicRmw8XchgU(MemoryArg::decode(reader)?),
+                0x46 => Instruction::I64AtomicRmw16XchgU(MemoryArg::decode(reader)?),
+                0x47 => Instruction::I64AtomicRmw32XchgU(MemoryArg::decode(reader)?),
+                0x48 => Instruction::I32AtomicRmwCmpxchg(MemoryArg::decode(reader)?),
+                0x49 => Instruction::I64AtomicRmwCmpxchg(MemoryArg::decode(reader)?),
+                0x4a => Instruction::I32AtomicRmw8CmpxchgU(MemoryArg::decode(reader)?),
+                0x4b => Instruction::I32AtomicRmw16CmpxchgU(MemoryArg::decode(reader)?),
+                0x4c => Instruction::I64AtomicRmw8CmpxchgU(MemoryArg::decode(reader)?),
+                0x4d => Instruction::I64AtomicRmw16CmpxchgU(MemoryArg::decode(reader)?),
+                0x4e => Instruction::I64AtomicRmw32CmpxchgU(MemoryArg::decode(reader)?),
+                opcode => Err(DecodeErr::UnknownOpcode(0xfe, opcode as u8))?,
+            },
             prefix => Err(DecodeErr::UnknownOpcodePrefix(prefix))?,
         };
 
@@ -793,9 +904,15 @@ impl Decode for Instruction {
 
 impl Decode for MemoryArg {
     fn decode(reader: &mut Reader) -> DecodeResult<MemoryArg> {
+        let flag = reader.get_leb_u32()?;
+        let mem_idx = match flag & MEM_IDX_FLAG {
+            0 => 0,
+            _ => reader.get_leb_u32()?,
+        };
         let mem_arg = MemoryArg {
-            align: reader.get_leb_u32()?,
-            offset: reader.get_leb_u32()?,
+            align: flag & !MEM_IDX_FLAG,
+            offset: reader.get_leb_u64()?,
+            mem_idx,
         };
 
         Ok(mem_arg)
@@ -814,6 +931,33 @@ impl Decode for Block {
     }
 }
 
+impl Decode for TryTable {
+    fn decode(reader: &mut Reader) -> DecodeResult<TryTable> {
+        let type_ = BlockType::decode(reader)?;
+        let catches = Catch::decodes(reader)?;
+        let (expr, last_instr) = Expr::decode(reader)?;
+
+        match last_instr {
+            Instruction::End => Ok(TryTable { type_, catches, expr }),
+            _ => Err(DecodeErr::InvalidBlock)?,
+        }
+    }
+}
+
+impl Decode for Catch {
+    fn decode(reader: &mut Reader) -> DecodeResult<Catch> {
+        let catch = match reader.get_u8()? {
+            0x00 => Catch::Tag(reader.get_leb_u32()?, reader.get_leb_u32()?),
+            0x01 => Catch::TagRef(reader.get_leb_u32()?, reader.get_leb_u32()?),
+            0x02 => Catch::All(reader.get_leb_u32()?),
+            0x03 => Catch::AllRef(reader.get_leb_u32()?),
+            kind => Err(DecodeErr::InvalidCatchKind(kind))?,
+        };
+
+        Ok(catch)
+    }
+}
+
 impl Decode for IfBlock {
     fn decode(reader: &mut Reader) -> DecodeResult<IfBlock> {
         let block_type = BlockType::decode(reader)?;
@@ -832,6 +976,14 @@ impl Decode for IfBlock {
                 Instruction::End => if_block.else_expr = else_expr,
                 _ => Err(DecodeErr::InvalidElseBlock)?,
             }
+
+            // 空的 else 分支不会保留下来，去掉它的位置，保证和指令一一对应
+            if if_block.else_expr.is_empty() {
+                let end = reader.instr_offsets.pop();
+
+                reader.instr_offsets.pop();
+                reader.instr_offsets.extend(end);
+            }
         }
 
         Ok(if_block)
@@ -840,7 +992,7 @@ impl Decode for IfBlock {
 
 impl Decode for BlockType {
     fn decode(reader: &mut Reader) -> DecodeResult<BlockType> {
-        Ok(BlockType::from(reader.get_leb_i32()?))
+        Ok(BlockType::try_from(reader.get_leb_i32()?)?)
     }
 }
 
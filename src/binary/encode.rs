@@ -1,8 +1,10 @@
-use super::instruction::{Block, BlockType, BrTableArg, IfBlock, Instruction, MemoryArg};
+use super::instruction::{
+    Block, BlockType, BrTableArg, Catch, IfBlock, Instruction, MemoryArg, TryTable, MEM_IDX_FLAG,
+};
 use super::leb128::{encode_name, encode_signed, encode_u32, encode_unsigned, encode_usize};
 use super::section::{
     CodeSeg, CustomSeg, DataMode, DataSeg, ElementMode, ElementSeg, ExportDesc, ExportSeg, Expr,
-    GlobalSeg, ImportDesc, ImportSeg, Locals, MaybeU32, Section, TypeIdx,
+    GlobalSeg, ImportDesc, ImportSeg, Locals, MaybeU32, Section, TagType, TypeIdx,
 };
 use super::types::{FuncType, GlobalType, Limits, RefType, TableType, ValType};
 
@@ -76,6 +78,7 @@ impl Encode for ImportSeg {
             ImportDesc::Table(type_) => [vec![0x01], type_.encode()].concat(),
             ImportDesc::Mem(type_) => [vec![0x02], type_.encode()].concat(),
             ImportDesc::Global(type_) => [vec![0x03], type_.encode()].concat(),
+            ImportDesc::Tag(type_) => [vec![0x04], type_.encode()].concat(),
         };
 
         result.extend(encode_name(&self.module));
@@ -92,6 +95,12 @@ impl Encode for TypeIdx {
     }
 }
 
+impl Encode for TagType {
+    fn encode(&self) -> Vec<u8> {
+        [vec![0x00], self.type_idx.encode()].concat()
+    }
+}
+
 impl Encode for TableType {
     fn encode(&self) -> Vec<u8> {
         let mut result = vec![];
@@ -99,6 +108,7 @@ impl Encode for TableType {
         let elem_type = match self.elem_type {
             RefType::FuncRef => 0x70,
             RefType::ExternRef => 0x6f,
+            RefType::ExnRef => 0x69,
         };
 
         result.push(elem_type);
@@ -113,15 +123,23 @@ impl Encode for Limits {
         let mut result = vec![];
 
         let with_max = match self.max {
-            Some(_) => 1,
+            Some(_) => 0b1,
             None => 0,
         };
+        let shared = match self.shared {
+            true => 0b10,
+            false => 0,
+        };
+        let is64 = match self.is64 {
+            true => 0b100,
+            false => 0,
+        };
 
-        result.extend(encode_u32(with_max));
-        result.extend(encode_u32(self.min));
+        result.push(with_max | shared | is64);
+        result.extend(encode_unsigned(self.min));
 
         if let Some(max) = self.max {
-            result.extend(encode_u32(max));
+            result.extend(encode_unsigned(max));
         }
 
         result
@@ -159,6 +177,7 @@ impl Encode for ExportSeg {
             ExportDesc::Table(idx) => [vec![0x01], idx.encode()].concat(),
             ExportDesc::Mem(idx) => [vec![0x02], idx.encode()].concat(),
             ExportDesc::Global(idx) => [vec![0x03], idx.encode()].concat(),
+            ExportDesc::Tag(idx) => [vec![0x04], idx.encode()].concat(),
         };
 
         result.extend(encode_name(&self.name));
@@ -314,7 +333,11 @@ impl Instruction {
             Instruction::BrTable(arg) => arg.encode(),
             Instruction::Call(data) => encode_u32(*data),
             Instruction::CallIndirect(idx1, idx2) => [idx1.encode(), idx2.encode()].concat(),
+            Instruction::ReturnCall(data) => encode_u32(*data),
+            Instruction::ReturnCallIndirect(idx1, idx2) => [idx1.encode(), idx2.encode()].concat(),
             Instruction::Select2(data, type_) => [vec![*data], type_.encode()].concat(),
+            Instruction::Throw(data) => encode_u32(*data),
+            Instruction::TryTable(block) => block.encode(),
             Instruction::LocalGet(data) => encode_u32(*data),
             Instruction::LocalSet(data) => encode_u32(*data),
             Instruction::LocalTee(data) => encode_u32(*data),
@@ -345,8 +368,8 @@ impl Instruction {
             Instruction::I64Store8(memarg) => memarg.encode(),
             Instruction::I64Store16(memarg) => memarg.encode(),
             Instruction::I64Store32(memarg) => memarg.encode(),
-            Instruction::MemorySize(data) => vec![*data],
-            Instruction::MemoryGrow(data) => vec![*data],
+            Instruction::MemorySize(data) => encode_u32(*data),
+            Instruction::MemoryGrow(data) => encode_u32(*data),
             Instruction::I32Const(data) => encode_signed(*data as i64),
             Instruction::I64Const(data) => encode_signed(*data),
             Instruction::F32Const(data) => data.to_le_bytes().to_vec(),
@@ -509,6 +532,74 @@ impl Instruction {
             Instruction::I32x4TruncSatF64x2UZero(data) => vec![*data],
             Instruction::F64x2ConvertLowI32x4S(data) => vec![*data],
             Instruction::F64x2ConvertLowI32x4U(data) => vec![*data],
+            Instruction::MemoryAtomicNotify(memarg) => memarg.encode(),
+            Instruction::MemoryAtomicWait32(memarg) => memarg.encode(),
+            Instruction::MemoryAtomicWait64(memarg) => memarg.encode(),
+            // 保留字节
+            Instruction::AtomicFence => vec![0x00],
+            Instruction::I32AtomicLoad(memarg) => memarg.encode(),
+            Instruction::I64AtomicLoad(memarg) => memarg.encode(),
+            Instruction::I32AtomicLoad8U(memarg) => memarg.encode(),
+            Instruction::I32AtomicLoad16U(memarg) => memarg.encode(),
+            Instruction::I64AtomicLoad8U(memarg) => memarg.encode(),
+            Instruction::I64AtomicLoad16U(memarg) => memarg.encode(),
+            Instruction::I64AtomicLoad32U(memarg) => memarg.encode(),
+            Instruction::I32AtomicStore(memarg) => memarg.encode(),
+            Instruction::I64AtomicStore(memarg) => memarg.encode(),
+            Instruction::I32AtomicStore8(memarg) => memarg.encode(),
+            Instruction::I32AtomicStore16(memarg) => memarg.encode(),
+            Instruction::I64AtomicStore8(memarg) => memarg.encode(),
+            Instruction::I64AtomicStore16(memarg) => memarg.encode(),
+            Instruction::I64AtomicStore32(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmwAdd(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmwAdd(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmw8AddU(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmw16AddU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw8AddU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw16AddU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw32AddU(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmwSub(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmwSub(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmw8SubU(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmw16SubU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw8SubU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw16SubU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw32SubU(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmwAnd(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmwAnd(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmw8AndU(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmw16AndU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw8AndU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw16AndU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw32AndU(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmwOr(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmwOr(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmw8OrU(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmw16OrU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw8OrU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw16OrU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw32OrU(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmwXor(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmwXor(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmw8XorU(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmw16XorU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw8XorU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw16XorU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw32XorU(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmwXchg(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmwXchg(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmw8XchgU(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmw16XchgU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw8XchgU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw16XchgU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw32XchgU(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmwCmpxchg(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmwCmpxchg(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmw8CmpxchgU(memarg) => memarg.encode(),
+            Instruction::I32AtomicRmw16CmpxchgU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw8CmpxchgU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw16CmpxchgU(memarg) => memarg.encode(),
+            Instruction::I64AtomicRmw32CmpxchgU(memarg) => memarg.encode(),
             _ => vec![],
         };
 
@@ -523,8 +614,16 @@ impl Encode for MemoryArg {
     fn encode(&self) -> Vec<u8> {
         let mut result = vec![];
 
-        result.extend(encode_u32(self.align));
-        result.extend(encode_u32(self.offset));
+        // 内存索引为 0 时省略，和单内存的编码一致
+        match self.mem_idx {
+            0 => result.extend(encode_u32(self.align)),
+            idx => {
+                result.extend(encode_u32(self.align | MEM_IDX_FLAG));
+                result.extend(encode_u32(idx));
+            }
+        }
+
+        result.extend(encode_unsigned(self.offset));
 
         result
     }
@@ -541,6 +640,37 @@ impl Encode for Block {
     }
 }
 
+impl Encode for TryTable {
+    fn encode(&self) -> Vec<u8> {
+        let mut result = vec![];
+
+        result.extend(self.type_.encode());
+        result.extend(self.catches.encodes(false));
+        result.extend(self.expr.encode());
+
+        result
+    }
+}
+
+impl Encode for Catch {
+    fn encode(&self) -> Vec<u8> {
+        let mut result = vec![];
+
+        let kind = match self {
+            Catch::Tag(..) => 0x00,
+            Catch::TagRef(..) => 0x01,
+            Catch::All(_) => 0x02,
+            Catch::AllRef(_) => 0x03,
+        };
+
+        result.push(kind);
+        result.extend(self.tag().map(encode_u32).unwrap_or_default());
+        result.extend(encode_u32(self.label()));
+
+        result
+    }
+}
+
 impl Encode for IfBlock {
     fn encode(&self) -> Vec<u8> {
         let mut result = vec![];
@@ -566,7 +696,9 @@ impl Encode for BlockType {
             BlockType::F32 => encode_signed(-3),
             BlockType::F64 => encode_signed(-4),
             BlockType::V128 => encode_signed(-5),
+            BlockType::FuncRef => encode_signed(-16),
             BlockType::ExternRef => encode_signed(-17),
+            BlockType::ExnRef => encode_signed(-23),
             BlockType::Empty => encode_signed(-64),
             BlockType::TypeIdx(idx) => encode_signed(*idx as i64),
         }
@@ -1,13 +1,26 @@
-use std::io;
+use std::error::Error;
+use std::{fmt, io};
 
 use super::section::Section;
-use super::types::{ResultType, ValType};
+use super::types::{RefType, ResultType, ValType};
 
 #[derive(thiserror::Error, Debug)]
 pub enum DecodeErr {
-    #[error("文件读取失败")]
+    #[error("文件读取失败：{0}")]
     FileRead(#[from] io::Error),
 
+    #[error("数据意外结束")]
+    UnexpectedEnd,
+
+    #[error("名字不是有效的 UTF-8 编码")]
+    InvalidUtf8,
+
+    #[error("无效的数值类型：{0:02X}")]
+    InvalidValType(u8),
+
+    #[error("无效的块类型：{0}")]
+    InvalidBlockType(i32),
+
     #[error("超出 LEB128 可编码的长度")]
     LEBDecodeTooLong,
 
@@ -74,29 +87,101 @@ pub enum DecodeErr {
     #[error("无效的 Limit 模式：{0:02X}")]
     InvalidLimitMode(u8),
 
+    #[error("表不能声明为共享")]
+    SharedTable,
+
     #[error("段 {0:?} 出现了 {1} 次，最多只能出现 1 次")]
     MultipleSection(Section, usize),
 
+    #[error("段 {0:?} 不能出现在 {1:?} 之后")]
+    SectionOutOfOrder(Section, Section),
+
     #[error("代码块数量 {0} 和函数段数量 {1} 不一致")]
     FuncAndCodeNotEq(usize, usize),
 
     #[error("数据段数量 {0} 和 DataCount {1} 不一致")]
     DataAndDataCountNotEq(usize, usize),
 
-    #[error("{0} 应为 0，现为 {1}")]
-    NotZero(String, u8),
-
     #[error("{0} 指令需要 DataCount 段")]
     LossDataCount(String),
+
+    #[error("无效的标签属性：{0:02X}")]
+    InvalidTagAttribute(u8),
+
+    #[error("无效的 catch 子句：{0:02X}")]
+    InvalidCatchKind(u8),
+
+    #[error("atomic.fence 的保留字节应为 0：{0:02X}")]
+    InvalidFenceFlag(u8),
 }
 
+/// 带有位置的解码错误
+#[derive(thiserror::Error, Debug)]
+pub struct DecodeError {
+    /// 出错的值在整个文件中的位置
+    pub offset: usize,
+    pub section: Option<Section>,
+    /// 在代码段中出错时的函数索引，包括导入的函数
+    pub func_idx: Option<u32>,
+    #[source]
+    pub kind: DecodeErr,
+}
+
+impl DecodeError {
+    /// 给 DecodeErr 加上位置，已经有位置的错误保持不变
+    pub fn at(err: Box<dyn Error>, offset: usize) -> Box<dyn Error> {
+        if err.is::<DecodeError>() {
+            return err;
+        }
+
+        match err.downcast::<DecodeErr>() {
+            Ok(kind) => Box::new(DecodeError {
+                offset,
+                section: None,
+                func_idx: None,
+                kind: *kind,
+            }),
+            Err(err) => err,
+        }
+    }
+
+    /// 补上外层才知道的段和函数索引
+    pub fn within(mut err: Box<dyn Error>, section: &Section, func_idx: Option<u32>) -> Box<dyn Error> {
+        if let Some(err) = err.downcast_mut::<DecodeError>() {
+            err.section.get_or_insert(section.clone());
+            err.func_idx = err.func_idx.or(func_idx);
+        }
+
+        err
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "位置 0x{:x}", self.offset)?;
+
+        if let Some(section) = &self.section {
+            write!(f, "，{:?} 段", section)?;
+        }
+
+        if let Some(func_idx) = self.func_idx {
+            write!(f, "，函数 {}", func_idx)?;
+        }
+
+        write!(f, "：{}", self.kind)
+    }
+}
+
+/// 函数体中的错误都以函数索引和指令序号开头：函数索引包括导入的函数；
+/// 指令序号不是字节位置，而是指令在函数体中从 0 开始按二进制顺序的编号，else 和 end 也各算一条，
+/// 从二进制解码的模块可以用它在 CodeSeg::instr_offsets 中查到指令在文件中的位置
 #[derive(thiserror::Error, Debug)]
 pub enum ValidateErr {
     #[error("找不到索引 {0} 对应的函数类型")]
     FnTypeNotFound(u32),
 
-    #[error("offset 初始表达式返回值应为 I32：{0:?}")]
-    OffsetRetNotEqI32(ValType),
+    #[error("offset 初始表达式返回值应为 {1:?}：{0:?}")]
+    OffsetRetNotEq(ValType, ValType),
 
     #[error("找不到索引 {0} 对应的内存块")]
     MemNotFound(u32),
@@ -131,15 +216,118 @@ pub enum ValidateErr {
     #[error("表达式的结果 {0:?} 和预期不符 {1:?}")]
     ExprRetNotEq(ValType, ValType),
 
-    #[error("导入了外部内存，则不能在模块内再次定义")]
-    ExitImportMem,
-
-    #[error("导入了外部表段，则不能在模块内再次定义")]
-    ExitImportTable,
-
     #[error("指定的上限小于下限：{0} < {1}")]
-    MaxLtMin(u32, u32),
+    MaxLtMin(u64, u64),
 
     #[error("上限 {0} 不能大于 {1}")]
-    MaxTooLarge(u32, u32),
+    MaxTooLarge(u64, u64),
+
+    #[error("共享内存必须指定上限")]
+    SharedWithoutMax,
+
+    #[error("下限 {0} 不能大于 {1}")]
+    MinTooLarge(u64, u64),
+
+    #[error("找不到索引 {0} 对应的异常标签")]
+    TagNotFound(u32),
+
+    #[error("异常标签的类型不应有返回值：{0:?}")]
+    TagHasResults(ResultType),
+
+    #[error("元素段类型 {0:?} 和表的元素类型 {1:?} 不一致")]
+    ElemTypeNotEq(ValType, RefType),
+
+    /// 初始表达式中的 ref.null
+    #[error("无效的引用类型：{0:02X}")]
+    InvalidRefType(u64),
+
+    #[error("函数 {0} 第 {1} 条指令：无效的引用类型：{2:02X}")]
+    InvalidHeapType(u32, usize, u64),
+
+    #[error("函数 {0} 第 {1} 条指令：类型不匹配，期望 {2:?}，实际为 {3:?}")]
+    OperandTypeMismatch(u32, usize, ValType, ValType),
+
+    #[error("函数 {0} 第 {1} 条指令：else 没有对应的 if")]
+    ElseWithoutIf(u32, usize),
+
+    #[error("函数 {0} 第 {1} 条指令：end 没有对应的块")]
+    UnmatchedEnd(u32, usize),
+
+    #[error("函数 {0} 第 {1} 条指令：操作数栈为空")]
+    OperandStackEmpty(u32, usize),
+
+    #[error("函数 {0} 第 {1} 条指令：操作数栈多出 {2} 个值")]
+    OperandStackRemain(u32, usize, usize),
+
+    #[error("函数 {0} 第 {1} 条指令：期望引用类型，实际为 {2:?}")]
+    ExpectRefType(u32, usize, ValType),
+
+    #[error("函数 {0} 第 {1} 条指令：select 不带类型时不能用于引用类型 {2:?}")]
+    SelectRefType(u32, usize, ValType),
+
+    #[error("函数 {0} 第 {1} 条指令：select 只能有 1 个结果，现为 {2}")]
+    InvalidResultArity(u32, usize, u8),
+
+    #[error("函数 {0} 第 {1} 条指令：未知的标签 {2}")]
+    UnknownLabel(u32, usize, u32),
+
+    #[error("函数 {0} 第 {1} 条指令：未知的局部变量 {2}")]
+    UnknownLocal(u32, usize, u32),
+
+    #[error("函数 {0} 第 {1} 条指令：未知的全局变量 {2}")]
+    UnknownGlobal(u32, usize, u32),
+
+    #[error("函数 {0} 第 {1} 条指令：未知的函数 {2}")]
+    UnknownFunc(u32, usize, u32),
+
+    #[error("函数 {0} 第 {1} 条指令：未知的表 {2}")]
+    UnknownTable(u32, usize, u32),
+
+    #[error("函数 {0} 第 {1} 条指令：未知的内存 {2}")]
+    UnknownMemory(u32, usize, u32),
+
+    #[error("函数 {0} 第 {1} 条指令：未知的类型 {2}")]
+    UnknownType(u32, usize, u32),
+
+    #[error("函数 {0} 第 {1} 条指令：未知的元素段 {2}")]
+    UnknownElem(u32, usize, u32),
+
+    #[error("函数 {0} 第 {1} 条指令：未知的数据段 {2}")]
+    UnknownData(u32, usize, u32),
+
+    #[error("函数 {0} 第 {1} 条指令：用到数据段的指令需要 DataCount 段")]
+    DataCountRequired(u32, usize),
+
+    #[error("函数 {0} 第 {1} 条指令：全局变量 {2} 不可变")]
+    ImmutableGlobal(u32, usize, u32),
+
+    #[error("函数 {0} 第 {1} 条指令：对齐 2^{2} 不能大于 {3} 字节")]
+    AlignTooLarge(u32, usize, u32, u32),
+
+    #[error("函数 {0} 第 {1} 条指令：原子指令的对齐 2^{2} 必须等于 {3} 字节")]
+    AtomicAlignMismatch(u32, usize, u32, u32),
+
+    #[error("函数 {0} 第 {1} 条指令：32 位内存的偏移 {2} 超出范围")]
+    OffsetTooLarge(u32, usize, u64),
+
+    #[error("函数 {0} 第 {1} 条指令：无效的通道索引 {2}，应小于 {3}")]
+    InvalidLaneIdx(u32, usize, u8, u8),
+
+    #[error("函数 {0} 第 {1} 条指令：函数 {2} 未在模块中声明引用")]
+    UndeclaredFuncRef(u32, usize, u32),
+
+    #[error("函数 {0} 第 {1} 条指令：br_table 标签的结果个数 {2} 和默认标签 {3} 不一致")]
+    BrTableArityMismatch(u32, usize, usize, usize),
+
+    #[error("函数 {0} 第 {1} 条指令：表类型不匹配，{2:?} 和 {3:?}")]
+    TableTypeMismatch(u32, usize, ValType, ValType),
+
+    #[error("函数 {0} 第 {1} 条指令：未知的异常标签 {2}")]
+    UnknownTag(u32, usize, u32),
+
+    #[error("函数 {0} 第 {1} 条指令：catch 子句传给标签 {2} 的值 {3:?} 和标签的类型 {4:?} 不一致")]
+    CatchLabelMismatch(u32, usize, u32, ResultType, ResultType),
+
+    #[error("函数 {0} 第 {1} 条指令：尾调用的结果 {2:?} 和当前函数的结果 {3:?} 不一致")]
+    ReturnCallResultMismatch(u32, usize, ResultType, ResultType),
 }
@@ -1,13 +1,20 @@
-use super::section::{Expr, LabelIdx};
+use super::errors::DecodeErr;
+use super::section::{Expr, LabelIdx, TagIdx};
 use super::types::ValType;
 use crate::execution::value::v128;
 
 #[derive(Debug, Clone)]
 pub struct MemoryArg {
     pub align: u32,
-    pub offset: u32,
+    /// memory64 的偏移可以超过 u32
+    pub offset: u64,
+    /// 多内存提案，对齐的第 6 位为 1 时后面跟着内存索引
+    pub mem_idx: u32,
 }
 
+/// 对齐字段中表示带有内存索引的标志位
+pub(super) const MEM_IDX_FLAG: u32 = 0x40;
+
 #[derive(Debug, Clone)]
 pub struct Block {
     pub type_: BlockType,
@@ -28,23 +35,29 @@ pub enum BlockType {
     F32,
     F64,
     V128,
+    FuncRef,
     ExternRef,
+    ExnRef,
     Empty,
     TypeIdx(i32),
 }
 
-impl From<i32> for BlockType {
-    fn from(v: i32) -> Self {
+impl TryFrom<i32> for BlockType {
+    type Error = DecodeErr;
+
+    fn try_from(v: i32) -> Result<Self, DecodeErr> {
         match v {
-            -1 => Self::I32,
-            -2 => Self::I64,
-            -3 => Self::F32,
-            -4 => Self::F64,
-            -5 => Self::V128,
-            -17 => Self::ExternRef,
-            -64 => Self::Empty,
-            value if value >= 0 => Self::TypeIdx(value),
-            _ => panic!("无效的 BlockType：{}", v),
+            -1 => Ok(Self::I32),
+            -2 => Ok(Self::I64),
+            -3 => Ok(Self::F32),
+            -4 => Ok(Self::F64),
+            -5 => Ok(Self::V128),
+            -16 => Ok(Self::FuncRef),
+            -17 => Ok(Self::ExternRef),
+            -23 => Ok(Self::ExnRef),
+            -64 => Ok(Self::Empty),
+            value if value >= 0 => Ok(Self::TypeIdx(value)),
+            _ => Err(DecodeErr::InvalidBlockType(v)),
         }
     }
 }
@@ -56,6 +69,44 @@ pub struct IfBlock {
     pub else_expr: Expr,
 }
 
+/// try_table 在 block 的基础上带有一组 catch 子句
+#[derive(Debug, Clone)]
+pub struct TryTable {
+    pub type_: BlockType,
+    pub catches: Vec<Catch>,
+    pub expr: Expr,
+}
+
+/// 带 Ref 的子句在跳转时额外压入 exnref
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Catch {
+    Tag(TagIdx, LabelIdx) = 0x00,
+    TagRef(TagIdx, LabelIdx),
+    All(LabelIdx),
+    AllRef(LabelIdx),
+}
+
+impl Catch {
+    pub fn label(&self) -> LabelIdx {
+        match self {
+            Self::Tag(_, label) | Self::TagRef(_, label) => *label,
+            Self::All(label) | Self::AllRef(label) => *label,
+        }
+    }
+
+    pub fn tag(&self) -> Option<TagIdx> {
+        match self {
+            Self::Tag(tag, _) | Self::TagRef(tag, _) => Some(*tag),
+            _ => None,
+        }
+    }
+
+    pub fn is_ref(&self) -> bool {
+        matches!(self, Self::TagRef(..) | Self::AllRef(_))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BrTableArg {
     pub labels: Vec<LabelIdx>,
@@ -77,6 +128,8 @@ pub enum Instruction {
     Loop(Block) = 0x03,                           // loop 0x03
     If(IfBlock) = 0x04,                           // if 0x04
     Else = 0x05,                                  // else 0x05
+    Throw(TagIdx) = 0x08,                         // throw 0x08
+    ThrowRef = 0x0a,                              // throw_ref 0x0A
     End = 0x0b,                                   // end 0x0B
     Br(LabelIdx) = 0x0c,                          // br 0x0C
     BrIf(LabelIdx) = 0x0d,                        // br_if 0x0D
@@ -84,9 +137,12 @@ pub enum Instruction {
     Return = 0x0f,                                // return 0x0F
     Call(u32) = 0x10,                             // call 0x10
     CallIndirect(u32, u32) = 0x11,                // call_indirect 0x11
+    ReturnCall(u32) = 0x12,                       // return_call 0x12
+    ReturnCallIndirect(u32, u32) = 0x13,          // return_call_indirect 0x13
     Drop = 0x1a,                                  // drop 0x1A
     Select = 0x1b,                                // select 0x1B
     Select2(u8, ValType) = 0x1c,                  // select 0x1C
+    TryTable(TryTable) = 0x1f,                    // try_table 0x1F
     LocalGet(u32) = 0x20,                         // local_get 0x20
     LocalSet(u32) = 0x21,                         // local_set 0x21
     LocalTee(u32) = 0x22,                         // local_tee 0x22
@@ -117,8 +173,8 @@ pub enum Instruction {
     I64Store8(MemoryArg) = 0x3c,                  // i64_store8 0x3C
     I64Store16(MemoryArg) = 0x3d,                 // i64_store16 0x3D
     I64Store32(MemoryArg) = 0x3e,                 // i64_store32 0x3E
-    MemorySize(u8) = 0x3f,                        // memory_size 0x3F
-    MemoryGrow(u8) = 0x40,                        // memory_grow 0x40
+    MemorySize(u32) = 0x3f,                       // memory_size 0x3F
+    MemoryGrow(u32) = 0x40,                       // memory_grow 0x40
     I32Const(i32) = 0x41,                         // i32_const 0x41
     I64Const(i64) = 0x42,                         // i64_const 0x42
     F32Const(f32) = 0x43,                         // f32_const 0x43
@@ -508,4 +564,71 @@ pub enum Instruction {
     I32x4TruncSatF64x2UZero(u8) = 0xfdfd,         // i32x4_trunc_sat_f64x2_u_zero 0xFD 0xFD 0x01
     F64x2ConvertLowI32x4S(u8) = 0xfdfe,           // f64x2_convert_low_i32x4_s 0xFD 0xFE 0x01
     F64x2ConvertLowI32x4U(u8) = 0xfdff,           // f64x2_convert_low_i32x4_u 0xFD 0xFF 0x01
+    MemoryAtomicNotify(MemoryArg) = 0xfe00,       // memory_atomic_notify 0xFE 0x00
+    MemoryAtomicWait32(MemoryArg) = 0xfe01,       // memory_atomic_wait32 0xFE 0x01
+    MemoryAtomicWait64(MemoryArg) = 0xfe02,       // memory_atomic_wait64 0xFE 0x02
+    AtomicFence = 0xfe03,                         // atomic_fence 0xFE 0x03
+    I32AtomicLoad(MemoryArg) = 0xfe10,            // i32_atomic_load 0xFE 0x10
+    I64AtomicLoad(MemoryArg) = 0xfe11,            // i64_atomic_load 0xFE 0x11
+    I32AtomicLoad8U(MemoryArg) = 0xfe12,          // i32_atomic_load8_u 0xFE 0x12
+    I32AtomicLoad16U(MemoryArg) = 0xfe13,         // i32_atomic_load16_u 0xFE 0x13
+    I64AtomicLoad8U(MemoryArg) = 0xfe14,          // i64_atomic_load8_u 0xFE 0x14
+    I64AtomicLoad16U(MemoryArg) = 0xfe15,         // i64_atomic_load16_u 0xFE 0x15
+    I64AtomicLoad32U(MemoryArg) = 0xfe16,         // i64_atomic_load32_u 0xFE 0x16
+    I32AtomicStore(MemoryArg) = 0xfe17,           // i32_atomic_store 0xFE 0x17
+    I64AtomicStore(MemoryArg) = 0xfe18,           // i64_atomic_store 0xFE 0x18
+    I32AtomicStore8(MemoryArg) = 0xfe19,          // i32_atomic_store8 0xFE 0x19
+    I32AtomicStore16(MemoryArg) = 0xfe1a,         // i32_atomic_store16 0xFE 0x1A
+    I64AtomicStore8(MemoryArg) = 0xfe1b,          // i64_atomic_store8 0xFE 0x1B
+    I64AtomicStore16(MemoryArg) = 0xfe1c,         // i64_atomic_store16 0xFE 0x1C
+    I64AtomicStore32(MemoryArg) = 0xfe1d,         // i64_atomic_store32 0xFE 0x1D
+    I32AtomicRmwAdd(MemoryArg) = 0xfe1e,          // i32_atomic_rmw_add 0xFE 0x1E
+    I64AtomicRmwAdd(MemoryArg) = 0xfe1f,          // i64_atomic_rmw_add 0xFE 0x1F
+    I32AtomicRmw8AddU(MemoryArg) = 0xfe20,        // i32_atomic_rmw8_add_u 0xFE 0x20
+    I32AtomicRmw16AddU(MemoryArg) = 0xfe21,       // i32_atomic_rmw16_add_u 0xFE 0x21
+    I64AtomicRmw8AddU(MemoryArg) = 0xfe22,        // i64_atomic_rmw8_add_u 0xFE 0x22
+    I64AtomicRmw16AddU(MemoryArg) = 0xfe23,       // i64_atomic_rmw16_add_u 0xFE 0x23
+    I64AtomicRmw32AddU(MemoryArg) = 0xfe24,       // i64_atomic_rmw32_add_u 0xFE 0x24
+    I32AtomicRmwSub(MemoryArg) = 0xfe25,          // i32_atomic_rmw_sub 0xFE 0x25
+    I64AtomicRmwSub(MemoryArg) = 0xfe26,          // i64_atomic_rmw_sub 0xFE 0x26
+    I32AtomicRmw8SubU(MemoryArg) = 0xfe27,        // i32_atomic_rmw8_sub_u 0xFE 0x27
+    I32AtomicRmw16SubU(MemoryArg) = 0xfe28,       // i32_atomic_rmw16_sub_u 0xFE 0x28
+    I64AtomicRmw8SubU(MemoryArg) = 0xfe29,        // i64_atomic_rmw8_sub_u 0xFE 0x29
+    I64AtomicRmw16SubU(MemoryArg) = 0xfe2a,       // i64_atomic_rmw16_sub_u 0xFE 0x2A
+    I64AtomicRmw32SubU(MemoryArg) = 0xfe2b,       // i64_atomic_rmw32_sub_u 0xFE 0x2B
+    I32AtomicRmwAnd(MemoryArg) = 0xfe2c,          // i32_atomic_rmw_and 0xFE 0x2C
+    I64AtomicRmwAnd(MemoryArg) = 0xfe2d,          // i64_atomic_rmw_and 0xFE 0x2D
+    I32AtomicRmw8AndU(MemoryArg) = 0xfe2e,        // i32_atomic_rmw8_and_u 0xFE 0x2E
+    I32AtomicRmw16AndU(MemoryArg) = 0xfe2f,       // i32_atomic_rmw16_and_u 0xFE 0x2F
+    I64AtomicRmw8AndU(MemoryArg) = 0xfe30,        // i64_atomic_rmw8_and_u 0xFE 0x30
+    I64AtomicRmw16AndU(MemoryArg) = 0xfe31,       // i64_atomic_rmw16_and_u 0xFE 0x31
+    I64AtomicRmw32AndU(MemoryArg) = 0xfe32,       // i64_atomic_rmw32_and_u 0xFE 0x32
+    I32AtomicRmwOr(MemoryArg) = 0xfe33,           // i32_atomic_rmw_or 0xFE 0x33
+    I64AtomicRmwOr(MemoryArg) = 0xfe34,           // i64_atomic_rmw_or 0xFE 0x34
+    I32AtomicRmw8OrU(MemoryArg) = 0xfe35,         // i32_atomic_rmw8_or_u 0xFE 0x35
+    I32AtomicRmw16OrU(MemoryArg) = 0xfe36,        // i32_atomic_rmw16_or_u 0xFE 0x36
+    I64AtomicRmw8OrU(MemoryArg) = 0xfe37,         // i64_atomic_rmw8_or_u 0xFE 0x37
+    I64AtomicRmw16OrU(MemoryArg) = 0xfe38,        // i64_atomic_rmw16_or_u 0xFE 0x38
+    I64AtomicRmw32OrU(MemoryArg) = 0xfe39,        // i64_atomic_rmw32_or_u 0xFE 0x39
+    I32AtomicRmwXor(MemoryArg) = 0xfe3a,          // i32_atomic_rmw_xor 0xFE 0x3A
+    I64AtomicRmwXor(MemoryArg) = 0xfe3b,          // i64_atomic_rmw_xor 0xFE 0x3B
+    I32AtomicRmw8XorU(MemoryArg) = 0xfe3c,        // i32_atomic_rmw8_xor_u 0xFE 0x3C
+    I32AtomicRmw16XorU(MemoryArg) = 0xfe3d,       // i32_atomic_rmw16_xor_u 0xFE 0x3D
+    I64AtomicRmw8XorU(MemoryArg) = 0xfe3e,        // i64_atomic_rmw8_xor_u 0xFE 0x3E
+    I64AtomicRmw16XorU(MemoryArg) = 0xfe3f,       // i64_atomic_rmw16_xor_u 0xFE 0x3F
+    I64AtomicRmw32XorU(MemoryArg) = 0xfe40,       // i64_atomic_rmw32_xor_u 0xFE 0x40
+    I32AtomicRmwXchg(MemoryArg) = 0xfe41,         // i32_atomic_rmw_xchg 0xFE 0x41
+    I64AtomicRmwXchg(MemoryArg) = 0xfe42,         // i64_atomic_rmw_xchg 0xFE 0x42
+    I32AtomicRmw8XchgU(MemoryArg) = 0xfe43,       // i32_atomic_rmw8_xchg_u 0xFE 0x43
+    I32AtomicRmw16XchgU(MemoryArg) = 0xfe44,      // i32_atomic_rmw16_xchg_u 0xFE 0x44
+    I64AtomicRmw8XchgU(MemoryArg) = 0xfe45,       // i64_atomic_rmw8_xchg_u 0xFE 0x45
+    I64AtomicRmw16XchgU(MemoryArg) = 0xfe46,      // i64_atomic_rmw16_xchg_u 0xFE 0x46
+    I64AtomicRmw32XchgU(MemoryArg) = 0xfe47,      // i64_atomic_rmw32_xchg_u 0xFE 0x47
+    I32AtomicRmwCmpxchg(MemoryArg) = 0xfe48,      // i32_atomic_rmw_cmpxchg 0xFE 0x48
+    I64AtomicRmwCmpxchg(MemoryArg) = 0xfe49,      // i64_atomic_rmw_cmpxchg 0xFE 0x49
+    I32AtomicRmw8CmpxchgU(MemoryArg) = 0xfe4a,    // i32_atomic_rmw8_cmpxchg_u 0xFE 0x4A
+    I32AtomicRmw16CmpxchgU(MemoryArg) = 0xfe4b,   // i32_atomic_rmw16_cmpxchg_u 0xFE 0x4B
+    I64AtomicRmw8CmpxchgU(MemoryArg) = 0xfe4c,    // i64_atomic_rmw8_cmpxchg_u 0xFE 0x4C
+    I64AtomicRmw16CmpxchgU(MemoryArg) = 0xfe4d,   // i64_atomic_rmw16_cmpxchg_u 0xFE 0x4D
+    I64AtomicRmw32CmpxchgU(MemoryArg) = 0xfe4e,   // i64_atomic_rmw32_cmpxchg_u 0xFE 0x4E
 }
@@ -2,7 +2,9 @@ use super::reader::DecodeResult;
 use crate::binary::errors::DecodeErr;
 
 /// https://en.wikipedia.org/wiki/LEB128
-pub fn decode_unsigned(data: &[u8]) -> DecodeResult<(u64, usize)> {
+///
+/// size 为 32 或 64，最后一个字节中超出 size 的位必须为 0
+pub fn decode_unsigned(data: &[u8], size: usize) -> DecodeResult<(u64, usize)> {
     let mut result = 0;
 
     for (i, b) in data.iter().enumerate() {
@@ -11,9 +13,9 @@ pub fn decode_unsigned(data: &[u8]) -> DecodeResult<(u64, usize)> {
         // 最高位为 0，停止读入后续字节
         let no_more = b & 0b1000_0000 == 0;
 
-        match i == 4 {
+        match i == size / 7 {
             true if !no_more => Err(DecodeErr::LEBDecodeTooLong)?,
-            true if (b >> (32 - i * 7)) > 0 => Err(DecodeErr::IntTooLarge)?,
+            true if (b >> (size - i * 7)) > 0 => Err(DecodeErr::IntTooLarge)?,
             _ if no_more => return Ok((result, i + 1)),
             _ => continue,
         }
@@ -134,23 +136,23 @@ mod test {
             0b1_0000011,
             0b0_0000001,
         ];
-        let (num, size) = decode_unsigned(&data[5..]).unwrap();
+        let (num, size) = decode_unsigned(&data[5..], 32).unwrap();
         assert_eq!(num, 0b0000001);
         assert_eq!(size, 1);
 
-        let (num, size) = decode_unsigned(&data[4..]).unwrap();
+        let (num, size) = decode_unsigned(&data[4..], 32).unwrap();
         assert_eq!(num, 0b1_0000011);
         assert_eq!(size, 2);
 
-        let (num, size) = decode_unsigned(&data[3..]).unwrap();
+        let (num, size) = decode_unsigned(&data[3..], 32).unwrap();
         assert_eq!(num, 0b1_0000011_0000111);
         assert_eq!(size, 3);
 
-        let (num, size) = decode_unsigned(&data[2..]).unwrap();
+        let (num, size) = decode_unsigned(&data[2..], 32).unwrap();
         assert_eq!(num, 0b1_0000011_0000111_0001111);
         assert_eq!(size, 4);
 
-        let (num, size) = decode_unsigned(&data[1..]).unwrap();
+        let (num, size) = decode_unsigned(&data[1..], 32).unwrap();
         assert_eq!(num, 0b1_0000011_0000111_0001111_0011111);
         assert_eq!(size, 5);
     }
@@ -167,9 +169,9 @@ mod test {
     fn test_encode_unsigned() {
         let data: u64 = 624485;
         let e = encode_unsigned(data);
-        let d = decode_unsigned(&e).unwrap();
+        let d = decode_unsigned(&e, 32).unwrap();
 
-        assert_eq!(data as u64, d.0);
+        assert_eq!(data, d.0);
     }
 
     #[test]
@@ -1,10 +1,12 @@
 pub mod decode;
 pub mod encode;
-mod errors;
+pub mod errors;
 pub mod instruction;
 mod leb128;
 pub mod module;
+pub mod names;
 pub mod reader;
 pub mod section;
+pub mod stream;
 pub mod types;
 pub mod validate;
@@ -1,30 +1,35 @@
-use std::fs;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::ops::ControlFlow;
 
 use super::decode::Decode;
 use super::encode::{encode_maybeu32_sec, Encode, Encodes};
-use super::errors::DecodeErr;
+use super::errors::{DecodeErr, DecodeError};
+use super::names::NameSec;
 use super::reader::{DecodeResult, Reader};
 use super::section::{
-    CodeSeg, CustomSeg, DataCountSeg, DataSeg, ElementSeg, ExportSeg, GlobalSeg, ImportSeg, Section,
-    StartSeg, TypeIdx,
+    CodeSeg, CustomSeg, DataCountSeg, DataSeg, ElementSeg, ExportSeg, GlobalSeg, ImportDesc, ImportSeg,
+    Section, SectionRange, StartSeg, TagType, TypeIdx,
 };
+use super::stream::{Payload, StreamDecoder};
 use super::types::*;
-use super::validate::{Validate, ValidateResult};
+use super::validate::{Context, Validate, ValidateResult};
 
-const MAGIC: u32 = 0x6d736100;
-const VERSION: u32 = 0x00000001;
+pub(super) const MAGIC: u32 = 0x6d736100;
+pub(super) const VERSION: u32 = 0x00000001;
 
 /// Modules
 #[derive(Debug, Default)]
 pub struct Module {
     magic: String,
     version: u32,
-    custom_sec: Vec<CustomSeg>,
+    pub custom_sec: Vec<CustomSeg>,
     pub type_sec: Vec<FuncType>,
     pub import_sec: Vec<ImportSeg>,
     pub func_sec: Vec<TypeIdx>,
     pub table_sec: Vec<TableType>,
     pub mem_sec: Vec<MemType>,
+    pub tag_sec: Vec<TagType>,
     pub global_sec: Vec<GlobalSeg>,
     pub export_sec: Vec<ExportSeg>,
     pub start_sec: StartSeg,
@@ -33,6 +38,10 @@ pub struct Module {
     pub data_sec: Vec<DataSeg>,
     /// 校验 data_sec
     pub data_counat_sec: DataCountSeg,
+    /// 按出现顺序记录各个段的位置，只有从二进制格式解码时才有
+    pub sections: Vec<SectionRange>,
+    /// 从名字段解码出的名字，从文本格式解析时取自 $id
+    pub names: NameSec,
 }
 
 impl Module {
@@ -44,10 +53,24 @@ impl Module {
         }
     }
 
+    /// 边读文件边解码，不用先把整个文件读进内存
     pub fn from_file(path: &str) -> DecodeResult<Self> {
-        let wasm = fs::read(path).expect("文件读取失败");
+        let file = File::open(path).map_err(DecodeErr::FileRead)?;
 
-        Self::from_data(wasm)
+        Self::from_reader(BufReader::new(file))
+    }
+
+    /// 把流式解码产生的事件收集成完整的模块
+    pub fn from_reader(src: impl Read) -> DecodeResult<Self> {
+        let mut module = Module::new();
+
+        StreamDecoder::new(src).decode(|payload| {
+            module.add(payload);
+
+            ControlFlow::Continue(())
+        })?;
+
+        Ok(module)
     }
 
     pub fn from_data(data: Vec<u8>) -> DecodeResult<Self> {
@@ -72,7 +95,23 @@ impl Module {
 }
 
 impl Decode for Module {
+    /// 出错时带上位置，以及所在的段和函数
     fn decode(reader: &mut Reader) -> DecodeResult<Module> {
+        let mut module = Module::new();
+
+        module
+            .decode_sections(reader)
+            .map_err(|err| DecodeError::at(err, reader.last()))?;
+        module
+            .check_counts(module.code_sec.len(), module.data_sec.len())
+            .map_err(|err| DecodeError::at(err, reader.offset()))?;
+
+        Ok(module)
+    }
+}
+
+impl Module {
+    fn decode_sections(&mut self, reader: &mut Reader) -> DecodeResult<()> {
         match reader.get_u32()? {
             MAGIC => (),
             magic => Err(DecodeErr::MagicUnMatch(magic))?,
@@ -82,10 +121,11 @@ impl Decode for Module {
             version => Err(DecodeErr::VersionUnMatch(version))?,
         };
 
-        let mut module = Module::new();
-        let mut sec_counts: Vec<usize> = vec![0; 13];
+        let mut sec_counts: Vec<usize> = vec![0; 14];
+        let mut last = Section::Custom;
 
         while reader.not_end()? {
+            let start = reader.offset();
             let i = reader.get_u8()? as usize;
             let id = Section::from_u8(i as u8)?;
 
@@ -93,54 +133,114 @@ impl Decode for Module {
             if id != Section::Custom && sec_counts[i] >= 1 {
                 Err(DecodeErr::MultipleSection(id.clone(), sec_counts[i]))?
             }
+            if id != Section::Custom {
+                last = id.check_order(last)?;
+            }
 
-            let sec_data = reader.seqs()?;
-            let mut sec_reader = Reader::new(&sec_data, module.data_counat_sec);
+            let size = reader.get_leb_u32()? as usize;
+            let mut sec_reader = reader
+                .sub(size)
+                .map_err(|err| DecodeError::within(DecodeError::at(err, reader.last()), &id, None))?;
 
+            sec_reader.data_count = self.data_counat_sec;
             sec_counts[i] += 1;
+            self.sections.push(SectionRange {
+                id: id.clone(),
+                start,
+                offset: sec_reader.offset(),
+                size,
+            });
 
-            match id {
-                Section::Custom => module.custom_sec.push(CustomSeg::decode(&mut sec_reader)?),
-                Section::Type => module.type_sec = FuncType::decodes(&mut sec_reader)?,
-                Section::Import => module.import_sec = ImportSeg::decodes(&mut sec_reader)?,
-                Section::Function => module.func_sec = TypeIdx::decodes(&mut sec_reader)?,
-                Section::Table => module.table_sec = TableType::decodes(&mut sec_reader)?,
-                Section::Memory => module.mem_sec = MemType::decodes(&mut sec_reader)?,
-                Section::Global => module.global_sec = GlobalSeg::decodes(&mut sec_reader)?,
-                Section::Export => module.export_sec = ExportSeg::decodes(&mut sec_reader)?,
-                Section::Start => module.start_sec = StartSeg::decode(&mut sec_reader)?,
-                Section::Element => module.elem_sec = ElementSeg::decodes(&mut sec_reader)?,
-                Section::Code => module.code_sec = CodeSeg::decodes(&mut sec_reader)?,
-                Section::Data => module.data_sec = DataSeg::decodes(&mut sec_reader)?,
-                Section::DataCount => module.data_counat_sec = DataCountSeg::decode(&mut sec_reader)?,
-            };
-
-            if sec_reader.remain().is_ok_and(|data| !data.is_empty()) {
-                Err(DecodeErr::SectionSizeMismatch)?;
-            }
+            let decoded = self.decode_section(&id, &mut sec_reader);
+
+            decoded.map_err(|err| {
+                DecodeError::within(DecodeError::at(err, sec_reader.last()), &id, None)
+            })?;
         }
 
-        module.validate_decode()?;
+        Ok(())
+    }
 
-        Ok(module)
+    fn decode_section(&mut self, id: &Section, reader: &mut Reader) -> DecodeResult<()> {
+        match id {
+            Section::Code => self.code_sec = self.decode_codes(reader)?,
+            _ => self.add(Payload::decode(id, reader)?),
+        };
+
+        if reader.remain().is_ok_and(|data| !data.is_empty()) {
+            Err(DecodeErr::SectionSizeMismatch)?;
+        }
+
+        Ok(())
+    }
+
+    /// 函数索引排在导入的函数之后
+    fn decode_codes(&self, reader: &mut Reader) -> DecodeResult<Vec<CodeSeg>> {
+        let imported = self
+            .import_sec
+            .iter()
+            .filter(|import| matches!(import.desc, ImportDesc::Func(_)))
+            .count() as u32;
+        let total = reader.get_leb_u32()?;
+
+        (0..total)
+            .map(|i| {
+                let decoded = CodeSeg::decode(reader);
+
+                decoded.map_err(|err| {
+                    DecodeError::within(
+                        DecodeError::at(err, reader.last()),
+                        &Section::Code,
+                        Some(imported + i),
+                    )
+                })
+            })
+            .collect()
     }
 }
 
 impl Module {
-    fn validate_decode(&self) -> DecodeResult<()> {
-        if self.code_sec.len() != self.func_sec.len() {
-            Err(DecodeErr::FuncAndCodeNotEq(
-                self.code_sec.len(),
-                self.func_sec.len(),
-            ))?;
+    /// 把解码出的段放进模块，流式解码时也用它收集代码段之前的各段
+    pub(super) fn add(&mut self, payload: Payload) {
+        match payload {
+            Payload::Header { version } => self.version = version,
+            Payload::Section(range) => self.sections.push(range),
+            Payload::Custom(custom) => {
+                // 名字段只用于调试，格式错误时忽略
+                if custom.name == NameSec::NAME {
+                    self.names =
+                        NameSec::decode(&mut Reader::new(&custom.data, None)).unwrap_or_default();
+                }
+
+                self.custom_sec.push(custom);
+            }
+            Payload::Type(type_sec) => self.type_sec = type_sec,
+            Payload::Import(import_sec) => self.import_sec = import_sec,
+            Payload::Function(func_sec) => self.func_sec = func_sec,
+            Payload::Table(table_sec) => self.table_sec = table_sec,
+            Payload::Memory(mem_sec) => self.mem_sec = mem_sec,
+            Payload::Tag(tag_sec) => self.tag_sec = tag_sec,
+            Payload::Global(global_sec) => self.global_sec = global_sec,
+            Payload::Export(export_sec) => self.export_sec = export_sec,
+            Payload::Start(func_idx) => self.start_sec = Some(func_idx),
+            Payload::Element(elem_sec) => self.elem_sec = elem_sec,
+            Payload::DataCount(count) => self.data_counat_sec = Some(count),
+            Payload::CodeStart(total) => self.code_sec.reserve(total as usize),
+            Payload::Code { code, .. } => self.code_sec.push(code),
+            Payload::Data(data_sec) => self.data_sec = data_sec,
+            Payload::End => {}
+        }
+    }
+
+    /// 代码段和数据段的个数由调用方给出，流式解码时它们并不保存在模块中
+    pub(super) fn check_counts(&self, codes: usize, datas: usize) -> DecodeResult<()> {
+        if codes != self.func_sec.len() {
+            Err(DecodeErr::FuncAndCodeNotEq(codes, self.func_sec.len()))?;
         }
 
         if let Some(count) = self.data_counat_sec {
-            if self.data_sec.len() != (count as usize) {
-                Err(DecodeErr::DataAndDataCountNotEq(
-                    self.data_sec.len(),
-                    count as usize,
-                ))?
+            if datas != (count as usize) {
+                Err(DecodeErr::DataAndDataCountNotEq(datas, count as usize))?
             }
         }
 
@@ -160,6 +260,7 @@ impl Encode for Module {
         results.extend(Module::encode_sec(Section::Function, &self.func_sec));
         results.extend(Module::encode_sec(Section::Table, &self.table_sec));
         results.extend(Module::encode_sec(Section::Memory, &self.mem_sec));
+        results.extend(Module::encode_sec(Section::Tag, &self.tag_sec));
         results.extend(Module::encode_sec(Section::Global, &self.global_sec));
         results.extend(Module::encode_sec(Section::Export, &self.export_sec));
         results.extend(encode_maybeu32_sec(Section::Start, self.start_sec));
@@ -175,18 +276,11 @@ impl Encode for Module {
 
 impl Validate for Module {
     fn validate(&self) -> ValidateResult {
-        let module = self;
-
-        Module::validates(&self.import_sec, module)?;
-        Module::validates(&self.func_sec, module)?;
-        Module::validates(&self.table_sec, module)?;
-        Module::validates(&self.mem_sec, module)?;
-        Module::validates(&self.global_sec, module)?;
-        self.export_sec.validate_use_module(module)?;
-        self.start_sec.validate_use_module(module)?;
-        Module::validates(&self.elem_sec, module)?;
-        Module::validates(&self.code_sec, module)?;
-        Module::validates(&self.data_sec, module)?;
+        let ctx = Context::new(self)?;
+
+        ctx.validate_sections()?;
+        ctx.validate_codes()?;
+        ctx.validates(&self.data_sec)?;
 
         Ok(())
     }
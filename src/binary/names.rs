@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use super::decode::Decode;
+use super::reader::{DecodeResult, Reader};
+
+/// 索引到名字
+pub type NameMap = BTreeMap<u32, String>;
+/// 函数索引到它内部的 NameMap，比如局部变量和标签
+pub type IndirectNameMap = BTreeMap<u32, NameMap>;
+
+/// 名字段，自定义段中名为 name 的那一个，只用于调试
+/// https://webassembly.github.io/spec/core/appendix/custom.html#name-section
+/// https://github.com/WebAssembly/extended-name-section/blob/main/proposals/extended-name-section/Overview.md
+#[derive(Debug, Default, Clone)]
+pub struct NameSec {
+    pub module: Option<String>,
+    pub funcs: NameMap,
+    pub locals: IndirectNameMap,
+    pub labels: IndirectNameMap,
+    pub types: NameMap,
+    pub tables: NameMap,
+    pub mems: NameMap,
+    pub globals: NameMap,
+    pub elems: NameMap,
+    pub datas: NameMap,
+}
+
+impl NameSec {
+    pub const NAME: &'static str = "name";
+}
+
+/// 每个子段为 id、长度和内容，不认识的子段直接跳过
+impl Decode for NameSec {
+    fn decode(reader: &mut Reader) -> DecodeResult<NameSec> {
+        let mut names = NameSec::default();
+
+        while reader.not_end()? {
+            let id = reader.get_u8()?;
+            let size = reader.get_leb_u32()? as usize;
+            let mut sub = reader.sub(size)?;
+
+            match id {
+                0 => names.module = Some(sub.get_name()?),
+                1 => names.funcs = name_map(&mut sub)?,
+                2 => names.locals = indirect_name_map(&mut sub)?,
+                3 => names.labels = indirect_name_map(&mut sub)?,
+                4 => names.types = name_map(&mut sub)?,
+                5 => names.tables = name_map(&mut sub)?,
+                6 => names.mems = name_map(&mut sub)?,
+                7 => names.globals = name_map(&mut sub)?,
+                8 => names.elems = name_map(&mut sub)?,
+                9 => names.datas = name_map(&mut sub)?,
+                _ => {}
+            }
+        }
+
+        Ok(names)
+    }
+}
+
+fn name_map(reader: &mut Reader) -> DecodeResult<NameMap> {
+    let total = reader.get_leb_u32()?;
+
+    (0..total)
+        .map(|_| Ok((reader.get_leb_u32()?, reader.get_name()?)))
+        .collect()
+}
+
+fn indirect_name_map(reader: &mut Reader) -> DecodeResult<IndirectNameMap> {
+    let total = reader.get_leb_u32()?;
+
+    (0..total)
+        .map(|_| Ok((reader.get_leb_u32()?, name_map(reader)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::NameSec;
+    use crate::binary::decode::Decode;
+    use crate::binary::reader::Reader;
+
+    #[test]
+    fn test_decode() {
+        let data = [
+            b"\x00\x02\x01m".as_slice(),
+            // 函数 0 叫 f，它的局部变量 1 叫 x
+            b"\x01\x04\x01\x00\x01f",
+            b"\x02\x06\x01\x00\x01\x01\x01x",
+            // 不认识的子段跳过
+            b"\x0c\x02\xff\xff",
+            b"\x07\x04\x01\x03\x01g",
+        ]
+        .concat();
+        let names = NameSec::decode(&mut Reader::new(&data, None)).unwrap();
+
+        assert_eq!(names.module.as_deref(), Some("m"));
+        assert_eq!(names.funcs[&0], "f");
+        assert_eq!(names.locals[&0][&1], "x");
+        assert_eq!(names.globals[&3], "g");
+        assert!(names.labels.is_empty());
+    }
+}
@@ -2,6 +2,7 @@ use std::error::Error;
 use std::io::{BufRead, Cursor, Read};
 use std::simd::u8x16;
 
+use super::errors::DecodeErr;
 use super::instruction::Lane16;
 use super::leb128;
 use super::section::DataCountSeg;
@@ -11,20 +12,76 @@ pub type DecodeResult<T> = Result<T, Box<dyn Error>>;
 
 pub struct Reader<'a> {
     buf: Cursor<&'a [u8]>,
+    /// data 在整个文件中的起始位置
+    base: usize,
+    /// 最近读取的值在整个文件中的位置，出错时用来定位
+    last: usize,
     pub data_count: DataCountSeg,
+    /// 依次记录解码过的每条指令的位置，包括 else 和 end
+    pub instr_offsets: Vec<usize>,
 }
 
 impl<'a> Reader<'a> {
     pub fn new(data: &'a [u8], data_count: DataCountSeg) -> Self {
         let buf = Cursor::new(data);
 
-        Self { buf, data_count }
+        Self {
+            buf,
+            base: 0,
+            last: 0,
+            data_count,
+            instr_offsets: vec![],
+        }
+    }
+
+    /// data 是从 base 处开始的一段，比如流式解码时单独读出的段
+    pub fn starting_at(mut self, base: usize) -> Self {
+        self.base = base;
+        self.last = base;
+        self
+    }
+
+    /// 接下来 size 个字节作为一个新的 Reader，位置仍然相对于整个文件
+    pub fn sub(&mut self, size: usize) -> DecodeResult<Reader<'a>> {
+        let data = self.remaining();
+
+        self.mark();
+
+        if data.len() < size {
+            Err(DecodeErr::UnexpectedEnd)?
+        }
+
+        let reader = Reader::new(&data[..size], self.data_count).starting_at(self.offset());
+
+        self.buf.consume(size);
+
+        Ok(reader)
+    }
+
+    /// 当前在整个文件中的位置
+    pub fn offset(&self) -> usize {
+        self.base + self.buf.position() as usize
+    }
+
+    pub fn last(&self) -> usize {
+        self.last
+    }
+
+    fn mark(&mut self) {
+        self.last = self.offset();
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> DecodeResult<()> {
+        self.mark();
+        self.buf.read_exact(buf).map_err(|_| DecodeErr::UnexpectedEnd)?;
+
+        Ok(())
     }
 
     pub fn bytes(&mut self, size: usize) -> DecodeResult<Vec<u8>> {
         let mut buf = vec![0u8; size];
 
-        self.buf.read_exact(&mut buf)?;
+        self.read(&mut buf)?;
 
         Ok(buf)
     }
@@ -38,19 +95,19 @@ impl<'a> Reader<'a> {
     pub fn byte_16(&mut self) -> DecodeResult<Lane16> {
         let mut buf = [0u8; 16];
 
-        self.buf.read_exact(&mut buf)?;
+        self.read(&mut buf)?;
 
         Ok(buf)
     }
 
     pub fn not_end(&mut self) -> DecodeResult<bool> {
-        Ok(self.buf.fill_buf().map(|b| !b.is_empty())?)
+        Ok(!self.remaining().is_empty())
     }
 
     pub fn get_u8(&mut self) -> DecodeResult<u8> {
         let mut buf = [0u8; 1];
 
-        self.buf.read_exact(&mut buf)?;
+        self.read(&mut buf)?;
 
         Ok(buf[0])
     }
@@ -59,7 +116,7 @@ impl<'a> Reader<'a> {
     pub fn get_u32(&mut self) -> DecodeResult<u32> {
         let mut buf = [0u8; 4];
 
-        self.buf.read_exact(&mut buf)?;
+        self.read(&mut buf)?;
 
         Ok(u32::from_le_bytes(buf))
     }
@@ -67,7 +124,7 @@ impl<'a> Reader<'a> {
     pub fn get_f32(&mut self) -> DecodeResult<f32> {
         let mut buf = [0u8; 4];
 
-        self.buf.read_exact(&mut buf)?;
+        self.read(&mut buf)?;
 
         Ok(f32::from_le_bytes(buf))
     }
@@ -75,7 +132,7 @@ impl<'a> Reader<'a> {
     pub fn get_f64(&mut self) -> DecodeResult<f64> {
         let mut buf = [0u8; 8];
 
-        self.buf.read_exact(&mut buf)?;
+        self.read(&mut buf)?;
 
         Ok(f64::from_le_bytes(buf))
     }
@@ -88,8 +145,11 @@ impl<'a> Reader<'a> {
     }
 
     pub fn get_leb_u32(&mut self) -> DecodeResult<u32> {
-        let data = self.buf.remaining_slice();
-        let (num, size) = leb128::decode_unsigned(data)?;
+        let data = self.remaining();
+
+        self.mark();
+
+        let (num, size) = leb128::decode_unsigned(data, 32)?;
 
         self.buf.consume(size);
 
@@ -97,8 +157,11 @@ impl<'a> Reader<'a> {
     }
 
     pub fn get_leb_u64(&mut self) -> DecodeResult<u64> {
-        let data = self.buf.remaining_slice();
-        let (num, size) = leb128::decode_unsigned(data)?;
+        let data = self.remaining();
+
+        self.mark();
+
+        let (num, size) = leb128::decode_unsigned(data, 64)?;
 
         self.buf.consume(size);
 
@@ -106,7 +169,10 @@ impl<'a> Reader<'a> {
     }
 
     pub fn get_leb_i32(&mut self) -> DecodeResult<i32> {
-        let data = self.buf.remaining_slice();
+        let data = self.remaining();
+
+        self.mark();
+
         let (num, size) = leb128::decode_signed(data, 32)?;
 
         self.buf.consume(size);
@@ -115,7 +181,10 @@ impl<'a> Reader<'a> {
     }
 
     pub fn get_leb_i64(&mut self) -> DecodeResult<i64> {
-        let data = self.buf.remaining_slice();
+        let data = self.remaining();
+
+        self.mark();
+
         let (num, size) = leb128::decode_signed(data, 64)?;
 
         self.buf.consume(size);
@@ -125,11 +194,18 @@ impl<'a> Reader<'a> {
 
     pub fn get_name(&mut self) -> DecodeResult<String> {
         let bytes = self.seqs()?;
-        let name = String::from_utf8(bytes)?;
+        let name = String::from_utf8(bytes).map_err(|_| DecodeErr::InvalidUtf8)?;
 
         Ok(name)
     }
 
+    fn remaining(&self) -> &'a [u8] {
+        let data = *self.buf.get_ref();
+        let position = (self.buf.position() as usize).min(data.len());
+
+        &data[position..]
+    }
+
     #[inline]
     pub fn remain(&mut self) -> DecodeResult<Vec<u8>> {
         let len = self.buf.get_ref().len();
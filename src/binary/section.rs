@@ -10,6 +10,7 @@ pub type FuncIdx = u32;
 pub type TableIdx = u32;
 pub type MemIdx = u32;
 pub type GlobalIdx = u32;
+pub type TagIdx = u32;
 pub type LocalIdx = u32;
 
 // 规范描述里简写成 l
@@ -36,6 +37,17 @@ pub enum Section {
     Code,
     Data,
     DataCount,
+    Tag,
+}
+
+/// 段在二进制文件中的位置，offset 和 size 只包括段的内容，不包括 id 和长度
+#[derive(Debug, Clone)]
+pub struct SectionRange {
+    pub id: Section,
+    /// id 所在的位置
+    pub start: usize,
+    pub offset: usize,
+    pub size: usize,
 }
 
 impl Section {
@@ -54,21 +66,50 @@ impl Section {
             0x0a => Ok(Self::Code),
             0x0b => Ok(Self::Data),
             0x0c => Ok(Self::DataCount),
+            0x0d => Ok(Self::Tag),
             _ => Err(DecodeErr::UnexpectedSection(v))?,
         }
     }
+
+    /// 段在文件中应有的先后次序，和 id 的大小不一致，自定义段可以出现在任意位置
+    pub fn order(&self) -> u8 {
+        match self {
+            Self::Custom => 0,
+            Self::Type => 1,
+            Self::Import => 2,
+            Self::Function => 3,
+            Self::Table => 4,
+            Self::Memory => 5,
+            Self::Tag => 6,
+            Self::Global => 7,
+            Self::Export => 8,
+            Self::Start => 9,
+            Self::Element => 10,
+            Self::DataCount => 11,
+            Self::Code => 12,
+            Self::Data => 13,
+        }
+    }
+
+    /// 非自定义段要排在上一个非自定义段之后，返回新的上一个段
+    pub fn check_order(&self, last: Section) -> DecodeResult<Section> {
+        match self.order() > last.order() {
+            true => Ok(self.clone()),
+            false => Err(DecodeErr::SectionOutOfOrder(self.clone(), last))?,
+        }
+    }
 }
 
 /// Custom Section
 /// https://webassembly.github.io/spec/core/appendix/custom.html
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct CustomSeg {
     pub name: String,
     pub data: Vec<u8>,
 }
 
 /// Import Section
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ImportSeg {
     pub module: String, // 模块名
     pub name: String,   // 成员名
@@ -82,10 +123,17 @@ pub enum ImportDesc {
     Table(TableType),
     Mem(MemType),
     Global(GlobalType),
+    Tag(TagType),
+}
+
+/// Tag Section，异常处理提案，标签的类型是一个没有结果的函数类型
+#[derive(Debug, Clone)]
+pub struct TagType {
+    pub type_idx: TypeIdx,
 }
 
 /// Global Section
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GlobalSeg {
     pub type_: GlobalType,
     pub init_expr: Expr,
@@ -107,6 +155,7 @@ pub enum ExportDesc {
     Table(TableIdx),
     Mem(MemIdx),
     Global(GlobalIdx),
+    Tag(TagIdx),
 }
 
 pub enum ExternalKind {
@@ -118,7 +167,7 @@ pub enum ExternalKind {
 }
 
 /// Element Section 存放表初始化数据
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ElementSeg {
     pub flag: u32,
     pub mode: ElementMode,
@@ -140,7 +189,7 @@ impl ElementSeg {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ElementMode {
     /// table.init
     Passive,
@@ -157,6 +206,10 @@ pub struct CodeSeg {
     pub size: u32,
     pub locals: Vec<Locals>,
     pub body: Expr,
+    /// 函数体（局部变量声明）在文件中的位置，从文本格式解析时为 0
+    pub offset: usize,
+    /// 按解码顺序排列的指令位置，包括 else 和 end，从文本格式解析时为空
+    pub instr_offsets: Vec<usize>,
 }
 
 impl CodeSeg {
@@ -184,7 +237,7 @@ pub struct Locals {
 }
 
 /// Data Section 存放内存初始化数据
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DataSeg {
     pub flag: u32,
     pub mode: DataMode,
@@ -200,7 +253,7 @@ impl DataSeg {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum DataMode {
     /// 使用 mem.init 指令初始化
     Passive,
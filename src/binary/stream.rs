@@ -0,0 +1,582 @@
+use std::io::{ErrorKind, Read};
+use std::ops::ControlFlow;
+
+use super::decode::Decode;
+use super::errors::{DecodeErr, DecodeError};
+use super::leb128;
+use super::module::{Module, MAGIC, VERSION};
+use super::reader::{DecodeResult, Reader};
+use super::section::{
+    CodeSeg, CustomSeg, DataCountSeg, DataSeg, ElementSeg, ExportSeg, FuncIdx, GlobalSeg, ImportDesc,
+    ImportSeg, Section, SectionRange, TagType, TypeIdx,
+};
+use super::types::{FuncType, MemType, TableType};
+use super::validate::Context;
+
+/// 流式解码时依次产生的事件
+#[derive(Debug, Clone)]
+pub enum Payload {
+    /// 文件头，magic 和版本号都已经检查过
+    Header {
+        version: u32,
+    },
+    /// 段的内容读出来之前先给出它的位置，这时停止就不会再读这个段
+    Section(SectionRange),
+    Custom(CustomSeg),
+    Type(Vec<FuncType>),
+    Import(Vec<ImportSeg>),
+    Function(Vec<TypeIdx>),
+    Table(Vec<TableType>),
+    Memory(Vec<MemType>),
+    Tag(Vec<TagType>),
+    Global(Vec<GlobalSeg>),
+    Export(Vec<ExportSeg>),
+    Start(FuncIdx),
+    Element(Vec<ElementSeg>),
+    DataCount(u32),
+    /// 代码段中函数体的个数，之后每个函数体单独产生一个事件
+    CodeStart(u32),
+    /// func_idx 包括导入的函数
+    Code {
+        func_idx: FuncIdx,
+        code: CodeSeg,
+    },
+    Data(Vec<DataSeg>),
+    /// 所有段都已读完，函数和代码、数据段和 DataCount 的数量也检查过
+    End,
+}
+
+impl Payload {
+    /// 代码段只解码开头的函数体个数，函数体由调用方逐个解码
+    pub(super) fn decode(id: &Section, reader: &mut Reader) -> DecodeResult<Payload> {
+        let payload = match id {
+            Section::Custom => Payload::Custom(CustomSeg::decode(reader)?),
+            Section::Type => Payload::Type(FuncType::decodes(reader)?),
+            Section::Import => Payload::Import(ImportSeg::decodes(reader)?),
+            Section::Function => Payload::Function(TypeIdx::decodes(reader)?),
+            Section::Table => Payload::Table(TableType::decodes(reader)?),
+            Section::Memory => Payload::Memory(MemType::decodes(reader)?),
+            Section::Global => Payload::Global(GlobalSeg::decodes(reader)?),
+            Section::Export => Payload::Export(ExportSeg::decodes(reader)?),
+            Section::Start => Payload::Start(reader.get_leb_u32()?),
+            Section::Element => Payload::Element(ElementSeg::decodes(reader)?),
+            Section::Code => Payload::CodeStart(reader.get_leb_u32()?),
+            Section::Data => Payload::Data(DataSeg::decodes(reader)?),
+            Section::DataCount => Payload::DataCount(reader.get_leb_u32()?),
+            Section::Tag => Payload::Tag(TagType::decodes(reader)?),
+        };
+
+        Ok(payload)
+    }
+}
+
+/// 回调返回 Break 时停止解码
+macro_rules! emit {
+    ($f:expr, $payload:expr) => {
+        if $f($payload).is_break() {
+            return Ok(ControlFlow::Break(()));
+        }
+    };
+}
+
+/// 从 io::Read 中逐段解码模块，每读完一段（代码段则是每个函数体）就交给回调
+///
+/// 只保留代码段之前的各段用于校验和计算函数索引，内存占用取决于最大的段或函数体，
+/// 而不是整个文件；回调返回 ControlFlow::Break 时立即停止，不再读取后面的内容
+pub struct StreamDecoder<R: Read> {
+    src: R,
+    /// 已经读取的字节数，也就是当前在文件中的位置
+    offset: usize,
+    /// 最近读取的值的位置，出错时用来定位
+    last: usize,
+    /// 当前段的结尾，读代码段时不能越过它
+    limit: usize,
+    /// 读到代码段时校验之前的各段，函数体读到一个校验一个
+    validate: bool,
+}
+
+impl<R: Read> StreamDecoder<R> {
+    pub fn new(src: R) -> Self {
+        Self {
+            src,
+            offset: 0,
+            last: 0,
+            limit: usize::MAX,
+            validate: false,
+        }
+    }
+
+    /// 解码的同时校验，出错时返回 ValidateErr，这时后面的段可能还没有读到
+    pub fn with_validation(src: R) -> Self {
+        Self {
+            validate: true,
+            ..Self::new(src)
+        }
+    }
+
+    /// 出错时带上位置，以及所在的段和函数
+    pub fn decode(&mut self, mut f: impl FnMut(Payload) -> ControlFlow<()>) -> DecodeResult<()> {
+        let decoded = self.payloads(&mut f);
+
+        decoded.map(|_| ()).map_err(|err| DecodeError::at(err, self.last))
+    }
+
+    fn payloads(
+        &mut self,
+        f: &mut impl FnMut(Payload) -> ControlFlow<()>,
+    ) -> DecodeResult<ControlFlow<()>> {
+        match self.get_u32()? {
+            MAGIC => (),
+            magic => Err(DecodeErr::MagicUnMatch(magic))?,
+        };
+        match self.get_u32()? {
+            VERSION => (),
+            version => Err(DecodeErr::VersionUnMatch(version))?,
+        };
+
+        emit!(f, Payload::Header { version: VERSION });
+
+        // 代码段之前的各段，以及代码段和数据段中项的个数
+        let mut module = Module::new();
+        let mut sec_counts: Vec<usize> = vec![0; 14];
+        let (mut codes, mut datas) = (0, 0);
+        let mut validated = false;
+        let mut last = Section::Custom;
+
+        while let Some(i) = self.next_byte()? {
+            let start = self.offset - 1;
+            let id = Section::from_u8(i)?;
+
+            if id != Section::Custom && sec_counts[i as usize] >= 1 {
+                Err(DecodeErr::MultipleSection(id.clone(), sec_counts[i as usize]))?
+            }
+            // 代码段之后只校验数据段，顺序不对的段会漏掉校验
+            if id != Section::Custom {
+                last = id.check_order(last)?;
+            }
+
+            let size = self.get_leb_u32()? as usize;
+            let range = SectionRange {
+                id: id.clone(),
+                start,
+                offset: self.offset,
+                size,
+            };
+
+            sec_counts[i as usize] += 1;
+            emit!(f, Payload::Section(range.clone()));
+
+            // 前面的段都读完了才能校验
+            if self.validate && !validated && matches!(id, Section::Code | Section::Data) {
+                validated = true;
+                context(&module)?.validate_sections()?;
+            }
+
+            let payload = match id {
+                Section::Code => {
+                    let decoded = self.codes(&range, &module, f);
+                    let (flow, total) = decoded.map_err(|err| {
+                        DecodeError::within(DecodeError::at(err, self.last), &id, None)
+                    })?;
+
+                    if flow.is_break() {
+                        return Ok(flow);
+                    }
+
+                    codes = total;
+                    continue;
+                }
+                _ => {
+                    let decoded = self.section(&range, module.data_counat_sec);
+
+                    decoded
+                        .map_err(|err| DecodeError::within(DecodeError::at(err, self.last), &id, None))?
+                }
+            };
+
+            match &payload {
+                Payload::Custom(_) => {}
+                Payload::Data(data_sec) => {
+                    datas = data_sec.len();
+
+                    if self.validate {
+                        context(&module)?.validates(data_sec)?;
+                    }
+                }
+                _ => module.add(payload.clone()),
+            }
+
+            emit!(f, payload);
+        }
+
+        module
+            .check_counts(codes, datas)
+            .map_err(|err| DecodeError::at(err, self.offset))?;
+
+        if self.validate && !validated {
+            context(&module)?.validate_sections()?;
+        }
+
+        emit!(f, Payload::End);
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// 代码段以外的段整个读出来再解码
+    fn section(&mut self, range: &SectionRange, data_count: DataCountSeg) -> DecodeResult<Payload> {
+        let data = self.exact(range.size)?;
+        let mut reader = Reader::new(&data, data_count).starting_at(range.offset);
+        let decoded = Payload::decode(&range.id, &mut reader);
+        let payload = decoded.map_err(|err| DecodeError::at(err, reader.last()))?;
+
+        if reader.not_end()? {
+            Err(DecodeError::at(
+                DecodeErr::SectionSizeMismatch.into(),
+                reader.offset(),
+            ))?;
+        }
+
+        Ok(payload)
+    }
+
+    /// 代码段逐个读取函数体，返回函数体的个数
+    fn codes(
+        &mut self,
+        range: &SectionRange,
+        module: &Module,
+        f: &mut impl FnMut(Payload) -> ControlFlow<()>,
+    ) -> DecodeResult<(ControlFlow<()>, usize)> {
+        let imported = module
+            .import_sec
+            .iter()
+            .filter(|import| matches!(import.desc, ImportDesc::Func(_)))
+            .count() as u32;
+        let ctx = match self.validate {
+            true => Some(context(module)?),
+            false => None,
+        };
+
+        self.limit = range.offset + range.size;
+
+        let total = self.get_leb_u32()?;
+
+        if f(Payload::CodeStart(total)).is_break() {
+            return Ok((ControlFlow::Break(()), 0));
+        }
+
+        for i in 0..total {
+            let func_idx = imported + i;
+            let decoded = self.code(module.data_counat_sec);
+            let code = decoded.map_err(|err| {
+                DecodeError::within(DecodeError::at(err, self.last), &Section::Code, Some(func_idx))
+            })?;
+
+            if let Some(ctx) = &ctx {
+                ctx.validate_code(func_idx, &code)?;
+            }
+
+            if f(Payload::Code { func_idx, code }).is_break() {
+                return Ok((ControlFlow::Break(()), 0));
+            }
+        }
+
+        if self.offset != self.limit {
+            self.last = self.offset;
+            Err(DecodeErr::SectionSizeMismatch)?;
+        }
+
+        self.limit = usize::MAX;
+
+        Ok((ControlFlow::Continue(()), total as usize))
+    }
+
+    /// 函数体连同开头的长度一起读出来，按原来的位置解码
+    fn code(&mut self, data_count: DataCountSeg) -> DecodeResult<CodeSeg> {
+        let start = self.offset;
+        let (leb, len) = self.leb_bytes()?;
+        let (size, _) = leb128::decode_unsigned(&leb[..len], 32)?;
+        let mut data = leb[..len].to_vec();
+
+        self.read_to(size as usize, &mut data)?;
+
+        CodeSeg::decode(&mut Reader::new(&data, data_count).starting_at(start))
+    }
+}
+
+/// 读取原始字节，位置相对于整个文件
+impl<R: Read> StreamDecoder<R> {
+    /// 最多读 n 个字节，不会越过当前段的结尾
+    fn bytes(&mut self, n: usize) -> DecodeResult<Vec<u8>> {
+        let mut buf = vec![];
+
+        self.read_to(n, &mut buf)?;
+
+        Ok(buf)
+    }
+
+    /// 追加到 buf 的末尾，按实际读到的数据分配内存，长度字段被改坏时也不会一次申请过多
+    fn read_to(&mut self, n: usize, buf: &mut Vec<u8>) -> DecodeResult<()> {
+        let n = n.min(self.limit.saturating_sub(self.offset));
+
+        self.last = self.offset;
+
+        let read = (&mut self.src)
+            .take(n as u64)
+            .read_to_end(buf)
+            .map_err(DecodeErr::FileRead)?;
+
+        self.offset += read;
+
+        Ok(())
+    }
+
+    fn exact(&mut self, n: usize) -> DecodeResult<Vec<u8>> {
+        let last = self.offset;
+        let buf = self.bytes(n)?;
+
+        self.last = last;
+
+        if buf.len() < n {
+            Err(DecodeErr::UnexpectedEnd)?
+        }
+
+        Ok(buf)
+    }
+
+    /// 段与段之间读到文件结尾时返回 None，逐字节读取时不分配内存
+    fn next_byte(&mut self) -> DecodeResult<Option<u8>> {
+        let mut byte = [0; 1];
+
+        self.last = self.offset;
+
+        if self.offset >= self.limit {
+            return Ok(None);
+        }
+
+        match self.src.read_exact(&mut byte) {
+            Ok(()) => {
+                self.offset += 1;
+
+                Ok(Some(byte[0]))
+            }
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => Ok(None),
+            Err(err) => Err(DecodeErr::FileRead(err))?,
+        }
+    }
+
+    fn get_u32(&mut self) -> DecodeResult<u32> {
+        let buf = self.exact(4)?;
+
+        Ok(u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]))
+    }
+
+    /// LEB128 编码的 u32 最多 5 个字节，读到最高位为 0 的字节为止，返回读到的字节和个数
+    fn leb_bytes(&mut self) -> DecodeResult<([u8; 5], usize)> {
+        let start = self.offset;
+        let mut data = [0; 5];
+        let mut len = 0;
+
+        while len < data.len() {
+            match self.next_byte()? {
+                Some(b) => data[len] = b,
+                None => break,
+            }
+
+            len += 1;
+
+            if data[len - 1] & 0b1000_0000 == 0 {
+                break;
+            }
+        }
+
+        self.last = start;
+
+        Ok((data, len))
+    }
+
+    fn get_leb_u32(&mut self) -> DecodeResult<u32> {
+        let (data, len) = self.leb_bytes()?;
+        let (num, _) = leb128::decode_unsigned(&data[..len], 32)?;
+
+        Ok(num as u32)
+    }
+}
+
+/// 流式解码时还没有读到数据段，数据段的个数以 DataCount 为准
+fn context(module: &Module) -> DecodeResult<Context<'_>> {
+    let mut ctx = Context::new(module)?;
+
+    ctx.datas = module.data_counat_sec.unwrap_or(0) as usize;
+
+    Ok(ctx)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, Read};
+    use std::ops::ControlFlow;
+
+    use super::{Payload, StreamDecoder};
+    use crate::binary::encode::Encode;
+    use crate::binary::errors::{DecodeErr, DecodeError, ValidateErr};
+    use crate::binary::module::Module;
+
+    /// 每次只给出一个字节，模拟数据分批到达
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((b, rest)), Some(dst)) => {
+                    *dst = *b;
+                    self.0 = rest;
+
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    fn encode(text: &str) -> Vec<u8> {
+        Module::from_text(text).unwrap().encode()
+    }
+
+    /// 依次记录事件，函数体只记下索引
+    fn collect(
+        decoder: &mut StreamDecoder<impl Read>,
+        stop: Option<u32>,
+    ) -> (Vec<String>, Result<(), String>) {
+        let mut events = vec![];
+        let decoded = decoder.decode(|payload| {
+            let event = match &payload {
+                Payload::Header { .. } => "header".to_string(),
+                Payload::Section(range) => format!("{:?}", range.id),
+                Payload::Code { func_idx, .. } => format!("code {}", func_idx),
+                Payload::End => "end".to_string(),
+                _ => return ControlFlow::Continue(()),
+            };
+
+            events.push(event);
+
+            match Some(events.len() as u32) == stop {
+                true => ControlFlow::Break(()),
+                false => ControlFlow::Continue(()),
+            }
+        });
+
+        (events, decoded.map_err(|err| err.to_string()))
+    }
+
+    #[test]
+    fn test_stream_decode() {
+        let data = encode(
+            r#"(module
+                 (import "env" "f" (func))
+                 (memory 1)
+                 (func (export "a") (result i32) (i32.const 1))
+                 (func (export "b") (param i64) (result i64) (local.get 0))
+                 (data (i32.const 0) "abc"))"#,
+        );
+
+        // 分批到达的数据和整个文件解码出的模块一致
+        let module = Module::from_reader(Trickle(&data)).unwrap();
+
+        assert_eq!(module.encode(), data);
+
+        let (events, decoded) = collect(&mut StreamDecoder::with_validation(Trickle(&data)), None);
+
+        assert!(decoded.is_ok());
+        assert_eq!(
+            events,
+            [
+                "header", "Type", "Import", "Function", "Memory", "Export", "Code", "code 1", "code 2",
+                "Data", "end"
+            ]
+            .map(String::from)
+        );
+
+        // 回调要求停止后不再读取后面的函数体
+        let mut src = Trickle(&data);
+        let (events, decoded) = collect(&mut StreamDecoder::new(&mut src), Some(8));
+
+        assert!(decoded.is_ok());
+        assert_eq!(events.last().unwrap(), "code 1");
+        assert!(!src.0.is_empty());
+    }
+
+    #[test]
+    fn test_stream_validate() {
+        let data = encode(
+            r#"(module
+                 (func (result i32) (i32.const 1))
+                 (func (result i32) (i64.const 1))
+                 (func))"#,
+        );
+
+        // 第二个函数体校验失败，第三个不会被读到
+        let (events, decoded) = collect(&mut StreamDecoder::with_validation(Trickle(&data)), None);
+
+        assert!(decoded.is_err());
+        assert_eq!(events.last().unwrap(), "code 0");
+
+        let err = StreamDecoder::with_validation(Trickle(&data))
+            .decode(|_| ControlFlow::Continue(()))
+            .unwrap_err();
+
+        assert!(err.is::<ValidateErr>());
+
+        // 不校验时可以完整读完，截断的文件报告出错的位置
+        let (events, decoded) = collect(&mut StreamDecoder::new(Trickle(&data)), None);
+
+        assert!(decoded.is_ok());
+        assert_eq!(events.last().unwrap(), "end");
+
+        let err = StreamDecoder::new(Trickle(&data[..data.len() - 2]))
+            .decode(|_| ControlFlow::Continue(()))
+            .unwrap_err();
+        let err = err.downcast_ref::<DecodeError>().unwrap();
+
+        assert!(matches!(err.kind, DecodeErr::UnexpectedEnd));
+        assert_eq!(err.func_idx, Some(2));
+    }
+
+    #[test]
+    fn test_section_order() {
+        // 代码段之后的导出段导出了不存在的函数 99
+        let data = [
+            b"\0asm\x01\0\0\0".as_slice(),
+            &[0x0a, 0x01, 0x00],
+            &[0x07, 0x05, 0x01, 0x01, b'a', 0x00, 0x63],
+        ]
+        .concat();
+
+        for decoded in [
+            StreamDecoder::with_validation(Trickle(&data)).decode(|_| ControlFlow::Continue(())),
+            StreamDecoder::new(Trickle(&data)).decode(|_| ControlFlow::Continue(())),
+            Module::from_data(data.clone()).map(|_| ()),
+        ] {
+            let err = decoded.unwrap_err();
+            let err = err.downcast_ref::<DecodeError>().unwrap();
+
+            assert!(matches!(err.kind, DecodeErr::SectionOutOfOrder(..)));
+            assert_eq!(err.offset, 11);
+        }
+
+        // 自定义段可以出现在任意位置，DataCount 排在代码段之前
+        let data = [
+            b"\0asm\x01\0\0\0".as_slice(),
+            &[0x00, 0x02, 0x01, b'x'],
+            &[0x0c, 0x01, 0x00],
+            &[0x0a, 0x01, 0x00],
+            &[0x00, 0x02, 0x01, b'y'],
+            &[0x0b, 0x01, 0x00],
+        ]
+        .concat();
+
+        assert!(Module::from_data(data.clone()).is_ok());
+        assert!(StreamDecoder::with_validation(Trickle(&data))
+            .decode(|_| ControlFlow::Continue(()))
+            .is_ok());
+    }
+}
@@ -1,7 +1,8 @@
+use std::fmt;
+
 use super::errors::DecodeErr;
 use super::instruction::BlockType;
 use super::reader::DecodeResult;
-use super::section::MaybeU32;
 
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Default, PartialEq)]
@@ -9,6 +10,27 @@ pub enum RefType {
     #[default]
     FuncRef = 0x70,
     ExternRef = 0x6f,
+    ExnRef = 0x69,
+}
+
+impl RefType {
+    /// ref.null 的 heaptype
+    pub fn from_heap_type(v: u64) -> Option<Self> {
+        match v {
+            0x70 => Some(Self::FuncRef),
+            0x6f => Some(Self::ExternRef),
+            0x69 => Some(Self::ExnRef),
+            _ => None,
+        }
+    }
+
+    pub fn as_val_type(&self) -> ValType {
+        match self {
+            Self::FuncRef => ValType::FuncRef,
+            Self::ExternRef => ValType::ExternRef,
+            Self::ExnRef => ValType::ExnRef,
+        }
+    }
 }
 
 #[repr(u8)]
@@ -21,36 +43,43 @@ pub enum ValType {
     V128 = 0x7b,    // 向量
     FuncRef = 0x70, // 引用
     ExternRef = 0x6f,
+    ExnRef = 0x69,
     NullRef = 0x6b,
 }
 
-impl From<u8> for ValType {
-    fn from(v: u8) -> Self {
+impl TryFrom<u8> for ValType {
+    type Error = DecodeErr;
+
+    fn try_from(v: u8) -> Result<Self, DecodeErr> {
         match v {
-            0x7f => Self::I32,
-            0x7e => Self::I64,
-            0x7d => Self::F32,
-            0x7c => Self::F64,
-            0x7b => Self::V128,
-            0x70 => Self::FuncRef,
-            0x6f => Self::ExternRef,
-            0x6b => Self::NullRef,
-            _ => panic!("无效的数值类型：{}", v),
+            0x7f => Ok(Self::I32),
+            0x7e => Ok(Self::I64),
+            0x7d => Ok(Self::F32),
+            0x7c => Ok(Self::F64),
+            0x7b => Ok(Self::V128),
+            0x70 => Ok(Self::FuncRef),
+            0x6f => Ok(Self::ExternRef),
+            0x69 => Ok(Self::ExnRef),
+            0x6b => Ok(Self::NullRef),
+            _ => Err(DecodeErr::InvalidValType(v)),
         }
     }
 }
 
 impl ValType {
-    fn is_num_type(&self) -> bool {
+    pub fn is_num_type(&self) -> bool {
         matches!(self, Self::I32 | Self::I64 | Self::F32 | Self::F64)
     }
 
-    fn is_vec_type(&self) -> bool {
+    pub fn is_vec_type(&self) -> bool {
         matches!(self, Self::V128)
     }
 
     pub fn is_ref_type(&self) -> bool {
-        matches!(self, Self::FuncRef | Self::ExternRef | Self::NullRef)
+        matches!(
+            self,
+            Self::FuncRef | Self::ExternRef | Self::ExnRef | Self::NullRef
+        )
     }
 }
 
@@ -85,6 +114,13 @@ impl FuncType {
     }
 }
 
+/// 形如 [I32, I64] -> [F32]
+impl fmt::Display for FuncType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} -> {:?}", self.params, self.results)
+    }
+}
+
 impl From<&BlockType> for FuncType {
     fn from(v: &BlockType) -> Self {
         match v {
@@ -93,24 +129,43 @@ impl From<&BlockType> for FuncType {
             BlockType::F32 => Self::new_result(ValType::F32),
             BlockType::F64 => Self::new_result(ValType::F64),
             BlockType::V128 => Self::new_result(ValType::V128),
+            BlockType::FuncRef => Self::new_result(ValType::FuncRef),
             BlockType::ExternRef => Self::new_result(ValType::ExternRef),
+            BlockType::ExnRef => Self::new_result(ValType::ExnRef),
             BlockType::Empty => Self::default(),
             BlockType::TypeIdx(_) => Self::default(),
         }
     }
 }
 
+/// memory64 和 table64 的大小可以超过 u32
 #[derive(Debug, Default, Clone)]
 pub struct Limits {
-    pub min: u32,
-    pub max: MaybeU32,
+    pub min: u64,
+    pub max: Option<u64>,
+    /// 地址和大小是否为 i64
+    pub is64: bool,
+    /// 是否为共享内存，只有内存可以共享
+    pub shared: bool,
 }
 
 pub type MemType = Limits;
 
 impl Limits {
+    /// 访存地址、memory.size 等指令结果的类型
+    pub fn addr_type(&self) -> ValType {
+        match self.is64 {
+            true => ValType::I64,
+            false => ValType::I32,
+        }
+    }
+
     // lhs 导入的，rhs 当前模块定义
     pub fn incompatible(&self, rhs: &Self) -> bool {
+        if self.is64 != rhs.is64 || self.shared != rhs.shared {
+            return true;
+        }
+
         // 导入的 min 不能比当前模块定义的要小
         if self.min < rhs.min {
             return true;
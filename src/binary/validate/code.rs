@@ -0,0 +1,1351 @@
+use super::{Context, ValidateResult};
+use crate::binary::errors::ValidateErr;
+use crate::binary::instruction::{BlockType, Catch, Instruction, MemoryArg};
+use crate::binary::section::{CodeSeg, Expr, FuncIdx, LabelIdx};
+use crate::binary::types::{FuncType, RefType, ValType};
+
+/// 函数体校验，操作数栈 + 控制栈
+/// https://webassembly.github.io/spec/core/appendix/algorithm.html
+pub struct FuncValidator<'a> {
+    ctx: &'a Context<'a>,
+    func_idx: FuncIdx,
+    code: &'a CodeSeg,
+    /// 参数 + 局部变量
+    locals: Vec<ValType>,
+    results: Vec<ValType>,
+    /// None 表示类型未知（不可达代码里弹出的值）
+    vals: Vec<Option<ValType>>,
+    ctrls: Vec<CtrlFrame>,
+    /// 当前指令在函数体里的序号，从 0 开始按二进制顺序计数，包含 else 和 end
+    instr_idx: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CtrlKind {
+    Block,
+    Loop,
+    If,
+    Else,
+}
+
+#[derive(Debug)]
+struct CtrlFrame {
+    kind: CtrlKind,
+    start_types: Vec<ValType>,
+    end_types: Vec<ValType>,
+    height: usize,
+    unreachable: bool,
+}
+
+impl CtrlFrame {
+    fn label_types(&self) -> &[ValType] {
+        match self.kind {
+            CtrlKind::Loop => &self.start_types,
+            _ => &self.end_types,
+        }
+    }
+}
+
+impl<'a> FuncValidator<'a> {
+    pub fn new(ctx: &'a Context<'a>, func_idx: FuncIdx, code: &'a CodeSeg) -> Self {
+        let func_type = &ctx.funcs[func_idx as usize];
+        let mut locals = func_type.params.clone();
+
+        for local in &code.locals {
+            locals.extend((0..local.n).map(|_| local.value_type));
+        }
+
+        Self {
+            ctx,
+            func_idx,
+            code,
+            locals,
+            results: func_type.results.clone(),
+            vals: vec![],
+            ctrls: vec![],
+            instr_idx: 0,
+        }
+    }
+
+    pub fn validate(mut self) -> ValidateResult {
+        self.push_ctrl(CtrlKind::Block, vec![], self.results.clone());
+        self.validate_expr(&self.code.body)?;
+        self.end()
+    }
+
+    fn validate_expr(&mut self, expr: &Expr) -> ValidateResult {
+        for instr in expr {
+            self.validate_instr(instr)?;
+        }
+
+        Ok(())
+    }
+
+    fn push_val(&mut self, val: Option<ValType>) {
+        self.vals.push(val);
+    }
+
+    fn push_vals(&mut self, types: &[ValType]) {
+        self.vals.extend(types.iter().map(|type_| Some(*type_)));
+    }
+
+    fn pop_val(&mut self) -> ValidateResult<Option<ValType>> {
+        let frame = self.ctrls.last().unwrap();
+
+        if self.vals.len() == frame.height {
+            return match frame.unreachable {
+                true => Ok(None),
+                false => Err(ValidateErr::OperandStackEmpty(self.func_idx, self.instr_idx)),
+            };
+        }
+
+        Ok(self.vals.pop().unwrap())
+    }
+
+    fn pop_expect(&mut self, expect: ValType) -> ValidateResult<Option<ValType>> {
+        match self.pop_val()? {
+            Some(actual) if actual != expect => Err(ValidateErr::OperandTypeMismatch(
+                self.func_idx,
+                self.instr_idx,
+                expect,
+                actual,
+            )),
+            Some(actual) => Ok(Some(actual)),
+            None => Ok(Some(expect)),
+        }
+    }
+
+    fn pop_vals(&mut self, types: &[ValType]) -> ValidateResult<Vec<Option<ValType>>> {
+        let mut popped = vec![];
+
+        for type_ in types.iter().rev() {
+            popped.insert(0, self.pop_expect(*type_)?);
+        }
+
+        Ok(popped)
+    }
+
+    fn pop_ref(&mut self) -> ValidateResult<Option<ValType>> {
+        match self.pop_val()? {
+            Some(actual) if !actual.is_ref_type() => {
+                Err(ValidateErr::ExpectRefType(self.func_idx, self.instr_idx, actual))
+            }
+            actual => Ok(actual),
+        }
+    }
+
+    fn push_ctrl(&mut self, kind: CtrlKind, start_types: Vec<ValType>, end_types: Vec<ValType>) {
+        let height = self.vals.len();
+
+        self.push_vals(&start_types);
+        self.ctrls.push(CtrlFrame {
+            kind,
+            start_types,
+            end_types,
+            height,
+            unreachable: false,
+        });
+    }
+
+    fn pop_ctrl(&mut self) -> ValidateResult<CtrlFrame> {
+        let end_types = self.ctrls.last().unwrap().end_types.clone();
+
+        self.pop_vals(&end_types)?;
+
+        let frame = self.ctrls.pop().unwrap();
+
+        if self.vals.len() != frame.height {
+            Err(ValidateErr::OperandStackRemain(
+                self.func_idx,
+                self.instr_idx,
+                self.vals.len() - frame.height,
+            ))?;
+        }
+
+        Ok(frame)
+    }
+
+    fn unreachable(&mut self) {
+        let frame = self.ctrls.last_mut().unwrap();
+
+        self.vals.truncate(frame.height);
+        frame.unreachable = true;
+    }
+
+    /// end 指令
+    fn end(&mut self) -> ValidateResult {
+        let frame = self.pop_ctrl()?;
+
+        self.push_vals(&frame.end_types);
+        self.instr_idx += 1;
+
+        Ok(())
+    }
+
+    fn label_types(&self, idx: LabelIdx) -> ValidateResult<Vec<ValType>> {
+        match (self.ctrls.len() as u32).checked_sub(idx + 1) {
+            Some(i) => Ok(self.ctrls[i as usize].label_types().to_vec()),
+            None => Err(ValidateErr::UnknownLabel(self.func_idx, self.instr_idx, idx)),
+        }
+    }
+
+    fn block_type(&self, type_: &BlockType) -> ValidateResult<(Vec<ValType>, Vec<ValType>)> {
+        let result = match type_ {
+            BlockType::I32 => ValType::I32,
+            BlockType::I64 => ValType::I64,
+            BlockType::F32 => ValType::F32,
+            BlockType::F64 => ValType::F64,
+            BlockType::V128 => ValType::V128,
+            BlockType::FuncRef => ValType::FuncRef,
+            BlockType::ExternRef => ValType::ExternRef,
+            BlockType::ExnRef => ValType::ExnRef,
+            BlockType::Empty => return Ok((vec![], vec![])),
+            BlockType::TypeIdx(idx) => match self.ctx.types.get(*idx as usize) {
+                Some(func_type) => return Ok((func_type.params.clone(), func_type.results.clone())),
+                None => Err(ValidateErr::UnknownType(
+                    self.func_idx,
+                    self.instr_idx,
+                    *idx as u32,
+                ))?,
+            },
+        };
+
+        Ok((vec![], vec![result]))
+    }
+
+    fn local(&self, idx: u32) -> ValidateResult<ValType> {
+        match self.locals.get(idx as usize) {
+            Some(type_) => Ok(*type_),
+            None => Err(ValidateErr::UnknownLocal(self.func_idx, self.instr_idx, idx)),
+        }
+    }
+
+    fn table(&self, idx: u32) -> ValidateResult<ValType> {
+        match self.ctx.tables.get(idx as usize) {
+            Some(table) => Ok(table.elem_type.as_val_type()),
+            None => Err(ValidateErr::UnknownTable(self.func_idx, self.instr_idx, idx)),
+        }
+    }
+
+    /// table64 的索引和大小为 i64
+    fn table_addr(&self, idx: u32) -> ValidateResult<ValType> {
+        match self.ctx.tables.get(idx as usize) {
+            Some(table) => Ok(table.limits.addr_type()),
+            None => Err(ValidateErr::UnknownTable(self.func_idx, self.instr_idx, idx)),
+        }
+    }
+
+    /// call_indirect 的表只能是 funcref 类型，同时返回表的索引类型
+    fn indirect_type(&self, type_idx: u32, table_idx: u32) -> ValidateResult<(&'a FuncType, ValType)> {
+        let elem_type = self.table(table_idx)?;
+
+        if elem_type != ValType::FuncRef {
+            Err(ValidateErr::TableTypeMismatch(
+                self.func_idx,
+                self.instr_idx,
+                ValType::FuncRef,
+                elem_type,
+            ))?;
+        }
+
+        match self.ctx.types.get(type_idx as usize) {
+            Some(func_type) => Ok((func_type, self.table_addr(table_idx)?)),
+            None => Err(ValidateErr::UnknownType(self.func_idx, self.instr_idx, type_idx)),
+        }
+    }
+
+    /// 被调用函数的结果直接作为当前函数的结果返回，之后的指令不可达
+    fn return_call(&mut self, func_type: &FuncType) -> ValidateResult {
+        if func_type.results != self.results {
+            Err(ValidateErr::ReturnCallResultMismatch(
+                self.func_idx,
+                self.instr_idx,
+                func_type.results.clone(),
+                self.results.clone(),
+            ))?;
+        }
+
+        self.pop_vals(&func_type.params)?;
+        self.unreachable();
+
+        Ok(())
+    }
+
+    fn tag(&self, idx: u32) -> ValidateResult<&'a FuncType> {
+        match self.ctx.tags.get(idx as usize) {
+            Some(func_type) => Ok(func_type),
+            None => Err(ValidateErr::UnknownTag(self.func_idx, self.instr_idx, idx)),
+        }
+    }
+
+    /// catch 子句跳出 try_table，在 try_table 之外检查标签的类型
+    fn catch(&self, catch: &Catch) -> ValidateResult {
+        let mut types = match catch.tag() {
+            Some(idx) => self.tag(idx)?.params.clone(),
+            None => vec![],
+        };
+
+        if catch.is_ref() {
+            types.push(ValType::ExnRef);
+        }
+
+        let label = catch.label();
+        let label_types = self.label_types(label)?;
+
+        match types == label_types {
+            true => Ok(()),
+            false => Err(ValidateErr::CatchLabelMismatch(
+                self.func_idx,
+                self.instr_idx,
+                label,
+                types,
+                label_types,
+            )),
+        }
+    }
+
+    fn elem(&self, idx: u32) -> ValidateResult<ValType> {
+        match self.ctx.elems.get(idx as usize) {
+            Some(type_) => Ok(*type_),
+            None => Err(ValidateErr::UnknownElem(self.func_idx, self.instr_idx, idx)),
+        }
+    }
+
+    /// 返回内存的地址类型
+    fn memory(&self, idx: u32) -> ValidateResult<ValType> {
+        match self.ctx.mems.get(idx as usize) {
+            Some(mem) => Ok(mem.addr_type()),
+            None => Err(ValidateErr::UnknownMemory(self.func_idx, self.instr_idx, idx)),
+        }
+    }
+
+    /// 二进制格式中数据段排在代码段之后，用到数据段索引时要先有 DataCount 段
+    fn data(&self, idx: u32) -> ValidateResult {
+        if self.ctx.module.data_counat_sec.is_none() {
+            Err(ValidateErr::DataCountRequired(self.func_idx, self.instr_idx))?;
+        }
+
+        match (idx as usize) < self.ctx.datas {
+            true => Ok(()),
+            false => Err(ValidateErr::UnknownData(self.func_idx, self.instr_idx, idx)),
+        }
+    }
+
+    /// 内存是否存在，对齐不能超过自然对齐，返回地址类型
+    fn mem_arg(&self, arg: &MemoryArg, bytes: u32) -> ValidateResult<ValType> {
+        let addr_type = self.memory(arg.mem_idx)?;
+
+        if arg.align >= 32 || 1u32 << arg.align > bytes {
+            Err(ValidateErr::AlignTooLarge(
+                self.func_idx,
+                self.instr_idx,
+                arg.align,
+                bytes,
+            ))?;
+        }
+
+        if addr_type == ValType::I32 && arg.offset > u32::MAX as u64 {
+            Err(ValidateErr::OffsetTooLarge(
+                self.func_idx,
+                self.instr_idx,
+                arg.offset,
+            ))?;
+        }
+
+        Ok(addr_type)
+    }
+
+    fn lane(&self, lane: u8, total: u8) -> ValidateResult {
+        match lane < total {
+            true => Ok(()),
+            false => Err(ValidateErr::InvalidLaneIdx(
+                self.func_idx,
+                self.instr_idx,
+                lane,
+                total,
+            )),
+        }
+    }
+
+    /// 弹出参数，压入结果
+    fn op(&mut self, params: &[ValType], results: &[ValType]) -> ValidateResult {
+        self.pop_vals(params)?;
+        self.push_vals(results);
+
+        Ok(())
+    }
+
+    fn validate_instr(&mut self, instr: &Instruction) -> ValidateResult {
+        use ValType::{F32, F64, I32, I64, V128};
+
+        if let Some((params, results)) = op_type(instr) {
+            self.op(params, results)?;
+            self.instr_idx += 1;
+
+            return Ok(());
+        }
+
+        match instr {
+            Instruction::Unreachable => self.unreachable(),
+            Instruction::Nop => {}
+            // else 和 end 已经变成了块的结构，单独出现时没有可以结束的 if 或块
+            Instruction::Else => Err(ValidateErr::ElseWithoutIf(self.func_idx, self.instr_idx))?,
+            Instruction::End => Err(ValidateErr::UnmatchedEnd(self.func_idx, self.instr_idx))?,
+            Instruction::Block(block) | Instruction::Loop(block) => {
+                let kind = match instr {
+                    Instruction::Loop(_) => CtrlKind::Loop,
+                    _ => CtrlKind::Block,
+                };
+                let (params, results) = self.block_type(&block.type_)?;
+
+                self.pop_vals(&params)?;
+                self.push_ctrl(kind, params, results);
+                self.instr_idx += 1;
+                self.validate_expr(&block.expr)?;
+
+                return self.end();
+            }
+            Instruction::TryTable(block) => {
+                let (params, results) = self.block_type(&block.type_)?;
+
+                for catch in &block.catches {
+                    self.catch(catch)?;
+                }
+
+                self.pop_vals(&params)?;
+                self.push_ctrl(CtrlKind::Block, params, results);
+                self.instr_idx += 1;
+                self.validate_expr(&block.expr)?;
+
+                return self.end();
+            }
+            Instruction::If(block) => {
+                let (params, results) = self.block_type(&block.type_)?;
+
+                self.pop_expect(I32)?;
+                self.pop_vals(&params)?;
+                self.push_ctrl(CtrlKind::If, params, results);
+                self.instr_idx += 1;
+                self.validate_expr(&block.if_expr)?;
+
+                // 没有 else 分支时，相当于一个空的 else，要求参数和结果类型一致
+                let frame = self.pop_ctrl()?;
+
+                self.push_ctrl(CtrlKind::Else, frame.start_types, frame.end_types);
+
+                if !block.else_expr.is_empty() {
+                    self.instr_idx += 1;
+                    self.validate_expr(&block.else_expr)?;
+                }
+
+                return self.end();
+            }
+            Instruction::Br(idx) => {
+                let types = self.label_types(*idx)?;
+
+                self.pop_vals(&types)?;
+                self.unreachable();
+            }
+            Instruction::BrIf(idx) => {
+                let types = self.label_types(*idx)?;
+
+                self.pop_expect(I32)?;
+                self.op(&types, &types)?;
+            }
+            Instruction::BrTable(arg) => {
+                self.pop_expect(I32)?;
+
+                let default = self.label_types(arg.default)?;
+
+                for idx in &arg.labels {
+                    let types = self.label_types(*idx)?;
+
+                    if types.len() != default.len() {
+                        Err(ValidateErr::BrTableArityMismatch(
+                            self.func_idx,
+                            self.instr_idx,
+                            types.len(),
+                            default.len(),
+                        ))?;
+                    }
+
+                    let popped = self.pop_vals(&types)?;
+
+                    self.vals.extend(popped);
+                }
+
+                self.pop_vals(&default)?;
+                self.unreachable();
+            }
+            Instruction::Return => {
+                let results = self.results.clone();
+
+                self.pop_vals(&results)?;
+                self.unreachable();
+            }
+            Instruction::Throw(idx) => {
+                let func_type = self.tag(*idx)?;
+
+                self.pop_vals(&func_type.params)?;
+                self.unreachable();
+            }
+            Instruction::ThrowRef => {
+                self.pop_expect(ValType::ExnRef)?;
+                self.unreachable();
+            }
+            Instruction::Call(idx) => match self.ctx.funcs.get(*idx as usize) {
+                Some(func_type) => self.op(&func_type.params, &func_type.results)?,
+                None => Err(ValidateErr::UnknownFunc(self.func_idx, self.instr_idx, *idx))?,
+            },
+            Instruction::CallIndirect(type_idx, table_idx) => {
+                let (func_type, addr_type) = self.indirect_type(*type_idx, *table_idx)?;
+
+                self.pop_expect(addr_type)?;
+                self.op(&func_type.params, &func_type.results)?;
+            }
+            Instruction::ReturnCall(idx) => match self.ctx.funcs.get(*idx as usize) {
+                Some(func_type) => self.return_call(func_type)?,
+                None => Err(ValidateErr::UnknownFunc(self.func_idx, self.instr_idx, *idx))?,
+            },
+            Instruction::ReturnCallIndirect(type_idx, table_idx) => {
+                let (func_type, addr_type) = self.indirect_type(*type_idx, *table_idx)?;
+
+                self.pop_expect(addr_type)?;
+                self.return_call(func_type)?;
+            }
+            Instruction::Drop => {
+                self.pop_val()?;
+            }
+            Instruction::Select => {
+                self.pop_expect(I32)?;
+
+                let lhs = self.pop_val()?;
+                let rhs = self.pop_val()?;
+
+                for type_ in [lhs, rhs].into_iter().flatten() {
+                    if type_.is_ref_type() {
+                        Err(ValidateErr::SelectRefType(self.func_idx, self.instr_idx, type_))?;
+                    }
+                }
+
+                match (lhs, rhs) {
+                    (Some(lhs), Some(rhs)) if lhs != rhs => Err(ValidateErr::OperandTypeMismatch(
+                        self.func_idx,
+                        self.instr_idx,
+                        lhs,
+                        rhs,
+                    ))?,
+                    (None, rhs) => self.push_val(rhs),
+                    (lhs, _) => self.push_val(lhs),
+                }
+            }
+            Instruction::Select2(n, type_) => {
+                if *n != 1 {
+                    Err(ValidateErr::InvalidResultArity(self.func_idx, self.instr_idx, *n))?;
+                }
+
+                self.op(&[*type_, *type_, I32], &[*type_])?;
+            }
+            Instruction::LocalGet(idx) => {
+                let type_ = self.local(*idx)?;
+
+                self.push_vals(&[type_]);
+            }
+            Instruction::LocalSet(idx) => {
+                let type_ = self.local(*idx)?;
+
+                self.pop_expect(type_)?;
+            }
+            Instruction::LocalTee(idx) => {
+                let type_ = self.local(*idx)?;
+
+                self.op(&[type_], &[type_])?;
+            }
+            Instruction::GlobalGet(idx) => match self.ctx.globals.get(*idx as usize) {
+                Some(global) => self.push_vals(&[global.val_type]),
+                None => Err(ValidateErr::UnknownGlobal(self.func_idx, self.instr_idx, *idx))?,
+            },
+            Instruction::GlobalSet(idx) => match self.ctx.globals.get(*idx as usize) {
+                Some(global) if global.is_const() => {
+                    Err(ValidateErr::ImmutableGlobal(self.func_idx, self.instr_idx, *idx))?
+                }
+                Some(global) => {
+                    self.pop_expect(global.val_type)?;
+                }
+                None => Err(ValidateErr::UnknownGlobal(self.func_idx, self.instr_idx, *idx))?,
+            },
+            Instruction::TableGet(idx) => {
+                let type_ = self.table(*idx)?;
+                let addr = self.table_addr(*idx)?;
+
+                self.op(&[addr], &[type_])?;
+            }
+            Instruction::TableSet(idx) => {
+                let type_ = self.table(*idx)?;
+                let addr = self.table_addr(*idx)?;
+
+                self.op(&[addr, type_], &[])?;
+            }
+            Instruction::I32Load(arg) => self.load(arg, 4, I32)?,
+            Instruction::I64Load(arg) => self.load(arg, 8, I64)?,
+            Instruction::F32Load(arg) => self.load(arg, 4, F32)?,
+            Instruction::F64Load(arg) => self.load(arg, 8, F64)?,
+            Instruction::I32Load8S(arg) | Instruction::I32Load8U(arg) => self.load(arg, 1, I32)?,
+            Instruction::I32Load16S(arg) | Instruction::I32Load16U(arg) => self.load(arg, 2, I32)?,
+            Instruction::I64Load8S(arg) | Instruction::I64Load8U(arg) => self.load(arg, 1, I64)?,
+            Instruction::I64Load16S(arg) | Instruction::I64Load16U(arg) => self.load(arg, 2, I64)?,
+            Instruction::I64Load32S(arg) | Instruction::I64Load32U(arg) => self.load(arg, 4, I64)?,
+            Instruction::I32Store(arg) => self.store(arg, 4, I32)?,
+            Instruction::I64Store(arg) => self.store(arg, 8, I64)?,
+            Instruction::F32Store(arg) => self.store(arg, 4, F32)?,
+            Instruction::F64Store(arg) => self.store(arg, 8, F64)?,
+            Instruction::I32Store8(arg) => self.store(arg, 1, I32)?,
+            Instruction::I32Store16(arg) => self.store(arg, 2, I32)?,
+            Instruction::I64Store8(arg) => self.store(arg, 1, I64)?,
+            Instruction::I64Store16(arg) => self.store(arg, 2, I64)?,
+            Instruction::I64Store32(arg) => self.store(arg, 4, I64)?,
+            Instruction::MemorySize(idx) => {
+                let addr = self.memory(*idx)?;
+
+                self.push_vals(&[addr]);
+            }
+            Instruction::MemoryGrow(idx) => {
+                let addr = self.memory(*idx)?;
+
+                self.op(&[addr], &[addr])?;
+            }
+            Instruction::RefNull(v) => match RefType::from_heap_type(*v) {
+                Some(type_) => self.push_vals(&[type_.as_val_type()]),
+                None => Err(ValidateErr::InvalidHeapType(self.func_idx, self.instr_idx, *v))?,
+            },
+            Instruction::RefIsNull => {
+                self.pop_ref()?;
+                self.push_vals(&[I32]);
+            }
+            Instruction::RefFunc(idx) => {
+                if self.ctx.funcs.get(*idx as usize).is_none() {
+                    Err(ValidateErr::UnknownFunc(self.func_idx, self.instr_idx, *idx))?;
+                }
+
+                if !self.ctx.refs.contains(idx) {
+                    Err(ValidateErr::UndeclaredFuncRef(
+                        self.func_idx,
+                        self.instr_idx,
+                        *idx,
+                    ))?;
+                }
+
+                self.push_vals(&[ValType::FuncRef]);
+            }
+            Instruction::MemoryInit(data_idx, mem_idx) => {
+                let addr = self.memory(*mem_idx)?;
+
+                self.data(*data_idx)?;
+                self.op(&[addr, I32, I32], &[])?;
+            }
+            Instruction::DataDrop(idx) => self.data(*idx)?,
+            Instruction::MemoryCopy(dst, src) => {
+                let dst_addr = self.memory(*dst)?;
+                let src_addr = self.memory(*src)?;
+
+                self.op(&[dst_addr, src_addr, len_type(dst_addr, src_addr)], &[])?;
+            }
+            Instruction::MemoryFill(idx) => {
+                let addr = self.memory(*idx)?;
+
+                self.op(&[addr, I32, addr], &[])?;
+            }
+            Instruction::TableInit(elem_idx, table_idx) => {
+                let table_type = self.table(*table_idx)?;
+                let elem_type = self.elem(*elem_idx)?;
+
+                if table_type != elem_type {
+                    Err(ValidateErr::TableTypeMismatch(
+                        self.func_idx,
+                        self.instr_idx,
+                        table_type,
+                        elem_type,
+                    ))?;
+                }
+
+                let addr = self.table_addr(*table_idx)?;
+
+                self.op(&[addr, I32, I32], &[])?;
+            }
+            Instruction::ElemDrop(idx) => {
+                self.elem(*idx)?;
+            }
+            Instruction::TableCopy(dst, src) => {
+                let dst_type = self.table(*dst)?;
+                let src_type = self.table(*src)?;
+
+                if dst_type != src_type {
+                    Err(ValidateErr::TableTypeMismatch(
+                        self.func_idx,
+                        self.instr_idx,
+                        dst_type,
+                        src_type,
+                    ))?;
+                }
+
+                let dst_addr = self.table_addr(*dst)?;
+                let src_addr = self.table_addr(*src)?;
+
+                self.op(&[dst_addr, src_addr, len_type(dst_addr, src_addr)], &[])?;
+            }
+            Instruction::TableGrow(idx) => {
+                let type_ = self.table(*idx)?;
+                let addr = self.table_addr(*idx)?;
+
+                self.op(&[type_, addr], &[addr])?;
+            }
+            Instruction::TableSize(idx) => {
+                let addr = self.table_addr(*idx)?;
+
+                self.push_vals(&[addr]);
+            }
+            Instruction::TableFill(idx) => {
+                let type_ = self.table(*idx)?;
+                let addr = self.table_addr(*idx)?;
+
+                self.op(&[addr, type_, addr], &[])?;
+            }
+            Instruction::V128Load(arg) => self.load(arg, 16, V128)?,
+            Instruction::V128Load8x8S(arg)
+            | Instruction::V128Load8x8U(arg)
+            | Instruction::V128Load16x4S(arg)
+            | Instruction::V128Load16x4U(arg)
+            | Instruction::V128Load32x2S(arg)
+            | Instruction::V128Load32x2U(arg)
+            | Instruction::V128Load64Splat(arg)
+            | Instruction::V128Load64Zero(arg) => self.load(arg, 8, V128)?,
+            Instruction::V128Load8Splat(arg) => self.load(arg, 1, V128)?,
+            Instruction::V128Load16Splat(arg) => self.load(arg, 2, V128)?,
+            Instruction::V128Load32Splat(arg) | Instruction::V128Load32Zero(arg) => {
+                self.load(arg, 4, V128)?
+            }
+            Instruction::V128Store(arg) => self.store(arg, 16, V128)?,
+            Instruction::I8x16Shuffle(lanes) => {
+                for lane in lanes {
+                    self.lane(*lane, 32)?;
+                }
+
+                self.op(&[V128, V128], &[V128])?;
+            }
+            Instruction::I8x16ExtractLaneS(lane) | Instruction::I8x16ExtractLaneU(lane) => {
+                self.extract_lane(*lane, 16, I32)?
+            }
+            Instruction::I16x8ExtractLaneS(lane) | Instruction::I16x8ExtractLaneU(lane) => {
+                self.extract_lane(*lane, 8, I32)?
+            }
+            Instruction::I32x4ExtractLane(lane) => self.extract_lane(*lane, 4, I32)?,
+            Instruction::I64x2ExtractLane(lane) => self.extract_lane(*lane, 2, I64)?,
+            Instruction::F32x4ExtractLane(lane) => self.extract_lane(*lane, 4, F32)?,
+            Instruction::F64x2ExtractLane(lane) => self.extract_lane(*lane, 2, F64)?,
+            Instruction::I8x16ReplaceLane(lane) => self.replace_lane(*lane, 16, I32)?,
+            Instruction::I16x8ReplaceLane(lane) => self.replace_lane(*lane, 8, I32)?,
+            Instruction::I32x4ReplaceLane(lane) => self.replace_lane(*lane, 4, I32)?,
+            Instruction::I64x2ReplaceLane(lane) => self.replace_lane(*lane, 2, I64)?,
+            Instruction::F32x4ReplaceLane(lane) => self.replace_lane(*lane, 4, F32)?,
+            Instruction::F64x2ReplaceLane(lane) => self.replace_lane(*lane, 2, F64)?,
+            Instruction::V128Load8Lane(arg, lane) => self.load_lane(arg, *lane, 1)?,
+            Instruction::V128Load16Lane(arg, lane) => self.load_lane(arg, *lane, 2)?,
+            Instruction::V128Load32Lane(arg, lane) => self.load_lane(arg, *lane, 4)?,
+            Instruction::V128Load64Lane(arg, lane) => self.load_lane(arg, *lane, 8)?,
+            Instruction::V128Store8Lane(arg, lane) => self.store_lane(arg, *lane, 1)?,
+            Instruction::V128Store16Lane(arg, lane) => self.store_lane(arg, *lane, 2)?,
+            Instruction::V128Store32Lane(arg, lane) => self.store_lane(arg, *lane, 4)?,
+            Instruction::V128Store64Lane(arg, lane) => self.store_lane(arg, *lane, 8)?,
+            Instruction::MemoryAtomicNotify(arg)
+            | Instruction::I32AtomicRmwAdd(arg)
+            | Instruction::I32AtomicRmwSub(arg)
+            | Instruction::I32AtomicRmwAnd(arg)
+            | Instruction::I32AtomicRmwOr(arg)
+            | Instruction::I32AtomicRmwXor(arg)
+            | Instruction::I32AtomicRmwXchg(arg) => self.atomic(arg, 4, &[I32], &[I32])?,
+            Instruction::MemoryAtomicWait32(arg) => self.atomic(arg, 4, &[I32, I64], &[I32])?,
+            Instruction::MemoryAtomicWait64(arg) => self.atomic(arg, 8, &[I64, I64], &[I32])?,
+            Instruction::AtomicFence => {}
+            Instruction::I32AtomicLoad(arg) => self.atomic(arg, 4, &[], &[I32])?,
+            Instruction::I64AtomicLoad(arg) => self.atomic(arg, 8, &[], &[I64])?,
+            Instruction::I32AtomicLoad8U(arg) => self.atomic(arg, 1, &[], &[I32])?,
+            Instruction::I32AtomicLoad16U(arg) => self.atomic(arg, 2, &[], &[I32])?,
+            Instruction::I64AtomicLoad8U(arg) => self.atomic(arg, 1, &[], &[I64])?,
+            Instruction::I64AtomicLoad16U(arg) => self.atomic(arg, 2, &[], &[I64])?,
+            Instruction::I64AtomicLoad32U(arg) => self.atomic(arg, 4, &[], &[I64])?,
+            Instruction::I32AtomicStore(arg) => self.atomic(arg, 4, &[I32], &[])?,
+            Instruction::I64AtomicStore(arg) => self.atomic(arg, 8, &[I64], &[])?,
+            Instruction::I32AtomicStore8(arg) => self.atomic(arg, 1, &[I32], &[])?,
+            Instruction::I32AtomicStore16(arg) => self.atomic(arg, 2, &[I32], &[])?,
+            Instruction::I64AtomicStore8(arg) => self.atomic(arg, 1, &[I64], &[])?,
+            Instruction::I64AtomicStore16(arg) => self.atomic(arg, 2, &[I64], &[])?,
+            Instruction::I64AtomicStore32(arg) => self.atomic(arg, 4, &[I64], &[])?,
+            Instruction::I64AtomicRmwAdd(arg)
+            | Instruction::I64AtomicRmwSub(arg)
+            | Instruction::I64AtomicRmwAnd(arg)
+            | Instruction::I64AtomicRmwOr(arg)
+            | Instruction::I64AtomicRmwXor(arg)
+            | Instruction::I64AtomicRmwXchg(arg) => self.atomic(arg, 8, &[I64], &[I64])?,
+            Instruction::I32AtomicRmw8AddU(arg)
+            | Instruction::I32AtomicRmw8SubU(arg)
+            | Instruction::I32AtomicRmw8AndU(arg)
+            | Instruction::I32AtomicRmw8OrU(arg)
+            | Instruction::I32AtomicRmw8XorU(arg)
+            | Instruction::I32AtomicRmw8XchgU(arg) => self.atomic(arg, 1, &[I32], &[I32])?,
+            Instruction::I32AtomicRmw16AddU(arg)
+            | Instruction::I32AtomicRmw16SubU(arg)
+            | Instruction::I32AtomicRmw16AndU(arg)
+            | Instruction::I32AtomicRmw16OrU(arg)
+            | Instruction::I32AtomicRmw16XorU(arg)
+            | Instruction::I32AtomicRmw16XchgU(arg) => self.atomic(arg, 2, &[I32], &[I32])?,
+            Instruction::I64AtomicRmw8AddU(arg)
+            | Instruction::I64AtomicRmw8SubU(arg)
+            | Instruction::I64AtomicRmw8AndU(arg)
+            | Instruction::I64AtomicRmw8OrU(arg)
+            | Instruction::I64AtomicRmw8XorU(arg)
+            | Instruction::I64AtomicRmw8XchgU(arg) => self.atomic(arg, 1, &[I64], &[I64])?,
+            Instruction::I64AtomicRmw16AddU(arg)
+            | Instruction::I64AtomicRmw16SubU(arg)
+            | Instruction::I64AtomicRmw16AndU(arg)
+            | Instruction::I64AtomicRmw16OrU(arg)
+            | Instruction::I64AtomicRmw16XorU(arg)
+            | Instruction::I64AtomicRmw16XchgU(arg) => self.atomic(arg, 2, &[I64], &[I64])?,
+            Instruction::I64AtomicRmw32AddU(arg)
+            | Instruction::I64AtomicRmw32SubU(arg)
+            | Instruction::I64AtomicRmw32AndU(arg)
+            | Instruction::I64AtomicRmw32OrU(arg)
+            | Instruction::I64AtomicRmw32XorU(arg)
+            | Instruction::I64AtomicRmw32XchgU(arg) => self.atomic(arg, 4, &[I64], &[I64])?,
+            Instruction::I32AtomicRmwCmpxchg(arg) => self.atomic(arg, 4, &[I32, I32], &[I32])?,
+            Instruction::I64AtomicRmwCmpxchg(arg) => self.atomic(arg, 8, &[I64, I64], &[I64])?,
+            Instruction::I32AtomicRmw8CmpxchgU(arg) => self.atomic(arg, 1, &[I32, I32], &[I32])?,
+            Instruction::I32AtomicRmw16CmpxchgU(arg) => self.atomic(arg, 2, &[I32, I32], &[I32])?,
+            Instruction::I64AtomicRmw8CmpxchgU(arg) => self.atomic(arg, 1, &[I64, I64], &[I64])?,
+            Instruction::I64AtomicRmw16CmpxchgU(arg) => self.atomic(arg, 2, &[I64, I64], &[I64])?,
+            Instruction::I64AtomicRmw32CmpxchgU(arg) => self.atomic(arg, 4, &[I64, I64], &[I64])?,
+            // 数值指令已经在 op_type 里处理
+            _ => unreachable!("{:?}", instr),
+        }
+
+        self.instr_idx += 1;
+
+        Ok(())
+    }
+
+    fn load(&mut self, arg: &MemoryArg, bytes: u32, type_: ValType) -> ValidateResult {
+        let addr = self.mem_arg(arg, bytes)?;
+
+        self.op(&[addr], &[type_])
+    }
+
+    fn store(&mut self, arg: &MemoryArg, bytes: u32, type_: ValType) -> ValidateResult {
+        let addr = self.mem_arg(arg, bytes)?;
+
+        self.op(&[addr, type_], &[])
+    }
+
+    /// 原子指令的对齐必须等于访问的字节数
+    fn atomic(
+        &mut self,
+        arg: &MemoryArg,
+        bytes: u32,
+        params: &[ValType],
+        results: &[ValType],
+    ) -> ValidateResult {
+        let addr = self.mem_arg(arg, bytes)?;
+
+        if 1u32 << arg.align != bytes {
+            Err(ValidateErr::AtomicAlignMismatch(
+                self.func_idx,
+                self.instr_idx,
+                arg.align,
+                bytes,
+            ))?;
+        }
+
+        self.op(&[&[addr], params].concat(), results)
+    }
+
+    fn load_lane(&mut self, arg: &MemoryArg, lane: u8, bytes: u32) -> ValidateResult {
+        let addr = self.mem_arg(arg, bytes)?;
+
+        self.lane(lane, 16 / bytes as u8)?;
+        self.op(&[addr, ValType::V128], &[ValType::V128])
+    }
+
+    fn store_lane(&mut self, arg: &MemoryArg, lane: u8, bytes: u32) -> ValidateResult {
+        let addr = self.mem_arg(arg, bytes)?;
+
+        self.lane(lane, 16 / bytes as u8)?;
+        self.op(&[addr, ValType::V128], &[])
+    }
+
+    fn extract_lane(&mut self, lane: u8, total: u8, type_: ValType) -> ValidateResult {
+        self.lane(lane, total)?;
+        self.op(&[ValType::V128], &[type_])
+    }
+
+    fn replace_lane(&mut self, lane: u8, total: u8, type_: ValType) -> ValidateResult {
+        self.lane(lane, total)?;
+        self.op(&[ValType::V128, type_], &[ValType::V128])
+    }
+}
+
+/// memory.copy、table.copy 的长度：两边有一个是 32 位时就是 i32
+fn len_type(dst: ValType, src: ValType) -> ValType {
+    match (dst, src) {
+        (ValType::I64, ValType::I64) => ValType::I64,
+        _ => ValType::I32,
+    }
+}
+
+pub(crate) type OpType = (&'static [ValType], &'static [ValType]);
+
+/// 只和操作数类型有关的指令：常量、数值、向量运算
+pub(crate) fn op_type(instr: &Instruction) -> Option<OpType> {
+    use Instruction::*;
+    use ValType::{F32, F64, I32, I64, V128};
+
+    let type_: OpType = match instr {
+        I32Const(_) => (&[], &[I32]),
+        I64Const(_) => (&[], &[I64]),
+        F32Const(_) => (&[], &[F32]),
+        F64Const(_) => (&[], &[F64]),
+        V128Const(_) => (&[], &[V128]),
+
+        I32Eqz | I32Clz | I32Ctz | I32Popcnt | I32Extend8S | I32Extend16S => (&[I32], &[I32]),
+        I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU | I32GeS | I32GeU
+        | I32Add | I32Sub | I32Mul | I32DivS | I32DivU | I32RemS | I32RemU | I32And | I32Or | I32Xor
+        | I32Shl | I32ShrS | I32ShrU | I32Rotl | I32Rotr => (&[I32, I32], &[I32]),
+
+        I64Eqz => (&[I64], &[I32]),
+        I64Clz | I64Ctz | I64Popcnt | I64Extend8S | I64Extend16S | I64Extend32S => (&[I64], &[I64]),
+        I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU | I64GeS | I64GeU => {
+            (&[I64, I64], &[I32])
+        }
+        I64Add | I64Sub | I64Mul | I64DivS | I64DivU | I64RemS | I64RemU | I64And | I64Or | I64Xor
+        | I64Shl | I64ShrS | I64ShrU | I64Rotl | I64Rotr => (&[I64, I64], &[I64]),
+
+        F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge => (&[F32, F32], &[I32]),
+        F32Abs | F32Neg | F32Ceil | F32Floor | F32Trunc | F32Nearest | F32Sqrt => (&[F32], &[F32]),
+        F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max | F32Copysign => (&[F32, F32], &[F32]),
+
+        F64Eq | F64Ne | F64Lt | F64Gt | F64Le | F64Ge => (&[F64, F64], &[I32]),
+        F64Abs | F64Neg | F64Ceil | F64Floor | F64Trunc | F64Nearest | F64Sqrt => (&[F64], &[F64]),
+        F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max | F64Copysign => (&[F64, F64], &[F64]),
+
+        I32WrapI64 => (&[I64], &[I32]),
+        I32TruncF32S | I32TruncF32U | I32TruncSatF32S | I32TruncSatF32U | I32ReinterpretF32 => {
+            (&[F32], &[I32])
+        }
+        I32TruncF64S | I32TruncF64U | I32TruncSatF64S | I32TruncSatF64U => (&[F64], &[I32]),
+        I64ExtendI32S | I64ExtendI32U => (&[I32], &[I64]),
+        I64TruncF32S | I64TruncF32U | I64TruncSatF32S | I64TruncSatF32U => (&[F32], &[I64]),
+        I64TruncF64S | I64TruncF64U | I64TruncSatF64S | I64TruncSatF64U | I64ReinterpretF64 => {
+            (&[F64], &[I64])
+        }
+        F32ConvertI32S | F32ConvertI32U | F32ReinterpretI32 => (&[I32], &[F32]),
+        F32ConvertI64S | F32ConvertI64U => (&[I64], &[F32]),
+        F32DemoteF64 => (&[F64], &[F32]),
+        F64ConvertI32S | F64ConvertI32U => (&[I32], &[F64]),
+        F64ConvertI64S | F64ConvertI64U | F64ReinterpretI64 => (&[I64], &[F64]),
+        F64PromoteF32 => (&[F32], &[F64]),
+
+        I8x16Splat | I16x8Splat | I32x4Splat => (&[I32], &[V128]),
+        I64x2Splat => (&[I64], &[V128]),
+        F32x4Splat => (&[F32], &[V128]),
+        F64x2Splat => (&[F64], &[V128]),
+
+        V128Bitselect => (&[V128, V128, V128], &[V128]),
+
+        V128AnyTrue | I8x16AllTrue | I8x16Bitmask | I16x8AllTrue(_) | I16x8Bitmask(_)
+        | I32x4AllTrue(_) | I32x4Bitmask(_) | I64x2AllTrue(_) | I64x2Bitmask(_) => (&[V128], &[I32]),
+
+        I8x16Shl | I8x16ShrS | I8x16ShrU | I16x8Shl(_) | I16x8ShrS(_) | I16x8ShrU(_) | I32x4Shl(_)
+        | I32x4ShrS(_) | I32x4ShrU(_) | I64x2Shl(_) | I64x2ShrS(_) | I64x2ShrU(_) => {
+            (&[V128, I32], &[V128])
+        }
+
+        V128Not
+        | F32x4DemoteF64x2Zero
+        | F64x2PromoteLowF32x4
+        | I8x16Abs
+        | I8x16Neg
+        | I8x16Popcnt
+        | F32x4Ceil
+        | F32x4Floor
+        | F32x4Trunc
+        | F32x4Nearest
+        | F64x2Ceil
+        | F64x2Floor
+        | F64x2Trunc
+        | I16x8ExtaddPairwiseI8x16S
+        | I16x8ExtaddPairwiseI8x16U
+        | I32x4ExtaddPairwiseI16x8S
+        | I32x4ExtaddPairwiseI16x8U
+        | I16x8Abs(_)
+        | I16x8Neg(_)
+        | I16x8ExtendLowI8x16S(_)
+        | I16x8ExtendHighI8x16S(_)
+        | I16x8ExtendLowI8x16U(_)
+        | I16x8ExtendHighI8x16U(_)
+        | F64x2Nearest(_)
+        | I32x4Abs(_)
+        | I32x4Neg(_)
+        | I32x4ExtendLowI16x8S(_)
+        | I32x4ExtendHighI16x8S(_)
+        | I32x4ExtendLowI16x8U(_)
+        | I32x4ExtendHighI16x8U(_)
+        | I64x2Abs(_)
+        | I64x2Neg(_)
+        | I64x2ExtendLowI32x4S(_)
+        | I64x2ExtendHighI32x4S(_)
+        | I64x2ExtendLowI32x4U(_)
+        | I64x2ExtendHighI32x4U(_)
+        | F32x4Abs(_)
+        | F32x4Neg(_)
+        | F32x4Sqrt(_)
+        | F64x2Abs(_)
+        | F64x2Neg(_)
+        | F64x2Sqrt(_)
+        | I32x4TruncSatF32x4S(_)
+        | I32x4TruncSatF32x4U(_)
+        | F32x4ConvertI32x4S(_)
+        | F32x4ConvertI32x4U(_)
+        | I32x4TruncSatF64x2SZero(_)
+        | I32x4TruncSatF64x2UZero(_)
+        | F64x2ConvertLowI32x4S(_)
+        | F64x2ConvertLowI32x4U(_) => (&[V128], &[V128]),
+
+        I8x16Swizzle
+        | I8x16Eq
+        | I8x16Ne
+        | I8x16LtS
+        | I8x16LtU
+        | I8x16GtS
+        | I8x16GtU
+        | I8x16LeS
+        | I8x16LeU
+        | I8x16GeS
+        | I8x16GeU
+        | I16x8Eq
+        | I16x8Ne
+        | I16x8LtS
+        | I16x8LtU
+        | I16x8GtS
+        | I16x8GtU
+        | I16x8LeS
+        | I16x8LeU
+        | I16x8GeS
+        | I16x8GeU
+        | I32x4Eq
+        | I32x4Ne
+        | I32x4LtS
+        | I32x4LtU
+        | I32x4GtS
+        | I32x4GtU
+        | I32x4LeS
+        | I32x4LeU
+        | I32x4GeS
+        | I32x4GeU
+        | F32x4Eq
+        | F32x4Ne
+        | F32x4Lt
+        | F32x4Gt
+        | F32x4Le
+        | F32x4Ge
+        | F64x2Eq
+        | F64x2Ne
+        | F64x2Lt
+        | F64x2Gt
+        | F64x2Le
+        | F64x2Ge
+        | V128And
+        | V128Andnot
+        | V128Or
+        | V128Xor
+        | I8x16NarrowI16x8S
+        | I8x16NarrowI16x8U
+        | I8x16Add
+        | I8x16AddSatS
+        | I8x16AddSatU
+        | I8x16Sub
+        | I8x16SubSatS
+        | I8x16SubSatU
+        | I8x16MinS
+        | I8x16MinU
+        | I8x16MaxS
+        | I8x16MaxU
+        | I8x16AvgrU
+        | I16x8Q15mulrSatS(_)
+        | I16x8NarrowI32x4S(_)
+        | I16x8NarrowI32x4U(_)
+        | I16x8Add(_)
+        | I16x8AddSatS(_)
+        | I16x8AddSatU(_)
+        | I16x8Sub(_)
+        | I16x8SubSatS(_)
+        | I16x8SubSatU(_)
+        | I16x8Mul(_)
+        | I16x8MinS(_)
+        | I16x8MinU(_)
+        | I16x8MaxS(_)
+        | I16x8MaxU(_)
+        | I16x8AvgrU(_)
+        | I16x8ExtmulLowI8x16S(_)
+        | I16x8ExtmulHighI8x16S(_)
+        | I16x8ExtmulLowI8x16U(_)
+        | I16x8ExtmulHighI8x16U(_)
+        | I32x4Add(_)
+        | I32x4Sub(_)
+        | I32x4Mul(_)
+        | I32x4MinS(_)
+        | I32x4MinU(_)
+        | I32x4MaxS(_)
+        | I32x4MaxU(_)
+        | I32x4DotI16x8S(_)
+        | I32x4ExtmulLowI16x8S(_)
+        | I32x4ExtmulHighI16x8S(_)
+        | I32x4ExtmulLowI16x8U(_)
+        | I32x4ExtmulHighI16x8U(_)
+        | I64x2Add(_)
+        | I64x2Sub(_)
+        | I64x2Mul(_)
+        | I64x2Eq(_)
+        | I64x2Ne(_)
+        | I64x2LtS(_)
+        | I64x2GtS(_)
+        | I64x2LeS(_)
+        | I64x2GeS(_)
+        | I64x2ExtmulLowI32x4S(_)
+        | I64x2ExtmulHighI32x4S(_)
+        | I64x2ExtmulLowI32x4U(_)
+        | I64x2ExtmulHighI32x4U(_)
+        | F32x4Add(_)
+        | F32x4Sub(_)
+        | F32x4Mul(_)
+        | F32x4Div(_)
+        | F32x4Min(_)
+        | F32x4Max(_)
+        | F32x4Pmin(_)
+        | F32x4Pmax(_)
+        | F64x2Add(_)
+        | F64x2Sub(_)
+        | F64x2Mul(_)
+        | F64x2Div(_)
+        | F64x2Min(_)
+        | F64x2Max(_)
+        | F64x2Pmin(_)
+        | F64x2Pmax(_) => (&[V128, V128], &[V128]),
+
+        _ => return None,
+    };
+
+    Some(type_)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::binary::encode::Encode;
+    use crate::binary::errors::ValidateErr;
+    use crate::binary::instruction::Instruction;
+    use crate::binary::module::Module;
+    use crate::binary::types::ValType::{FuncRef, I32, I64};
+    use crate::binary::validate::{Validate, ValidateResult};
+
+    fn validate(text: &str) -> ValidateResult {
+        Module::from_text(text).unwrap().validate()
+    }
+
+    fn validate_with(instr: Instruction) -> ValidateErr {
+        let mut module = Module::from_text("(module (func (if (i32.const 1) (then nop))))").unwrap();
+
+        module.code_sec[0].body.push(instr);
+
+        module.validate().unwrap_err()
+    }
+
+    #[test]
+    fn test_stray_else_end() {
+        assert!(matches!(
+            validate_with(Instruction::Else),
+            ValidateErr::ElseWithoutIf(0, 4)
+        ));
+        assert!(matches!(
+            validate_with(Instruction::End),
+            ValidateErr::UnmatchedEnd(0, 4)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_heap_type() {
+        assert!(matches!(
+            validate_with(Instruction::RefNull(0x42)),
+            ValidateErr::InvalidHeapType(0, 4, 0x42)
+        ));
+    }
+
+    #[test]
+    fn test_select() {
+        assert!(validate(
+            "(module (func (result i32) (select (i32.const 1) (i32.const 2) (i32.const 0))))"
+        )
+        .is_ok());
+        assert!(matches!(
+            validate("(module (func (drop (select (i32.const 1) (i64.const 2) (i32.const 0)))))"),
+            Err(ValidateErr::OperandTypeMismatch(0, 3, I64, I32))
+        ));
+        // 不带类型的 select 不能用于引用类型
+        assert!(matches!(
+            validate("(module (func (drop (select (ref.null func) (ref.null func) (i32.const 0)))))"),
+            Err(ValidateErr::SelectRefType(0, 3, FuncRef))
+        ));
+
+        assert!(validate(
+            "(module (func (drop (select (result funcref) (ref.null func) (ref.null func) (i32.const \
+             0)))))"
+        )
+        .is_ok());
+        assert!(matches!(
+            validate(
+                "(module (func (drop (select (result i64) (i32.const 1) (i32.const 2) (i32.const 0)))))"
+            ),
+            Err(ValidateErr::OperandTypeMismatch(0, 3, ..))
+        ));
+        assert!(matches!(
+            validate(
+                "(module (func (select (result i32 i32) (i32.const 1) (i32.const 2) (i32.const 0))))"
+            ),
+            Err(ValidateErr::InvalidResultArity(0, 3, 2))
+        ));
+    }
+
+    #[test]
+    fn test_br_table_arity() {
+        assert!(matches!(
+            validate(
+                "(module (func (result i32)
+                   (block (result i32) (block (br_table 0 1 (i32.const 0) (i32.const 0))) (i32.const \
+                 1))))"
+            ),
+            Err(ValidateErr::BrTableArityMismatch(0, 4, 0, 1))
+        ));
+        assert!(validate(
+            "(module (func (result i32)
+               (block (result i32) (block (result i32) (br_table 0 1 (i32.const 0) (i32.const 0))))))"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_unreachable_polymorphic() {
+        assert!(validate("(module (func (result i32) unreachable))").is_ok());
+        assert!(validate("(module (func (result i32) unreachable i32.add))").is_ok());
+        assert!(validate("(module (func (result i32) unreachable select))").is_ok());
+        assert!(validate("(module (func unreachable br_table 0 drop))").is_ok());
+
+        // 弹出的值类型未知，但压入的值类型是确定的
+        assert!(matches!(
+            validate("(module (func (result i32) unreachable (i64.const 0)))"),
+            Err(ValidateErr::OperandTypeMismatch(0, 2, I32, I64))
+        ));
+        assert!(matches!(
+            validate("(module (func unreachable i64.const 0 i32.add drop))"),
+            Err(ValidateErr::OperandTypeMismatch(0, 2, I32, I64))
+        ));
+        // 块结束后不再是不可达代码
+        assert!(matches!(
+            validate("(module (func (block unreachable) i32.add drop))"),
+            Err(ValidateErr::OperandStackEmpty(0, 3))
+        ));
+    }
+
+    #[test]
+    fn test_ref_func_declared() {
+        assert!(matches!(
+            validate("(module (func $f) (func (drop (ref.func $f))))"),
+            Err(ValidateErr::UndeclaredFuncRef(1, 0, 0))
+        ));
+        assert!(
+            validate("(module (func $f) (elem declare func $f) (func (drop (ref.func $f))))").is_ok()
+        );
+        assert!(validate("(module (func $f (drop (ref.func $f))) (export \"f\" (func $f)))").is_ok());
+        assert!(matches!(
+            validate("(module (func (drop (ref.func 5))))"),
+            Err(ValidateErr::UnknownFunc(0, 0, 5))
+        ));
+    }
+
+    #[test]
+    fn test_align_too_large() {
+        assert!(matches!(
+            validate("(module (memory 1) (func (drop (i32.load align=8 (i32.const 0)))))"),
+            Err(ValidateErr::AlignTooLarge(0, 1, 3, 4))
+        ));
+        assert!(matches!(
+            validate("(module (memory 1) (func (i32.store16 align=4 (i32.const 0) (i32.const 0))))"),
+            Err(ValidateErr::AlignTooLarge(0, 2, 2, 2))
+        ));
+        assert!(validate("(module (memory 1) (func (drop (i64.load align=8 (i32.const 0)))))").is_ok());
+    }
+
+    #[test]
+    fn test_lane_idx() {
+        assert!(matches!(
+            validate("(module (func (drop (i32x4.extract_lane 4 (v128.const i64x2 0 0)))))"),
+            Err(ValidateErr::InvalidLaneIdx(0, 1, 4, 4))
+        ));
+        assert!(matches!(
+            validate(
+                "(module (func (drop (i8x16.replace_lane 16 (v128.const i64x2 0 0) (i32.const 0)))))"
+            ),
+            Err(ValidateErr::InvalidLaneIdx(0, 2, 16, 16))
+        ));
+        assert!(matches!(
+            validate(
+                "(module (memory 1)
+                   (func (drop (v128.load64_lane 2 (i32.const 0) (v128.const i64x2 0 0)))))"
+            ),
+            Err(ValidateErr::InvalidLaneIdx(0, 2, 2, 2))
+        ));
+        assert!(
+            validate("(module (func (drop (i64x2.extract_lane 1 (v128.const i64x2 0 0)))))").is_ok()
+        );
+    }
+
+    #[test]
+    fn test_data_count_required() {
+        let text = r#"(module (memory 1) (data "a") (func (data.drop 0)))"#;
+        let mut module = Module::from_text(text).unwrap();
+
+        assert!(module.validate().is_ok());
+
+        module.data_counat_sec = None;
+
+        assert!(matches!(
+            module.validate(),
+            Err(ValidateErr::DataCountRequired(0, 0))
+        ));
+        assert!(matches!(
+            validate("(module (func (data.drop 0)))"),
+            Err(ValidateErr::UnknownData(0, 0, 0))
+        ));
+    }
+
+    /// 指令序号对应 instr_offsets 中记录的位置
+    #[test]
+    fn test_instr_idx_offset() {
+        let text = "(module (func (if (i32.const 1) (then nop)) (block nop) (i32.const 0) (i64.const \
+                    0) i32.add drop))";
+        let data = Module::from_text(text).unwrap().encode();
+        let module = Module::from_data(data.clone()).unwrap();
+
+        let idx = match module.validate() {
+            Err(ValidateErr::OperandTypeMismatch(0, idx, I32, I64)) => idx,
+            ret => panic!("{:?}", ret),
+        };
+
+        // i32.add
+        assert_eq!(data[module.code_sec[0].instr_offsets[idx]], 0x6a);
+    }
+}
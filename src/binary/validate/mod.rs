@@ -0,0 +1,434 @@
+use std::collections::HashSet;
+
+use self::code::FuncValidator;
+use super::errors::ValidateErr;
+use super::instruction::Instruction;
+use super::module::Module;
+use super::section::{
+    CodeSeg, DataMode, DataSeg, ElementMode, ElementSeg, ExportDesc, ExportSeg, Expr, FuncIdx,
+    GlobalSeg, ImportDesc, ImportSeg, StartSeg, TagType, TypeIdx,
+};
+use super::types::{FuncType, GlobalType, MemType, RefType, TableType, ValType};
+
+mod code;
+
+pub(crate) use self::code::op_type;
+
+pub type ValidateResult<T = ()> = Result<T, ValidateErr>;
+
+/// 内存最多 65536 页（4GiB）
+pub const MAX_MEM_PAGES: u64 = 65536;
+
+/// memory64 最多 2^48 页，地址正好不超过 u64
+pub const MAX_MEM64_PAGES: u64 = 1 << 48;
+
+pub trait Validate {
+    fn validate(&self) -> ValidateResult {
+        Ok(())
+    }
+
+    fn validate_use_ctx(&self, _ctx: &Context) -> ValidateResult {
+        Ok(())
+    }
+}
+
+/// 校验上下文，对应规范里的 C
+/// https://webassembly.github.io/spec/core/valid/conventions.html#contexts
+#[derive(Debug)]
+pub struct Context<'a> {
+    pub module: &'a Module,
+    pub types: &'a [FuncType],
+    /// 导入函数 + 模块内定义的函数
+    pub funcs: Vec<FuncType>,
+    pub tables: Vec<TableType>,
+    pub mems: Vec<MemType>,
+    pub globals: Vec<GlobalType>,
+    /// 异常标签的类型，导入的排在前面
+    pub tags: Vec<FuncType>,
+    /// 导入的全局变量个数，全局段的初始化表达式只能引用导入的全局变量
+    pub import_globals: usize,
+    pub elems: Vec<ValType>,
+    pub datas: usize,
+    /// 可以被 ref.func 引用的函数
+    pub refs: HashSet<FuncIdx>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(module: &'a Module) -> ValidateResult<Self> {
+        let mut ctx = Self {
+            module,
+            types: &module.type_sec,
+            funcs: vec![],
+            tables: vec![],
+            mems: vec![],
+            globals: vec![],
+            tags: vec![],
+            import_globals: 0,
+            elems: module.elem_sec.iter().map(|elem| elem.type_).collect(),
+            datas: module.data_sec.len(),
+            refs: HashSet::new(),
+        };
+
+        for import in &module.import_sec {
+            match &import.desc {
+                ImportDesc::Func(idx) => ctx.funcs.push(ctx.func_type(*idx)?.clone()),
+                ImportDesc::Table(type_) => ctx.tables.push(type_.clone()),
+                ImportDesc::Mem(type_) => ctx.mems.push(type_.clone()),
+                ImportDesc::Global(type_) => ctx.globals.push(type_.clone()),
+                ImportDesc::Tag(type_) => ctx.tags.push(ctx.func_type(type_.type_idx)?.clone()),
+            }
+        }
+
+        ctx.import_globals = ctx.globals.len();
+
+        for idx in &module.func_sec {
+            ctx.funcs.push(ctx.func_type(*idx)?.clone());
+        }
+
+        ctx.tables.extend(module.table_sec.iter().cloned());
+        ctx.mems.extend(module.mem_sec.iter().cloned());
+
+        for tag in &module.tag_sec {
+            ctx.tags.push(ctx.func_type(tag.type_idx)?.clone());
+        }
+
+        ctx.globals
+            .extend(module.global_sec.iter().map(|global| global.type_.clone()));
+        ctx.refs = declared_refs(module);
+
+        Ok(ctx)
+    }
+
+    pub fn func_type(&self, idx: TypeIdx) -> ValidateResult<&'a FuncType> {
+        match self.types.get(idx as usize) {
+            Some(func_type) => Ok(func_type),
+            None => Err(ValidateErr::FnTypeNotFound(idx)),
+        }
+    }
+
+    /// 代码段之前的各段，流式解码时读到代码段就可以校验
+    pub fn validate_sections(&self) -> ValidateResult {
+        let module = self.module;
+
+        self.validates(&module.import_sec)?;
+        self.validates(&module.func_sec)?;
+        self.validates(&module.table_sec)?;
+        self.validates(&module.mem_sec)?;
+        self.validates(&module.tag_sec)?;
+        self.validates(&module.global_sec)?;
+        module.export_sec.validate_use_ctx(self)?;
+        module.start_sec.validate_use_ctx(self)?;
+        self.validates(&module.elem_sec)
+    }
+
+    pub fn validates<T>(&self, secs: &[T]) -> ValidateResult
+    where
+        T: Validate,
+    {
+        for sec in secs {
+            sec.validate_use_ctx(self)?;
+        }
+
+        Ok(())
+    }
+
+    /// 常量表达式，返回表达式结果的类型
+    /// https://webassembly.github.io/spec/core/valid/instructions.html#constant-expressions
+    pub fn validate_const_expr(&self, expr: &Expr, global_total: usize) -> ValidateResult<ValType> {
+        if expr.len() != 1 {
+            Err(ValidateErr::InitExprLen(expr.len()))?;
+        }
+
+        match &expr[0] {
+            Instruction::I32Const(_) => Ok(ValType::I32),
+            Instruction::I64Const(_) => Ok(ValType::I64),
+            Instruction::F32Const(_) => Ok(ValType::F32),
+            Instruction::F64Const(_) => Ok(ValType::F64),
+            Instruction::V128Const(_) => Ok(ValType::V128),
+            Instruction::RefNull(v) => match RefType::from_heap_type(*v) {
+                Some(type_) => Ok(type_.as_val_type()),
+                None => Err(ValidateErr::InvalidRefType(*v)),
+            },
+            Instruction::RefFunc(idx) if (*idx as usize) < self.funcs.len() => Ok(ValType::FuncRef),
+            Instruction::RefFunc(idx) => Err(ValidateErr::FnNotFound(*idx)),
+            Instruction::GlobalGet(idx) if (*idx as usize) >= global_total => {
+                Err(ValidateErr::GlobalVarNotFound(*idx))
+            }
+            Instruction::GlobalGet(idx) => match &self.globals[*idx as usize] {
+                global if !global.is_const() => Err(ValidateErr::GlobalVarNotConst(*idx)),
+                global => Ok(global.val_type),
+            },
+            instr => Err(ValidateErr::InitNotConst(instr.discriminant()))?,
+        }
+    }
+}
+
+/// 导入段
+impl Validate for ImportSeg {
+    fn validate_use_ctx(&self, ctx: &Context) -> ValidateResult {
+        match &self.desc {
+            ImportDesc::Func(idx) => idx.validate_use_ctx(ctx),
+            ImportDesc::Table(type_) => type_.validate(),
+            ImportDesc::Mem(type_) => type_.validate_use_ctx(ctx),
+            ImportDesc::Global(_) => Ok(()),
+            ImportDesc::Tag(type_) => type_.validate_use_ctx(ctx),
+        }
+    }
+}
+
+/// 函数（类型索引）段
+impl Validate for TypeIdx {
+    fn validate_use_ctx(&self, ctx: &Context) -> ValidateResult {
+        ctx.func_type(*self)?;
+
+        Ok(())
+    }
+}
+
+/// 表段，2.0 开始允许多张表
+impl Validate for TableType {
+    fn validate(&self) -> ValidateResult {
+        let limits = &self.limits;
+
+        match limits.max {
+            Some(max) if max < limits.min => Err(ValidateErr::MaxLtMin(max, limits.min))?,
+            _ => Ok(()),
+        }
+    }
+
+    fn validate_use_ctx(&self, _ctx: &Context) -> ValidateResult {
+        self.validate()
+    }
+}
+
+/// 内存段
+impl Validate for MemType {
+    fn validate(&self) -> ValidateResult {
+        let (min, max) = (self.min, self.max);
+        let pages = match self.is64 {
+            true => MAX_MEM64_PAGES,
+            false => MAX_MEM_PAGES,
+        };
+
+        if min > pages {
+            Err(ValidateErr::MinTooLarge(min, pages))?;
+        }
+
+        if self.shared && max.is_none() {
+            Err(ValidateErr::SharedWithoutMax)?;
+        }
+
+        match max {
+            Some(max) if max < min => Err(ValidateErr::MaxLtMin(max, min))?,
+            Some(max) if max > pages => Err(ValidateErr::MaxTooLarge(max, pages))?,
+            _ => Ok(()),
+        }
+    }
+
+    /// 多内存提案允许同时导入和定义多块内存
+    fn validate_use_ctx(&self, _ctx: &Context) -> ValidateResult {
+        self.validate()
+    }
+}
+
+/// 标签段，异常只带参数
+impl Validate for TagType {
+    fn validate_use_ctx(&self, ctx: &Context) -> ValidateResult {
+        let func_type = ctx.func_type(self.type_idx)?;
+
+        match func_type.results.is_empty() {
+            true => Ok(()),
+            false => Err(ValidateErr::TagHasResults(func_type.results.clone())),
+        }
+    }
+}
+
+/// 全局段
+impl Validate for GlobalSeg {
+    fn validate_use_ctx(&self, ctx: &Context) -> ValidateResult {
+        let val_type = ctx.validate_const_expr(&self.init_expr, ctx.import_globals)?;
+
+        match val_type != self.type_.val_type {
+            true => Err(ValidateErr::ExprRetNotEq(val_type, self.type_.val_type)),
+            false => Ok(()),
+        }
+    }
+}
+
+/// 导出段
+impl Validate for Vec<ExportSeg> {
+    fn validate_use_ctx(&self, ctx: &Context) -> ValidateResult {
+        let mut names: HashSet<String> = HashSet::new();
+        let mut dups: Vec<String> = vec![];
+
+        for export in self {
+            export.desc.validate_use_ctx(ctx)?;
+
+            let name = &export.name;
+
+            if !names.insert(name.clone()) {
+                dups.push(name.to_string());
+            }
+        }
+
+        match !dups.is_empty() {
+            true => Err(ValidateErr::DuplicateExport(dups))?,
+            false => Ok(()),
+        }
+    }
+}
+
+impl Validate for ExportDesc {
+    fn validate_use_ctx(&self, ctx: &Context) -> ValidateResult {
+        match self {
+            ExportDesc::Func(i) if ctx.funcs.get(*i as usize).is_none() => {
+                Err(ValidateErr::FnNotFound(*i))
+            }
+            ExportDesc::Table(i) if ctx.tables.get(*i as usize).is_none() => {
+                Err(ValidateErr::TableNotFound(*i))
+            }
+            ExportDesc::Mem(i) if ctx.mems.get(*i as usize).is_none() => {
+                Err(ValidateErr::MemNotFound(*i))
+            }
+            ExportDesc::Global(i) if ctx.globals.get(*i as usize).is_none() => {
+                Err(ValidateErr::GlobalVarNotFound(*i))
+            }
+            ExportDesc::Tag(i) if ctx.tags.get(*i as usize).is_none() => {
+                Err(ValidateErr::TagNotFound(*i))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 开始段
+impl Validate for StartSeg {
+    fn validate_use_ctx(&self, ctx: &Context) -> ValidateResult {
+        if let Some(idx) = self {
+            match ctx.funcs.get(*idx as usize) {
+                Some(func_type) => {
+                    if !func_type.params.is_empty() {
+                        Err(ValidateErr::StartFnNoParam(func_type.params.clone()))?;
+                    }
+
+                    if !func_type.results.is_empty() {
+                        Err(ValidateErr::StartFnNoResult(func_type.results.clone()))?;
+                    }
+                }
+                None => Err(ValidateErr::FnNotFound(*idx))?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 元素段
+impl Validate for ElementSeg {
+    fn validate_use_ctx(&self, ctx: &Context) -> ValidateResult {
+        if let ElementMode::Active {
+            table_idx,
+            offset_expr: offset,
+        } = &self.mode
+        {
+            let table = match ctx.tables.get(*table_idx as usize) {
+                Some(table) => table,
+                None => Err(ValidateErr::TableNotFound(*table_idx))?,
+            };
+
+            if table.elem_type.as_val_type() != self.type_ {
+                Err(ValidateErr::ElemTypeNotEq(self.type_, table.elem_type))?;
+            }
+
+            let val_type = ctx.validate_const_expr(offset, ctx.globals.len())?;
+            let addr_type = table.limits.addr_type();
+
+            if val_type != addr_type {
+                Err(ValidateErr::OffsetRetNotEq(val_type, addr_type))?;
+            }
+        }
+
+        for func_idx in &self.func_idxs {
+            if (*func_idx as usize) >= ctx.funcs.len() {
+                Err(ValidateErr::FnNotFound(*func_idx))?;
+            }
+        }
+
+        for expr in &self.init_expr {
+            let val_type = ctx.validate_const_expr(expr, ctx.globals.len())?;
+
+            if val_type != self.type_ {
+                Err(ValidateErr::ExprRetNotEq(val_type, self.type_))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 代码段
+impl Context<'_> {
+    pub fn validate_codes(&self) -> ValidateResult {
+        let codes = &self.module.code_sec;
+        let import_total = self.funcs.len() - codes.len();
+
+        for (i, code) in codes.iter().enumerate() {
+            let func_idx = (import_total + i) as FuncIdx;
+
+            self.validate_code(func_idx, code)?;
+        }
+
+        Ok(())
+    }
+
+    /// func_idx 包括导入的函数
+    pub fn validate_code(&self, func_idx: FuncIdx, code: &CodeSeg) -> ValidateResult {
+        FuncValidator::new(self, func_idx, code).validate()
+    }
+}
+
+/// 数据段
+impl Validate for DataSeg {
+    fn validate_use_ctx(&self, ctx: &Context) -> ValidateResult {
+        match &self.mode {
+            DataMode::Passive => Ok(()),
+            DataMode::Active => {
+                let addr_type = match ctx.mems.get(self.mem_idx as usize) {
+                    Some(mem) => mem.addr_type(),
+                    None => Err(ValidateErr::MemNotFound(self.mem_idx))?,
+                };
+                let val_type = ctx.validate_const_expr(&self.offset_expr, ctx.globals.len())?;
+
+                match val_type != addr_type {
+                    true => Err(ValidateErr::OffsetRetNotEq(val_type, addr_type))?,
+                    false => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// 在函数体之外出现过的函数索引，即规范里的 C.refs
+fn declared_refs(module: &Module) -> HashSet<FuncIdx> {
+    let global_exprs = module.global_sec.iter().map(|global| &global.init_expr);
+    let elem_exprs = module.elem_sec.iter().flat_map(|elem| elem.init_expr.iter());
+    let mut refs = global_exprs
+        .chain(elem_exprs)
+        .filter_map(|expr| match expr.first() {
+            Some(Instruction::RefFunc(idx)) => Some(*idx),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+
+    for elem in &module.elem_sec {
+        refs.extend(elem.func_idxs.iter());
+    }
+
+    for export in &module.export_sec {
+        if let ExportDesc::Func(idx) = export.desc {
+            refs.insert(idx);
+        }
+    }
+
+    refs
+}
@@ -0,0 +1,163 @@
+use std::fmt;
+
+use super::stack::frame::Frame;
+use super::store::Store;
+use crate::binary::section::ImportDesc;
+
+/// trap 发生时的 wasm 调用栈，从最内层的调用开始，每个调用栈帧一项
+#[derive(Debug, Clone, Default)]
+pub struct WasmBacktrace {
+    pub frames: Vec<FrameInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameInfo {
+    /// 函数所属实例的名字
+    pub module: String,
+    /// 函数索引，包括导入的函数
+    pub func_idx: u32,
+    /// 名字段中给出的函数名
+    pub name: Option<String>,
+    /// 正在执行的指令在文件中的位置，模块不是从二进制格式解码时没有
+    pub offset: Option<usize>,
+}
+
+impl WasmBacktrace {
+    /// frames 按调用顺序排列，最后一帧就是出错的地方
+    ///
+    /// 执行指令前 pc 已经指向下一条，调用方的 pc - 1 是 call 指令；
+    /// 燃料耗尽时出错的指令还没有执行，pc 没有移动
+    pub(crate) fn capture(store: &Store, frames: &[Frame], executed: bool) -> Self {
+        let frames = frames
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, frame)| {
+                let pc = match i == 0 && !executed {
+                    true => frame.pc,
+                    false => frame.pc.saturating_sub(1),
+                };
+
+                FrameInfo::new(store, frame, pc)
+            })
+            .collect();
+
+        Self { frames }
+    }
+}
+
+impl FrameInfo {
+    fn new(store: &Store, frame: &Frame, pc: usize) -> Self {
+        let inst = store.get(frame.inst);
+        let module = &inst.module;
+        let imported = module
+            .import_sec
+            .iter()
+            .filter(|import| matches!(import.desc, ImportDesc::Func(_)))
+            .count();
+        let func_idx = (imported + frame.func) as u32;
+
+        let offsets = frame.code.offsets();
+        let offset = offsets.get(pc).or(offsets.last()).copied();
+
+        Self {
+            module: inst.name.clone(),
+            func_idx,
+            name: module.names.funcs.get(&func_idx).cloned(),
+            offset,
+        }
+    }
+}
+
+impl fmt::Display for WasmBacktrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "wasm 调用栈：")?;
+
+        for (i, frame) in self.frames.iter().enumerate() {
+            write!(f, "\n  {}: ", i)?;
+
+            match frame.offset {
+                Some(offset) => write!(f, "0x{:06x} - ", offset)?,
+                None => write!(f, "{:>8} - ", "?")?,
+            }
+
+            match &frame.name {
+                Some(name) => write!(f, "{}!{} (func {})", frame.module, name, frame.func_idx)?,
+                None => write!(f, "{}!func[{}]", frame.module, frame.func_idx)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::binary::encode::Encode;
+    use crate::binary::module::Module as BinaryModule;
+    use crate::binary::section::CustomSeg;
+    use crate::execution::config::{Config, Engine};
+    use crate::execution::linker::Linker;
+    use crate::execution::module::Module;
+    use crate::execution::vm::VM;
+
+    /// 编码成二进制格式，再附上只给导入之后的函数命名的名字段
+    fn binary() -> Vec<u8> {
+        let mut module = BinaryModule::from_text(
+            r#"(module
+                 (import "env" "f" (func))
+                 (func (export "run") (call 2))
+                 (func (nop) (unreachable))
+                 (func (export "ok")))"#,
+        )
+        .unwrap();
+
+        module.custom_sec.push(CustomSeg {
+            name: "name".to_string(),
+            data: b"\x01\x0f\x02\x01\x05outer\x02\x05inner".to_vec(),
+        });
+
+        module.encode()
+    }
+
+    #[test]
+    fn test_backtrace() {
+        let data = binary();
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let module = Module::new(BinaryModule::from_data(data.clone()).unwrap(), engine).unwrap();
+            let mut vm = VM::empty("test", Config::default());
+            let mut linker = Linker::new();
+
+            linker.func("env", "f", || {});
+
+            let instance = linker.instantiate(&mut vm, "main", &module).unwrap();
+
+            assert!(vm.call_export(instance, "run", vec![]).is_err());
+
+            let backtrace = vm.backtrace().unwrap();
+            let frames = backtrace
+                .frames
+                .iter()
+                .map(|frame| (frame.module.as_str(), frame.func_idx, frame.name.as_deref()))
+                .collect::<Vec<_>>();
+
+            // 索引包括导入的函数，最内层的调用在前
+            assert_eq!(frames, [("main", 2, Some("inner")), ("main", 1, Some("outer"))]);
+
+            // 位置分别指向 unreachable 和 call 指令
+            let offsets = backtrace
+                .frames
+                .iter()
+                .map(|frame| data[frame.offset.unwrap()])
+                .collect::<Vec<_>>();
+
+            assert_eq!(offsets, [0x00, 0x10]);
+            assert!(backtrace.to_string().contains("main!inner (func 2)"));
+
+            // 之后的调用成功时清除
+            vm.call_export(instance, "ok", vec![]).unwrap();
+            assert!(vm.backtrace().is_none());
+        }
+    }
+}
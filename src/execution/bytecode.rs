@@ -0,0 +1,593 @@
+use std::fmt;
+
+use crate::binary::instruction::{BlockType, Catch, Instruction};
+use crate::binary::module::Module;
+use crate::binary::section::{Expr, ImportDesc, LabelIdx};
+use crate::binary::types::FuncType;
+use crate::binary::validate::op_type;
+use crate::execution::config::Engine;
+use crate::execution::register::RegCode;
+use crate::execution::value::ValInsts;
+
+/// 编译后的指令，用于计算燃料和跟踪
+pub trait Opcode: fmt::Debug {
+    /// 对应的原始操作码
+    fn opcode(&self) -> u16;
+}
+
+/// 跳转目标，栈高度都相对于当前函数的第一个参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Target {
+    pub pc: usize,
+    /// 跳转时保留栈顶的值的个数
+    pub keep: usize,
+    /// 保留的值之下的栈高度
+    pub height: usize,
+}
+
+/// try_table 编译后的 catch 子句，[start, end) 之间的指令抛出异常时按顺序匹配
+#[derive(Debug, Clone)]
+pub struct Handler {
+    pub start: usize,
+    pub end: usize,
+    /// 跳转目标在 try_table 之外，keep 为压入的值的个数
+    pub catches: Box<[(Catch, Target)]>,
+}
+
+/// 扁平化后的指令，块在编译时展开，运行时不再需要块的栈帧
+#[derive(Clone)]
+pub enum Op {
+    /// 非控制指令，原样交给 exec_instr
+    Instr(Instruction),
+    /// br、return
+    Br(Target),
+    BrIf(Target),
+    /// 最后一个为默认分支
+    BrTable(Box<[Target]>),
+    /// if：条件为假时跳到 else 分支或块尾
+    BrUnless(usize),
+    /// then 分支执行完后跳过 else 分支
+    Jump(usize),
+}
+
+impl Opcode for Op {
+    fn opcode(&self) -> u16 {
+        match self {
+            Op::Instr(instr) => instr.discriminant(),
+            Op::Br(_) => 0x0c,
+            Op::BrIf(_) => 0x0d,
+            Op::BrTable(_) => 0x0e,
+            Op::BrUnless(_) => 0x04,
+            Op::Jump(_) => 0x05,
+        }
+    }
+}
+
+impl fmt::Debug for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Instr(instr) => write!(f, "{:?}", instr),
+            Op::Br(target) => write!(f, "Br({:?})", target),
+            Op::BrIf(target) => write!(f, "BrIf({:?})", target),
+            Op::BrTable(targets) => write!(f, "BrTable({:?})", targets),
+            Op::BrUnless(pc) => write!(f, "BrUnless({})", pc),
+            Op::Jump(pc) => write!(f, "Jump({})", pc),
+        }
+    }
+}
+
+/// 编译后的函数体
+#[derive(Debug, Clone)]
+pub struct Bytecode {
+    /// 局部变量（不含参数）的初始值
+    pub locals: ValInsts,
+    pub ops: Vec<Op>,
+    /// 每条指令对应的原始指令在文件中的位置，模块不是从二进制格式解码时为空
+    pub offsets: Vec<usize>,
+    /// 嵌套的 try_table 排在外层之后
+    pub handlers: Vec<Handler>,
+    /// 使用寄存器解释器时才有
+    pub regs: Option<RegCode>,
+}
+
+impl Bytecode {
+    /// 指令的条数，pc 到这里时函数返回
+    pub fn end(&self) -> usize {
+        match &self.regs {
+            Some(regs) => regs.ops.len(),
+            None => self.ops.len(),
+        }
+    }
+
+    /// try_table 的 catch 子句，下标和 pc 一样按实际执行的指令计算
+    pub fn handlers(&self) -> &[Handler] {
+        match &self.regs {
+            Some(regs) => &regs.handlers,
+            None => &self.handlers,
+        }
+    }
+
+    /// 实际执行的每条指令对应的原始指令的位置
+    pub fn offsets(&self) -> &[usize] {
+        match &self.regs {
+            Some(regs) => &regs.offsets,
+            None => &self.offsets,
+        }
+    }
+
+    /// idx 为函数在代码段中的索引，模块需要已经通过校验
+    pub fn compile(module: &Module, idx: usize, engine: Engine) -> Self {
+        let func_type = &module.type_sec[module.func_sec[idx] as usize];
+        let code = &module.code_sec[idx];
+        let locals = code.init_local();
+        let base = func_type.params.len() + locals.len();
+        let mut compiler = Compiler::new(module, &code.instr_offsets);
+
+        compiler.height = base;
+        // 函数体本身是最外层的块，跳到这里等同于 return
+        compiler.labels.push(Label {
+            height: 0,
+            keep: func_type.results.len(),
+            start: None,
+            fixups: vec![],
+        });
+        compiler.expr(&code.body);
+        compiler.pop_label();
+
+        if code.instr_offsets.is_empty() {
+            compiler.offsets.clear();
+        }
+
+        let regs = match engine {
+            Engine::Stack => None,
+            Engine::Register => Some(RegCode::lower(
+                &compiler.sigs,
+                &compiler.ops,
+                &compiler.handlers,
+                &compiler.heights,
+                &compiler.offsets,
+                base,
+                func_type.results.len(),
+            )),
+        };
+
+        Self {
+            locals,
+            ops: compiler.ops,
+            offsets: compiler.offsets,
+            handlers: compiler.handlers,
+            regs,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Label {
+    /// 进入块时的栈高度，不含块参数
+    height: usize,
+    /// 跳转到这个块时保留的值的个数：loop 为参数个数，其他为结果个数
+    keep: usize,
+    /// loop 跳回块首，其他块跳到块尾
+    start: Option<usize>,
+    /// 块尾位置确定后要回填的跳转
+    fixups: Vec<Fixup>,
+}
+
+#[derive(Debug)]
+enum Fixup {
+    Op(usize),
+    BrTable(usize, usize),
+    /// 第几个 try_table 的第几个 catch 子句
+    Catch(usize, usize),
+}
+
+/// 模块里的函数签名，用来计算指令弹出和压入的值的个数
+pub(crate) struct Signatures<'a> {
+    types: &'a [FuncType],
+    /// 导入函数 + 模块内定义的函数
+    funcs: Vec<&'a FuncType>,
+    /// 导入的标签 + 模块内定义的标签
+    tags: Vec<&'a FuncType>,
+}
+
+impl<'a> Signatures<'a> {
+    fn new(module: &'a Module) -> Self {
+        let types = &module.type_sec;
+        let imports = module.import_sec.iter().filter_map(|import| match import.desc {
+            ImportDesc::Func(idx) => Some(&types[idx as usize]),
+            _ => None,
+        });
+        let funcs = imports
+            .chain(module.func_sec.iter().map(|idx| &types[*idx as usize]))
+            .collect();
+        let tags = module
+            .import_sec
+            .iter()
+            .filter_map(|import| match &import.desc {
+                ImportDesc::Tag(tag) => Some(tag),
+                _ => None,
+            })
+            .chain(&module.tag_sec)
+            .map(|tag| &types[tag.type_idx as usize])
+            .collect();
+
+        Self { types, funcs, tags }
+    }
+
+    fn block_type(&self, block_type: &BlockType) -> FuncType {
+        match block_type {
+            BlockType::TypeIdx(idx) => self.types[*idx as usize].clone(),
+            _ => FuncType::from(block_type),
+        }
+    }
+
+    /// 非控制指令弹出和压入的值的个数
+    pub(crate) fn stack_effect(&self, instr: &Instruction) -> (usize, usize) {
+        use Instruction::*;
+
+        if let Some((params, results)) = op_type(instr) {
+            return (params.len(), results.len());
+        }
+
+        match instr {
+            Call(idx) => {
+                let func_type = self.funcs[*idx as usize];
+
+                (func_type.params.len(), func_type.results.len())
+            }
+            CallIndirect(idx, _) => {
+                let func_type = &self.types[*idx as usize];
+
+                (func_type.params.len() + 1, func_type.results.len())
+            }
+            ReturnCall(idx) => (self.funcs[*idx as usize].params.len(), 0),
+            ReturnCallIndirect(idx, _) => (self.types[*idx as usize].params.len() + 1, 0),
+            Throw(idx) => (self.tags[*idx as usize].params.len(), 0),
+            ThrowRef => (1, 0),
+            Nop | DataDrop(_) | ElemDrop(_) => (0, 0),
+            Drop | LocalSet(_) | GlobalSet(_) => (1, 0),
+            LocalGet(_) | GlobalGet(_) | MemorySize(_) | TableSize(_) | RefNull(_) | RefFunc(_) => {
+                (0, 1)
+            }
+            Select | Select2(..) => (3, 1),
+            TableSet(_) | I32Store(_) | I64Store(_) | F32Store(_) | F64Store(_) | I32Store8(_)
+            | I32Store16(_) | I64Store8(_) | I64Store16(_) | I64Store32(_) | V128Store(_)
+            | V128Store8Lane(..) | V128Store16Lane(..) | V128Store32Lane(..) | V128Store64Lane(..) => {
+                (2, 0)
+            }
+            MemoryInit(..) | MemoryCopy(..) | MemoryFill(_) | TableInit(..) | TableCopy(..)
+            | TableFill(_) => (3, 0),
+            TableGrow(_) | I8x16Shuffle(_) | I8x16ReplaceLane(_) | I16x8ReplaceLane(_)
+            | I32x4ReplaceLane(_) | I64x2ReplaceLane(_) | F32x4ReplaceLane(_) | F64x2ReplaceLane(_)
+            | V128Load8Lane(..) | V128Load16Lane(..) | V128Load32Lane(..) | V128Load64Lane(..) => (2, 1),
+            LocalTee(_) | TableGet(_) | MemoryGrow(_) | RefIsNull | I32Load(_) | I64Load(_)
+            | F32Load(_) | F64Load(_) | I32Load8S(_) | I32Load8U(_) | I32Load16S(_) | I32Load16U(_)
+            | I64Load8S(_) | I64Load8U(_) | I64Load16S(_) | I64Load16U(_) | I64Load32S(_)
+            | I64Load32U(_) | V128Load(_) | V128Load8x8S(_) | V128Load8x8U(_) | V128Load16x4S(_)
+            | V128Load16x4U(_) | V128Load32x2S(_) | V128Load32x2U(_) | V128Load8Splat(_)
+            | V128Load16Splat(_) | V128Load32Splat(_) | V128Load64Splat(_) | V128Load32Zero(_)
+            | V128Load64Zero(_) | I8x16ExtractLaneS(_) | I8x16ExtractLaneU(_) | I16x8ExtractLaneS(_)
+            | I16x8ExtractLaneU(_) | I32x4ExtractLane(_) | I64x2ExtractLane(_) | F32x4ExtractLane(_)
+            | F64x2ExtractLane(_) => (1, 1),
+            AtomicFence => (0, 0),
+            I32AtomicLoad(_) | I64AtomicLoad(_) | I32AtomicLoad8U(_) | I32AtomicLoad16U(_)
+            | I64AtomicLoad8U(_) | I64AtomicLoad16U(_) | I64AtomicLoad32U(_) => (1, 1),
+            I32AtomicStore(_) | I64AtomicStore(_) | I32AtomicStore8(_) | I32AtomicStore16(_)
+            | I64AtomicStore8(_) | I64AtomicStore16(_) | I64AtomicStore32(_) => (2, 0),
+            MemoryAtomicNotify(_)
+            | I32AtomicRmwAdd(_)
+            | I64AtomicRmwAdd(_)
+            | I32AtomicRmw8AddU(_)
+            | I32AtomicRmw16AddU(_)
+            | I64AtomicRmw8AddU(_)
+            | I64AtomicRmw16AddU(_)
+            | I64AtomicRmw32AddU(_)
+            | I32AtomicRmwSub(_)
+            | I64AtomicRmwSub(_)
+            | I32AtomicRmw8SubU(_)
+            | I32AtomicRmw16SubU(_)
+            | I64AtomicRmw8SubU(_)
+            | I64AtomicRmw16SubU(_)
+            | I64AtomicRmw32SubU(_)
+            | I32AtomicRmwAnd(_)
+            | I64AtomicRmwAnd(_)
+            | I32AtomicRmw8AndU(_)
+            | I32AtomicRmw16AndU(_)
+            | I64AtomicRmw8AndU(_)
+            | I64AtomicRmw16AndU(_)
+            | I64AtomicRmw32AndU(_)
+            | I32AtomicRmwOr(_)
+            | I64AtomicRmwOr(_)
+            | I32AtomicRmw8OrU(_)
+            | I32AtomicRmw16OrU(_)
+            | I64AtomicRmw8OrU(_)
+            | I64AtomicRmw16OrU(_)
+            | I64AtomicRmw32OrU(_)
+            | I32AtomicRmwXor(_)
+            | I64AtomicRmwXor(_)
+            | I32AtomicRmw8XorU(_)
+            | I32AtomicRmw16XorU(_)
+            | I64AtomicRmw8XorU(_)
+            | I64AtomicRmw16XorU(_)
+            | I64AtomicRmw32XorU(_)
+            | I32AtomicRmwXchg(_)
+            | I64AtomicRmwXchg(_)
+            | I32AtomicRmw8XchgU(_)
+            | I32AtomicRmw16XchgU(_)
+            | I64AtomicRmw8XchgU(_)
+            | I64AtomicRmw16XchgU(_)
+            | I64AtomicRmw32XchgU(_) => (2, 1),
+            MemoryAtomicWait32(_)
+            | MemoryAtomicWait64(_)
+            | I32AtomicRmwCmpxchg(_)
+            | I64AtomicRmwCmpxchg(_)
+            | I32AtomicRmw8CmpxchgU(_)
+            | I32AtomicRmw16CmpxchgU(_)
+            | I64AtomicRmw8CmpxchgU(_)
+            | I64AtomicRmw16CmpxchgU(_)
+            | I64AtomicRmw32CmpxchgU(_) => (3, 1),
+            // 控制指令已经在 instr 里处理
+            _ => unreachable!("{:?}", instr),
+        }
+    }
+}
+
+struct Compiler<'a> {
+    sigs: Signatures<'a>,
+    ops: Vec<Op>,
+    /// 每条指令执行前的栈高度
+    heights: Vec<usize>,
+    /// 每条指令对应的原始指令的位置
+    offsets: Vec<usize>,
+    /// 解码时记录的位置，按原始指令的顺序排列，包括 else 和 end
+    instr_offsets: &'a [usize],
+    /// 下一条原始指令在 instr_offsets 中的下标
+    next: usize,
+    labels: Vec<Label>,
+    handlers: Vec<Handler>,
+    /// 当前的栈高度
+    height: usize,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(module: &'a Module, instr_offsets: &'a [usize]) -> Self {
+        Self {
+            sigs: Signatures::new(module),
+            ops: vec![],
+            heights: vec![],
+            offsets: vec![],
+            instr_offsets,
+            next: 0,
+            labels: vec![],
+            handlers: vec![],
+            height: 0,
+        }
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.ops.push(op);
+        self.heights.push(self.height);
+        self.offsets.push(
+            self.instr_offsets
+                .get(self.next.saturating_sub(1))
+                .copied()
+                .unwrap_or(0),
+        );
+        self.ops.len() - 1
+    }
+
+    /// 移到下一条原始指令，之后生成的指令都对应它
+    fn advance(&mut self, n: usize) {
+        self.next += n;
+    }
+
+    fn push_label(&mut self, func_type: &FuncType, is_loop: bool) {
+        let params = func_type.params.len();
+
+        self.labels.push(Label {
+            height: self.height - params,
+            keep: match is_loop {
+                true => params,
+                false => func_type.results.len(),
+            },
+            start: is_loop.then_some(self.ops.len()),
+            fixups: vec![],
+        });
+    }
+
+    /// 块结束，回填跳到块尾的位置
+    fn pop_label(&mut self) -> Label {
+        let label = self.labels.pop().unwrap();
+        let end = self.ops.len();
+
+        for fixup in &label.fixups {
+            match *fixup {
+                Fixup::Op(at) => match &mut self.ops[at] {
+                    Op::Br(target) | Op::BrIf(target) => target.pc = end,
+                    Op::BrUnless(pc) | Op::Jump(pc) => *pc = end,
+                    _ => {}
+                },
+                Fixup::BrTable(at, i) => {
+                    if let Op::BrTable(targets) = &mut self.ops[at] {
+                        targets[i].pc = end;
+                    }
+                }
+                Fixup::Catch(h, i) => self.handlers[h].catches[i].1.pc = end,
+            }
+        }
+
+        label
+    }
+
+    fn label_mut(&mut self, l: LabelIdx) -> &mut Label {
+        let i = self.labels.len() - 1 - l as usize;
+
+        &mut self.labels[i]
+    }
+
+    /// 块尾还不知道时 pc 先填 0，等块结束后回填
+    fn target(&mut self, l: LabelIdx, fixup: Fixup) -> Target {
+        let label = self.label_mut(l);
+        let target = Target {
+            pc: label.start.unwrap_or(0),
+            keep: label.keep,
+            height: label.height,
+        };
+
+        if label.start.is_none() {
+            label.fixups.push(fixup);
+        }
+
+        target
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        for (i, instr) in expr.iter().enumerate() {
+            // 之后直到块尾的指令都执行不到，不用编译，但要跳过它们的位置
+            if !self.instr(instr) {
+                self.advance(instr_count(&expr[i + 1..]));
+
+                break;
+            }
+        }
+    }
+
+    /// 返回后面的指令是否可达
+    fn instr(&mut self, instr: &Instruction) -> bool {
+        self.advance(1);
+
+        match instr {
+            Instruction::Block(block) | Instruction::Loop(block) => {
+                let func_type = self.sigs.block_type(&block.type_);
+
+                self.push_label(&func_type, matches!(instr, Instruction::Loop(_)));
+                self.expr(&block.expr);
+                self.end_block(&func_type);
+            }
+            Instruction::TryTable(block) => {
+                let func_type = self.sigs.block_type(&block.type_);
+                let h = self.handlers.len();
+                // catch 子句的标签在 try_table 之外
+                let catches = block
+                    .catches
+                    .iter()
+                    .enumerate()
+                    .map(|(i, catch)| (*catch, self.target(catch.label(), Fixup::Catch(h, i))))
+                    .collect();
+                let start = self.ops.len();
+
+                self.handlers.push(Handler {
+                    start,
+                    end: start,
+                    catches,
+                });
+                self.push_label(&func_type, false);
+                self.expr(&block.expr);
+                self.handlers[h].end = self.ops.len();
+                self.end_block(&func_type);
+            }
+            Instruction::If(block) => {
+                let func_type = self.sigs.block_type(&block.type_);
+
+                let br_unless = self.emit(Op::BrUnless(0));
+
+                self.height -= 1;
+
+                self.push_label(&func_type, false);
+                self.expr(&block.if_expr);
+
+                match block.else_expr.is_empty() {
+                    true => self.label_mut(0).fixups.push(Fixup::Op(br_unless)),
+                    false => {
+                        self.advance(1);
+
+                        let jump = self.emit(Op::Jump(0));
+                        let label = self.label_mut(0);
+
+                        label.fixups.push(Fixup::Op(jump));
+                        self.height = label.height + func_type.params.len();
+                        self.ops[br_unless] = Op::BrUnless(self.ops.len());
+                        self.expr(&block.else_expr);
+                    }
+                }
+
+                self.end_block(&func_type);
+            }
+            Instruction::Br(l) => {
+                let target = self.target(*l, Fixup::Op(self.ops.len()));
+
+                self.emit(Op::Br(target));
+
+                return false;
+            }
+            Instruction::BrIf(l) => {
+                let target = self.target(*l, Fixup::Op(self.ops.len()));
+
+                self.emit(Op::BrIf(target));
+                self.height -= 1;
+            }
+            Instruction::BrTable(arg) => {
+                let at = self.ops.len();
+                let targets = arg
+                    .labels
+                    .iter()
+                    .chain([&arg.default])
+                    .enumerate()
+                    .map(|(i, l)| self.target(*l, Fixup::BrTable(at, i)))
+                    .collect();
+
+                self.emit(Op::BrTable(targets));
+
+                return false;
+            }
+            Instruction::Return => {
+                let l = self.labels.len() - 1;
+                let target = self.target(l as LabelIdx, Fixup::Op(self.ops.len()));
+
+                self.emit(Op::Br(target));
+
+                return false;
+            }
+            // 尾调用直接替换当前函数的栈帧，抛出异常后从 catch 子句继续，之后的指令都执行不到
+            Instruction::Unreachable
+            | Instruction::ReturnCall(_)
+            | Instruction::ReturnCallIndirect(..)
+            | Instruction::Throw(_)
+            | Instruction::ThrowRef => {
+                self.emit(Op::Instr(instr.clone()));
+
+                return false;
+            }
+            _ => {
+                let (pops, pushes) = self.sigs.stack_effect(instr);
+
+                self.emit(Op::Instr(instr.clone()));
+                self.height = self.height - pops + pushes;
+            }
+        }
+
+        true
+    }
+
+    fn end_block(&mut self, func_type: &FuncType) {
+        let label = self.pop_label();
+
+        self.advance(1);
+        self.height = label.height + func_type.results.len();
+    }
+}
+
+/// 解码时为这些指令记录的位置个数，块要算上 else 和 end
+fn instr_count(expr: &[Instruction]) -> usize {
+    expr.iter()
+        .map(|instr| match instr {
+            Instruction::Block(block) | Instruction::Loop(block) => instr_count(&block.expr) + 2,
+            Instruction::TryTable(block) => instr_count(&block.expr) + 2,
+            Instruction::If(block) => match block.else_expr.is_empty() {
+                true => instr_count(&block.if_expr) + 2,
+                false => instr_count(&block.if_expr) + instr_count(&block.else_expr) + 3,
+            },
+            _ => 1,
+        })
+        .sum()
+}
@@ -0,0 +1,78 @@
+/// 虚拟机的运行限制
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// 最大调用深度，每层调用都会在宿主栈上递归一次
+    pub max_call_depth: usize,
+    /// 操作数栈（含局部变量）最多能容纳的值的个数
+    pub max_stack_size: usize,
+    /// 初始燃料，None 表示不计量
+    pub fuel: Option<u64>,
+    pub fuel_costs: FuelCosts,
+    /// 中断句柄的 epoch 前进多少次就中断执行，从创建虚拟机或上一次中断返回时算起
+    pub epoch_deadline: u64,
+    /// 函数体的执行方式
+    pub engine: Engine,
+}
+
+/// 解释器的类型
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Engine {
+    /// 在操作数栈上压入、弹出
+    #[default]
+    Stack,
+    /// 局部变量和栈上的位置都映射成固定的寄存器，指令直接读写寄存器
+    Register,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            // debug 构建下每层调用占用的宿主栈要大得多，要保证在 2M 的线程栈里也不会溢出
+            max_call_depth: if cfg!(debug_assertions) { 256 } else { 2048 },
+            max_stack_size: 1 << 20,
+            fuel: None,
+            fuel_costs: FuelCosts::default(),
+            epoch_deadline: 1,
+            engine: Engine::default(),
+        }
+    }
+}
+
+/// 每类指令消耗的燃料
+#[derive(Debug, Clone)]
+pub struct FuelCosts {
+    /// 分支、返回等，块在编译时已经展开，不再消耗燃料
+    pub control: u64,
+    /// 内存、表的读写以及段操作，包括 0xfe 前缀的原子指令
+    pub memory: u64,
+    /// 其余的数值、变量、引用指令
+    pub numeric: u64,
+    /// 0xfd 前缀的向量指令
+    pub vector: u64,
+    /// call、call_indirect 以及对应的尾调用
+    pub call: u64,
+}
+
+impl Default for FuelCosts {
+    fn default() -> Self {
+        Self {
+            control: 1,
+            memory: 1,
+            numeric: 1,
+            vector: 1,
+            call: 1,
+        }
+    }
+}
+
+impl FuelCosts {
+    pub fn cost(&self, opcode: u16) -> u64 {
+        match opcode {
+            0x10..=0x13 => self.call,
+            0x00..=0x0f => self.control,
+            0x25 | 0x26 | 0x28..=0x40 | 0xfc08..=0xfc11 | 0xfe00..=0xfeff => self.memory,
+            0xfd00..=0xfdff => self.vector,
+            _ => self.numeric,
+        }
+    }
+}
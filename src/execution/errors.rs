@@ -1,4 +1,12 @@
 use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+use super::inst::tag::ExnInst;
+use super::inst::{RExnInst, RTagInst};
+use super::instr::control::check_vals;
+use super::value::{ValInst, ValInsts};
+use crate::binary::types::{FuncType, GlobalType, RefType, ValType};
 
 pub type VMState<T = ()> = Result<T, Box<dyn Error>>;
 
@@ -25,8 +33,48 @@ pub enum LinkError {
     #[error("模块 {0} 中找不到导出项：{1}")]
     ExportNotFound(String, String),
 
-    #[error("导入项类型不匹配")]
-    IncompatibleImportType,
+    #[error("导入项 {0}.{1} 类型不匹配：{2}")]
+    IncompatibleImportType(String, String, Mismatch),
+
+    #[error("导入项 {0}.{1} 是其他 Store 中的函数")]
+    ForeignFunc(String, String),
+
+    #[error("需要 {0} 个导入项，实际提供了 {1} 个")]
+    ImportCountNotEq(usize, usize),
+}
+
+/// 导入项和模块中声明的类型不一致的原因
+#[derive(thiserror::Error, Debug)]
+pub enum Mismatch {
+    #[error("需要{0}，提供的是{1}")]
+    Kind(&'static str, &'static str),
+
+    #[error("需要函数签名 {0}，提供的是 {1}")]
+    FuncType(FuncType, FuncType),
+
+    #[error("需要元素类型 {0:?}，提供的是 {1:?}")]
+    ElemType(RefType, RefType),
+
+    #[error("需要地址类型 {0:?}，提供的是 {1:?}")]
+    AddrType(ValType, ValType),
+
+    #[error("需要共享属性为 {0}，提供的是 {1}")]
+    Shared(bool, bool),
+
+    #[error("需要最小大小至少为 {0}，提供的是 {1}")]
+    Min(u64, u64),
+
+    #[error("需要最大大小不超过 {0}，提供的没有上限")]
+    NoMax(u64),
+
+    #[error("需要最大大小不超过 {0}，提供的是 {1}")]
+    Max(u64, u64),
+
+    #[error("需要全局变量类型 {0:?}，提供的是 {1:?}")]
+    GlobalType(GlobalType, GlobalType),
+
+    #[error("需要异常标签类型 {0}，提供的是 {1}")]
+    TagType(FuncType, FuncType),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -61,12 +109,12 @@ pub enum Trap {
     #[error("未初始化")]
     UnInitTableElem,
 
+    #[error("未定义的表元素")]
+    UndefinedElem,
+
     #[error("不是一个有效的引用")]
     InvalidRef,
 
-    #[error("out of range")]
-    OutofRange,
-
     #[error("integer overflow")]
     IntegerOverflow,
 
@@ -75,4 +123,86 @@ pub enum Trap {
 
     #[error("invalid conversion to integer")]
     InvalidConversionToInteger,
+
+    #[error("调用栈溢出")]
+    CallStackExhausted,
+
+    #[error("燃料耗尽")]
+    OutOfFuel,
+
+    #[error("没有可以恢复执行的调用")]
+    NothingToResume,
+
+    #[error("执行被中断")]
+    Interrupted,
+
+    #[error("函数不属于当前 Store，只能通过导入方调用")]
+    ForeignFunc,
+
+    #[error("导入方正在执行，不能重入")]
+    ImporterBusy,
+
+    #[error("null exception reference")]
+    NullExnRef,
+
+    #[error("unaligned atomic")]
+    UnalignedAtomic,
+
+    #[error("expected shared memory")]
+    ExpectedSharedMemory,
+}
+
+/// 从外部调用函数时，参数、返回值和签名不一致
+///
+/// 和 Trap 分开，避免 Trap 变大，执行指令时每个出错的分支都要占用更多的栈空间
+#[derive(thiserror::Error, Debug)]
+pub enum CallError {
+    #[error("需要 {1} 个{0}，实际是 {2} 个")]
+    CountNotEq(&'static str, usize, usize),
+
+    #[error("第 {1} 个{0}类型不匹配：需要 {2:?}，实际是 {3:?}")]
+    TypeNotEq(&'static str, usize, ValType, ValType),
+
+    #[error("函数签名不匹配：需要 {0}，实际是 {1}")]
+    FuncTypeNotEq(FuncType, FuncType),
+}
+
+/// 抛出后没有被捕获的异常，作为错误交给嵌入方
+///
+/// 宿主函数返回这个错误就是在 wasm 中抛出异常，经过其他虚拟机的导入方时也原样传递
+#[derive(thiserror::Error, Debug)]
+pub struct Exception(pub(crate) RExnInst);
+
+impl Exception {
+    /// 异常值要和标签的参数一致
+    pub fn new(tag: RTagInst, values: ValInsts) -> VMState<Self> {
+        check_vals(&tag.get_type().params, &values, "异常值")?;
+
+        Ok(Self(Rc::new(ExnInst { tag, values })))
+    }
+
+    pub fn tag(&self) -> &RTagInst {
+        &self.0.tag
+    }
+
+    pub fn values(&self) -> &[ValInst] {
+        &self.0.values
+    }
+
+    pub fn exn_ref(&self) -> ValInst {
+        ValInst::ExnRef(Some(Rc::clone(&self.0)))
+    }
+}
+
+/// 重新抛出捕获到的 exnref
+impl From<RExnInst> for Exception {
+    fn from(exn: RExnInst) -> Self {
+        Self(exn)
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "未捕获的异常，异常值 {:?}", self.values())
+    }
 }
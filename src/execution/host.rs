@@ -0,0 +1,243 @@
+use std::rc::Rc;
+
+use super::errors::VMState;
+use super::inst::ExportInst;
+use super::store::ModuleInst;
+use super::value::{v128, ExternRef, RefInst, ValInst, ValInsts};
+use crate::binary::types::{FuncType, ValType};
+
+/// 宿主函数，参数和返回值的类型与签名一致
+pub type HostFunc = Rc<dyn Fn(Caller, ValInsts) -> VMState<ValInsts>>;
+
+/// 宿主函数被调用时调用方的上下文
+#[derive(Debug, Clone, Copy)]
+pub struct Caller<'a> {
+    inst: Option<&'a ModuleInst>,
+}
+
+impl<'a> Caller<'a> {
+    pub(crate) fn new(inst: Option<&'a ModuleInst>) -> Self {
+        Self { inst }
+    }
+
+    /// 从虚拟机外部直接调用宿主函数时没有调用方实例
+    pub fn instance(&self) -> Option<&'a ModuleInst> {
+        self.inst
+    }
+
+    pub fn get_export(&self, name: &str) -> Option<ExportInst> {
+        self.inst.and_then(|inst| inst.get_export(name))
+    }
+}
+
+/// 可以在宿主和 wasm 之间直接传递的值
+pub trait WasmTy: Sized {
+    fn val_type() -> ValType;
+
+    /// 值的类型已经检查过
+    fn from_val(val: &ValInst) -> Self;
+
+    fn into_val(self) -> ValInst;
+}
+
+macro_rules! wasm_ty {
+    ($(($ty:ty, $val_type:ident, $as:ident))*) => {
+        $(
+            impl WasmTy for $ty {
+                fn val_type() -> ValType {
+                    ValType::$val_type
+                }
+
+                fn from_val(val: &ValInst) -> Self {
+                    val.$as()
+                }
+
+                fn into_val(self) -> ValInst {
+                    ValInst::from(self)
+                }
+            }
+        )*
+    };
+}
+
+wasm_ty! {
+    (i32, I32, as_i32)
+    (i64, I64, as_i64)
+    (f32, F32, as_f32)
+    (f64, F64, as_f64)
+    (v128, V128, as_v128)
+}
+
+/// funcref，None 为空引用
+impl WasmTy for Option<RefInst> {
+    fn val_type() -> ValType {
+        ValType::FuncRef
+    }
+
+    fn from_val(val: &ValInst) -> Self {
+        match val {
+            ValInst::FuncRef(v) => v.clone(),
+            _ => panic!("不能将 {:?} 转为 FuncRef，类型不匹配", val),
+        }
+    }
+
+    fn into_val(self) -> ValInst {
+        ValInst::FuncRef(self)
+    }
+}
+
+/// externref，None 为空引用
+impl WasmTy for Option<ExternRef> {
+    fn val_type() -> ValType {
+        ValType::ExternRef
+    }
+
+    fn from_val(val: &ValInst) -> Self {
+        match val {
+            ValInst::ExternRef(v) => v.clone(),
+            _ => panic!("不能将 {:?} 转为 ExternRef，类型不匹配", val),
+        }
+    }
+
+    fn into_val(self) -> ValInst {
+        ValInst::ExternRef(self)
+    }
+}
+
+/// 一组参数或返回值，以元组表示
+pub trait WasmList: Sized {
+    fn val_types() -> Vec<ValType>;
+
+    fn from_vals(vals: &[ValInst]) -> Self;
+
+    fn into_vals(self) -> ValInsts;
+}
+
+/// 宿主函数的返回值，可以是单个值、元组或者 VMState
+pub trait HostReturn {
+    fn val_types() -> Vec<ValType>;
+
+    fn into_results(self) -> VMState<ValInsts>;
+}
+
+impl<R: WasmList> HostReturn for R {
+    fn val_types() -> Vec<ValType> {
+        R::val_types()
+    }
+
+    fn into_results(self) -> VMState<ValInsts> {
+        Ok(self.into_vals())
+    }
+}
+
+impl<R: WasmList> HostReturn for VMState<R> {
+    fn val_types() -> Vec<ValType> {
+        R::val_types()
+    }
+
+    fn into_results(self) -> VMState<ValInsts> {
+        self.map(R::into_vals)
+    }
+}
+
+macro_rules! wasm_single {
+    ($($ty:ty),*) => {
+        $(
+            impl WasmList for $ty {
+                fn val_types() -> Vec<ValType> {
+                    vec![<$ty>::val_type()]
+                }
+
+                fn from_vals(vals: &[ValInst]) -> Self {
+                    <$ty>::from_val(&vals[0])
+                }
+
+                fn into_vals(self) -> ValInsts {
+                    vec![self.into_val()]
+                }
+            }
+        )*
+    };
+}
+
+wasm_single!(i32, i64, f32, f64, v128, Option<RefInst>, Option<ExternRef>);
+
+/// 把 Rust 闭包转换成宿主函数，Params 只用来区分不同的参数形式
+pub trait IntoFunc<Params, Results> {
+    fn into_func(self) -> (FuncType, HostFunc);
+}
+
+macro_rules! wasm_tuple {
+    ($($name:ident)*) => {
+        #[allow(non_snake_case, unused_variables, unused_mut, clippy::unused_unit)]
+        impl<$($name: WasmTy,)*> WasmList for ($($name,)*) {
+            fn val_types() -> Vec<ValType> {
+                vec![$($name::val_type(),)*]
+            }
+
+            fn from_vals(vals: &[ValInst]) -> Self {
+                let mut vals = vals.iter();
+
+                ($($name::from_val(vals.next().unwrap()),)*)
+            }
+
+            fn into_vals(self) -> ValInsts {
+                let ($($name,)*) = self;
+
+                vec![$($name.into_val(),)*]
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl<F, $($name: WasmTy,)* R: HostReturn> IntoFunc<($($name,)*), R> for F
+        where
+            F: Fn($($name),*) -> R + 'static,
+        {
+            fn into_func(self) -> (FuncType, HostFunc) {
+                let func_type = FuncType {
+                    params: <($($name,)*) as WasmList>::val_types(),
+                    results: R::val_types(),
+                };
+                let func: HostFunc = Rc::new(move |_, args| {
+                    let ($($name,)*) = <($($name,)*) as WasmList>::from_vals(&args);
+
+                    self($($name),*).into_results()
+                });
+
+                (func_type, func)
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl<F, $($name: WasmTy,)* R: HostReturn> IntoFunc<(Caller<'static>, $($name,)*), R> for F
+        where
+            F: Fn(Caller, $($name),*) -> R + 'static,
+        {
+            fn into_func(self) -> (FuncType, HostFunc) {
+                let func_type = FuncType {
+                    params: <($($name,)*) as WasmList>::val_types(),
+                    results: R::val_types(),
+                };
+                let func: HostFunc = Rc::new(move |caller, args| {
+                    let ($($name,)*) = <($($name,)*) as WasmList>::from_vals(&args);
+
+                    self(caller, $($name),*).into_results()
+                });
+
+                (func_type, func)
+            }
+        }
+    };
+}
+
+wasm_tuple!();
+wasm_tuple!(A1);
+wasm_tuple!(A1 A2);
+wasm_tuple!(A1 A2 A3);
+wasm_tuple!(A1 A2 A3 A4);
+wasm_tuple!(A1 A2 A3 A4 A5);
+wasm_tuple!(A1 A2 A3 A4 A5 A6);
+wasm_tuple!(A1 A2 A3 A4 A5 A6 A7);
+wasm_tuple!(A1 A2 A3 A4 A5 A6 A7 A8);
+wasm_tuple!(A1 A2 A3 A4 A5 A6 A7 A8 A9);
+wasm_tuple!(A1 A2 A3 A4 A5 A6 A7 A8 A9 A10);
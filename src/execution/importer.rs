@@ -2,9 +2,11 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
-use super::errors::VMState;
-use super::inst::{RFuncInst, RGlobalInst, RMemInst, RTableInst};
+use super::errors::{LinkError, VMState};
+use super::inst::function::FuncInstKind;
+use super::inst::{ExportInst, RFuncInst, RGlobalInst, RMemInst, RTableInst, RTagInst};
 use super::value::ValInsts;
+use crate::binary::section::{ImportDesc, ImportSeg};
 
 pub trait Importer {
     fn get_name(&self) -> &str;
@@ -17,6 +19,11 @@ pub trait Importer {
         None
     }
 
+    /// 按函数索引取函数
+    fn resolve_func_by_idx(&self, _idx: u32) -> Option<RFuncInst> {
+        None
+    }
+
     fn resolve_table(&self, _name: &str) -> Option<RTableInst> {
         None
     }
@@ -29,6 +36,10 @@ pub trait Importer {
         None
     }
 
+    fn resolve_tag(&self, _name: &str) -> Option<RTagInst> {
+        None
+    }
+
     fn call_by_name(&mut self, name: &str, args: ValInsts) -> VMState<ValInsts>;
 }
 
@@ -39,3 +50,33 @@ impl PartialEq for dyn Importer {
         self.get_id() == rhs.get_id()
     }
 }
+
+/// 从导入方取出一个导入项，类型在实例化时检查
+///
+/// 导入方的函数都包装成外部函数，调用时交给导入方执行；宿主函数可以直接调用，不用包装
+pub fn resolve_import(import: &ImportSeg, importer_: &Rc<RefCell<dyn Importer>>) -> VMState<ExportInst> {
+    let importer = importer_.borrow();
+    let name = &import.name;
+    let extern_ = match &import.desc {
+        ImportDesc::Func(_) => importer.resolve_func(name).map(|func_inst| {
+            let func = match &func_inst.borrow().kind {
+                FuncInstKind::Host(..) => Rc::clone(&func_inst),
+                _ => func_inst.borrow().as_outer(Rc::clone(importer_), name),
+            };
+
+            ExportInst::Func(func)
+        }),
+        ImportDesc::Table(_) => importer.resolve_table(name).map(ExportInst::Table),
+        ImportDesc::Mem(_) => importer.resolve_mem(name).map(ExportInst::Mem),
+        ImportDesc::Global(_) => importer.resolve_global(name).map(ExportInst::Global),
+        ImportDesc::Tag(_) => importer.resolve_tag(name).map(ExportInst::Tag),
+    };
+
+    match extern_ {
+        Some(extern_) => Ok(extern_),
+        None => Err(LinkError::ItemNotFound(
+            importer.get_name().to_string(),
+            name.clone(),
+        ))?,
+    }
+}
@@ -3,10 +3,12 @@ use std::fmt;
 use std::rc::Rc;
 
 use super::RFuncInst;
-use crate::binary::section::CodeSeg;
 use crate::binary::types::{FuncType, ValType};
+use crate::execution::bytecode::Bytecode;
+use crate::execution::host::HostFunc;
 use crate::execution::importer::Importer;
 use crate::execution::random_str;
+use crate::execution::store::Instance;
 
 #[derive(Debug)]
 pub struct FuncInst {
@@ -17,12 +19,13 @@ pub struct FuncInst {
 }
 
 impl FuncInst {
-    pub fn from_wasm(ft: FuncType, i: usize, code: Rc<CodeSeg>, from: &str) -> Self {
+    /// from 为所在 Store 的 id，inst 为所属实例
+    pub fn from_wasm(ft: FuncType, inst: Instance, idx: usize, code: Rc<Bytecode>, from: &str) -> Self {
         Self {
             id: random_str(7),
             type_: ft,
             from: from.to_string(),
-            kind: FuncInstKind::Inner(i, code),
+            kind: FuncInstKind::Inner { inst, idx, code },
         }
     }
 
@@ -37,10 +40,25 @@ impl FuncInst {
         }
     }
 
+    /// 宿主函数不依赖任何实例，可以放进多个 Store 中，name 只用于显示
+    pub fn from_host(ft: FuncType, func: HostFunc, name: &str) -> Self {
+        Self {
+            id: random_str(7),
+            type_: ft,
+            from: "host".to_string(),
+            kind: FuncInstKind::Host(name.to_string(), func),
+        }
+    }
+
     pub fn get_id(&self) -> &str {
         &self.id
     }
 
+    /// 模块中的函数只能在创建它的 Store 中调用，其他函数不受限制
+    pub fn callable_in(&self, store_id: &str) -> bool {
+        !matches!(self.kind, FuncInstKind::Inner { .. }) || self.from == store_id
+    }
+
     pub fn get_type(&self) -> &FuncType {
         &self.type_
     }
@@ -70,15 +88,22 @@ impl PartialEq for FuncInst {
 }
 
 pub enum FuncInstKind {
-    Inner(usize, Rc<CodeSeg>),
+    /// idx 为函数在代码段中的索引
+    Inner {
+        inst: Instance,
+        idx: usize,
+        code: Rc<Bytecode>,
+    },
     Outer(Rc<RefCell<dyn Importer>>, String),
+    Host(String, HostFunc),
 }
 
 impl fmt::Debug for FuncInstKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Inner(_, code) => write!(f, "{:?}", code.as_ref()),
+            Self::Inner { code, .. } => write!(f, "{:?}", code.as_ref()),
             Self::Outer(_, v2) => write!(f, "外部函数：{}", v2),
+            Self::Host(name, _) => write!(f, "宿主函数：{}", name),
         }
     }
 }
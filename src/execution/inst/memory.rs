@@ -1,21 +1,30 @@
+use std::collections::{HashMap, VecDeque};
 use std::simd::ToBytes;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, RwLock};
+use std::time::{Duration, Instant};
 
 use crate::binary::instruction::{Lane16, Lane8};
-use crate::binary::section::MaybeU32;
 use crate::binary::types::MemType;
-use crate::execution::errors::{InstError, VMState, Trap};
+use crate::execution::errors::{InstError, Trap, VMState};
 use crate::execution::value::v128;
 
 pub const PAGE_SIZE: u32 = 65536;
 pub const MAX_PAGE_SIZE: u32 = 65536;
+/// memory64 没有指定 max 时最多增长到 2^48 页
+pub const MAX_PAGE_SIZE_64: u64 = 1 << 48;
+/// 等待期间每隔这么久醒来一次，检查超时和中断
+const WAIT_SLICE: Duration = Duration::from_millis(10);
 
 pub trait Memory {
-    fn alloc(size: u32) -> Vec<u8> {
-        vec![0; size as usize]
+    fn alloc(size: usize) -> Vec<u8> {
+        vec![0; size]
     }
 
-    fn mem_size(&self) -> u32;
-    fn mem_grow(&mut self, size: u32) -> i32;
+    /// 页数，memory64 可以超过 u32
+    fn mem_size(&self) -> u64;
+    /// 返回原来的页数，失败时返回 -1
+    fn mem_grow(&mut self, size: u64) -> i64;
 
     fn mem_read(&self, addr: u64) -> VMState<u8> {
         let data = self.mem_reads(addr, 1)?;
@@ -107,99 +116,410 @@ pub trait Memory {
     }
 }
 
+/// 共享内存，克隆后可以发送到其他线程，交给那里的虚拟机导入
+///
+/// 所有访问都经过读写锁，原子指令在写锁内完成读改写，因此天然是顺序一致的
+#[derive(Debug, Clone)]
+pub struct SharedMemory(Arc<SharedData>);
+
+#[derive(Debug)]
+struct SharedData {
+    type_: MemType,
+    data: RwLock<Vec<u8>>,
+    /// 每个地址上正在等待的编号，按等待的先后排列
+    waiters: Mutex<HashMap<u64, VecDeque<u64>>>,
+    woken: Condvar,
+    next_id: AtomicU64,
+}
+
+impl SharedMemory {
+    fn new(type_: MemType, init_size: usize) -> Self {
+        Self(Arc::new(SharedData {
+            type_,
+            data: RwLock::new(MemInst::alloc(init_size)),
+            waiters: Mutex::default(),
+            woken: Condvar::new(),
+            next_id: AtomicU64::new(0),
+        }))
+    }
+
+    fn waiters(&self) -> MutexGuard<'_, HashMap<u64, VecDeque<u64>>> {
+        self.0.waiters.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 先在等待表的锁内比较期望值，通知方也要拿这把锁，因此不会错过唤醒
+    fn wait(
+        &self,
+        addr: u64,
+        expected: &[u8],
+        timeout: Option<Duration>,
+        check: impl Fn() -> VMState,
+    ) -> VMState<u32> {
+        let mut waiters = self.waiters();
+        let actual = {
+            let data = self.0.data.read().unwrap_or_else(PoisonError::into_inner);
+
+            data[range(&data, addr, expected.len() as u64)?].to_vec()
+        };
+
+        if actual != expected {
+            return Ok(1);
+        }
+
+        let id = self.0.next_id.fetch_add(1, Ordering::Relaxed);
+        let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+
+        waiters.entry(addr).or_default().push_back(id);
+
+        loop {
+            let slice = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()).min(WAIT_SLICE),
+                None => WAIT_SLICE,
+            };
+
+            waiters = match self.0.woken.wait_timeout(waiters, slice) {
+                Ok((guard, _)) => guard,
+                Err(err) => err.into_inner().0,
+            };
+
+            // 被唤醒的编号已经由通知方从队列里移除
+            let Some(queue) = waiters.get_mut(&addr).filter(|queue| queue.contains(&id)) else {
+                return Ok(0);
+            };
+
+            let result = match deadline {
+                Some(deadline) if Instant::now() >= deadline => Ok(2),
+                _ => match check() {
+                    Ok(()) => continue,
+                    Err(err) => Err(err),
+                },
+            };
+
+            queue.retain(|&waiter| waiter != id);
+
+            if queue.is_empty() {
+                waiters.remove(&addr);
+            }
+
+            return result;
+        }
+    }
+
+    fn notify(&self, addr: u64, count: u32) -> u32 {
+        let mut waiters = self.waiters();
+        let Some(queue) = waiters.get_mut(&addr) else {
+            return 0;
+        };
+        let n = queue.len().min(count as usize);
+
+        queue.drain(..n);
+
+        if queue.is_empty() {
+            waiters.remove(&addr);
+        }
+
+        if n > 0 {
+            self.0.woken.notify_all();
+        }
+
+        n as u32
+    }
+}
+
+#[derive(Debug)]
+enum Storage {
+    Owned(Vec<u8>),
+    Shared(SharedMemory),
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self::Owned(vec![])
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct MemInst {
     type_: MemType,
-    data: Vec<u8>,
+    data: Storage,
 }
 
 impl MemInst {
     pub fn new(type_: MemType) -> Self {
-        let init_size = type_.min * PAGE_SIZE;
+        let init_size = type_.min as usize * PAGE_SIZE as usize;
+        let data = match type_.shared {
+            true => Storage::Shared(SharedMemory::new(type_.clone(), init_size)),
+            false => Storage::Owned(Self::alloc(init_size)),
+        };
 
-        Self {
-            type_,
-            data: Self::alloc(init_size),
-        }
+        Self { type_, data }
     }
 
-    pub fn get_type(&self) -> &MemType {
-        &self.type_
+    /// 当前的页数作为 min，共享内存可能已经被其他线程扩容
+    pub fn get_type(&self) -> MemType {
+        MemType {
+            min: self.mem_size(),
+            ..self.type_.clone()
+        }
     }
 
-    pub fn max(&self) -> MaybeU32 {
+    pub fn max(&self) -> Option<u64> {
         self.type_.max
     }
 
-    pub fn copy(&mut self, addr: usize, n: usize, dest: usize) {
-        self.data.copy_within(addr..addr + n, dest);
+    /// 共享内存返回可以发送到其他线程的句柄
+    pub fn shared(&self) -> Option<SharedMemory> {
+        match &self.data {
+            Storage::Shared(shared) => Some(shared.clone()),
+            Storage::Owned(_) => None,
+        }
     }
-}
 
-impl Memory for MemInst {
-    fn mem_read(&self, addr: u64) -> VMState<u8> {
-        let addr = addr as usize;
+    fn with_data<R>(&self, f: impl FnOnce(&Vec<u8>) -> R) -> R {
+        match &self.data {
+            Storage::Owned(data) => f(data),
+            Storage::Shared(shared) => f(&shared.0.data.read().unwrap_or_else(PoisonError::into_inner)),
+        }
+    }
 
-        Ok(self.data[addr])
+    fn with_data_mut<R>(&mut self, f: impl FnOnce(&mut Vec<u8>) -> R) -> R {
+        match &mut self.data {
+            Storage::Owned(data) => f(data),
+            Storage::Shared(shared) => {
+                f(&mut shared.0.data.write().unwrap_or_else(PoisonError::into_inner))
+            }
+        }
     }
 
-    fn mem_reads(&self, addr: u64, n: u64) -> VMState<Vec<u8>> {
-        let addr = addr as usize;
-        let n = n as usize;
+    /// 先检查范围，长度被改坏时也不会申请过多内存
+    pub fn fill(&mut self, addr: u64, n: u64, val: u8) -> VMState {
+        self.with_data_mut(|data| {
+            let range = range(data, addr, n)?;
 
-        if (addr + n) > self.data.len() {
-            Err(Trap::OutofRange)?;
-        }
+            data[range].fill(val);
 
-        Ok(self.data[addr..addr + n].to_vec())
+            Ok(())
+        })
     }
 
-    fn mem_writes(&mut self, addr: u64, bytes: &[u8]) -> VMState {
-        let addr = addr as usize;
-        let total = addr + bytes.len();
+    pub fn copy(&mut self, addr: usize, n: usize, dest: usize) {
+        self.with_data_mut(|data| data.copy_within(addr..addr + n, dest));
+    }
 
-        if total > self.data.len() || total < addr {
-            Err(InstError::OutofBoundMem)?;
-        }
+    /// 在一次加锁内读出 bytes 字节（小端），用 f 算出新值写回，返回旧值
+    pub fn atomic_rmw(&mut self, addr: u64, bytes: u64, f: impl FnOnce(u64) -> u64) -> VMState<u64> {
+        self.with_data_mut(|data| {
+            let range = range(data, addr, bytes)?;
+            let mut buf = [0u8; 8];
+
+            buf[..range.len()].copy_from_slice(&data[range.clone()]);
 
-        let slice = &mut self.data[addr..total];
+            let old = u64::from_le_bytes(buf);
+            let new = f(old).to_le_bytes();
 
-        slice.copy_from_slice(bytes);
+            data[range.clone()].copy_from_slice(&new[..range.len()]);
 
-        Ok(())
+            Ok(old)
+        })
     }
 
-    fn mem_size(&self) -> u32 {
-        (self.data.len() as u32) / PAGE_SIZE
+    /// 返回 0 表示被唤醒，1 表示值不等于 expected，2 表示超时；timeout 为 None 时一直等待
+    ///
+    /// check 在等待期间反复调用，返回错误时停止等待
+    pub fn atomic_wait(
+        &self,
+        addr: u64,
+        expected: &[u8],
+        timeout: Option<Duration>,
+        check: impl Fn() -> VMState,
+    ) -> VMState<u32> {
+        match &self.data {
+            Storage::Shared(shared) => shared.wait(addr, expected, timeout, check),
+            Storage::Owned(data) => {
+                range(data, addr, expected.len() as u64)?;
+
+                Err(Trap::ExpectedSharedMemory)?
+            }
+        }
     }
 
-    fn mem_grow(&mut self, size: u32) -> i32 {
-        let old_size = self.mem_size();
+    /// 最多唤醒 count 个等待者，返回实际唤醒的个数，非共享内存上没有等待者
+    pub fn atomic_notify(&self, addr: u64, count: u32) -> VMState<u32> {
+        self.with_data(|data| range(data, addr, 4))?;
+
+        match &self.data {
+            Storage::Shared(shared) => Ok(shared.notify(addr, count)),
+            Storage::Owned(_) => Ok(0),
+        }
+    }
+}
 
-        if size == 0 {
-            return old_size as i32;
+/// 导入其他线程的共享内存
+impl From<SharedMemory> for MemInst {
+    fn from(shared: SharedMemory) -> Self {
+        Self {
+            type_: shared.0.type_.clone(),
+            data: Storage::Shared(shared),
         }
+    }
+}
 
-        let new_size = old_size + size;
-        let max = self.type_.max;
+/// 越界或溢出时返回 OutofBoundMem
+fn range(data: &[u8], addr: u64, n: u64) -> VMState<std::ops::Range<usize>> {
+    match addr.checked_add(n) {
+        Some(end) if end <= data.len() as u64 => Ok(addr as usize..end as usize),
+        _ => Err(InstError::OutofBoundMem)?,
+    }
+}
 
-        match max {
-            Some(max) if max < new_size => {
-                println!("{} 超过该内存块可以分配的总上限：{}", new_size, max);
+impl Memory for MemInst {
+    fn mem_read(&self, addr: u64) -> VMState<u8> {
+        self.with_data(|data| Ok(data[range(data, addr, 1)?.start]))
+    }
 
-                return -1;
+    fn mem_reads(&self, addr: u64, n: u64) -> VMState<Vec<u8>> {
+        self.with_data(|data| Ok(data[range(data, addr, n)?].to_vec()))
+    }
+
+    fn mem_writes(&mut self, addr: u64, bytes: &[u8]) -> VMState {
+        self.with_data_mut(|data| {
+            let range = range(data, addr, bytes.len() as u64)?;
+
+            data[range].copy_from_slice(bytes);
+
+            Ok(())
+        })
+    }
+
+    fn mem_size(&self) -> u64 {
+        self.with_data(|data| data.len() as u64 / PAGE_SIZE as u64)
+    }
+
+    /// 共享内存在写锁内读出原大小再扩容，多个线程同时增长时不会互相覆盖
+    fn mem_grow(&mut self, size: u64) -> i64 {
+        let limit = match (self.type_.max, self.type_.is64) {
+            (Some(max), _) => max,
+            (None, true) => MAX_PAGE_SIZE_64,
+            (None, false) => MAX_PAGE_SIZE as u64,
+        };
+
+        self.with_data_mut(|data| {
+            let old_size = data.len() as u64 / PAGE_SIZE as u64;
+
+            if size == 0 {
+                return old_size as i64;
             }
-            None if new_size > MAX_PAGE_SIZE => {
-                println!("{} 超过可以分配的内存总上限：{}", new_size, MAX_PAGE_SIZE);
 
-                return -1;
+            let additional = match old_size.checked_add(size) {
+                Some(new_size) if new_size <= limit => size.checked_mul(PAGE_SIZE as u64),
+                _ => return -1,
+            };
+
+            // 宿主内存不够时增长失败，而不是直接退出
+            match additional.and_then(|n| usize::try_from(n).ok()) {
+                Some(n) if data.try_reserve_exact(n).is_ok() => data.resize(data.len() + n, 0),
+                _ => return -1,
             }
-            _ => self.data.resize((new_size * PAGE_SIZE) as usize, 0),
+
+            old_size as i64
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::thread;
+
+    use super::{MemInst, SharedMemory};
+    use crate::execution::config::{Config, Engine};
+    use crate::execution::linker::Linker;
+    use crate::execution::module::Module;
+    use crate::execution::store::Instance;
+    use crate::execution::value::ValInst;
+    use crate::execution::vm::VM;
+
+    const WORKER: &str = r#"
+        (module
+          (import "env" "mem" (memory 1 1 shared))
+          (func (export "add") (param i32)
+            (loop $l
+              (drop (i32.atomic.rmw.add (i32.const 0) (i32.const 1)))
+              (br_if $l (local.tee 0 (i32.sub (local.get 0) (i32.const 1))))))
+          (func (export "wait") (result i32)
+            (memory.atomic.wait32 (i32.const 8) (i32.const 0) (i64.const -1))))
+    "#;
+
+    /// 每个线程有自己的虚拟机，导入同一块共享内存
+    fn spawn(
+        shared: &SharedMemory,
+        f: impl FnOnce(&mut VM, Instance) -> i32 + Send + 'static,
+    ) -> thread::JoinHandle<i32> {
+        let shared = shared.clone();
+
+        thread::spawn(move || {
+            let module = Module::from_text(WORKER, Engine::Stack).unwrap();
+            let mut vm = VM::empty("worker", Config::default());
+            let mut linker = Linker::new();
+
+            linker.memory("env", "mem", Rc::new(RefCell::new(MemInst::from(shared))));
+            let instance = linker.instantiate(&mut vm, "worker", &module).unwrap();
+
+            f(&mut vm, instance)
+        })
+    }
+
+    fn call(vm: &mut VM, instance: Instance, name: &str, args: Vec<ValInst>) -> i32 {
+        vm.call_export(instance, name, args)
+            .unwrap()
+            .first()
+            .map_or(0, ValInst::as_i32)
+    }
+
+    #[test]
+    fn test_shared_across_threads() {
+        let module = Module::from_text(
+            r#"(module
+                 (memory (export "mem") 1 1 shared)
+                 (func (export "load") (param i32) (result i32) (i32.atomic.load (local.get 0)))
+                 (func (export "notify") (result i32) (memory.atomic.notify (i32.const 8) (i32.const 1))))"#,
+            Engine::Stack,
+        )
+        .unwrap();
+        let mut vm = VM::empty("main", Config::default());
+        let instance = Linker::new().instantiate(&mut vm, "main", &module).unwrap();
+        let shared = vm
+            .store
+            .get(instance)
+            .get_mem("mem")
+            .unwrap()
+            .borrow()
+            .shared()
+            .unwrap();
+
+        // 原子加不会丢失其他线程的修改
+        let adders = (0..4)
+            .map(|_| {
+                spawn(&shared, |vm, instance| {
+                    call(vm, instance, "add", vec![ValInst::I32(1000)])
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for adder in adders {
+            adder.join().unwrap();
         }
 
-        // 如果被其他模块导入，链接的时候将导致类型不匹配，所以需要进行变更
-        self.type_.min += 1;
+        assert_eq!(call(&mut vm, instance, "load", vec![ValInst::I32(0)]), 4000);
+
+        // 没有超时的等待只能被通知唤醒，等待方开始等待之前的通知唤醒不了任何人
+        let waiter = spawn(&shared, |vm, instance| call(vm, instance, "wait", vec![]));
+
+        while call(&mut vm, instance, "notify", vec![]) == 0 {
+            thread::yield_now();
+        }
 
-        old_size as i32
+        assert_eq!(waiter.join().unwrap(), 0);
     }
 }
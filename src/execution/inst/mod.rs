@@ -6,6 +6,7 @@ use self::function::FuncInst;
 use self::global::GlobalInst;
 use self::memory::MemInst;
 use self::table::TableInst;
+use self::tag::{ExnInst, TagInst};
 use crate::binary::section::ExportSeg;
 
 pub mod element;
@@ -13,6 +14,7 @@ pub mod function;
 pub mod global;
 pub mod memory;
 pub mod table;
+pub mod tag;
 
 pub type ExportMap = HashMap<String, ExportSeg>;
 
@@ -20,10 +22,28 @@ pub type RFuncInst = Rc<RefCell<FuncInst>>;
 pub type RTableInst = Rc<RefCell<TableInst>>;
 pub type RMemInst = Rc<RefCell<MemInst>>;
 pub type RGlobalInst = Rc<RefCell<GlobalInst>>;
+/// 标签和异常创建后都不会改变
+pub type RTagInst = Rc<TagInst>;
+pub type RExnInst = Rc<ExnInst>;
 
+/// 导出项对应的实例
+#[derive(Debug, Clone)]
 pub enum ExportInst {
     Func(RFuncInst),
     Table(RTableInst),
     Mem(RMemInst),
     Global(RGlobalInst),
+    Tag(RTagInst),
+}
+
+impl ExportInst {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Func(_) => "函数",
+            Self::Table(_) => "表",
+            Self::Mem(_) => "内存",
+            Self::Global(_) => "全局变量",
+            Self::Tag(_) => "异常标签",
+        }
+    }
 }
@@ -1,7 +1,7 @@
 use super::memory::MAX_PAGE_SIZE;
 use super::RFuncInst;
 use crate::binary::types::TableType;
-use crate::execution::errors::{Trap, VMState};
+use crate::execution::errors::{InstError, VMState};
 use crate::execution::value::{ValInst, ValInsts};
 
 /// 表
@@ -13,7 +13,7 @@ pub struct TableInst {
 
 impl TableInst {
     pub fn new(type_: TableType) -> Self {
-        let init_val = ValInst::new_ref_null(type_.elem_type as u8);
+        let init_val = ValInst::from(&type_.elem_type.as_val_type());
 
         Self {
             elems: vec![init_val; type_.limits.min as usize], // 不能用 with_capacity 进行初始化，会取不到值
@@ -25,63 +25,56 @@ impl TableInst {
         &self.type_
     }
 
-    pub fn size(&self) -> u32 {
-        self.elems.len() as u32
+    /// table64 的大小可以超过 u32
+    pub fn size(&self) -> u64 {
+        self.elems.len() as u64
     }
 
     pub fn usize(&self) -> usize {
         self.elems.len()
     }
 
-    pub fn grow(&mut self, size: u32, ref_val: ValInst) -> i32 {
+    /// 返回原来的大小，失败时返回 -1
+    pub fn grow(&mut self, size: u64, ref_val: ValInst) -> i64 {
         let old_size = self.size();
 
         if size == 0 {
-            return old_size as i32;
+            return old_size as i64;
         }
 
-        let (new_size, overflow) = size.overflowing_add(old_size);
-
-        if overflow {
-            return -1;
-        }
+        let new_size = match old_size.checked_add(size) {
+            Some(new_size) => new_size,
+            None => return -1,
+        };
 
         let max = self.type_.limits.max;
 
         match max {
-            Some(max) if max < new_size => {
-                println!("{} 超过该表可以分配的总上限：{}", new_size, max);
-
-                return -1;
-            }
-            None if new_size > MAX_PAGE_SIZE => {
-                println!("{} 超过可以分配的总上限：{}", new_size, MAX_PAGE_SIZE);
-
-                return -1;
-            }
+            Some(max) if max < new_size => return -1,
+            None if new_size > MAX_PAGE_SIZE as u64 => return -1,
             _ => self.elems.resize(new_size as usize, ref_val),
         }
 
-        old_size as i32
+        old_size as i64
     }
 
-    pub fn get_func_inst(&self, idx: u32) -> VMState<&RFuncInst> {
+    pub fn get_func_inst(&self, idx: u64) -> VMState<&RFuncInst> {
         let ref_val = self.get_elem(idx)?;
 
         ref_val.as_func_inst()
     }
 
-    pub fn get_elem(&self, idx: u32) -> VMState<&ValInst> {
+    pub fn get_elem(&self, idx: u64) -> VMState<&ValInst> {
         if idx >= self.size() {
-            Err(Trap::OutofRange)?;
+            Err(InstError::OutofBoundTable)?;
         }
 
         Ok(&self.elems[idx as usize])
     }
 
-    pub fn set_elem(&mut self, idx: u32, ref_val: ValInst) -> VMState {
+    pub fn set_elem(&mut self, idx: u64, ref_val: ValInst) -> VMState {
         if idx >= self.size() {
-            Err(Trap::OutofRange)?;
+            Err(InstError::OutofBoundTable)?;
         }
 
         self.elems[idx as usize] = ref_val;
@@ -89,29 +82,37 @@ impl TableInst {
         Ok(())
     }
 
-    pub fn get_elems(&self, src: u32, size: u32) -> VMState<&[ValInst]> {
-        let src = src as usize;
-        let size = size as usize;
+    pub fn get_elems(&self, src: u64, size: u64) -> VMState<&[ValInst]> {
+        let range = self.range(src, size)?;
 
-        if (src + size) > self.usize() {
-            Err(Trap::OutofRange)?;
-        }
+        Ok(&self.elems[range])
+    }
+
+    pub fn set_elems(&mut self, offset: u64, refs: &[ValInst]) -> VMState {
+        let range = self.range(offset, refs.len() as u64)?;
 
-        Ok(&self.elems[src..src + size])
+        self.elems[range].clone_from_slice(refs);
+
+        Ok(())
     }
 
-    pub fn set_elems(&mut self, offset: u32, refs: &[ValInst]) -> VMState {
-        if (offset as usize) + refs.len() > self.usize() {
-            Err(Trap::OutofRange)?;
-        }
+    /// 越界时一个元素也不写
+    pub fn fill(&mut self, offset: u64, size: u64, ref_val: ValInst) -> VMState {
+        let range = self.range(offset, size)?;
 
-        for (i, ref_val) in refs.iter().enumerate() {
-            self.set_elem((i as u32) + offset, ref_val.clone())?;
-        }
+        self.elems[range].fill(ref_val);
 
         Ok(())
     }
 
+    /// 越界或溢出时返回 OutofBoundTable
+    fn range(&self, offset: u64, size: u64) -> VMState<std::ops::Range<usize>> {
+        match offset.checked_add(size) {
+            Some(end) if end <= self.size() => Ok(offset as usize..end as usize),
+            _ => Err(InstError::OutofBoundTable)?,
+        }
+    }
+
     pub fn drop_(&mut self) {
         self.elems.clear();
     }
@@ -0,0 +1,26 @@
+use super::RTagInst;
+use crate::binary::types::FuncType;
+use crate::execution::value::ValInsts;
+
+/// 异常标签，类型相同的两个标签也不是同一个，catch 按实例匹配
+#[derive(Debug)]
+pub struct TagInst {
+    type_: FuncType,
+}
+
+impl TagInst {
+    pub fn new(type_: FuncType) -> Self {
+        Self { type_ }
+    }
+
+    pub fn get_type(&self) -> &FuncType {
+        &self.type_
+    }
+}
+
+/// throw 时创建的异常，exnref 引用的就是它
+#[derive(Debug)]
+pub struct ExnInst {
+    pub tag: RTagInst,
+    pub values: ValInsts,
+}
@@ -0,0 +1,232 @@
+use std::rc::Rc;
+use std::sync::atomic::{fence, Ordering};
+use std::time::Duration;
+
+use crate::binary::instruction::{Instruction, MemoryArg};
+use crate::execution::errors::{Trap, VMState};
+use crate::execution::inst::memory::Memory;
+use crate::execution::stack::operand::Operand;
+use crate::execution::tracer::Tracer;
+use crate::execution::vm::VM;
+
+/// 读改写指令对旧值和操作数做的运算
+#[derive(Debug, Clone, Copy)]
+pub enum RmwOp {
+    Add,
+    Sub,
+    And,
+    Or,
+    Xor,
+    Xchg,
+}
+
+impl RmwOp {
+    /// 结果写回时会截断到访问的字节数，这里直接按 64 位计算
+    fn apply(self, old: u64, v: u64) -> u64 {
+        match self {
+            RmwOp::Add => old.wrapping_add(v),
+            RmwOp::Sub => old.wrapping_sub(v),
+            RmwOp::And => old & v,
+            RmwOp::Or => old | v,
+            RmwOp::Xor => old ^ v,
+            RmwOp::Xchg => v,
+        }
+    }
+}
+
+/// 窄位宽的原子指令只看低 bytes 字节
+fn mask(bytes: u64) -> u64 {
+    match bytes {
+        8 => u64::MAX,
+        _ => (1 << (bytes * 8)) - 1,
+    }
+}
+
+impl<T: Tracer> VM<T> {
+    /// 有效地址必须按访问的字节数对齐，对齐检查在越界检查之前
+    fn atomic_addr(&mut self, memarg: &MemoryArg, bytes: u64) -> VMState<u64> {
+        let addr = self.get_mem_addr(memarg);
+
+        if !addr.is_multiple_of(bytes) {
+            Err(Trap::UnalignedAtomic)?;
+        }
+
+        Ok(addr)
+    }
+
+    fn pop_atomic(&mut self, is64: bool) -> u64 {
+        match is64 {
+            true => self.pop_i64() as u64,
+            false => self.pop_u32() as u64,
+        }
+    }
+
+    fn push_atomic(&mut self, v: u64, is64: bool) {
+        match is64 {
+            true => self.push_i64(v as i64),
+            false => self.push_u32(v as u32),
+        }
+    }
+
+    /// https://github.com/WebAssembly/threads/blob/main/proposals/threads/Overview.md#atomic-memory-accesses
+    pub fn atomic_load(&mut self, memarg: &MemoryArg, bytes: u64, is64: bool) -> VMState {
+        let addr = self.atomic_addr(memarg, bytes)?;
+        let data = self.mem_reads(addr, bytes)?;
+        let mut buf = [0u8; 8];
+
+        buf[..data.len()].copy_from_slice(&data);
+        self.push_atomic(u64::from_le_bytes(buf), is64);
+
+        Ok(())
+    }
+
+    pub fn atomic_store(&mut self, memarg: &MemoryArg, bytes: u64, is64: bool) -> VMState {
+        let v = self.pop_atomic(is64);
+        let addr = self.atomic_addr(memarg, bytes)?;
+
+        self.mem_writes(addr, &v.to_le_bytes()[..bytes as usize])
+    }
+
+    /// 返回旧值，窄位宽时零扩展
+    pub fn atomic_rmw(&mut self, memarg: &MemoryArg, bytes: u64, is64: bool, op: RmwOp) -> VMState {
+        let v = self.pop_atomic(is64);
+        let addr = self.atomic_addr(memarg, bytes)?;
+        let mem = Rc::clone(&self.module_inst().mems[self.mem_idx]);
+        let old = mem.borrow_mut().atomic_rmw(addr, bytes, |old| op.apply(old, v))?;
+
+        self.push_atomic(old, is64);
+
+        Ok(())
+    }
+
+    /// 期望值先截断到访问的字节数再比较，相等时才写入替换值
+    pub fn atomic_cmpxchg(&mut self, memarg: &MemoryArg, bytes: u64, is64: bool) -> VMState {
+        let replacement = self.pop_atomic(is64);
+        let expected = self.pop_atomic(is64) & mask(bytes);
+        let addr = self.atomic_addr(memarg, bytes)?;
+        let mem = Rc::clone(&self.module_inst().mems[self.mem_idx]);
+        let old = mem
+            .borrow_mut()
+            .atomic_rmw(addr, bytes, |old| match old == expected {
+                true => replacement,
+                false => old,
+            })?;
+
+        self.push_atomic(old, is64);
+
+        Ok(())
+    }
+
+    /// https://github.com/WebAssembly/threads/blob/main/proposals/threads/Overview.md#wait-and-notify-operators
+    pub fn memory_atomic_notify(&mut self, memarg: &MemoryArg) -> VMState {
+        let count = self.pop_u32();
+        let addr = self.atomic_addr(memarg, 4)?;
+        let woken = self.module_inst().mems[self.mem_idx]
+            .borrow()
+            .atomic_notify(addr, count)?;
+
+        self.push_u32(woken);
+
+        Ok(())
+    }
+
+    /// 阻塞当前线程直到被唤醒或超时，负数的超时表示一直等待，等待期间仍然响应中断
+    pub fn memory_atomic_wait(&mut self, memarg: &MemoryArg, bytes: u64) -> VMState {
+        let timeout = self.pop_i64();
+        let expected = self.pop_atomic(bytes == 8);
+        let addr = self.atomic_addr(memarg, bytes)?;
+        let timeout = (timeout >= 0).then(|| Duration::from_nanos(timeout as u64));
+        let mem = Rc::clone(&self.module_inst().mems[self.mem_idx]);
+        let result = mem.borrow().atomic_wait(
+            addr,
+            &expected.to_le_bytes()[..bytes as usize],
+            timeout,
+            || self.check_interrupt(),
+        )?;
+
+        self.push_u32(result);
+
+        Ok(())
+    }
+
+    pub fn atomic_fence(&mut self) {
+        fence(Ordering::SeqCst);
+    }
+
+    pub(super) fn exec_atomic(&mut self, instr: &Instruction) -> VMState {
+        match instr {
+            Instruction::MemoryAtomicNotify(memarg) => self.memory_atomic_notify(memarg)?,
+            Instruction::MemoryAtomicWait32(memarg) => self.memory_atomic_wait(memarg, 4)?,
+            Instruction::MemoryAtomicWait64(memarg) => self.memory_atomic_wait(memarg, 8)?,
+            Instruction::AtomicFence => self.atomic_fence(),
+            Instruction::I32AtomicLoad(memarg) => self.atomic_load(memarg, 4, false)?,
+            Instruction::I64AtomicLoad(memarg) => self.atomic_load(memarg, 8, true)?,
+            Instruction::I32AtomicLoad8U(memarg) => self.atomic_load(memarg, 1, false)?,
+            Instruction::I32AtomicLoad16U(memarg) => self.atomic_load(memarg, 2, false)?,
+            Instruction::I64AtomicLoad8U(memarg) => self.atomic_load(memarg, 1, true)?,
+            Instruction::I64AtomicLoad16U(memarg) => self.atomic_load(memarg, 2, true)?,
+            Instruction::I64AtomicLoad32U(memarg) => self.atomic_load(memarg, 4, true)?,
+            Instruction::I32AtomicStore(memarg) => self.atomic_store(memarg, 4, false)?,
+            Instruction::I64AtomicStore(memarg) => self.atomic_store(memarg, 8, true)?,
+            Instruction::I32AtomicStore8(memarg) => self.atomic_store(memarg, 1, false)?,
+            Instruction::I32AtomicStore16(memarg) => self.atomic_store(memarg, 2, false)?,
+            Instruction::I64AtomicStore8(memarg) => self.atomic_store(memarg, 1, true)?,
+            Instruction::I64AtomicStore16(memarg) => self.atomic_store(memarg, 2, true)?,
+            Instruction::I64AtomicStore32(memarg) => self.atomic_store(memarg, 4, true)?,
+            Instruction::I32AtomicRmwAdd(memarg) => self.atomic_rmw(memarg, 4, false, RmwOp::Add)?,
+            Instruction::I64AtomicRmwAdd(memarg) => self.atomic_rmw(memarg, 8, true, RmwOp::Add)?,
+            Instruction::I32AtomicRmw8AddU(memarg) => self.atomic_rmw(memarg, 1, false, RmwOp::Add)?,
+            Instruction::I32AtomicRmw16AddU(memarg) => self.atomic_rmw(memarg, 2, false, RmwOp::Add)?,
+            Instruction::I64AtomicRmw8AddU(memarg) => self.atomic_rmw(memarg, 1, true, RmwOp::Add)?,
+            Instruction::I64AtomicRmw16AddU(memarg) => self.atomic_rmw(memarg, 2, true, RmwOp::Add)?,
+            Instruction::I64AtomicRmw32AddU(memarg) => self.atomic_rmw(memarg, 4, true, RmwOp::Add)?,
+            Instruction::I32AtomicRmwSub(memarg) => self.atomic_rmw(memarg, 4, false, RmwOp::Sub)?,
+            Instruction::I64AtomicRmwSub(memarg) => self.atomic_rmw(memarg, 8, true, RmwOp::Sub)?,
+            Instruction::I32AtomicRmw8SubU(memarg) => self.atomic_rmw(memarg, 1, false, RmwOp::Sub)?,
+            Instruction::I32AtomicRmw16SubU(memarg) => self.atomic_rmw(memarg, 2, false, RmwOp::Sub)?,
+            Instruction::I64AtomicRmw8SubU(memarg) => self.atomic_rmw(memarg, 1, true, RmwOp::Sub)?,
+            Instruction::I64AtomicRmw16SubU(memarg) => self.atomic_rmw(memarg, 2, true, RmwOp::Sub)?,
+            Instruction::I64AtomicRmw32SubU(memarg) => self.atomic_rmw(memarg, 4, true, RmwOp::Sub)?,
+            Instruction::I32AtomicRmwAnd(memarg) => self.atomic_rmw(memarg, 4, false, RmwOp::And)?,
+            Instruction::I64AtomicRmwAnd(memarg) => self.atomic_rmw(memarg, 8, true, RmwOp::And)?,
+            Instruction::I32AtomicRmw8AndU(memarg) => self.atomic_rmw(memarg, 1, false, RmwOp::And)?,
+            Instruction::I32AtomicRmw16AndU(memarg) => self.atomic_rmw(memarg, 2, false, RmwOp::And)?,
+            Instruction::I64AtomicRmw8AndU(memarg) => self.atomic_rmw(memarg, 1, true, RmwOp::And)?,
+            Instruction::I64AtomicRmw16AndU(memarg) => self.atomic_rmw(memarg, 2, true, RmwOp::And)?,
+            Instruction::I64AtomicRmw32AndU(memarg) => self.atomic_rmw(memarg, 4, true, RmwOp::And)?,
+            Instruction::I32AtomicRmwOr(memarg) => self.atomic_rmw(memarg, 4, false, RmwOp::Or)?,
+            Instruction::I64AtomicRmwOr(memarg) => self.atomic_rmw(memarg, 8, true, RmwOp::Or)?,
+            Instruction::I32AtomicRmw8OrU(memarg) => self.atomic_rmw(memarg, 1, false, RmwOp::Or)?,
+            Instruction::I32AtomicRmw16OrU(memarg) => self.atomic_rmw(memarg, 2, false, RmwOp::Or)?,
+            Instruction::I64AtomicRmw8OrU(memarg) => self.atomic_rmw(memarg, 1, true, RmwOp::Or)?,
+            Instruction::I64AtomicRmw16OrU(memarg) => self.atomic_rmw(memarg, 2, true, RmwOp::Or)?,
+            Instruction::I64AtomicRmw32OrU(memarg) => self.atomic_rmw(memarg, 4, true, RmwOp::Or)?,
+            Instruction::I32AtomicRmwXor(memarg) => self.atomic_rmw(memarg, 4, false, RmwOp::Xor)?,
+            Instruction::I64AtomicRmwXor(memarg) => self.atomic_rmw(memarg, 8, true, RmwOp::Xor)?,
+            Instruction::I32AtomicRmw8XorU(memarg) => self.atomic_rmw(memarg, 1, false, RmwOp::Xor)?,
+            Instruction::I32AtomicRmw16XorU(memarg) => self.atomic_rmw(memarg, 2, false, RmwOp::Xor)?,
+            Instruction::I64AtomicRmw8XorU(memarg) => self.atomic_rmw(memarg, 1, true, RmwOp::Xor)?,
+            Instruction::I64AtomicRmw16XorU(memarg) => self.atomic_rmw(memarg, 2, true, RmwOp::Xor)?,
+            Instruction::I64AtomicRmw32XorU(memarg) => self.atomic_rmw(memarg, 4, true, RmwOp::Xor)?,
+            Instruction::I32AtomicRmwXchg(memarg) => self.atomic_rmw(memarg, 4, false, RmwOp::Xchg)?,
+            Instruction::I64AtomicRmwXchg(memarg) => self.atomic_rmw(memarg, 8, true, RmwOp::Xchg)?,
+            Instruction::I32AtomicRmw8XchgU(memarg) => self.atomic_rmw(memarg, 1, false, RmwOp::Xchg)?,
+            Instruction::I32AtomicRmw16XchgU(memarg) => {
+                self.atomic_rmw(memarg, 2, false, RmwOp::Xchg)?
+            }
+            Instruction::I64AtomicRmw8XchgU(memarg) => self.atomic_rmw(memarg, 1, true, RmwOp::Xchg)?,
+            Instruction::I64AtomicRmw16XchgU(memarg) => self.atomic_rmw(memarg, 2, true, RmwOp::Xchg)?,
+            Instruction::I64AtomicRmw32XchgU(memarg) => self.atomic_rmw(memarg, 4, true, RmwOp::Xchg)?,
+            Instruction::I32AtomicRmwCmpxchg(memarg) => self.atomic_cmpxchg(memarg, 4, false)?,
+            Instruction::I64AtomicRmwCmpxchg(memarg) => self.atomic_cmpxchg(memarg, 8, true)?,
+            Instruction::I32AtomicRmw8CmpxchgU(memarg) => self.atomic_cmpxchg(memarg, 1, false)?,
+            Instruction::I32AtomicRmw16CmpxchgU(memarg) => self.atomic_cmpxchg(memarg, 2, false)?,
+            Instruction::I64AtomicRmw8CmpxchgU(memarg) => self.atomic_cmpxchg(memarg, 1, true)?,
+            Instruction::I64AtomicRmw16CmpxchgU(memarg) => self.atomic_cmpxchg(memarg, 2, true)?,
+            Instruction::I64AtomicRmw32CmpxchgU(memarg) => self.atomic_cmpxchg(memarg, 4, true)?,
+            _ => unreachable!("{:?}", instr),
+        };
+
+        Ok(())
+    }
+}
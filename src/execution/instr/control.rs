@@ -1,132 +1,320 @@
+use std::error::Error;
 use std::rc::Rc;
 
-use crate::binary::instruction::{Block, BlockType, BrTableArg, IfBlock};
-use crate::binary::section::{Expr, LabelIdx};
+use crate::binary::instruction::Catch;
 use crate::binary::types::{FuncType, ValType};
-use crate::execution::errors::{Trap, VMState};
+use crate::execution::bytecode::{Bytecode, Op, Target};
+use crate::execution::errors::{CallError, Exception, Trap, VMState};
+use crate::execution::host::Caller;
 use crate::execution::inst::function::{FuncInst, FuncInstKind};
-use crate::execution::stack::frame::{CallStack, Frame, LabelKind};
+use crate::execution::inst::tag::ExnInst;
+use crate::execution::inst::{RExnInst, RFuncInst};
+use crate::execution::stack::frame::{CallStack, Frame};
 use crate::execution::stack::operand::Operand;
-use crate::execution::value::ValInsts;
+use crate::execution::store::Instance;
+use crate::execution::tracer::Tracer;
+use crate::execution::value::{ValInst, ValInsts};
 use crate::execution::vm::VM;
 
-/// 实现块逻辑
-impl VM {
-    pub fn enter_block(&mut self, kind: LabelKind, func_type: &FuncType, expr: &Expr) {
+/// 实现栈帧逻辑
+impl<T: Tracer> VM<T> {
+    /// 参数已经在栈顶，局部变量在这里压栈，之后执行的是 inst 中代码段下标为 func 的函数
+    pub fn enter_call(
+        &mut self,
+        func_type: &FuncType,
+        code: &Rc<Bytecode>,
+        inst: Instance,
+        func: usize,
+    ) {
         let frame = Frame {
             pc: 0,
-            kind,
             sp: self.stack_size() - func_type.params.len(),
-            expr: expr as *const Expr,
-            arg_num: func_type.params.len(),
+            code: Rc::clone(code),
             arity: func_type.results.len(),
+            inst,
+            func,
         };
 
-        if frame.kind == LabelKind::Call {
-            self.local_idx = frame.sp;
-        }
-
+        self.local_idx = frame.sp;
+        self.inst = inst;
+        self.call_depth += 1;
         self.push_frame(frame);
+        self.push_n(code.locals.clone());
+        self.fit_window();
     }
 
-    pub fn exit_block(&mut self) -> VMState {
+    pub fn exit_call(&mut self) -> VMState {
         let frame = self.pop_frame();
 
-        self.clear_block(&frame)
+        match frame.code.regs {
+            None => self.drop_keep(frame.sp, frame.arity),
+            // 结果已经在最前面的寄存器里
+            Some(_) => self.operands.truncate(frame.sp + frame.arity),
+        }
+
+        self.tracer.exit_func(self.call_depth);
+        self.call_depth -= 1;
+
+        if self.depth() > 0 {
+            let Frame { sp, inst, .. } = *self.top_frame();
+
+            self.local_idx = sp;
+            self.inst = inst;
+        }
+
+        Ok(())
+    }
+
+    /// 寄存器形式的函数要在操作数栈上占满所有寄存器
+    pub(crate) fn fit_window(&mut self) {
+        let frame = self.top_frame();
+
+        if let Some(regs) = &frame.code.regs {
+            let size = frame.sp + regs.size;
+
+            self.operands.resize(size, ValInst::I32(0));
+        }
     }
 
-    pub fn reset_block(&mut self, frame: Frame) {
-        let results = self.pop_n(frame.arg_num);
+    /// 把栈顶 keep 个值移到 base 处，丢掉中间的值
+    fn drop_keep(&mut self, base: usize, keep: usize) {
+        let len = self.stack_size();
 
-        self.pop_n(self.stack_size() - frame.sp);
-        self.push_n(results);
+        if len - keep > base {
+            self.operands.drain(base..len - keep);
+        }
     }
 
-    pub fn clear_block(&mut self, frame: &Frame) -> VMState {
-        let results = self.pop_n(frame.arity);
+    /// 从栈顶往下找到 depth 层为止，第一个能捕获异常的 catch 子句，之上的栈帧全部退出
+    ///
+    /// 宿主函数和导入方不占用栈帧，异常穿过它们时和 trap 一样作为错误返回，到调用它的栈帧里再找
+    pub(crate) fn catch(&mut self, err: Box<dyn Error>, depth: usize) -> VMState {
+        let exn = match err.downcast_ref::<Exception>() {
+            Some(exception) => Rc::clone(&exception.0),
+            None => return Err(err),
+        };
+        let found = (depth..self.depth())
+            .rev()
+            .find_map(|i| self.handler(i, &exn).map(|handler| (i, handler)));
+        let (i, (catch, target)) = match found {
+            Some(found) => found,
+            None => return Err(err),
+        };
+
+        while self.depth() > i + 1 {
+            self.pop_frame();
+            self.tracer.exit_func(self.call_depth);
+            self.call_depth -= 1;
+        }
 
-        self.pop_n(self.stack_size() - frame.sp);
-        self.push_n(results);
+        let Frame { sp, inst, .. } = *self.top_frame();
 
-        if frame.kind == LabelKind::Call && self.depth() > 0 {
-            let (call_frame, _) = self.top_call();
+        self.local_idx = sp;
+        self.inst = inst;
+        self.operands.truncate(sp + target.height);
 
-            if let Some(call_frame) = call_frame {
-                self.local_idx = call_frame.sp;
-            } else {
-                Err(Trap::CallFrameNotFount)?;
-            }
+        if catch.tag().is_some() {
+            self.push_n(exn.values.clone());
         }
 
+        if catch.is_ref() {
+            self.push(ValInst::ExnRef(Some(exn)));
+        }
+
+        self.top_mut().pc = target.pc;
+        self.fit_window();
+
         Ok(())
     }
 
-    pub fn block_type_to_func_type(&mut self, block_type: &BlockType) -> FuncType {
-        let module = Rc::clone(&self.module);
-        let func_type = match block_type {
-            BlockType::TypeIdx(idx) => module.type_sec[*idx as usize].clone(),
-            _ => FuncType::from(block_type),
+    /// 第 i 层栈帧中正在执行的指令所在的 try_table，从内往外匹配，返回匹配的 catch 子句和跳转目标
+    fn handler(&self, i: usize, exn: &RExnInst) -> Option<(Catch, Target)> {
+        let frame = self.get_frame(i);
+        let pc = frame.pc.checked_sub(1)?;
+        let tags = &self.store.get(frame.inst).tags;
+
+        frame
+            .code
+            .handlers()
+            .iter()
+            .rev()
+            .filter(|handler| (handler.start..handler.end).contains(&pc))
+            .flat_map(|handler| handler.catches.iter())
+            .find(|(catch, _)| match catch.tag() {
+                Some(idx) => Rc::ptr_eq(&tags[idx as usize], &exn.tag),
+                None => true,
+            })
+            .copied()
+    }
+
+    pub fn exec_op(&mut self, op: &Op) -> VMState {
+        match op {
+            Op::Instr(instr) => self.exec_instr(instr)?,
+            Op::Br(target) => self.br(target)?,
+            Op::BrIf(target) => self.br_if(target)?,
+            Op::BrTable(targets) => self.br_table(targets)?,
+            Op::BrUnless(pc) => self.br_unless(*pc),
+            Op::Jump(pc) => self.top_mut().pc = *pc,
         };
 
-        func_type
+        Ok(())
     }
 }
 
 /// 实现函数调用逻辑
-impl VM {
+impl<T: Tracer> VM<T> {
     /// args 存在（外部手动调用的情况），则要将参数压栈，结果出栈
     pub fn invoke(&mut self, func_inst: &FuncInst, args: Option<ValInsts>) -> VMState<ValInsts> {
+        let external = args.is_some();
+        let snapshot = self.snapshot();
+
+        if external {
+            self.check_owner(func_inst)?;
+            self.clear_backtrace();
+        }
+
+        let ret = self.invoke_func(func_inst, args);
+
+        // 外部调用出错后虚拟机还可以继续使用
+        if let (true, Err(err)) = (external, &ret) {
+            self.suspend_or_restore(err.as_ref(), snapshot, func_inst.ret_types());
+        }
+
+        ret
+    }
+
+    /// 其他 Store 的函数引用的实例在这里并不存在
+    fn check_owner(&self, func_inst: &FuncInst) -> VMState {
+        if !func_inst.callable_in(self.store.get_id()) {
+            Err(Trap::ForeignFunc)?;
+        }
+
+        Ok(())
+    }
+
+    /// 调用层数或操作数栈超出限制时，都视为调用栈溢出
+    ///
+    /// call_depth 为调用前的层数，stack_size 为参数也在栈上时操作数栈的大小
+    fn check_stack(&self, code: &Bytecode, call_depth: usize, stack_size: usize) -> VMState {
+        let frame_size = match &code.regs {
+            Some(regs) => regs.size,
+            None => code.locals.len(),
+        };
+        let stack_size = stack_size + frame_size;
+
+        if call_depth >= self.config.max_call_depth || stack_size > self.config.max_stack_size {
+            Err(Trap::CallStackExhausted)?;
+        }
+
+        Ok(())
+    }
+
+    fn invoke_func(&mut self, func_inst: &FuncInst, args: Option<ValInsts>) -> VMState<ValInsts> {
         let pop_push = args.is_some();
         let fn_type = func_inst.get_type().clone();
 
-        if pop_push {
-            self.push_n_and_check_type(&fn_type.params, args.unwrap());
+        if let Some(args) = args {
+            self.push_n_and_check_type(&fn_type.params, args, "参数")?;
         }
 
         match &func_inst.kind {
-            FuncInstKind::Inner(_, code) => {
-                self.enter_block(LabelKind::Call, &fn_type, &code.body);
-                self.push_n(code.init_local());
+            FuncInstKind::Inner { inst, idx, code } => {
+                self.check_interrupt()?;
+                self.check_stack(code, self.call_depth, self.stack_size())?;
+                self.enter_call(&fn_type, code, *inst, *idx);
+                self.tracer.enter_func(func_inst, self.call_depth);
 
                 self.start_loop()?;
             }
-            FuncInstKind::Outer(ctx, name) => {
-                // 存在嵌套调用，使用指针而不是 borrow_mut 绕过检查
-                let ptr = ctx.as_ptr();
-                let importer = unsafe { ptr.as_mut().unwrap() };
-
-                let args = self.pop_n_and_check_type(&fn_type.params);
-                let rets = importer.call_by_name(name, args)?;
-
-                self.push_n_and_check_type(&fn_type.results, rets);
-            }
+            _ => self.call_host(func_inst, &fn_type)?,
         };
 
         let ret = match pop_push {
-            true => self.pop_n_and_check_type(&fn_type.results),
+            true => self.pop_n_and_check_type(&fn_type.results, "返回值")?,
             false => vec![],
         };
 
         Ok(ret)
     }
 
-    pub fn pop_n_and_check_type(&mut self, val_types: &[ValType]) -> ValInsts {
-        // Todo: check
+    /// 宿主函数和其他虚拟机的函数，参数从栈上取出，返回值检查后再压栈
+    ///
+    /// 单独放在一个函数里，避免 wasm 函数之间的递归调用占用更多的宿主栈
+    #[inline(never)]
+    fn call_host(&mut self, func_inst: &FuncInst, fn_type: &FuncType) -> VMState {
+        let args = self.pop_n_and_check_type(&fn_type.params, "参数")?;
+
+        // 宿主函数不占用调用栈帧，按多一层调用处理
+        self.tracer.enter_func(func_inst, self.call_depth + 1);
+
+        let rets = match &func_inst.kind {
+            FuncInstKind::Outer(ctx, name) => {
+                // 同一个 Store 中的实例互相调用不经过导入方，这里只会是宿主或其他虚拟机
+                let mut importer = ctx.try_borrow_mut().map_err(|_| Trap::ImporterBusy)?;
+
+                importer.call_by_name(name, args)?
+            }
+            FuncInstKind::Host(_, func) => {
+                // 从外部直接调用时，当前实例并不是调用方
+                let caller = Caller::new((self.depth() > 0).then(|| self.module_inst()));
+
+                func(caller, args)?
+            }
+            FuncInstKind::Inner { .. } => unreachable!(),
+        };
+
+        self.tracer.exit_func(self.call_depth + 1);
+        self.push_n_and_check_type(&fn_type.results, rets, "返回值")
+    }
+
+    /// 栈上的值已经通过校验，这里只防止调用前后栈被破坏
+    pub fn pop_n_and_check_type(
+        &mut self,
+        val_types: &[ValType],
+        what: &'static str,
+    ) -> VMState<ValInsts> {
+        if self.stack_size() < val_types.len() {
+            Err(CallError::CountNotEq(what, val_types.len(), self.stack_size()))?;
+        }
+
         let vals = self.pop_n(val_types.len());
 
-        vals
+        check_vals(val_types, &vals, what)?;
+
+        Ok(vals)
     }
 
-    pub fn push_n_and_check_type(&mut self, val_types: &[ValType], vals: ValInsts) {
-        // Todo: check
-        if val_types.len() == vals.len() {
-            self.push_n(vals);
+    /// 外部传入的参数、宿主函数的返回值不经过校验，不一致时不压栈
+    pub fn push_n_and_check_type(
+        &mut self,
+        val_types: &[ValType],
+        vals: ValInsts,
+        what: &'static str,
+    ) -> VMState {
+        check_vals(val_types, &vals, what)?;
+        self.push_n(vals);
+
+        Ok(())
+    }
+}
+
+/// what 为出错时对这组值的称呼，比如 参数、返回值
+pub(crate) fn check_vals(val_types: &[ValType], vals: &[ValInst], what: &'static str) -> VMState {
+    if val_types.len() != vals.len() {
+        Err(CallError::CountNotEq(what, val_types.len(), vals.len()))?;
+    }
+
+    for (i, (val_type, val)) in val_types.iter().zip(vals).enumerate() {
+        if val.get_type() != *val_type {
+            Err(CallError::TypeNotEq(what, i, *val_type, val.get_type()))?;
         }
     }
+
+    Ok(())
 }
 
 /// 实现指令逻辑
-impl VM {
+impl<T: Tracer> VM<T> {
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-unreachable
     pub fn unreachable(&mut self) -> VMState {
         Err(Trap::Unreachable)?
@@ -135,83 +323,63 @@ impl VM {
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-nop
     pub fn nop(&mut self) {}
 
-    /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-block
-    pub fn block(&mut self, block: &Block) {
-        let ft = self.block_type_to_func_type(&block.type_);
+    /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-throw
+    pub fn throw(&mut self, idx: u32) -> VMState {
+        let tag = Rc::clone(&self.module_inst().tags[idx as usize]);
+        let values = self.pop_n(tag.get_type().params.len());
 
-        self.enter_block(LabelKind::Block, &ft, &block.expr);
+        Err(Exception::from(Rc::new(ExnInst { tag, values })))?
     }
 
-    /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-loop
-    pub fn loop_(&mut self, block: &Block) {
-        let ft = self.block_type_to_func_type(&block.type_);
-
-        self.enter_block(LabelKind::Loop, &ft, &block.expr);
-    }
-
-    /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-if
-    pub fn if_(&mut self, block: &IfBlock) {
-        let ft = self.block_type_to_func_type(&block.type_);
-        let expr = match self.pop_bool() {
-            true => &block.if_expr,
-            false => &block.else_expr,
-        };
-
-        self.enter_block(LabelKind::If, &ft, expr);
+    /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-throw-ref
+    pub fn throw_ref(&mut self) -> VMState {
+        match self.pop() {
+            ValInst::ExnRef(Some(exn)) => Err(Exception::from(exn))?,
+            _ => Err(Trap::NullExnRef)?,
+        }
     }
 
-    pub fn else_(&mut self) {}
-
-    pub fn end(&mut self) {}
-
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-br
-    pub fn br(&mut self, l: LabelIdx) -> VMState {
-        for _ in 0..l {
-            self.pop_frame();
-        }
+    pub fn br(&mut self, target: &Target) -> VMState {
+        let Frame { pc, sp, .. } = *self.top_frame();
 
-        let frame = self.top_frame();
+        // 往回跳的只有 loop
+        if target.pc < pc {
+            self.check_interrupt()?;
+        }
 
-        match frame.kind {
-            LabelKind::Loop => {
-                self.reset_block(frame.basic_info());
-                self.top_mut().reset_pc();
-            }
-            _ => self.exit_block()?,
-        };
+        self.drop_keep(sp + target.height, target.keep);
+        self.top_mut().pc = target.pc;
 
         Ok(())
     }
 
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-br-if
-    pub fn br_if(&mut self, l: LabelIdx) -> VMState {
+    pub fn br_if(&mut self, target: &Target) -> VMState {
         if self.pop_bool() {
-            self.br(l)?;
+            self.br(target)?;
         }
 
         Ok(())
     }
 
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-br-table
-    pub fn br_table(&mut self, arg: &BrTableArg) -> VMState {
-        let idx = self.pop_u32() as usize;
+    pub fn br_table(&mut self, targets: &[Target]) -> VMState {
+        let idx = (self.pop_u32() as usize).min(targets.len() - 1);
 
-        match idx < arg.labels.len() {
-            true => self.br(arg.labels[idx]),
-            false => self.br(arg.default),
-        }
+        self.br(&targets[idx])
     }
 
-    /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-return
-    pub fn return_(&mut self) -> VMState {
-        let (_, label_idx) = self.top_call();
-
-        self.br(label_idx as u32)
+    /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-if
+    pub fn br_unless(&mut self, pc: usize) {
+        if !self.pop_bool() {
+            self.top_mut().pc = pc;
+        }
     }
 
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-call
     pub fn call(&mut self, idx: u32) -> VMState {
-        let func_inst = Rc::clone(&self.funcs[idx as usize]);
+        let func_inst = Rc::clone(&self.module_inst().funcs[idx as usize]);
 
         {
             let func_inst = func_inst.borrow();
@@ -224,23 +392,87 @@ impl VM {
 
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-call-indirect
     pub fn call_indirect(&mut self, type_idx: u32, table_idx: u32) -> VMState {
-        let i = self.pop_u32();
-        let table = Rc::clone(&self.tables[table_idx as usize]);
+        let func_inst = self.indirect_func(type_idx, table_idx)?;
+        let func_inst = func_inst.borrow();
+
+        self.invoke(&func_inst, None)?;
+
+        Ok(())
+    }
+
+    /// 从表中取出要调用的函数，并检查签名
+    fn indirect_func(&mut self, type_idx: u32, table_idx: u32) -> VMState<RFuncInst> {
+        let i = self.pop_addr();
+        let table = Rc::clone(&self.module_inst().tables[table_idx as usize]);
+        let table = table.borrow();
+        let module = Rc::clone(&self.module_inst().module);
+        let ft = &module.type_sec[type_idx as usize];
+
+        if i >= table.size() {
+            Err(Trap::UndefinedElem)?;
+        }
+
+        let func_inst = Rc::clone(table.get_func_inst(i)?);
 
         {
-            let table = table.borrow();
-            let module = Rc::clone(&self.module);
-            let ft = &module.type_sec[type_idx as usize];
-            let func_inst = table.get_func_inst(i)?;
+            let func_inst = func_inst.borrow();
 
-            {
-                let func_inst = func_inst.borrow();
+            if ft != func_inst.get_type() {
+                Err(Trap::ArgNotEq)?;
+            }
+
+            // 表可能是从其他虚拟机导入的
+            self.check_owner(&func_inst)?;
+        }
+
+        Ok(func_inst)
+    }
+
+    /// https://github.com/WebAssembly/tail-call/blob/main/proposals/tail-call/Overview.md
+    pub fn return_call(&mut self, idx: u32) -> VMState {
+        let func_inst = Rc::clone(&self.module_inst().funcs[idx as usize]);
+        let func_inst = func_inst.borrow();
+
+        self.tail_invoke(&func_inst)
+    }
+
+    pub fn return_call_indirect(&mut self, type_idx: u32, table_idx: u32) -> VMState {
+        let func_inst = self.indirect_func(type_idx, table_idx)?;
+        let func_inst = func_inst.borrow();
+
+        self.tail_invoke(&func_inst)
+    }
+
+    /// 尾调用，参数已经在栈顶
+    ///
+    /// 模块中的函数直接替换当前栈帧，调用栈不会变深，也不会多占用宿主栈；
+    /// 宿主函数调用完后把结果当作当前函数的结果，跳到函数结尾返回
+    fn tail_invoke(&mut self, func_inst: &FuncInst) -> VMState {
+        let fn_type = func_inst.get_type();
+        let Frame { sp, arity, .. } = *self.top_frame();
+
+        match &func_inst.kind {
+            FuncInstKind::Inner { inst, idx, code } => {
+                let params = fn_type.params.len();
+
+                self.check_interrupt()?;
+                self.check_stack(code, self.call_depth - 1, sp + params)?;
+
+                // 参数移到当前栈帧的开头，当前函数的局部变量和操作数都不再需要
+                self.drop_keep(sp, params);
+                self.pop_frame();
+                self.tracer.exit_func(self.call_depth);
+                self.call_depth -= 1;
+                self.enter_call(fn_type, code, *inst, *idx);
+                self.tracer.enter_func(func_inst, self.call_depth);
+            }
+            _ => {
+                self.call_host(func_inst, fn_type)?;
+                self.drop_keep(sp, arity);
 
-                if ft != func_inst.get_type() {
-                    Err(Trap::ArgNotEq)?;
-                }
+                let frame = self.top_mut();
 
-                self.invoke(&func_inst, None)?;
+                frame.pc = frame.code.end();
             }
         }
 
@@ -1,25 +1,32 @@
 use crate::binary::instruction::Instruction;
-use crate::execution::errors::VMState;
+use crate::execution::errors::{Trap, VMState};
+use crate::execution::tracer::Tracer;
 use crate::execution::vm::VM;
 
-impl VM {
+impl<T: Tracer> VM<T> {
     pub fn exec_instr(&mut self, instr: &Instruction) -> VMState {
-        println!("执行指令：{:?}", instr);
-
         match instr {
             Instruction::Unreachable => self.unreachable()?,
             Instruction::Nop => self.nop(),
-            Instruction::Block(block) => self.block(block),
-            Instruction::Loop(block) => self.loop_(block),
-            Instruction::If(block) => self.if_(block),
-            Instruction::Else => self.else_(),
-            Instruction::End => self.end(),
-            Instruction::Br(l) => self.br(*l)?,
-            Instruction::BrIf(l) => self.br_if(*l)?,
-            Instruction::BrTable(arg) => self.br_table(arg)?,
-            Instruction::Return => self.return_()?,
+            // 控制指令在编译时已经展开成 Op
+            Instruction::Block(_)
+            | Instruction::Loop(_)
+            | Instruction::If(_)
+            | Instruction::Else
+            | Instruction::End
+            | Instruction::Br(_)
+            | Instruction::BrIf(_)
+            | Instruction::BrTable(_)
+            | Instruction::Return
+            | Instruction::TryTable(_) => Err(Trap::NoOpcode)?,
+            Instruction::Throw(idx) => self.throw(*idx)?,
+            Instruction::ThrowRef => self.throw_ref()?,
             Instruction::Call(idx) => self.call(*idx)?,
             Instruction::CallIndirect(type_i, table_i) => self.call_indirect(*type_i, *table_i)?,
+            Instruction::ReturnCall(idx) => self.return_call(*idx)?,
+            Instruction::ReturnCallIndirect(type_i, table_i) => {
+                self.return_call_indirect(*type_i, *table_i)?
+            }
             Instruction::Drop => self.drop_(),
             Instruction::Select => self.select(),
             Instruction::Select2(x, type_) => self.select2(*x, type_),
@@ -187,7 +194,7 @@ impl VM {
             Instruction::I64Extend8S => self.i64_extend8_s(),
             Instruction::I64Extend16S => self.i64_extend16_s(),
             Instruction::I64Extend32S => self.i64_extend32_s(),
-            Instruction::RefNull(x) => self.ref_null(*x),
+            Instruction::RefNull(x) => self.ref_null(*x)?,
             Instruction::RefIsNull => self.ref_is_null(),
             Instruction::RefFunc(idx) => self.ref_func(*idx),
             Instruction::I32TruncSatF32S => self.i32_trunc_sat_f32_s(),
@@ -200,7 +207,7 @@ impl VM {
             Instruction::I64TruncSatF64U => self.i64_trunc_sat_f64_u(),
             Instruction::MemoryInit(segment, idx) => self.memory_init(*segment, *idx)?,
             Instruction::DataDrop(data_idx) => self.data_drop(*data_idx),
-            Instruction::MemoryCopy(src_idx, dst_idx) => self.memory_copy(*src_idx, *dst_idx)?,
+            Instruction::MemoryCopy(dst_idx, src_idx) => self.memory_copy(*dst_idx, *src_idx)?,
             Instruction::MemoryFill(idx) => self.memory_fill(*idx)?,
             Instruction::TableInit(elem_idx, table_idx) => self.table_init(*elem_idx, *table_idx)?,
             Instruction::ElemDrop(idx) => self.elem_drop(*idx),
@@ -444,6 +451,74 @@ impl VM {
             Instruction::I32x4TruncSatF64x2UZero(_) => self.i32x4_trunc_sat_f64x2_u_zero(),
             Instruction::F64x2ConvertLowI32x4S(_) => self.f64x2_convert_low_i32x4_s(),
             Instruction::F64x2ConvertLowI32x4U(_) => self.f64x2_convert_low_i32x4_u(),
+            // 原子指令单独分派，这里的栈帧不会因此变大，wasm 函数递归调用时每层都要占用它
+            Instruction::MemoryAtomicNotify(_)
+            | Instruction::MemoryAtomicWait32(_)
+            | Instruction::MemoryAtomicWait64(_)
+            | Instruction::AtomicFence
+            | Instruction::I32AtomicLoad(_)
+            | Instruction::I64AtomicLoad(_)
+            | Instruction::I32AtomicLoad8U(_)
+            | Instruction::I32AtomicLoad16U(_)
+            | Instruction::I64AtomicLoad8U(_)
+            | Instruction::I64AtomicLoad16U(_)
+            | Instruction::I64AtomicLoad32U(_)
+            | Instruction::I32AtomicStore(_)
+            | Instruction::I64AtomicStore(_)
+            | Instruction::I32AtomicStore8(_)
+            | Instruction::I32AtomicStore16(_)
+            | Instruction::I64AtomicStore8(_)
+            | Instruction::I64AtomicStore16(_)
+            | Instruction::I64AtomicStore32(_)
+            | Instruction::I32AtomicRmwAdd(_)
+            | Instruction::I64AtomicRmwAdd(_)
+            | Instruction::I32AtomicRmw8AddU(_)
+            | Instruction::I32AtomicRmw16AddU(_)
+            | Instruction::I64AtomicRmw8AddU(_)
+            | Instruction::I64AtomicRmw16AddU(_)
+            | Instruction::I64AtomicRmw32AddU(_)
+            | Instruction::I32AtomicRmwSub(_)
+            | Instruction::I64AtomicRmwSub(_)
+            | Instruction::I32AtomicRmw8SubU(_)
+            | Instruction::I32AtomicRmw16SubU(_)
+            | Instruction::I64AtomicRmw8SubU(_)
+            | Instruction::I64AtomicRmw16SubU(_)
+            | Instruction::I64AtomicRmw32SubU(_)
+            | Instruction::I32AtomicRmwAnd(_)
+            | Instruction::I64AtomicRmwAnd(_)
+            | Instruction::I32AtomicRmw8AndU(_)
+            | Instruction::I32AtomicRmw16AndU(_)
+            | Instruction::I64AtomicRmw8AndU(_)
+            | Instruction::I64AtomicRmw16AndU(_)
+            | Instruction::I64AtomicRmw32AndU(_)
+            | Instruction::I32AtomicRmwOr(_)
+            | Instruction::I64AtomicRmwOr(_)
+            | Instruction::I32AtomicRmw8OrU(_)
+            | Instruction::I32AtomicRmw16OrU(_)
+            | Instruction::I64AtomicRmw8OrU(_)
+            | Instruction::I64AtomicRmw16OrU(_)
+            | Instruction::I64AtomicRmw32OrU(_)
+            | Instruction::I32AtomicRmwXor(_)
+            | Instruction::I64AtomicRmwXor(_)
+            | Instruction::I32AtomicRmw8XorU(_)
+            | Instruction::I32AtomicRmw16XorU(_)
+            | Instruction::I64AtomicRmw8XorU(_)
+            | Instruction::I64AtomicRmw16XorU(_)
+            | Instruction::I64AtomicRmw32XorU(_)
+            | Instruction::I32AtomicRmwXchg(_)
+            | Instruction::I64AtomicRmwXchg(_)
+            | Instruction::I32AtomicRmw8XchgU(_)
+            | Instruction::I32AtomicRmw16XchgU(_)
+            | Instruction::I64AtomicRmw8XchgU(_)
+            | Instruction::I64AtomicRmw16XchgU(_)
+            | Instruction::I64AtomicRmw32XchgU(_)
+            | Instruction::I32AtomicRmwCmpxchg(_)
+            | Instruction::I64AtomicRmwCmpxchg(_)
+            | Instruction::I32AtomicRmw8CmpxchgU(_)
+            | Instruction::I32AtomicRmw16CmpxchgU(_)
+            | Instruction::I64AtomicRmw8CmpxchgU(_)
+            | Instruction::I64AtomicRmw16CmpxchgU(_)
+            | Instruction::I64AtomicRmw32CmpxchgU(_) => self.exec_atomic(instr)?,
         };
 
         Ok(())
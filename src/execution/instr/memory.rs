@@ -1,20 +1,25 @@
+use std::rc::Rc;
+
 use crate::binary::instruction::MemoryArg;
-use crate::execution::errors::{VMState, Trap};
+use crate::execution::errors::{InstError, VMState};
 use crate::execution::inst::memory::Memory;
 use crate::execution::stack::operand::Operand;
+use crate::execution::tracer::Tracer;
 use crate::execution::vm::VM;
 
-impl VM {
+impl<T: Tracer> VM<T> {
+    /// 同时选中 memarg 指定的内存，之后的读写都在这块内存上
     pub fn get_mem_addr(&mut self, memarg: &MemoryArg) -> u64 {
-        // cast 到 u64，避免溢出，方便做越界检查
-        let v = self.pop_u32() as u64;
-        let offset = memarg.offset as u64;
+        self.mem_idx = memarg.mem_idx as usize;
+
+        // memory64 的地址加上偏移可能溢出，饱和到 u64::MAX 后读写时一定越界
+        let v = self.pop_addr();
 
-        offset + v
+        memarg.offset.saturating_add(v)
     }
 }
 
-impl VM {
+impl<T: Tracer> VM<T> {
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-load
     pub fn i32_load(&mut self, memarg: &MemoryArg) -> VMState {
         let addr = self.get_mem_addr(memarg);
@@ -214,62 +219,65 @@ impl VM {
     }
 
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-memory-size
-    /// mem_idx 默认 0，暂不使用
-    pub fn memory_size(&mut self, idx: u8) {
-        let size = self.mems[idx as usize].borrow().mem_size();
+    pub fn memory_size(&mut self, idx: u32) {
+        let mem = Rc::clone(&self.module_inst().mems[idx as usize]);
+        let mem = mem.borrow();
 
-        self.push_u32(size);
+        self.push_addr(mem.mem_size(), mem.get_type().is64);
     }
 
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-memory-grow
-    /// mem_idx 默认 0，暂不使用
-    pub fn memory_grow(&mut self, idx: u8) {
-        let size = self.pop_u32();
-        let old_size = self.mems[idx as usize].borrow_mut().mem_grow(size);
-
-        self.push_i32(old_size);
+    pub fn memory_grow(&mut self, idx: u32) {
+        let size = self.pop_addr();
+        let mem = Rc::clone(&self.module_inst().mems[idx as usize]);
+        let mut mem = mem.borrow_mut();
+        let old_size = mem.mem_grow(size);
+
+        self.tracer.mem_grow(idx, size, old_size);
+        self.push_addr(old_size as u64, mem.get_type().is64);
     }
 
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-memory-init
     pub fn memory_init(&mut self, segment: u32, idx: u32) -> VMState {
         let n = self.pop_u32() as usize;
         let addr = self.pop_u32() as usize;
-        let dst = self.pop_u32() as u64;
+        let dst = self.pop_addr();
 
-        let data = &self.datas[segment as usize];
+        let data = &self.module_inst().datas[segment as usize];
 
         if (addr + n) > data.len() {
-            Err(Trap::OutofRange)?;
+            Err(InstError::OutofBoundMem)?;
         }
 
         let bytes = &data[addr..addr + n];
 
-        self.mems[idx as usize].borrow_mut().mem_writes(dst, bytes)
+        self.module_inst().mems[idx as usize].borrow_mut().mem_writes(dst, bytes)
     }
 
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-data-drop
     pub fn data_drop(&mut self, data_idx: u32) {
-        self.datas[data_idx as usize].clear();
+        self.module_inst_mut().datas[data_idx as usize].clear();
     }
 
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-memory-copy
-    pub fn memory_copy(&mut self, src_idx: u32, dst_idx: u32) -> VMState {
-        let n = self.pop_u32() as u64;
-        let addr = self.pop_u32() as u64;
-        let dest = self.pop_u32() as u64;
+    ///
+    /// 两块内存可以不同，先整段读出再写入，同一块内存中区间重叠也没有问题
+    pub fn memory_copy(&mut self, dst_idx: u32, src_idx: u32) -> VMState {
+        let n = self.pop_addr();
+        let addr = self.pop_addr();
+        let dest = self.pop_addr();
 
-        let data = self.mems[src_idx as usize].borrow().mem_reads(addr, n)?;
+        let data = self.module_inst().mems[src_idx as usize].borrow().mem_reads(addr, n)?;
 
-        self.mems[dst_idx as usize].borrow_mut().mem_writes(dest, &data)
+        self.module_inst().mems[dst_idx as usize].borrow_mut().mem_writes(dest, &data)
     }
 
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-memory-fill
     pub fn memory_fill(&mut self, idx: u32) -> VMState {
-        let n = self.pop_u32() as usize;
+        let n = self.pop_addr();
         let val = self.pop_u32() as u8;
-        let addr = self.pop_u32() as u64;
-        let data = vec![val; n];
+        let addr = self.pop_addr();
 
-        self.mems[idx as usize].borrow_mut().mem_writes(addr, &data)
+        self.module_inst().mems[idx as usize].borrow_mut().fill(addr, n, val)
     }
 }
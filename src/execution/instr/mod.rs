@@ -1,9 +1,11 @@
+pub mod atomic;
 pub mod control;
 pub mod exec;
 pub mod memory;
 pub mod numeric;
 pub mod parametric;
 pub mod reference;
+pub mod register;
 pub mod table;
 pub mod trunc_sat;
 pub mod variable;
@@ -2,9 +2,10 @@ use std::ops::{Shl, Shr};
 
 use crate::execution::errors::{Trap, VMState};
 use crate::execution::stack::operand::Operand;
+use crate::execution::tracer::Tracer;
 use crate::execution::vm::VM;
 
-impl VM {
+impl<T: Tracer> VM<T> {
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-const
     /// 常量指令：立即数压栈
     pub fn i32_const(&mut self, v: i32) {
@@ -858,7 +859,7 @@ impl VM {
             Err(Trap::InvalidConversionToInteger)?;
         }
 
-        if v1 < -2147483648.0f32 || v1 >= 2147483648.0f32 {
+        if !(-2147483648.0f32..2147483648.0f32).contains(&v1) {
             Err(Trap::IntegerOverflow)?;
         }
 
@@ -922,7 +923,7 @@ impl VM {
             Err(Trap::InvalidConversionToInteger)?;
         }
 
-        if v1 < -9223372036854775808.0f32 || v1 >= 9223372036854775808.0f32 {
+        if !(-9223372036854775808.0f32..9223372036854775808.0f32).contains(&v1) {
             Err(Trap::IntegerOverflow)?;
         }
 
@@ -954,7 +955,7 @@ impl VM {
             Err(Trap::InvalidConversionToInteger)?;
         }
 
-        if v1 < -9223372036854775808. || v1 >= 9223372036854775808. {
+        if !(-9223372036854775808. ..9223372036854775808.).contains(&v1) {
             Err(Trap::IntegerOverflow)?;
         }
 
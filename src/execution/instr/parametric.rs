@@ -1,8 +1,9 @@
 use crate::binary::types::ValType;
 use crate::execution::stack::operand::Operand;
+use crate::execution::tracer::Tracer;
 use crate::execution::vm::VM;
 
-impl VM {
+impl<T: Tracer> VM<T> {
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-drop
     pub fn drop_(&mut self) {
         self.pop();
@@ -20,7 +21,7 @@ impl VM {
         }
     }
 
-    pub fn select2(&mut self, x: u8, type_: &ValType) {
+    pub fn select2(&mut self, _x: u8, _type_: &ValType) {
         self.select();
     }
 }
@@ -1,13 +1,17 @@
 use std::rc::Rc;
 
+use crate::execution::errors::VMState;
 use crate::execution::stack::operand::Operand;
+use crate::execution::tracer::Tracer;
 use crate::execution::value::ValInst;
 use crate::execution::vm::VM;
 
-impl VM {
+impl<T: Tracer> VM<T> {
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-ref-null
-    pub fn ref_null(&mut self, v: u64) {
-        self.push(ValInst::new_ref_null(v as u8));
+    pub fn ref_null(&mut self, v: u64) -> VMState {
+        self.push(ValInst::new_ref_null(v)?);
+
+        Ok(())
     }
 
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-ref-is-null
@@ -19,7 +23,7 @@ impl VM {
 
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-ref-func
     pub fn ref_func(&mut self, idx: u32) {
-        let func_inst = &self.funcs[idx as usize];
+        let func_inst = &self.module_inst().funcs[idx as usize];
         let ref_inst = Rc::clone(func_inst);
 
         self.push(ValInst::new_func_ref(ref_inst));
@@ -0,0 +1,92 @@
+use crate::execution::errors::VMState;
+use crate::execution::register::{Move, RegOp};
+use crate::execution::stack::frame::CallStack;
+use crate::execution::stack::operand::Operand;
+use crate::execution::tracer::Tracer;
+use crate::execution::vm::VM;
+
+/// 寄存器解释器，寄存器 r 就是操作数栈上的 local_idx + r
+impl<T: Tracer> VM<T> {
+    pub fn exec_reg_op(&mut self, op: &RegOp) -> VMState {
+        let base = self.local_idx;
+
+        match op {
+            RegOp::Copy { dst, src } => {
+                let value = self.operands[base + src].clone();
+
+                self.operands[base + dst] = value;
+            }
+            RegOp::Const { dst, value } => self.operands[base + dst] = value.clone(),
+            RegOp::Unary { f, dst, src, .. } => {
+                let value = f(&self.operands[base + src]);
+
+                self.operands[base + dst] = value;
+            }
+            RegOp::Binary { f, dst, lhs, rhs, .. } => {
+                let value = f(&self.operands[base + lhs], &self.operands[base + rhs]);
+
+                self.operands[base + dst] = value;
+            }
+            RegOp::Apply { instr, args, dst } => {
+                for arg in args.iter() {
+                    let value = self.operands[base + arg].clone();
+
+                    self.push(value);
+                }
+
+                self.exec_instr(instr)?;
+
+                if let Some(dst) = dst {
+                    let value = self.pop();
+
+                    self.operands[base + dst] = value;
+                }
+            }
+            RegOp::Call { instr, height } => {
+                self.operands.truncate(base + height);
+                self.exec_instr(instr)?;
+                self.fit_window();
+            }
+            RegOp::Br { pc, moves } => self.reg_br(*pc, moves)?,
+            RegOp::BrIf { cond, pc, moves } => {
+                if self.operands[base + cond].as_i32() != 0 {
+                    self.reg_br(*pc, moves)?;
+                }
+            }
+            RegOp::BrUnless { cond, pc } => {
+                if self.operands[base + cond].as_i32() == 0 {
+                    self.top_mut().pc = *pc;
+                }
+            }
+            RegOp::BrTable { idx, targets } => {
+                let i = (self.operands[base + idx].as_u32() as usize).min(targets.len() - 1);
+                let (pc, moves) = &targets[i];
+
+                self.reg_br(*pc, moves)?;
+            }
+        };
+
+        Ok(())
+    }
+
+    fn reg_br(&mut self, pc: usize, moves: &Move) -> VMState {
+        let base = self.local_idx;
+
+        // 往回跳的只有 loop
+        if pc < self.top_frame().pc {
+            self.check_interrupt()?;
+        }
+
+        if moves.from != moves.to {
+            for i in 0..moves.n {
+                let value = self.operands[base + moves.from + i].clone();
+
+                self.operands[base + moves.to + i] = value;
+            }
+        }
+
+        self.top_mut().pc = pc;
+
+        Ok(())
+    }
+}
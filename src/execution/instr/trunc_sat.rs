@@ -1,4 +1,5 @@
 use crate::execution::stack::operand::Operand;
+use crate::execution::tracer::Tracer;
 use crate::execution::vm::VM;
 
 pub enum TruncSize {
@@ -30,7 +31,7 @@ impl TruncSize {
 }
 
 /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-cvtop
-impl VM {
+impl<T: Tracer> VM<T> {
     pub fn i32_trunc_sat_f32_u(&mut self) {
         let v1 = self.pop_f32();
         let v1 = trunc_sat_u(v1 as f64, TruncSize::N32);
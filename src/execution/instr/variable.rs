@@ -2,9 +2,10 @@ use std::rc::Rc;
 
 use crate::execution::errors::VMState;
 use crate::execution::stack::operand::Operand;
+use crate::execution::tracer::Tracer;
 use crate::execution::vm::VM;
 
-impl VM {
+impl<T: Tracer> VM<T> {
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-local-get
     pub fn local_get(&mut self, idx: u32) {
         let idx = idx as usize;
@@ -32,7 +33,7 @@ impl VM {
 
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-global-get
     pub fn global_get(&mut self, idx: u32) {
-        let global = Rc::clone(&self.globals[idx as usize]);
+        let global = Rc::clone(&self.module_inst().globals[idx as usize]);
 
         {
             let global = global.borrow();
@@ -45,7 +46,7 @@ impl VM {
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-global-set
     pub fn global_set(&mut self, idx: u32) -> VMState {
         let v1 = self.pop();
-        let global = &self.globals[idx as usize];
+        let global = &self.module_inst().globals[idx as usize];
 
         global.borrow_mut().set(v1)
     }
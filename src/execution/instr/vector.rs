@@ -7,6 +7,7 @@ use crate::binary::instruction::{Lane16, LaneIdx, MemoryArg};
 use crate::execution::errors::VMState;
 use crate::execution::inst::memory::Memory;
 use crate::execution::stack::operand::Operand;
+use crate::execution::tracer::Tracer;
 use crate::execution::value::{v128, ToV128};
 use crate::execution::vm::VM;
 
@@ -23,7 +24,7 @@ macro_rules! saturate {
     }};
 }
 
-impl VM {
+impl<T: Tracer> VM<T> {
     /// https://webassembly.github.io/spec/core/exec/instructions.html#exec-load
     pub fn v128_load(&mut self, memarg: &MemoryArg) -> VMState {
         let addr = self.get_mem_addr(memarg);
@@ -1916,7 +1917,7 @@ impl VM {
             let a = v1[i] as u16;
             let b = v2[i] as u16;
 
-            v[i] = ((a + b + 1) / 2) as u8;
+            v[i] = (a + b).div_ceil(2) as u8;
         }
 
         self.push_v128(v.v128());
@@ -1931,7 +1932,7 @@ impl VM {
             let a = v1[i] as u32;
             let b = v2[i] as u32;
 
-            v[i] = ((a + b + 1) / 2) as u16;
+            v[i] = (a + b).div_ceil(2) as u16;
         }
 
         self.push_v128(v.v128());
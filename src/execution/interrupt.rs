@@ -0,0 +1,25 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// 中断句柄，可以发送到其他线程，每次调用 interrupt 都会让 epoch 前进一次
+///
+/// 虚拟机在循环回跳和函数入口处检查 epoch，
+/// 前进的次数达到 Config::epoch_deadline 时，当前调用返回 Trap::Interrupted
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+    epoch: Arc<AtomicU64>,
+}
+
+impl InterruptHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn interrupt(&self) {
+        self.epoch.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::Relaxed)
+    }
+}
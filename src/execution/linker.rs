@@ -0,0 +1,305 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::errors::{LinkError, Mismatch, VMState};
+use super::host::{Caller, HostFunc, IntoFunc};
+use super::importer::{resolve_import, Importer, MImporter};
+use super::inst::function::FuncInst;
+use super::inst::{ExportInst, RGlobalInst, RMemInst, RTableInst, RTagInst};
+use super::module::Module;
+use super::store::Instance;
+use super::tracer::Tracer;
+use super::value::ValInsts;
+use super::vm::VM;
+use crate::binary::section::{ImportDesc, ImportSeg};
+use crate::binary::types::{FuncType, Limits};
+
+/// 按 模块名.导入项名 组织导入项，用来实例化模块
+///
+/// 导入项可以是宿主函数、宿主的表/内存/全局变量、已有实例的导出，也可以交给 Importer 解析
+#[derive(Default)]
+pub struct Linker {
+    defs: HashMap<String, HashMap<String, ExportInst>>,
+    importers: MImporter,
+}
+
+impl Linker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 同名的定义会被覆盖
+    pub fn define(&mut self, module: &str, name: &str, item: ExportInst) -> &mut Self {
+        self.defs
+            .entry(module.to_string())
+            .or_default()
+            .insert(name.to_string(), item);
+
+        self
+    }
+
+    /// 签名由闭包的参数和返回值决定，第一个参数可以是 Caller，返回 VMState 时可以抛出错误
+    pub fn func<Params, Results>(
+        &mut self,
+        module: &str,
+        name: &str,
+        func: impl IntoFunc<Params, Results>,
+    ) -> &mut Self {
+        let (func_type, func) = func.into_func();
+
+        self.host_func(module, name, func_type, func)
+    }
+
+    /// 签名在运行时才确定的宿主函数，参数按签名给出，返回值要和签名一致
+    pub fn func_new(
+        &mut self,
+        module: &str,
+        name: &str,
+        func_type: FuncType,
+        func: impl Fn(Caller, ValInsts) -> VMState<ValInsts> + 'static,
+    ) -> &mut Self {
+        self.host_func(module, name, func_type, Rc::new(func))
+    }
+
+    fn host_func(&mut self, module: &str, name: &str, func_type: FuncType, func: HostFunc) -> &mut Self {
+        let func_inst = FuncInst::from_host(func_type, func, &format!("{}.{}", module, name));
+
+        self.define(module, name, ExportInst::Func(Rc::new(RefCell::new(func_inst))))
+    }
+
+    pub fn table(&mut self, module: &str, name: &str, table: RTableInst) -> &mut Self {
+        self.define(module, name, ExportInst::Table(table))
+    }
+
+    pub fn memory(&mut self, module: &str, name: &str, mem: RMemInst) -> &mut Self {
+        self.define(module, name, ExportInst::Mem(mem))
+    }
+
+    pub fn global(&mut self, module: &str, name: &str, global: RGlobalInst) -> &mut Self {
+        self.define(module, name, ExportInst::Global(global))
+    }
+
+    /// 宿主定义的标签，宿主函数可以用它抛出异常，也可以判断捕获到的异常
+    pub fn tag(&mut self, module: &str, name: &str, tag: RTagInst) -> &mut Self {
+        self.define(module, name, ExportInst::Tag(tag))
+    }
+
+    /// 把实例的所有导出定义在 module 下，实例只能用于同一个虚拟机
+    pub fn instance<T: Tracer>(&mut self, vm: &VM<T>, module: &str, instance: Instance) -> &mut Self {
+        let inst = vm.store.get(instance);
+
+        for name in inst.exports.keys() {
+            if let Some(item) = inst.get_export(name) {
+                self.define(module, name, item);
+            }
+        }
+
+        self
+    }
+
+    /// 直接定义中找不到的导入项，再交给同名的导入方解析
+    pub fn importer(&mut self, module: &str, importer: Rc<RefCell<dyn Importer>>) -> &mut Self {
+        self.importers.insert(module.to_string(), importer);
+
+        self
+    }
+
+    pub fn get(&self, module: &str, name: &str) -> Option<ExportInst> {
+        self.defs.get(module).and_then(|defs| defs.get(name)).cloned()
+    }
+
+    /// 按导入段的顺序取出导入项，类型在实例化时检查
+    pub fn resolve(&self, imports: &[ImportSeg]) -> VMState<Vec<ExportInst>> {
+        imports.iter().map(|import| self.resolve_import(import)).collect()
+    }
+
+    fn resolve_import(&self, import: &ImportSeg) -> VMState<ExportInst> {
+        if let Some(item) = self.get(&import.module, &import.name) {
+            return Ok(item);
+        }
+
+        match self.importers.get(&import.module) {
+            Some(importer) => resolve_import(import, importer),
+            None if self.defs.contains_key(&import.module) => Err(LinkError::ItemNotFound(
+                import.module.clone(),
+                import.name.clone(),
+            ))?,
+            None => Err(LinkError::ModuleNotFound(import.module.clone()))?,
+        }
+    }
+
+    pub fn instantiate<T: Tracer>(
+        &self,
+        vm: &mut VM<T>,
+        name: &str,
+        module: &Module,
+    ) -> VMState<Instance> {
+        let imports = self.resolve(&module.binary().import_sec)?;
+
+        vm.instantiate(name, module, imports)
+    }
+}
+
+/// 检查导入项是否满足模块中的声明，types 为模块的类型段
+/// https://webassembly.github.io/spec/core/valid/types.html#import-subtyping
+pub(crate) fn check_import(
+    desc: &ImportDesc,
+    types: &[FuncType],
+    item: &ExportInst,
+) -> Result<(), Mismatch> {
+    match (desc, item) {
+        (ImportDesc::Func(idx), ExportInst::Func(func_inst)) => {
+            let expected = &types[*idx as usize];
+            let actual = func_inst.borrow().get_type().clone();
+
+            match expected == &actual {
+                true => Ok(()),
+                false => Err(Mismatch::FuncType(expected.clone(), actual)),
+            }
+        }
+        (ImportDesc::Table(expected), ExportInst::Table(table)) => {
+            let actual = table.borrow().get_type().clone();
+
+            if expected.elem_type != actual.elem_type {
+                return Err(Mismatch::ElemType(expected.elem_type, actual.elem_type));
+            }
+
+            check_limits(&expected.limits, &actual.limits)
+        }
+        (ImportDesc::Mem(expected), ExportInst::Mem(mem)) => {
+            check_limits(expected, &mem.borrow().get_type())
+        }
+        (ImportDesc::Global(expected), ExportInst::Global(global)) => {
+            let actual = global.borrow().get_type().clone();
+
+            match expected == &actual {
+                true => Ok(()),
+                false => Err(Mismatch::GlobalType(expected.clone(), actual)),
+            }
+        }
+        (ImportDesc::Tag(expected), ExportInst::Tag(tag)) => {
+            let expected = &types[expected.type_idx as usize];
+            let actual = tag.get_type();
+
+            match expected == actual {
+                true => Ok(()),
+                false => Err(Mismatch::TagType(expected.clone(), actual.clone())),
+            }
+        }
+        (desc, item) => Err(Mismatch::Kind(desc_kind(desc), item.kind())),
+    }
+}
+
+/// 提供的大小范围要落在声明的范围之内
+fn check_limits(expected: &Limits, actual: &Limits) -> Result<(), Mismatch> {
+    if actual.is64 != expected.is64 {
+        return Err(Mismatch::AddrType(expected.addr_type(), actual.addr_type()));
+    }
+
+    if actual.shared != expected.shared {
+        return Err(Mismatch::Shared(expected.shared, actual.shared));
+    }
+
+    if actual.min < expected.min {
+        return Err(Mismatch::Min(expected.min, actual.min));
+    }
+
+    match (expected.max, actual.max) {
+        (Some(max), None) => Err(Mismatch::NoMax(max)),
+        (Some(max), Some(actual)) if actual > max => Err(Mismatch::Max(max, actual)),
+        _ => Ok(()),
+    }
+}
+
+fn desc_kind(desc: &ImportDesc) -> &'static str {
+    match desc {
+        ImportDesc::Func(_) => "函数",
+        ImportDesc::Table(_) => "表",
+        ImportDesc::Mem(_) => "内存",
+        ImportDesc::Global(_) => "全局变量",
+        ImportDesc::Tag(_) => "异常标签",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Linker;
+    use crate::execution::config::{Config, Engine};
+    use crate::execution::errors::{LinkError, Mismatch};
+    use crate::execution::module::Module;
+    use crate::execution::value::ValInst;
+    use crate::execution::vm::VM;
+
+    fn link_err(linker: &Linker, text: &str) -> LinkError {
+        let mut vm = VM::empty("test", Config::default());
+        let module = Module::from_text(text, Engine::Stack).unwrap();
+        let err = linker.instantiate(&mut vm, "test", &module).unwrap_err();
+
+        *err.downcast::<LinkError>().unwrap()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut vm = VM::empty("test", Config::default());
+        let mut linker = Linker::new();
+
+        linker.func("env", "add", |a: i32, b: i32| a + b);
+
+        // 宿主函数和其他实例的导出都可以作为导入项
+        let lib = Module::from_text(
+            r#"(module (import "env" "add" (func $add (param i32 i32) (result i32)))
+                 (func (export "inc") (param i32) (result i32) (call $add (local.get 0) (i32.const 1))))"#,
+            Engine::Stack,
+        )
+        .unwrap();
+        let lib = linker.instantiate(&mut vm, "lib", &lib).unwrap();
+
+        linker.instance(&vm, "lib", lib);
+
+        let main = Module::from_text(
+            r#"(module (import "lib" "inc" (func $inc (param i32) (result i32)))
+                 (func (export "main") (result i32) (call $inc (i32.const 41))))"#,
+            Engine::Stack,
+        )
+        .unwrap();
+        let main = linker.instantiate(&mut vm, "main", &main).unwrap();
+
+        assert_eq!(
+            vm.call_export(main, "main", vec![]).unwrap(),
+            vec![ValInst::I32(42)]
+        );
+    }
+
+    #[test]
+    fn test_link_errors() {
+        let mut linker = Linker::new();
+
+        linker.func("env", "f", |a: i32| a);
+
+        let err = link_err(&linker, r#"(module (import "sys" "f" (func)))"#);
+
+        assert!(matches!(err, LinkError::ModuleNotFound(module) if module == "sys"));
+
+        let err = link_err(&linker, r#"(module (import "env" "g" (func)))"#);
+
+        assert!(matches!(err, LinkError::ItemNotFound(_, name) if name == "g"));
+
+        let err = link_err(
+            &linker,
+            r#"(module (import "env" "f" (func (param i64) (result i32))))"#,
+        );
+
+        assert!(matches!(
+            err,
+            LinkError::IncompatibleImportType(_, _, Mismatch::FuncType(..))
+        ));
+
+        let err = link_err(&linker, r#"(module (import "env" "f" (memory 1)))"#);
+
+        assert!(matches!(
+            err,
+            LinkError::IncompatibleImportType(_, _, Mismatch::Kind("内存", "函数"))
+        ));
+    }
+}
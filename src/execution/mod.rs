@@ -5,9 +5,20 @@ mod instr;
 mod stack;
 pub mod value;
 
+pub mod backtrace;
+pub mod bytecode;
+pub mod config;
 pub mod errors;
+pub mod host;
 pub mod importer;
 pub mod inst;
+pub mod interrupt;
+pub mod linker;
+pub mod module;
+pub mod register;
+pub mod store;
+pub mod tracer;
+pub mod typed_func;
 pub mod vm;
 
 pub fn random_str(n: usize) -> String {
@@ -0,0 +1,59 @@
+use std::rc::Rc;
+
+use super::bytecode::Bytecode;
+use super::config::Engine;
+use super::errors::VMState;
+use crate::binary;
+use crate::binary::validate::Validate;
+
+/// 校验并编译过的模块，不可修改，可以被多次实例化
+///
+/// 克隆只增加引用计数，各个实例共用同一份模块和函数体
+#[derive(Debug, Clone)]
+pub struct Module {
+    binary: Rc<binary::module::Module>,
+    codes: Rc<[Rc<Bytecode>]>,
+    engine: Engine,
+}
+
+impl Module {
+    /// 函数体按 engine 编译成对应解释器的形式
+    pub fn new(module: binary::module::Module, engine: Engine) -> VMState<Self> {
+        module.validate()?;
+
+        let codes = (0..module.func_sec.len())
+            .map(|i| Rc::new(Bytecode::compile(&module, i, engine)))
+            .collect();
+
+        Ok(Self {
+            binary: Rc::new(module),
+            codes,
+            engine,
+        })
+    }
+
+    pub fn from_file(path: &str, engine: Engine) -> VMState<Self> {
+        Self::new(binary::module::Module::from_file(path)?, engine)
+    }
+
+    pub fn from_data(data: Vec<u8>, engine: Engine) -> VMState<Self> {
+        Self::new(binary::module::Module::from_data(data)?, engine)
+    }
+
+    pub fn from_text(text: &str, engine: Engine) -> VMState<Self> {
+        Self::new(binary::module::Module::from_text(text)?, engine)
+    }
+
+    pub fn binary(&self) -> &Rc<binary::module::Module> {
+        &self.binary
+    }
+
+    /// idx 为函数在代码段中的索引
+    pub fn code(&self, idx: usize) -> &Rc<Bytecode> {
+        &self.codes[idx]
+    }
+
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+}
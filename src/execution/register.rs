@@ -0,0 +1,667 @@
+use std::fmt;
+
+use crate::binary::instruction::Instruction;
+use crate::execution::bytecode::{Handler, Op, Opcode, Signatures, Target};
+use crate::execution::value::ValInst;
+
+/// 寄存器编号，相对于当前函数的第一个参数
+///
+/// 参数和局部变量占前面的寄存器，之后每个寄存器对应操作数栈上的一个位置
+pub type Reg = usize;
+
+pub type UnaryFn = fn(&ValInst) -> ValInst;
+pub type BinaryFn = fn(&ValInst, &ValInst) -> ValInst;
+
+/// 把 from 开始的 n 个寄存器依次复制到 to 开始的位置，to 不会大于 from
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Move {
+    pub from: Reg,
+    pub to: Reg,
+    pub n: usize,
+}
+
+/// 寄存器形式的指令，跳转目标是 RegOp 的下标
+#[derive(Clone)]
+pub enum RegOp {
+    Copy {
+        dst: Reg,
+        src: Reg,
+    },
+    Const {
+        dst: Reg,
+        value: ValInst,
+    },
+    Unary {
+        instr: Instruction,
+        f: UnaryFn,
+        dst: Reg,
+        src: Reg,
+    },
+    Binary {
+        instr: Instruction,
+        f: BinaryFn,
+        dst: Reg,
+        lhs: Reg,
+        rhs: Reg,
+    },
+    /// 没有寄存器实现的指令：参数压栈后交给 exec_instr，结果再出栈
+    Apply {
+        instr: Instruction,
+        args: Box<[Reg]>,
+        dst: Option<Reg>,
+    },
+    /// call、call_indirect 以及尾调用：参数已经放在 height 之下，被调用方的栈帧从参数开始
+    Call {
+        instr: Instruction,
+        height: Reg,
+    },
+    Br {
+        pc: usize,
+        moves: Move,
+    },
+    BrIf {
+        cond: Reg,
+        pc: usize,
+        moves: Move,
+    },
+    BrUnless {
+        cond: Reg,
+        pc: usize,
+    },
+    /// 最后一个为默认分支
+    BrTable {
+        idx: Reg,
+        targets: Box<[(usize, Move)]>,
+    },
+}
+
+impl RegOp {
+    /// 写入结果的寄存器
+    fn dst_mut(&mut self) -> Option<&mut Reg> {
+        match self {
+            RegOp::Copy { dst, .. }
+            | RegOp::Const { dst, .. }
+            | RegOp::Unary { dst, .. }
+            | RegOp::Binary { dst, .. }
+            | RegOp::Apply { dst: Some(dst), .. } => Some(dst),
+            _ => None,
+        }
+    }
+}
+
+impl Opcode for RegOp {
+    fn opcode(&self) -> u16 {
+        match self {
+            RegOp::Copy { .. } => 0x20,
+            RegOp::Const { value, .. } => match value {
+                ValInst::I32(_) => 0x41,
+                ValInst::I64(_) => 0x42,
+                ValInst::F32(_) => 0x43,
+                _ => 0x44,
+            },
+            RegOp::Unary { instr, .. }
+            | RegOp::Binary { instr, .. }
+            | RegOp::Apply { instr, .. }
+            | RegOp::Call { instr, .. } => instr.discriminant(),
+            RegOp::Br { .. } => 0x0c,
+            RegOp::BrIf { .. } => 0x0d,
+            RegOp::BrUnless { .. } => 0x04,
+            RegOp::BrTable { .. } => 0x0e,
+        }
+    }
+}
+
+impl fmt::Debug for RegOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegOp::Copy { dst, src } => write!(f, "r{} = r{}", dst, src),
+            RegOp::Const { dst, value } => write!(f, "r{} = {:?}", dst, value),
+            RegOp::Unary { instr, dst, src, .. } => write!(f, "r{} = {:?} r{}", dst, instr, src),
+            RegOp::Binary {
+                instr, dst, lhs, rhs, ..
+            } => write!(f, "r{} = {:?} r{} r{}", dst, instr, lhs, rhs),
+            RegOp::Apply { instr, args, dst } => {
+                if let Some(dst) = dst {
+                    write!(f, "r{} = ", dst)?;
+                }
+
+                write!(f, "{:?}", instr)?;
+
+                args.iter().try_for_each(|arg| write!(f, " r{}", arg))
+            }
+            RegOp::Call { instr, height } => write!(f, "{:?} @r{}", instr, height),
+            RegOp::Br { pc, moves } => write!(f, "Br({}, {:?})", pc, moves),
+            RegOp::BrIf { cond, pc, moves } => write!(f, "BrIf(r{}, {}, {:?})", cond, pc, moves),
+            RegOp::BrUnless { cond, pc } => write!(f, "BrUnless(r{}, {})", cond, pc),
+            RegOp::BrTable { idx, targets } => write!(f, "BrTable(r{}, {:?})", idx, targets),
+        }
+    }
+}
+
+/// 寄存器形式的函数体
+#[derive(Debug, Clone)]
+pub struct RegCode {
+    pub ops: Vec<RegOp>,
+    /// 和 Bytecode::offsets 一样，每条指令对应的原始指令的位置
+    pub offsets: Vec<usize>,
+    /// 寄存器个数，进入函数时在操作数栈上预留
+    pub size: usize,
+    /// 和 Bytecode::handlers 一样，位置换成 RegOp 的下标
+    pub handlers: Vec<Handler>,
+}
+
+impl RegCode {
+    /// heights 为每条指令执行前的栈高度，offsets 为对应的原始指令的位置，base 为参数和局部变量的个数
+    ///
+    /// 结果最终放在前 arity 个寄存器里
+    pub(crate) fn lower(
+        sigs: &Signatures,
+        ops: &[Op],
+        handlers: &[Handler],
+        heights: &[usize],
+        offsets: &[usize],
+        base: usize,
+        arity: usize,
+    ) -> Self {
+        let end = ops.len();
+        let targets = jump_targets(ops, handlers);
+        let mut lower = Lowering {
+            sigs,
+            ops: vec![],
+            stack: vec![],
+            base,
+            size: base.max(arity),
+            barrier: 0,
+        };
+        // Op 的下标到 RegOp 的下标，最后一项是函数体结尾
+        let mut map = vec![0; end + 2];
+        let mut reachable = true;
+
+        for pc in 0..=end {
+            if targets[pc] {
+                match reachable {
+                    true => lower.flush(),
+                    false => lower.reset(heights.get(pc).copied().unwrap_or(base + arity)),
+                }
+
+                reachable = true;
+                lower.barrier = lower.ops.len();
+            }
+
+            map[pc] = lower.ops.len();
+
+            if reachable {
+                reachable = match ops.get(pc) {
+                    Some(op) => lower.op(op),
+                    None => lower.epilogue(end + 1, arity),
+                };
+            }
+        }
+
+        map[end + 1] = lower.ops.len();
+
+        // 一条 Op 生成的 RegOp 都对应它的位置，结尾的指令算作最后一条
+        let offsets = match offsets.last() {
+            Some(last) => (0..=end)
+                .flat_map(|pc| {
+                    let offset = *offsets.get(pc).unwrap_or(last);
+
+                    (map[pc]..map[pc + 1]).map(move |_| offset)
+                })
+                .collect(),
+            None => vec![],
+        };
+
+        for op in &mut lower.ops {
+            match op {
+                RegOp::Br { pc, .. } | RegOp::BrIf { pc, .. } | RegOp::BrUnless { pc, .. } => {
+                    *pc = map[*pc]
+                }
+                RegOp::BrTable { targets, .. } => targets.iter_mut().for_each(|(pc, _)| *pc = map[*pc]),
+                _ => {}
+            }
+        }
+
+        // 捕获到异常时局部变量保持原样，值放在局部变量之上
+        let handlers = handlers
+            .iter()
+            .map(|handler| Handler {
+                start: map[handler.start],
+                end: map[handler.end],
+                catches: handler
+                    .catches
+                    .iter()
+                    .map(|(catch, target)| {
+                        let target = Target {
+                            pc: map[target.pc],
+                            height: target.height.max(base),
+                            ..*target
+                        };
+
+                        (*catch, target)
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Self {
+            ops: lower.ops,
+            offsets,
+            size: lower.size,
+            handlers,
+        }
+    }
+}
+
+/// 会被跳转到的位置，包括函数体结尾
+///
+/// try_table 的开头也算在内，这样进入时栈上的值都已经放到自己的位置上，catch 子句跳转时不用再移动
+fn jump_targets(ops: &[Op], handlers: &[Handler]) -> Vec<bool> {
+    let mut targets = vec![false; ops.len() + 1];
+
+    for handler in handlers {
+        targets[handler.start] = true;
+        handler
+            .catches
+            .iter()
+            .for_each(|(_, target)| targets[target.pc] = true);
+    }
+
+    for op in ops {
+        match op {
+            Op::Br(target) | Op::BrIf(target) => targets[target.pc] = true,
+            Op::BrTable(list) => list.iter().for_each(|target| targets[target.pc] = true),
+            Op::BrUnless(pc) | Op::Jump(pc) => targets[*pc] = true,
+            Op::Instr(_) => {}
+        }
+    }
+
+    targets
+}
+
+struct Lowering<'a> {
+    sigs: &'a Signatures<'a>,
+    ops: Vec<RegOp>,
+    /// 栈上每个位置的值所在的寄存器，local.get 推迟到值被覆盖或跳转前才复制
+    stack: Vec<Reg>,
+    base: usize,
+    size: usize,
+    /// 最近一个跳转目标处的 RegOp 下标，之前的指令不能再改写结果寄存器
+    barrier: usize,
+}
+
+impl Lowering<'_> {
+    fn emit(&mut self, op: RegOp) {
+        self.ops.push(op);
+    }
+
+    /// 栈顶之上的第一个寄存器
+    fn top(&self) -> Reg {
+        self.base + self.stack.len()
+    }
+
+    fn push(&mut self) -> Reg {
+        let reg = self.top();
+
+        self.push_reg(reg);
+
+        reg
+    }
+
+    /// 值不一定在自己的位置上，但位置要留出来
+    fn push_reg(&mut self, reg: Reg) {
+        self.size = self.size.max(self.top() + 1);
+        self.stack.push(reg);
+    }
+
+    fn pop(&mut self) -> Reg {
+        self.stack.pop().unwrap()
+    }
+
+    /// 把 from 之上满足条件的值复制到它们在栈上的位置
+    fn materialize(&mut self, from: usize, pred: impl Fn(Reg) -> bool) {
+        for i in from..self.stack.len() {
+            let (src, dst) = (self.stack[i], self.base + i);
+
+            if src != dst && pred(src) {
+                self.emit(RegOp::Copy { dst, src });
+                self.stack[i] = dst;
+            }
+        }
+    }
+
+    /// 跳转前所有的值都要放到自己的位置上
+    fn flush(&mut self) {
+        self.materialize(0, |_| true);
+    }
+
+    fn reset(&mut self, height: usize) {
+        self.stack = (self.base..height).collect();
+    }
+
+    fn moves(&self, target: &Target) -> Move {
+        Move {
+            from: self.top() - target.keep,
+            // 跳到函数体结尾时先把结果放在局部变量之上，由结尾统一移到最前面
+            to: target.height.max(self.base),
+            n: target.keep,
+        }
+    }
+
+    /// 返回后面的指令是否可达
+    fn op(&mut self, op: &Op) -> bool {
+        match op {
+            Op::Instr(instr) => return self.instr(instr),
+            Op::Br(target) => {
+                self.flush();
+
+                let moves = self.moves(target);
+
+                self.emit(RegOp::Br { pc: target.pc, moves });
+
+                return false;
+            }
+            Op::BrIf(target) => {
+                let cond = self.pop();
+
+                self.flush();
+
+                let moves = self.moves(target);
+
+                self.emit(RegOp::BrIf {
+                    cond,
+                    pc: target.pc,
+                    moves,
+                });
+            }
+            Op::BrTable(list) => {
+                let idx = self.pop();
+
+                self.flush();
+
+                let targets = list
+                    .iter()
+                    .map(|target| (target.pc, self.moves(target)))
+                    .collect();
+
+                self.emit(RegOp::BrTable { idx, targets });
+
+                return false;
+            }
+            Op::BrUnless(pc) => {
+                let cond = self.pop();
+
+                self.flush();
+                self.emit(RegOp::BrUnless { cond, pc: *pc });
+            }
+            Op::Jump(pc) => {
+                self.flush();
+                self.emit(RegOp::Br {
+                    pc: *pc,
+                    moves: Move::default(),
+                });
+
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn instr(&mut self, instr: &Instruction) -> bool {
+        use Instruction::*;
+
+        match instr {
+            Nop => {}
+            Drop => {
+                self.pop();
+            }
+            LocalGet(idx) => self.push_reg(*idx as Reg),
+            LocalSet(idx) => {
+                let src = self.pop();
+
+                self.set_local(*idx as Reg, src);
+            }
+            LocalTee(idx) => {
+                let src = self.pop();
+
+                self.set_local(*idx as Reg, src);
+                self.push_reg(*idx as Reg);
+            }
+            I32Const(v) => self.constant(ValInst::I32(*v)),
+            I64Const(v) => self.constant(ValInst::I64(*v)),
+            F32Const(v) => self.constant(ValInst::F32(*v)),
+            F64Const(v) => self.constant(ValInst::F64(*v)),
+            Call(_) | CallIndirect(..) | ReturnCall(_) | ReturnCallIndirect(..) => {
+                let (pops, pushes) = self.sigs.stack_effect(instr);
+                let args = self.stack.len() - pops;
+
+                // 被调用方不会修改调用方的局部变量，只有参数需要放到位
+                self.materialize(args, |_| true);
+                self.emit(RegOp::Call {
+                    instr: instr.clone(),
+                    height: self.top(),
+                });
+                self.stack.truncate(args);
+
+                // 尾调用替换了当前栈帧，之后的指令都执行不到
+                if matches!(instr, ReturnCall(_) | ReturnCallIndirect(..)) {
+                    return false;
+                }
+
+                for _ in 0..pushes {
+                    self.push();
+                }
+            }
+            Unreachable | Throw(_) | ThrowRef => {
+                let (pops, _) = match instr {
+                    Unreachable => (0, 0),
+                    _ => self.sigs.stack_effect(instr),
+                };
+                let args = self.stack.split_off(self.stack.len() - pops);
+
+                self.emit(RegOp::Apply {
+                    instr: instr.clone(),
+                    args: args.into_boxed_slice(),
+                    dst: None,
+                });
+
+                return false;
+            }
+            _ => match native(instr) {
+                Some(Native::Unary(f)) => {
+                    let src = self.pop();
+                    let dst = self.push();
+
+                    self.emit(RegOp::Unary {
+                        instr: instr.clone(),
+                        f,
+                        dst,
+                        src,
+                    });
+                }
+                Some(Native::Binary(f)) => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    let dst = self.push();
+
+                    self.emit(RegOp::Binary {
+                        instr: instr.clone(),
+                        f,
+                        dst,
+                        lhs,
+                        rhs,
+                    });
+                }
+                None => {
+                    let (pops, pushes) = self.sigs.stack_effect(instr);
+                    let args = self.stack.split_off(self.stack.len() - pops);
+                    let dst = (pushes == 1).then(|| self.push());
+
+                    self.emit(RegOp::Apply {
+                        instr: instr.clone(),
+                        args: args.into_boxed_slice(),
+                        dst,
+                    });
+                }
+            },
+        }
+
+        true
+    }
+
+    fn constant(&mut self, value: ValInst) {
+        let dst = self.push();
+
+        self.emit(RegOp::Const { dst, value });
+    }
+
+    fn set_local(&mut self, local: Reg, src: Reg) {
+        if src == local {
+            return;
+        }
+
+        let len = self.ops.len();
+
+        // 栈上还没取出来的旧值要先复制走
+        self.materialize(0, |reg| reg == local);
+
+        // 刚算出来的值直接写到局部变量里，省掉一次复制
+        if self.ops.len() == len && src >= self.base && len > self.barrier {
+            if let Some(dst) = self.ops[len - 1].dst_mut() {
+                if *dst == src {
+                    *dst = local;
+
+                    return;
+                }
+            }
+        }
+
+        self.emit(RegOp::Copy { dst: local, src });
+    }
+
+    /// 函数体结尾：结果移到最前面，之后就可以直接退出
+    fn epilogue(&mut self, exit: usize, arity: usize) -> bool {
+        self.flush();
+
+        if arity > 0 && self.base > 0 {
+            self.emit(RegOp::Br {
+                pc: exit,
+                moves: Move {
+                    from: self.base,
+                    to: 0,
+                    n: arity,
+                },
+            });
+        }
+
+        false
+    }
+}
+
+enum Native {
+    Unary(UnaryFn),
+    Binary(BinaryFn),
+}
+
+macro_rules! unary {
+    ($as:ident, $out:ident, | $a:ident | $body:expr) => {
+        Native::Unary(|v| {
+            let $a = v.$as();
+
+            ValInst::$out($body)
+        })
+    };
+}
+
+macro_rules! binary {
+    ($as:ident, $out:ident, | $a:ident, $b:ident | $body:expr) => {
+        Native::Binary(|lhs, rhs| {
+            let ($a, $b) = (lhs.$as(), rhs.$as());
+
+            ValInst::$out($body)
+        })
+    };
+}
+
+/// 直接读写寄存器的指令，都不会 trap
+fn native(instr: &Instruction) -> Option<Native> {
+    use Instruction::*;
+
+    let native = match instr {
+        I32Eqz => unary!(as_i32, I32, |a| (a == 0) as i32),
+        I64Eqz => unary!(as_i64, I32, |a| (a == 0) as i32),
+        I32WrapI64 => unary!(as_i64, I32, |a| a as i32),
+        I64ExtendI32S => unary!(as_i32, I64, |a| a as i64),
+        I64ExtendI32U => unary!(as_u32, I64, |a| a as i64),
+
+        I32Eq => binary!(as_i32, I32, |a, b| (a == b) as i32),
+        I32Ne => binary!(as_i32, I32, |a, b| (a != b) as i32),
+        I32LtS => binary!(as_i32, I32, |a, b| (a < b) as i32),
+        I32LtU => binary!(as_u32, I32, |a, b| (a < b) as i32),
+        I32GtS => binary!(as_i32, I32, |a, b| (a > b) as i32),
+        I32GtU => binary!(as_u32, I32, |a, b| (a > b) as i32),
+        I32LeS => binary!(as_i32, I32, |a, b| (a <= b) as i32),
+        I32LeU => binary!(as_u32, I32, |a, b| (a <= b) as i32),
+        I32GeS => binary!(as_i32, I32, |a, b| (a >= b) as i32),
+        I32GeU => binary!(as_u32, I32, |a, b| (a >= b) as i32),
+        I32Add => binary!(as_i32, I32, |a, b| a.wrapping_add(b)),
+        I32Sub => binary!(as_i32, I32, |a, b| a.wrapping_sub(b)),
+        I32Mul => binary!(as_i32, I32, |a, b| a.wrapping_mul(b)),
+        I32And => binary!(as_i32, I32, |a, b| a & b),
+        I32Or => binary!(as_i32, I32, |a, b| a | b),
+        I32Xor => binary!(as_i32, I32, |a, b| a ^ b),
+        I32Shl => binary!(as_u32, I32, |a, b| a.wrapping_shl(b) as i32),
+        I32ShrS => binary!(as_i32, I32, |a, b| a.wrapping_shr(b as u32)),
+        I32ShrU => binary!(as_u32, I32, |a, b| a.wrapping_shr(b) as i32),
+        I32Rotl => binary!(as_u32, I32, |a, b| a.rotate_left(b) as i32),
+        I32Rotr => binary!(as_u32, I32, |a, b| a.rotate_right(b) as i32),
+
+        I64Eq => binary!(as_i64, I32, |a, b| (a == b) as i32),
+        I64Ne => binary!(as_i64, I32, |a, b| (a != b) as i32),
+        I64LtS => binary!(as_i64, I32, |a, b| (a < b) as i32),
+        I64LtU => binary!(as_u64, I32, |a, b| (a < b) as i32),
+        I64GtS => binary!(as_i64, I32, |a, b| (a > b) as i32),
+        I64GtU => binary!(as_u64, I32, |a, b| (a > b) as i32),
+        I64LeS => binary!(as_i64, I32, |a, b| (a <= b) as i32),
+        I64LeU => binary!(as_u64, I32, |a, b| (a <= b) as i32),
+        I64GeS => binary!(as_i64, I32, |a, b| (a >= b) as i32),
+        I64GeU => binary!(as_u64, I32, |a, b| (a >= b) as i32),
+        I64Add => binary!(as_i64, I64, |a, b| a.wrapping_add(b)),
+        I64Sub => binary!(as_i64, I64, |a, b| a.wrapping_sub(b)),
+        I64Mul => binary!(as_i64, I64, |a, b| a.wrapping_mul(b)),
+        I64And => binary!(as_i64, I64, |a, b| a & b),
+        I64Or => binary!(as_i64, I64, |a, b| a | b),
+        I64Xor => binary!(as_i64, I64, |a, b| a ^ b),
+        I64Shl => binary!(as_u64, I64, |a, b| a.wrapping_shl(b as u32) as i64),
+        I64ShrS => binary!(as_i64, I64, |a, b| a.wrapping_shr(b as u32)),
+        I64ShrU => binary!(as_u64, I64, |a, b| a.wrapping_shr(b as u32) as i64),
+        I64Rotl => binary!(as_u64, I64, |a, b| a.rotate_left(b as u32) as i64),
+        I64Rotr => binary!(as_u64, I64, |a, b| a.rotate_right(b as u32) as i64),
+
+        F32Eq => binary!(as_f32, I32, |a, b| (a == b) as i32),
+        F32Ne => binary!(as_f32, I32, |a, b| (a != b) as i32),
+        F32Lt => binary!(as_f32, I32, |a, b| (a < b) as i32),
+        F32Gt => binary!(as_f32, I32, |a, b| (a > b) as i32),
+        F32Le => binary!(as_f32, I32, |a, b| (a <= b) as i32),
+        F32Ge => binary!(as_f32, I32, |a, b| (a >= b) as i32),
+        F32Add => binary!(as_f32, F32, |a, b| a + b),
+        F32Sub => binary!(as_f32, F32, |a, b| a - b),
+        F32Mul => binary!(as_f32, F32, |a, b| a * b),
+        F32Div => binary!(as_f32, F32, |a, b| a / b),
+
+        F64Eq => binary!(as_f64, I32, |a, b| (a == b) as i32),
+        F64Ne => binary!(as_f64, I32, |a, b| (a != b) as i32),
+        F64Lt => binary!(as_f64, I32, |a, b| (a < b) as i32),
+        F64Gt => binary!(as_f64, I32, |a, b| (a > b) as i32),
+        F64Le => binary!(as_f64, I32, |a, b| (a <= b) as i32),
+        F64Ge => binary!(as_f64, I32, |a, b| (a >= b) as i32),
+        F64Add => binary!(as_f64, F64, |a, b| a + b),
+        F64Sub => binary!(as_f64, F64, |a, b| a - b),
+        F64Mul => binary!(as_f64, F64, |a, b| a * b),
+        F64Div => binary!(as_f64, F64, |a, b| a / b),
+        _ => return None,
+    };
+
+    Some(native)
+}
@@ -1,38 +1,18 @@
-use crate::binary::section::Expr;
+use std::rc::Rc;
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum LabelKind {
-    Call,
-    If,
-    Loop,
-    Block,
-}
+use crate::execution::bytecode::Bytecode;
+use crate::execution::store::Instance;
 
+/// 函数调用栈帧，块在编译时已经展开，不再占用栈帧
 #[derive(Debug)]
 pub struct Frame {
-    pub kind: LabelKind,
     pub pc: usize,
     pub sp: usize,
-    pub expr: *const Expr,
+    /// 正在执行的函数体，和函数实例共享
+    pub code: Rc<Bytecode>,
     pub arity: usize,   // 返回值数量
-    pub arg_num: usize, // 参数数量
-}
-
-impl Frame {
-    pub fn reset_pc(&mut self) {
-        self.pc = 0;
-    }
-
-    pub fn basic_info(&self) -> Self {
-        Self {
-            expr: std::ptr::null(),
-            pc: self.pc,
-            sp: self.sp,
-            arity: self.arity,
-            arg_num: self.arg_num,
-            kind: self.kind.clone(),
-        }
-    }
+    pub inst: Instance, // 函数所属的实例
+    pub func: usize,    // 函数在代码段中的索引
 }
 
 pub trait CallStack {
@@ -42,16 +22,4 @@ pub trait CallStack {
     fn top_frame(&self) -> &Frame;
     fn top_mut(&mut self) -> &mut Frame;
     fn get_frame(&self, n: usize) -> &Frame;
-
-    fn top_call(&self) -> (Option<&Frame>, i32) {
-        for n in (0..self.depth()).rev() {
-            let frame = self.get_frame(n);
-
-            if frame.kind == LabelKind::Call {
-                return (Some(frame), (self.depth() - 1 - n) as i32);
-            }
-        }
-
-        (None, -1)
-    }
 }
@@ -72,6 +72,19 @@ pub trait Operand {
         self.push_i32(v as i32);
     }
 
+    /// 内存、表的地址和长度，memory64、table64 为 i64，否则为 i32
+    fn pop_addr(&mut self) -> u64 {
+        self.pop().as_mem_addr()
+    }
+
+    /// 大小和 grow 的结果，-1 转成 u64 后再截断仍然是 -1
+    fn push_addr(&mut self, v: u64, is64: bool) {
+        match is64 {
+            true => self.push_u64(v),
+            false => self.push_u32(v as u32),
+        }
+    }
+
     fn pop_n(&mut self, n: usize) -> ValInsts {
         let mut vals: ValInsts = (0..n).map(|_| self.pop()).collect();
 
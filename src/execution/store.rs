@@ -0,0 +1,134 @@
+use std::rc::Rc;
+
+use super::inst::element::ElemInst;
+use super::inst::{ExportInst, ExportMap, RFuncInst, RGlobalInst, RMemInst, RTableInst, RTagInst};
+use super::random_str;
+use crate::binary::module::Module;
+use crate::binary::section::ExportDesc;
+
+/// 实例在 Store 中的句柄，只在创建它的 Store 中有效
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Instance(pub(crate) usize);
+
+/// 模块实例，记录模块各个索引空间对应的实例
+/// https://webassembly.github.io/spec/core/exec/runtime.html#module-instances
+#[derive(Debug)]
+pub struct ModuleInst {
+    pub name: String,
+    pub module: Rc<Module>,
+
+    pub funcs: Vec<RFuncInst>,
+    pub tables: Vec<RTableInst>,
+    pub mems: Vec<RMemInst>,
+    pub globals: Vec<RGlobalInst>,
+    pub tags: Vec<RTagInst>,
+
+    pub exports: ExportMap,
+    pub datas: Vec<Vec<u8>>,
+    pub elements: Vec<ElemInst>,
+}
+
+impl ModuleInst {
+    pub fn new(name: &str, module: Rc<Module>) -> Self {
+        Self {
+            name: name.to_string(),
+            module,
+            funcs: vec![],
+            tables: vec![],
+            mems: vec![],
+            globals: vec![],
+            tags: vec![],
+            exports: ExportMap::default(),
+            datas: vec![],
+            elements: vec![],
+        }
+    }
+
+    pub fn get_export(&self, name: &str) -> Option<ExportInst> {
+        self.exports.get(name).map(|export| match export.desc {
+            ExportDesc::Func(idx) => ExportInst::Func(Rc::clone(&self.funcs[idx as usize])),
+            ExportDesc::Table(idx) => ExportInst::Table(Rc::clone(&self.tables[idx as usize])),
+            ExportDesc::Mem(idx) => ExportInst::Mem(Rc::clone(&self.mems[idx as usize])),
+            ExportDesc::Global(idx) => ExportInst::Global(Rc::clone(&self.globals[idx as usize])),
+            ExportDesc::Tag(idx) => ExportInst::Tag(Rc::clone(&self.tags[idx as usize])),
+        })
+    }
+
+    pub fn get_func(&self, name: &str) -> Option<RFuncInst> {
+        match self.get_export(name) {
+            Some(ExportInst::Func(inst)) => Some(inst),
+            _ => None,
+        }
+    }
+
+    pub fn get_table(&self, name: &str) -> Option<RTableInst> {
+        match self.get_export(name) {
+            Some(ExportInst::Table(inst)) => Some(inst),
+            _ => None,
+        }
+    }
+
+    pub fn get_mem(&self, name: &str) -> Option<RMemInst> {
+        match self.get_export(name) {
+            Some(ExportInst::Mem(inst)) => Some(inst),
+            _ => None,
+        }
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<RGlobalInst> {
+        match self.get_export(name) {
+            Some(ExportInst::Global(inst)) => Some(inst),
+            _ => None,
+        }
+    }
+
+    pub fn get_tag(&self, name: &str) -> Option<RTagInst> {
+        match self.get_export(name) {
+            Some(ExportInst::Tag(inst)) => Some(inst),
+            _ => None,
+        }
+    }
+}
+
+/// 持有所有模块实例，同一个 Store 中的实例可以直接互相调用
+/// https://webassembly.github.io/spec/core/exec/runtime.html#store
+///
+/// 实例化失败的实例也会留下来，它的函数可能已经被写进了导入的表中
+#[derive(Debug)]
+pub struct Store {
+    id: String,
+    pub(crate) instances: Vec<ModuleInst>,
+}
+
+impl Store {
+    pub fn new(name: &str) -> Self {
+        Self {
+            id: name.to_string() + "-" + &random_str(10),
+            instances: vec![],
+        }
+    }
+
+    /// 本 Store 中创建的函数都以此作为来源
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn get(&self, instance: Instance) -> &ModuleInst {
+        &self.instances[instance.0]
+    }
+
+    pub fn get_mut(&mut self, instance: Instance) -> &mut ModuleInst {
+        &mut self.instances[instance.0]
+    }
+
+    /// 下一个实例的句柄，实例的函数在加入 Store 之前就要知道它
+    pub(crate) fn next_instance(&self) -> Instance {
+        Instance(self.instances.len())
+    }
+
+    pub(crate) fn alloc(&mut self, inst: ModuleInst) -> Instance {
+        self.instances.push(inst);
+
+        Instance(self.instances.len() - 1)
+    }
+}
@@ -0,0 +1,239 @@
+use std::error::Error;
+use std::io::{self, Stdout, Write};
+
+use serde_json::json;
+
+use super::bytecode::Opcode;
+use super::inst::function::{FuncInst, FuncInstKind};
+
+/// 执行过程的钩子，默认实现什么都不做
+///
+/// op 是编译后的 Op 或 RegOp，pc 是它在函数体中的下标，
+/// stack_height 是指令执行前或执行后操作数栈（含局部变量）的高度，frame_depth 是调用栈帧的层数
+pub trait Tracer: 'static {
+    #[inline(always)]
+    fn before_instr(&mut self, _op: &dyn Opcode, _pc: usize, _stack_height: usize, _frame_depth: usize) {
+    }
+
+    #[inline(always)]
+    fn after_instr(&mut self, _op: &dyn Opcode, _pc: usize, _stack_height: usize, _frame_depth: usize) {}
+
+    /// call_depth 为进入后的调用层数
+    #[inline(always)]
+    fn enter_func(&mut self, _func: &FuncInst, _call_depth: usize) {}
+
+    /// call_depth 为退出前的调用层数
+    #[inline(always)]
+    fn exit_func(&mut self, _call_depth: usize) {}
+
+    /// 增长失败时 old_size 为 -1
+    #[inline(always)]
+    fn mem_grow(&mut self, _mem_idx: u32, _delta: u64, _old_size: i64) {}
+
+    /// 外部调用以错误结束时触发，此时栈还没有被恢复
+    #[inline(always)]
+    fn trap(&mut self, _err: &(dyn Error + 'static)) {}
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopTracer;
+
+impl Tracer for NoopTracer {}
+
+fn func_name(func: &FuncInst) -> String {
+    match &func.kind {
+        FuncInstKind::Inner { idx, .. } => format!("func[{}]", idx),
+        // 导入方可能正处于调用中，这时取不到模块名
+        FuncInstKind::Outer(ctx, name) => match ctx.try_borrow() {
+            Ok(ctx) => format!("{}.{}", ctx.get_name(), name),
+            Err(_) => name.clone(),
+        },
+        FuncInstKind::Host(name, _) => name.clone(),
+    }
+}
+
+/// 每个事件输出一行文本，按调用层数缩进
+#[derive(Debug)]
+pub struct TextTracer<W: Write = Stdout> {
+    pub out: W,
+    call_depth: usize,
+}
+
+impl TextTracer {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write> TextTracer<W> {
+    pub fn new(out: W) -> Self {
+        Self { out, call_depth: 0 }
+    }
+
+    fn line(&mut self, text: impl AsRef<str>) {
+        let indent = "  ".repeat(self.call_depth);
+
+        // 跟踪信息写失败不应该影响执行
+        let _ = writeln!(self.out, "{}{}", indent, text.as_ref());
+    }
+}
+
+impl<W: Write + 'static> Tracer for TextTracer<W> {
+    fn before_instr(&mut self, op: &dyn Opcode, pc: usize, stack_height: usize, _frame_depth: usize) {
+        self.line(format!("{:>4} [{}] {:?}", pc, stack_height, op));
+    }
+
+    fn enter_func(&mut self, func: &FuncInst, call_depth: usize) {
+        self.line(format!("-> {} {:?}", func_name(func), func.get_type()));
+        self.call_depth = call_depth;
+    }
+
+    fn exit_func(&mut self, call_depth: usize) {
+        self.call_depth = call_depth.saturating_sub(1);
+        self.line("<-");
+    }
+
+    fn mem_grow(&mut self, mem_idx: u32, delta: u64, old_size: i64) {
+        self.line(format!("memory[{}].grow {} -> {}", mem_idx, delta, old_size));
+    }
+
+    fn trap(&mut self, err: &(dyn Error + 'static)) {
+        self.line(format!("trap：{}", err));
+        self.call_depth = 0;
+    }
+}
+
+/// 每个事件输出一个 json 对象，一行一个
+#[derive(Debug)]
+pub struct JsonTracer<W: Write = Stdout> {
+    pub out: W,
+}
+
+impl JsonTracer {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write> JsonTracer<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    fn emit(&mut self, value: serde_json::Value) {
+        let _ = writeln!(self.out, "{}", value);
+    }
+}
+
+impl<W: Write + 'static> Tracer for JsonTracer<W> {
+    fn before_instr(&mut self, op: &dyn Opcode, pc: usize, stack_height: usize, frame_depth: usize) {
+        self.emit(json!({
+            "event": "before_instr",
+            "op": format!("{:?}", op),
+            "opcode": op.opcode(),
+            "pc": pc,
+            "stack_height": stack_height,
+            "frame_depth": frame_depth,
+        }));
+    }
+
+    fn after_instr(&mut self, op: &dyn Opcode, pc: usize, stack_height: usize, frame_depth: usize) {
+        self.emit(json!({
+            "event": "after_instr",
+            "opcode": op.opcode(),
+            "pc": pc,
+            "stack_height": stack_height,
+            "frame_depth": frame_depth,
+        }));
+    }
+
+    fn enter_func(&mut self, func: &FuncInst, call_depth: usize) {
+        self.emit(json!({
+            "event": "enter_func",
+            "func": func_name(func),
+            "type": format!("{:?}", func.get_type()),
+            "call_depth": call_depth,
+        }));
+    }
+
+    fn exit_func(&mut self, call_depth: usize) {
+        self.emit(json!({ "event": "exit_func", "call_depth": call_depth }));
+    }
+
+    fn mem_grow(&mut self, mem_idx: u32, delta: u64, old_size: i64) {
+        self.emit(json!({
+            "event": "mem_grow",
+            "mem_idx": mem_idx,
+            "delta": delta,
+            "old_size": old_size,
+        }));
+    }
+
+    fn trap(&mut self, err: &(dyn Error + 'static)) {
+        self.emit(json!({ "event": "trap", "message": err.to_string() }));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{JsonTracer, TextTracer, Tracer};
+    use crate::binary::module::Module;
+    use crate::execution::config::Config;
+    use crate::execution::value::ValInst;
+    use crate::execution::vm::VM;
+
+    const ADD: &str = r#"
+        (module
+          (func (export "add") (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1))))
+    "#;
+
+    fn trace<T: Tracer>(tracer: T) -> T {
+        let module = Module::from_text(ADD).unwrap();
+        let mut vm = VM::with_tracer("test", module, None, Config::default(), tracer).unwrap();
+        let instance = vm.instance().unwrap();
+        let rets = vm.call_export(instance, "add", vec![ValInst::I32(1), ValInst::I32(2)]);
+
+        assert_eq!(rets.unwrap(), vec![ValInst::I32(3)]);
+
+        vm.tracer
+    }
+
+    #[test]
+    fn test_text_tracer() {
+        let out = String::from_utf8(trace(TextTracer::new(vec![])).out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+
+        // 两个参数已经在栈上，每条指令前的高度依次为 2、3、4
+        assert_eq!(
+            lines,
+            [
+                "-> func[0] FuncType { params: [I32, I32], results: [I32] }",
+                "     0 [2] LocalGet(0)",
+                "     1 [3] LocalGet(1)",
+                "     2 [4] I32Add",
+                "<-",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_tracer() {
+        let out = String::from_utf8(trace(JsonTracer::new(vec![])).out).unwrap();
+        let events = out
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        let heights = events
+            .iter()
+            .filter(|event| event["event"] == "after_instr")
+            .map(|event| event["stack_height"].as_u64().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 8);
+        assert_eq!(events[0]["event"], "enter_func");
+        assert_eq!(events[1]["frame_depth"], 1);
+        assert_eq!(heights, [3, 4, 3]);
+        assert_eq!(events[7]["event"], "exit_func");
+    }
+}
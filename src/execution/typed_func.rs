@@ -0,0 +1,120 @@
+use std::marker::PhantomData;
+
+use super::errors::{CallError, VMState};
+use super::host::WasmList;
+use super::inst::RFuncInst;
+use super::tracer::Tracer;
+use super::vm::VM;
+use crate::binary::types::FuncType;
+
+/// 签名已经检查过的函数，调用时直接传入和返回 Rust 的值
+///
+/// Params 和 Results 都以元组表示，只有一个值时可以直接写类型
+pub struct TypedFunc<Params, Results> {
+    func: RFuncInst,
+    _marker: PhantomData<fn(Params) -> Results>,
+}
+
+impl<Params, Results> Clone for TypedFunc<Params, Results> {
+    fn clone(&self) -> Self {
+        Self {
+            func: self.func.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<Params: WasmList, Results: WasmList> TypedFunc<Params, Results> {
+    /// 签名只在这里检查一次
+    pub fn new(func: RFuncInst) -> VMState<Self> {
+        let expected = FuncType {
+            params: Params::val_types(),
+            results: Results::val_types(),
+        };
+        let actual = func.borrow().get_type().clone();
+
+        if expected != actual {
+            Err(CallError::FuncTypeNotEq(expected, actual))?;
+        }
+
+        Ok(Self {
+            func,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn call<T: Tracer>(&self, vm: &mut VM<T>, params: Params) -> VMState<Results> {
+        let rets = vm.invoke(&self.func.borrow(), Some(params.into_vals()))?;
+
+        Ok(Results::from_vals(&rets))
+    }
+
+    pub fn func(&self) -> &RFuncInst {
+        &self.func
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::binary::module::Module;
+    use crate::binary::types::ValType;
+    use crate::execution::errors::CallError;
+    use crate::execution::value::ValInst;
+    use crate::execution::vm::VM;
+
+    fn vm() -> VM {
+        let text = r#"(module (func (export "add") (param i32 i64) (result i64)
+                         (i64.add (i64.extend_i32_s (local.get 0)) (local.get 1))))"#;
+
+        VM::new("test", Module::from_text(text).unwrap(), None).unwrap()
+    }
+
+    #[test]
+    fn test_typed_call() {
+        let mut vm = vm();
+        let instance = vm.instance().unwrap();
+        let add = vm.typed_func::<(i32, i64), i64>(instance, "add").unwrap();
+
+        assert_eq!(add.call(&mut vm, (-1, 10)).unwrap(), 9);
+    }
+
+    #[test]
+    fn test_type_errors() {
+        let mut vm = vm();
+        let instance = vm.instance().unwrap();
+
+        // 签名在取出函数时检查
+        let err = vm.typed_func::<(i32, i32), i64>(instance, "add").err().unwrap();
+
+        assert!(matches!(
+            err.downcast_ref::<CallError>(),
+            Some(CallError::FuncTypeNotEq(..))
+        ));
+
+        let err = vm.typed_func::<(i32, i64), i32>(instance, "add").err().unwrap();
+
+        assert!(matches!(
+            err.downcast_ref::<CallError>(),
+            Some(CallError::FuncTypeNotEq(..))
+        ));
+
+        // 不经过 TypedFunc 时，参数在调用时检查
+        let err = vm
+            .call_export(instance, "add", vec![ValInst::I32(1)])
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<CallError>(),
+            Some(CallError::CountNotEq(_, 2, 1))
+        ));
+
+        let err = vm
+            .call_export(instance, "add", vec![ValInst::I32(1), ValInst::I32(2)])
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<CallError>(),
+            Some(CallError::TypeNotEq(_, 1, ValType::I64, ValType::I32))
+        ));
+    }
+}
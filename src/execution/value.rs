@@ -1,12 +1,13 @@
 use core::{fmt, simd};
+use std::any::Any;
+use std::rc::Rc;
 use std::simd::u8x16;
 
 use super::errors::{Trap, VMState};
-use super::inst::RFuncInst;
+use super::inst::{RExnInst, RFuncInst};
 use crate::binary::instruction::Lane16;
 use crate::binary::module::Module;
-use crate::binary::section::MaybeU32;
-use crate::binary::types::ValType;
+use crate::binary::types::{RefType, ValType};
 
 pub trait ToV128 {
     fn v128(self) -> v128;
@@ -65,6 +66,46 @@ conversions! {
 // 目前（2.0）只能是函数引用
 pub type RefInst = RFuncInst;
 
+/// externref 指向的宿主对象，wasm 只能保存和传递它，不能访问其中的内容
+///
+/// 克隆只增加引用计数，放在表或全局变量中的对象会一直存活；两个引用指向同一个对象时才相等
+#[derive(Clone)]
+pub struct ExternRef(Rc<dyn Any>);
+
+impl ExternRef {
+    pub fn new<T: 'static>(value: T) -> Self {
+        Self(Rc::new(value))
+    }
+
+    pub fn is<T: 'static>(&self) -> bool {
+        self.0.is::<T>()
+    }
+
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+
+    pub fn downcast<T: 'static>(&self) -> Option<Rc<T>> {
+        Rc::clone(&self.0).downcast().ok()
+    }
+
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl PartialEq for ExternRef {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other)
+    }
+}
+
+impl fmt::Debug for ExternRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:p}", Rc::as_ptr(&self.0))
+    }
+}
+
 #[derive(Clone)]
 pub enum ValInst {
     I32(i32),
@@ -73,28 +114,19 @@ pub enum ValInst {
     F64(f64),
     V128(v128),
     FuncRef(Option<RefInst>),
-    ExternRef(MaybeU32),
+    ExternRef(Option<ExternRef>),
+    ExnRef(Option<RExnInst>),
     NullRef,
 }
 
 pub type ValInsts = Vec<ValInst>;
 
 impl ValInst {
-    pub fn new_ref_null(v: u8) -> Self {
-        let val_type = ValType::from(v);
-
-        match val_type {
-            ValType::FuncRef => Self::FuncRef(None),
-            ValType::ExternRef => Self::ExternRef(None),
-            _ => panic!("不是一个有效的引用：{:?}", val_type),
-        }
-    }
-
-    pub fn new_ref(val_type: ValType, ref_inst: Option<RefInst>, idx: MaybeU32) -> Self {
-        match val_type {
-            ValType::FuncRef => Self::new_func_ref(ref_inst.unwrap()),
-            ValType::ExternRef => Self::new_extern_ref(idx.unwrap()),
-            _ => panic!("不是一个有效的引用：{:?}", val_type),
+    /// 校验过的模块中只会是 funcref、externref 或 exnref，其他 heaptype 返回 InvalidRef
+    pub fn new_ref_null(v: u64) -> VMState<Self> {
+        match RefType::from_heap_type(v) {
+            Some(ref_type) => Ok(Self::from(&ref_type.as_val_type())),
+            None => Err(Trap::InvalidRef)?,
         }
     }
 
@@ -102,7 +134,7 @@ impl ValInst {
         Self::FuncRef(Some(ref_inst))
     }
 
-    pub fn new_extern_ref(ref_inst: u32) -> Self {
+    pub fn new_extern_ref(ref_inst: ExternRef) -> Self {
         Self::ExternRef(Some(ref_inst))
     }
 
@@ -115,6 +147,7 @@ impl ValInst {
             Self::V128(_) => ValType::V128,
             Self::FuncRef(_) => ValType::FuncRef,
             Self::ExternRef(_) => ValType::ExternRef,
+            Self::ExnRef(_) => ValType::ExnRef,
             Self::NullRef => ValType::NullRef,
         }
     }
@@ -180,6 +213,7 @@ impl ValInst {
             Self::V128(v) => !v.all_zero(),
             Self::FuncRef(v) => v.is_some(),
             Self::ExternRef(v) => v.is_some(),
+            Self::ExnRef(v) => v.is_some(),
             Self::NullRef => false,
         }
     }
@@ -198,9 +232,18 @@ impl ValInst {
         self.as_ref_inst()
     }
 
+    /// 空引用返回 None
+    pub fn as_extern_ref(&self) -> VMState<Option<&ExternRef>> {
+        match self {
+            ValInst::ExternRef(v) => Ok(v.as_ref()),
+            _ => Err(Trap::InvalidRef)?,
+        }
+    }
+
+    /// i32 的地址按无符号数处理
     pub fn as_mem_addr(&self) -> u64 {
         match self {
-            ValInst::I32(v) => *v as i64 as u64,
+            ValInst::I32(v) => *v as u32 as u64,
             ValInst::I64(v) => *v as u64,
             _ => panic!("无效的地址：{:?}", self),
         }
@@ -259,6 +302,7 @@ impl From<&ValType> for ValInst {
             ValType::V128 => Self::V128(v128(0, 0, 0, 0)),
             ValType::FuncRef => Self::FuncRef(None),
             ValType::ExternRef => Self::ExternRef(None),
+            ValType::ExnRef => Self::ExnRef(None),
             ValType::NullRef => Self::NullRef,
         }
     }
@@ -276,6 +320,8 @@ impl fmt::Debug for ValInst {
             Self::FuncRef(Some(v)) => write!(f, "FuncRef({:?})", v),
             Self::ExternRef(None) => write!(f, "Null ExternRef"),
             Self::ExternRef(Some(v)) => write!(f, "ExternRef({:?})", v),
+            Self::ExnRef(None) => write!(f, "Null ExnRef"),
+            Self::ExnRef(Some(v)) => write!(f, "ExnRef({:p})", Rc::as_ptr(v)),
             Self::NullRef => write!(f, "NullRef"),
         }
     }
@@ -297,14 +343,106 @@ impl PartialEq for ValInst {
             (Self::V128(a), Self::V128(b)) => a == b,
             (Self::FuncRef(a), Self::FuncRef(b)) => a == b,
             (Self::ExternRef(a), Self::ExternRef(b)) => a == b,
+            (Self::ExnRef(Some(a)), Self::ExnRef(Some(b))) => Rc::ptr_eq(a, b),
+            (Self::ExnRef(None), Self::ExnRef(None)) => true,
             (Self::NullRef, Self::NullRef) => true,
             _ => false,
         }
     }
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum LoadFrom<'a> {
     File(&'a str),
     Data(Vec<u8>),
+    Text(&'a str),
     Module(Module),
 }
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::{ExternRef, ValInst};
+    use crate::binary::module::Module;
+    use crate::execution::errors::Trap;
+    use crate::execution::vm::VM;
+
+    /// 被释放时把标记设为 true
+    struct Handle(Rc<Cell<bool>>);
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    #[test]
+    fn test_extern_ref_round_trip() {
+        let text = r#"
+            (module
+              (table $t 2 externref)
+              (global $g (export "g") (mut externref) (ref.null extern))
+              (func (export "store") (param i32 externref) (table.set $t (local.get 0) (local.get 1)))
+              (func (export "load") (param i32) (result externref) (table.get $t (local.get 0)))
+              (func (export "keep") (param externref) (global.set $g (local.get 0))))
+        "#;
+        let mut vm = VM::new("test", Module::from_text(text).unwrap(), None).unwrap();
+        let instance = vm.instance().unwrap();
+        let dropped = Rc::new(Cell::new(false));
+        let handle = ExternRef::new(Handle(Rc::clone(&dropped)));
+
+        vm.call_export(
+            instance,
+            "store",
+            vec![ValInst::I32(1), ValInst::new_extern_ref(handle.clone())],
+        )
+        .unwrap();
+        vm.call_export(instance, "keep", vec![ValInst::new_extern_ref(handle.clone())])
+            .unwrap();
+
+        let rets = vm.call_export(instance, "load", vec![ValInst::I32(1)]).unwrap();
+        let loaded = rets[0].as_extern_ref().unwrap().unwrap();
+
+        // 取回的是同一个对象
+        assert!(loaded.ptr_eq(&handle));
+        assert!(loaded.is::<Handle>());
+        assert!(!loaded.is::<u32>());
+        assert!(Rc::ptr_eq(&loaded.downcast_ref::<Handle>().unwrap().0, &dropped));
+        assert_ne!(*loaded, ExternRef::new(0u32));
+
+        drop(rets);
+        drop(handle);
+
+        // 宿主不再持有时，表和全局变量中的引用让对象继续存活
+        assert!(!dropped.get());
+
+        vm.call_export(instance, "store", vec![ValInst::I32(1), ValInst::ExternRef(None)])
+            .unwrap();
+
+        assert!(!dropped.get());
+
+        vm.call_export(instance, "keep", vec![ValInst::ExternRef(None)])
+            .unwrap();
+
+        assert!(dropped.get());
+    }
+
+    #[test]
+    fn test_new_ref_null() {
+        assert!(matches!(ValInst::new_ref_null(0x70), Ok(ValInst::FuncRef(None))));
+        assert!(matches!(
+            ValInst::new_ref_null(0x6f),
+            Ok(ValInst::ExternRef(None))
+        ));
+        assert!(matches!(ValInst::new_ref_null(0x69), Ok(ValInst::ExnRef(None))));
+
+        // 不是引用类型，或者只有低字节是引用类型，都不能当作空引用
+        for v in [0x7f, 0x170] {
+            let err = ValInst::new_ref_null(v).unwrap_err();
+
+            assert!(matches!(err.downcast_ref::<Trap>(), Some(Trap::InvalidRef)));
+        }
+    }
+}
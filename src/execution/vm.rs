@@ -1,73 +1,119 @@
 use std::cell::RefCell;
+use std::error::Error;
 use std::rc::Rc;
 
+use super::backtrace::WasmBacktrace;
+use super::bytecode::Opcode;
+use super::config::Config;
 use super::errors::{InstError, LinkError, Trap, VMState};
+use super::host::WasmList;
 use super::importer::{Importer, MImporter};
 use super::inst::element::ElemInst;
 use super::inst::function::FuncInst;
 use super::inst::global::GlobalInst;
 use super::inst::memory::{MemInst, Memory};
 use super::inst::table::TableInst;
-use super::inst::{ExportMap, RFuncInst, RGlobalInst, RMemInst, RTableInst};
-use super::random_str;
+use super::inst::tag::TagInst;
+use super::inst::{ExportInst, RFuncInst, RGlobalInst, RMemInst, RTableInst, RTagInst};
+use super::interrupt::InterruptHandle;
+use super::linker::{check_import, Linker};
+use super::module::Module;
 use super::stack::frame::{CallStack, Frame};
 use super::stack::operand::Operand;
+use super::store::{Instance, ModuleInst, Store};
+use super::tracer::{NoopTracer, Tracer};
+use super::typed_func::TypedFunc;
 use super::value::{LoadFrom, ValInst, ValInsts};
-use crate::binary::module::Module;
-use crate::binary::section::{DataMode, ElementMode, ExportDesc, ImportDesc, ImportSeg};
-
-#[derive(Debug, Default)]
-pub struct VM {
-    id: String,
+use crate::binary::module::Module as BinaryModule;
+use crate::binary::section::{DataMode, ElementMode, ImportSeg};
+use crate::binary::types::ValType;
+
+/// T 为执行跟踪器，默认不做任何事
+///
+/// 虚拟机持有一个 Store，可以在其中实例化多个模块，实例之间的调用共用同一个栈
+#[derive(Debug)]
+pub struct VM<T = NoopTracer> {
     name: String,
-    pub module: Rc<Module>,
+    pub store: Store,
+    /// 通过 VM::new 等构造函数创建的实例
+    instance: Option<Instance>,
+    /// 当前正在执行的实例
+    pub inst: Instance,
 
     pub operands: ValInsts,
     pub frames: Vec<Frame>,
 
-    pub funcs: Vec<RFuncInst>,
-    pub tables: Vec<RTableInst>,
-    pub mems: Vec<RMemInst>,
-    pub globals: Vec<RGlobalInst>,
+    pub local_idx: usize,
+    /// 访存指令使用的内存，执行时按 memarg 中的内存索引设置
+    pub mem_idx: usize,
+
+    pub config: Config,
+    pub call_depth: usize,
+
+    fuel: Option<u64>,
+    suspended: Vec<Suspended>,
+    /// 最近一次外部调用出错时的调用栈
+    backtrace: Option<WasmBacktrace>,
 
-    pub exports: ExportMap,
-    pub datas: Vec<Vec<u8>>,
-    pub elements: Vec<ElemInst>,
+    interrupt: InterruptHandle,
+    /// 中断句柄的 epoch 达到这个值时中断执行
+    epoch_deadline: u64,
 
+    pub tracer: T,
+}
+
+/// 外部调用开始前栈的状态，出错时据此恢复
+#[derive(Debug, Clone, Copy)]
+pub struct Snapshot {
+    pub depth: usize,
+    pub stack_size: usize,
     pub local_idx: usize,
-    pub mem_idx: usize,
+    pub call_depth: usize,
+    pub inst: Instance,
+}
+
+/// 因燃料耗尽而挂起的外部调用
+#[derive(Debug)]
+struct Suspended {
+    snapshot: Snapshot,
+    results: Vec<ValType>,
 }
 
 /// 构造函数
 impl VM {
-    pub fn new(name: &str, module: Module, maps: Option<MImporter>) -> VMState<Self> {
-        let mut vm = Self {
-            id: name.to_string() + "-" + &random_str(10),
-            name: name.to_string(),
-            module: Rc::new(module),
-            ..Default::default()
-        };
-
-        if let Some(maps) = maps {
-            if !maps.is_empty() {
-                vm.resolve_imports(maps)?;
-            }
-        }
+    pub fn new(name: &str, module: BinaryModule, maps: Option<MImporter>) -> VMState<Self> {
+        Self::with_config(name, module, maps, Config::default())
+    }
 
-        vm.init()?;
-        vm.call_start()?;
+    /// 实例化时就会执行 start 函数，所以限制要在创建时给出
+    pub fn with_config(
+        name: &str,
+        module: BinaryModule,
+        maps: Option<MImporter>,
+        config: Config,
+    ) -> VMState<Self> {
+        Self::with_tracer(name, module, maps, config, NoopTracer)
+    }
 
-        Ok(vm)
+    /// 不含任何实例，之后通过 instantiate 添加
+    pub fn empty(name: &str, config: Config) -> Self {
+        Self::empty_with_tracer(name, config, NoopTracer)
     }
 
     pub fn from_file(name: &str, path: &str, importers: Option<MImporter>) -> VMState<Self> {
-        let module = Module::from_file(path).expect("模块解析错误");
+        let module = BinaryModule::from_file(path)?;
 
         Self::new(name, module, importers)
     }
 
     pub fn from_data(name: &str, data: Vec<u8>, importers: Option<MImporter>) -> VMState<Self> {
-        let module = Module::from_data(data).expect("模块解析错误");
+        let module = BinaryModule::from_data(data)?;
+
+        Self::new(name, module, importers)
+    }
+
+    pub fn from_text(name: &str, text: &str, importers: Option<MImporter>) -> VMState<Self> {
+        let module = BinaryModule::from_text(text)?;
 
         Self::new(name, module, importers)
     }
@@ -76,14 +122,94 @@ impl VM {
         match kind {
             LoadFrom::Data(data) => Self::from_data(name, data, importers),
             LoadFrom::File(path) => Self::from_file(name, path, importers),
+            LoadFrom::Text(text) => Self::from_text(name, text, importers),
             LoadFrom::Module(module) => Self::new(name, module, importers),
         }
     }
 }
 
-impl Importer for VM {
+impl<T: Tracer> VM<T> {
+    /// start 函数的执行也会被跟踪
+    pub fn with_tracer(
+        name: &str,
+        module: BinaryModule,
+        maps: Option<MImporter>,
+        config: Config,
+        tracer: T,
+    ) -> VMState<Self> {
+        let mut vm = Self::empty_with_tracer(name, config, tracer);
+        let module = Module::new(module, vm.config.engine)?;
+        let mut linker = Linker::new();
+
+        for (name, importer) in maps.unwrap_or_default() {
+            linker.importer(&name, importer);
+        }
+
+        vm.instance = Some(linker.instantiate(&mut vm, name, &module)?);
+
+        Ok(vm)
+    }
+
+    pub fn empty_with_tracer(name: &str, config: Config, tracer: T) -> Self {
+        Self {
+            name: name.to_string(),
+            store: Store::new(name),
+            instance: None,
+            inst: Instance(0),
+            operands: vec![],
+            frames: vec![],
+            local_idx: 0,
+            mem_idx: 0,
+            fuel: config.fuel,
+            epoch_deadline: config.epoch_deadline,
+            config,
+            call_depth: 0,
+            suspended: vec![],
+            backtrace: None,
+            interrupt: InterruptHandle::default(),
+            tracer,
+        }
+    }
+
+    pub fn instance(&self) -> Option<Instance> {
+        self.instance
+    }
+
+    /// 当前正在执行的实例
+    #[inline(always)]
+    pub(crate) fn module_inst(&self) -> &ModuleInst {
+        &self.store.instances[self.inst.0]
+    }
+
+    #[inline(always)]
+    pub(crate) fn module_inst_mut(&mut self) -> &mut ModuleInst {
+        &mut self.store.instances[self.inst.0]
+    }
+
+    /// 按名字调用实例导出的函数
+    pub fn call_export(&mut self, instance: Instance, name: &str, args: ValInsts) -> VMState<ValInsts> {
+        let func_inst = self.store.get(instance).get_func(name).ok_or(Trap::FnNotFound)?;
+        let func_inst = func_inst.borrow();
+
+        self.invoke(&func_inst, Some(args))
+    }
+
+    /// 取出实例导出的函数并检查签名，之后可以直接用 Rust 的值调用
+    pub fn typed_func<Params: WasmList, Results: WasmList>(
+        &self,
+        instance: Instance,
+        name: &str,
+    ) -> VMState<TypedFunc<Params, Results>> {
+        let func_inst = self.store.get(instance).get_func(name).ok_or(Trap::FnNotFound)?;
+
+        TypedFunc::new(func_inst)
+    }
+}
+
+/// 以 VM::new 等构造函数创建的实例作为导入方
+impl<T: Tracer> Importer for VM<T> {
     fn get_id(&self) -> &str {
-        &self.id
+        self.store.get_id()
     }
 
     fn get_name(&self) -> &str {
@@ -91,47 +217,45 @@ impl Importer for VM {
     }
 
     fn resolve_func(&self, name: &str) -> Option<RFuncInst> {
-        self.exports.get(name).and_then(|export| match export.desc {
-            ExportDesc::Func(idx) => Some(Rc::clone(&self.funcs[idx as usize])),
-            _ => None,
-        })
+        self.instance
+            .and_then(|instance| self.store.get(instance).get_func(name))
+    }
+
+    fn resolve_func_by_idx(&self, idx: u32) -> Option<RFuncInst> {
+        self.instance
+            .and_then(|instance| self.store.get(instance).funcs.get(idx as usize).map(Rc::clone))
     }
 
     fn resolve_table(&self, name: &str) -> Option<RTableInst> {
-        self.exports.get(name).and_then(|export| match export.desc {
-            ExportDesc::Table(idx) => Some(Rc::clone(&self.tables[idx as usize])),
-            _ => None,
-        })
+        self.instance
+            .and_then(|instance| self.store.get(instance).get_table(name))
     }
 
     fn resolve_mem(&self, name: &str) -> Option<RMemInst> {
-        self.exports.get(name).and_then(|export| match export.desc {
-            ExportDesc::Mem(idx) => Some(Rc::clone(&self.mems[idx as usize])),
-            _ => None,
-        })
+        self.instance
+            .and_then(|instance| self.store.get(instance).get_mem(name))
     }
 
     fn resolve_global(&self, name: &str) -> Option<RGlobalInst> {
-        self.exports.get(name).and_then(|export| match export.desc {
-            ExportDesc::Global(idx) => Some(Rc::clone(&self.globals[idx as usize])),
-            _ => None,
-        })
+        self.instance
+            .and_then(|instance| self.store.get(instance).get_global(name))
     }
 
-    fn call_by_name(&mut self, name: &str, args: ValInsts) -> VMState<ValInsts> {
-        self.resolve_func(name).map_or_else(
-            || Err(Trap::FnNotFound)?,
-            move |func_inst| {
-                let func_inst = func_inst.borrow();
+    fn resolve_tag(&self, name: &str) -> Option<RTagInst> {
+        self.instance
+            .and_then(|instance| self.store.get(instance).get_tag(name))
+    }
 
-                self.invoke(&func_inst, Some(args))
-            },
-        )
+    fn call_by_name(&mut self, name: &str, args: ValInsts) -> VMState<ValInsts> {
+        match self.instance {
+            Some(instance) => self.call_export(instance, name, args),
+            None => Err(Trap::FnNotFound)?,
+        }
     }
 }
 
 /// 实现操作数栈
-impl Operand for VM {
+impl<T: Tracer> Operand for VM<T> {
     fn pop(&mut self) -> ValInst {
         self.operands.pop().expect("栈空")
     }
@@ -154,7 +278,7 @@ impl Operand for VM {
 }
 
 /// 实现调用栈
-impl CallStack for VM {
+impl<T: Tracer> CallStack for VM<T> {
     fn pop_frame(&mut self) -> Frame {
         self.frames.pop().expect("调用栈帧空")
     }
@@ -181,25 +305,27 @@ impl CallStack for VM {
 }
 
 /// 实现内存
-impl Memory for VM {
+impl<T: Tracer> Memory for VM<T> {
     fn mem_reads(&self, addr: u64, n: u64) -> VMState<Vec<u8>> {
-        self.mems[self.mem_idx].borrow().mem_reads(addr, n)
+        self.module_inst().mems[self.mem_idx].borrow().mem_reads(addr, n)
     }
 
     fn mem_writes(&mut self, addr: u64, bytes: &[u8]) -> VMState {
-        self.mems[self.mem_idx].borrow_mut().mem_writes(addr, bytes)
+        self.module_inst().mems[self.mem_idx]
+            .borrow_mut()
+            .mem_writes(addr, bytes)
     }
 
-    fn mem_size(&self) -> u32 {
-        self.mems[self.mem_idx].borrow().mem_size()
+    fn mem_size(&self) -> u64 {
+        self.module_inst().mems[self.mem_idx].borrow().mem_size()
     }
 
-    fn mem_grow(&mut self, size: u32) -> i32 {
-        self.mems[self.mem_idx].borrow_mut().mem_grow(size)
+    fn mem_grow(&mut self, size: u64) -> i64 {
+        self.module_inst().mems[self.mem_idx].borrow_mut().mem_grow(size)
     }
 }
 
-impl VM {
+impl<T: Tracer> VM<T> {
     fn reset(&mut self) {
         self.operands = vec![];
         self.frames = vec![];
@@ -208,196 +334,363 @@ impl VM {
     pub fn start_loop(&mut self) -> VMState {
         let depth = self.depth();
 
-        while self.depth() >= depth {
-            let frame = self.top_mut();
+        self.run_until(depth - 1)
+    }
+
+    /// 执行到调用栈只剩 depth 层为止
+    ///
+    /// 出错时如果是异常，先在这几层栈帧里找 catch 子句，找到了就从跳转目标继续执行
+    fn run_until(&mut self, depth: usize) -> VMState {
+        while self.depth() > depth {
+            if let Err(err) = self.run_frame(depth) {
+                self.catch(err, depth)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 连续执行栈顶函数的指令，直到函数返回或者栈顶换成了其他函数
+    ///
+    /// 函数体在这里取一次，不用每条指令都增减引用计数
+    #[inline(always)]
+    fn run_frame(&mut self, depth: usize) -> VMState {
+        let code = Rc::clone(&self.top_frame().code);
+        let frame_depth = self.depth();
 
-            match unsafe { frame.expr.as_ref() } {
-                Some(expr) if frame.pc == expr.len() => self.exit_block()?,
-                Some(expr) => {
-                    let instr = &expr[frame.pc];
+        while self.depth() == frame_depth && Rc::ptr_eq(&self.top_frame().code, &code) {
+            let pc = self.top_frame().pc;
 
-                    frame.pc += 1;
+            match &code.regs {
+                None if pc == code.ops.len() => self.exit_call()?,
+                None => self.step(&code.ops[pc], pc, Self::exec_op)?,
+                Some(regs) if pc == regs.ops.len() => {
+                    self.exit_call()?;
 
-                    self.exec_instr(instr)?;
+                    // 燃料耗尽后恢复执行时，调用方的 call 指令已经返回，要在这里重新占满寄存器
+                    if self.depth() > depth {
+                        self.fit_window();
+                    }
                 }
-                None => Err(Trap::NoOpcode)?,
+                Some(regs) => self.step(&regs.ops[pc], pc, Self::exec_reg_op)?,
             };
         }
 
         Ok(())
     }
 
-    // 执行入口函数
-    pub fn call_start(&mut self) -> VMState {
-        if let Some(idx) = self.module.start_sec {
-            let func_inst = Rc::clone(&self.funcs[idx as usize]);
+    #[inline(always)]
+    fn step<O: Opcode>(&mut self, op: &O, pc: usize, exec: fn(&mut Self, &O) -> VMState) -> VMState {
+        let frame_depth = self.depth();
+
+        // 先扣燃料再移动 pc，燃料耗尽时可以从这条指令继续执行
+        self.consume_fuel(op.opcode())?;
+        self.top_mut().pc += 1;
+        self.tracer.before_instr(op, pc, self.stack_size(), frame_depth);
+        exec(self, op)?;
+        self.tracer.after_instr(op, pc, self.stack_size(), frame_depth);
+
+        Ok(())
+    }
+}
 
-            {
-                let func_inst = func_inst.borrow();
+/// 燃料计量
+impl<T: Tracer> VM<T> {
+    pub fn set_fuel(&mut self, fuel: u64) {
+        self.fuel = Some(fuel);
+    }
+
+    pub fn add_fuel(&mut self, fuel: u64) {
+        self.fuel = Some(self.fuel.unwrap_or(0).saturating_add(fuel));
+    }
 
-                self.invoke(&func_inst, Some(vec![]))?;
+    /// None 表示没有开启燃料计量
+    pub fn fuel_remaining(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    fn consume_fuel(&mut self, opcode: u16) -> VMState {
+        if let Some(fuel) = self.fuel {
+            let cost = self.config.fuel_costs.cost(opcode);
+
+            if fuel < cost {
+                Err(Trap::OutOfFuel)?;
             }
+
+            self.fuel = Some(fuel - cost);
         }
 
         Ok(())
     }
-}
 
-/// 初始化的所有逻辑
-impl VM {
-    // 处理导入
-    fn resolve_imports(&mut self, importers: MImporter) -> VMState {
-        let module = Rc::clone(&self.module);
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            depth: self.depth(),
+            stack_size: self.stack_size(),
+            local_idx: self.local_idx,
+            call_depth: self.call_depth,
+            inst: self.inst,
+        }
+    }
 
-        for import in &module.import_sec {
-            match importers.get(&import.module) {
-                Some(importer) => self.resolve_import(import, Rc::clone(importer))?,
-                _ => Err(LinkError::ModuleNotFound(import.module.clone()))?,
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.frames.truncate(snapshot.depth);
+        self.operands.truncate(snapshot.stack_size);
+        self.local_idx = snapshot.local_idx;
+        self.call_depth = snapshot.call_depth;
+        self.inst = snapshot.inst;
+    }
+
+    /// 燃料耗尽的调用保留现场等待恢复，其他错误则将栈恢复到调用前
+    ///
+    /// 恢复之前先记下这次调用的栈帧，出错的地方不在 wasm 函数中时没有调用栈
+    pub(crate) fn suspend_or_restore(
+        &mut self,
+        err: &(dyn Error + 'static),
+        snapshot: Snapshot,
+        results: &[ValType],
+    ) {
+        let frames = &self.frames[snapshot.depth.min(self.frames.len())..];
+        let out_of_fuel = matches!(err.downcast_ref::<Trap>(), Some(Trap::OutOfFuel));
+
+        self.tracer.trap(err);
+        self.backtrace =
+            (!frames.is_empty()).then(|| WasmBacktrace::capture(&self.store, frames, !out_of_fuel));
+
+        match err.downcast_ref::<Trap>() {
+            Some(Trap::OutOfFuel) => self.suspended.push(Suspended {
+                snapshot,
+                results: results.to_vec(),
+            }),
+            // 中断已经送达，之后的调用重新开始计数
+            Some(Trap::Interrupted) => {
+                self.set_epoch_deadline(self.config.epoch_deadline);
+                self.restore(snapshot);
             }
+            _ => self.restore(snapshot),
         }
+    }
 
-        Ok(())
+    /// 补充燃料后，从中断的指令处继续执行最近一次挂起的调用，返回该调用的结果
+    pub fn resume(&mut self) -> VMState<ValInsts> {
+        let Suspended { snapshot, results } = self.suspended.pop().ok_or(Trap::NothingToResume)?;
+
+        self.backtrace = None;
+
+        // 挂起之后可能执行过其他调用或实例化，现场以栈顶的帧为准
+        let Frame { sp, inst, .. } = *self.top_frame();
+
+        self.local_idx = sp;
+        self.inst = inst;
+
+        match self.run_until(snapshot.depth) {
+            Ok(_) => self.pop_n_and_check_type(&results, "返回值"),
+            Err(err) => {
+                self.suspend_or_restore(err.as_ref(), snapshot, &results);
+
+                Err(err)
+            }
+        }
     }
 
-    fn resolve_import(&mut self, import: &ImportSeg, importer_: Rc<RefCell<dyn Importer>>) -> VMState {
-        let importer = importer_.borrow();
-        let module_name = importer.get_name().to_string();
-        let item_name = import.name.clone();
+    /// 是否有因燃料耗尽而挂起的调用
+    pub fn is_suspended(&self) -> bool {
+        !self.suspended.is_empty()
+    }
 
-        match &import.desc {
-            ImportDesc::Func(idx) => match importer.resolve_func(&import.name) {
-                Some(func_inst) => {
-                    let func_inst = func_inst.borrow();
-                    let sig = &self.module.type_sec[*idx as usize];
+    /// 最近一次外部调用以 trap 结束时的 wasm 调用栈，之后的调用成功时会被清除
+    pub fn backtrace(&self) -> Option<&WasmBacktrace> {
+        self.backtrace.as_ref()
+    }
 
-                    if func_inst.get_type() != sig {
-                        Err(LinkError::IncompatibleImportType)?;
-                    }
+    pub(crate) fn clear_backtrace(&mut self) {
+        self.backtrace = None;
+    }
+}
 
-                    {
-                        let func_inst = func_inst.as_outer(Rc::clone(&importer_), &import.name);
+/// 异步中断
+impl<T: Tracer> VM<T> {
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
 
-                        self.funcs.push(func_inst);
+    /// 多个虚拟机可以共用一个句柄，由同一个计时线程驱动
+    pub fn set_interrupt_handle(&mut self, handle: InterruptHandle) {
+        self.interrupt = handle;
+        self.set_epoch_deadline(self.config.epoch_deadline);
+    }
 
-                        Ok(())
-                    }
-                }
-                _ => Err(LinkError::ItemNotFound(module_name, item_name))?,
-            },
-            ImportDesc::Table(type_) => match importer.resolve_table(&import.name) {
-                Some(inst) => {
-                    if inst.borrow().get_type().incompatible(type_) {
-                        Err(LinkError::IncompatibleImportType)?;
-                    }
+    /// 从当前的 epoch 算起，句柄再前进 delta 次就中断执行
+    ///
+    /// 调用之前和调用之间发出的中断同样计数，不会被下一次调用忽略
+    pub fn set_epoch_deadline(&mut self, delta: u64) {
+        self.epoch_deadline = self.interrupt.epoch().saturating_add(delta);
+    }
 
-                    self.tables.push(inst);
+    pub(crate) fn check_interrupt(&self) -> VMState {
+        if self.interrupt.epoch() >= self.epoch_deadline {
+            Err(Trap::Interrupted)?;
+        }
 
-                    Ok(())
-                }
-                _ => Err(LinkError::ItemNotFound(module_name, item_name))?,
-            },
-            ImportDesc::Mem(type_) => match importer.resolve_mem(&import.name) {
-                Some(inst) => {
-                    if inst.borrow().get_type().incompatible(type_) {
-                        Err(LinkError::IncompatibleImportType)?;
-                    }
+        Ok(())
+    }
+}
 
-                    self.mems.push(inst);
+/// 实例化的所有逻辑
+/// https://webassembly.github.io/spec/core/exec/modules.html#instantiation
+impl<T: Tracer> VM<T> {
+    /// imports 按模块导入段的顺序给出
+    ///
+    /// 实例化失败时，已经写入导入的表和内存中的内容会保留下来
+    pub fn instantiate(
+        &mut self,
+        name: &str,
+        module: &Module,
+        imports: Vec<ExportInst>,
+    ) -> VMState<Instance> {
+        let binary = Rc::clone(module.binary());
+        let mut inst = ModuleInst::new(name, Rc::clone(&binary));
+
+        self.link(&mut inst, &binary.import_sec, imports)?;
+        self.init_funcs(&mut inst, module);
+
+        let instance = self.store.alloc(inst);
+        let snapshot = self.snapshot();
+
+        self.inst = instance;
+
+        // 初始化表达式出错时，要把栈恢复到实例化前的状态
+        if let Err(err) = self.init(&binary) {
+            self.restore(snapshot);
+
+            return Err(err);
+        }
 
-                    Ok(())
-                }
-                _ => Err(LinkError::ItemNotFound(module_name, item_name))?,
-            },
-            ImportDesc::Global(type_) => match importer.resolve_global(&import.name) {
-                Some(inst) => {
-                    if inst.borrow().get_type() != type_ {
-                        Err(LinkError::IncompatibleImportType)?;
-                    }
+        self.call_start()?;
 
-                    self.globals.push(inst);
+        Ok(instance)
+    }
 
-                    Ok(())
+    // 处理导入
+    fn link(&self, inst: &mut ModuleInst, imports: &[ImportSeg], externs: Vec<ExportInst>) -> VMState {
+        if imports.len() != externs.len() {
+            Err(LinkError::ImportCountNotEq(imports.len(), externs.len()))?;
+        }
+
+        for (import, extern_) in imports.iter().zip(externs) {
+            check_import(&import.desc, &inst.module.type_sec, &extern_).map_err(|reason| {
+                LinkError::IncompatibleImportType(import.module.clone(), import.name.clone(), reason)
+            })?;
+
+            match extern_ {
+                ExportInst::Func(func_inst) => {
+                    // 其他 Store 的函数要经过导入方才能调用
+                    if !func_inst.borrow().callable_in(self.store.get_id()) {
+                        Err(LinkError::ForeignFunc(import.module.clone(), import.name.clone()))?;
+                    }
+
+                    inst.funcs.push(func_inst);
                 }
-                _ => Err(LinkError::ItemNotFound(module_name, item_name))?,
-            },
+                ExportInst::Table(table) => inst.tables.push(table),
+                ExportInst::Mem(mem) => inst.mems.push(mem),
+                ExportInst::Global(global) => inst.globals.push(global),
+                ExportInst::Tag(tag) => inst.tags.push(tag),
+            }
         }
+
+        Ok(())
     }
 
-    fn init(&mut self) -> VMState {
-        let module = Rc::clone(&self.module);
+    // 初始化函数段，函数体在模块中已经编译好
+    fn init_funcs(&self, inst: &mut ModuleInst, module: &Module) {
+        let instance = self.store.next_instance();
+        let binary = module.binary();
 
-        self.init_funcs(module.as_ref());
-        self.init_table_and_elem(module.as_ref())?;
-        self.init_mem_and_data(module.as_ref())?;
-        self.init_global(module.as_ref())?;
+        for (i, ft_idx) in binary.func_sec.iter().enumerate() {
+            let ft = &binary.type_sec[*ft_idx as usize];
+            let code = Rc::clone(module.code(i));
+            let func_inst = FuncInst::from_wasm(ft.clone(), instance, i, code, self.store.get_id());
+
+            inst.funcs.push(Rc::new(RefCell::new(func_inst)));
+        }
+    }
+
+    fn init(&mut self, module: &BinaryModule) -> VMState {
+        self.init_tag(module);
+        self.init_table_and_elem(module)?;
+        self.init_mem_and_data(module)?;
+        self.init_global(module)?;
 
         for export in &module.export_sec {
-            self.exports.insert(export.name.clone(), export.clone());
+            self.module_inst_mut()
+                .exports
+                .insert(export.name.clone(), export.clone());
         }
 
         Ok(())
     }
 
+    // 每次实例化都创建新的标签，同一个模块的两个实例抛出的异常互不捕获
+    fn init_tag(&mut self, module: &BinaryModule) {
+        for tag in &module.tag_sec {
+            let tag_inst = TagInst::new(module.type_sec[tag.type_idx as usize].clone());
+
+            self.module_inst_mut().tags.push(Rc::new(tag_inst));
+        }
+    }
+
     // 初始化内存：定义了内存才能使用 data 段，下表、元素段同理
-    fn init_mem_and_data(&mut self, module: &Module) -> VMState {
+    fn init_mem_and_data(&mut self, module: &BinaryModule) -> VMState {
         for mem in &module.mem_sec {
             let mem_inst = MemInst::new(mem.clone());
 
-            self.mems.push(Rc::new(RefCell::new(mem_inst)));
+            self.module_inst_mut().mems.push(Rc::new(RefCell::new(mem_inst)));
         }
 
         // 初始化 data
         for (i, data) in module.data_sec.iter().enumerate() {
-            self.datas.push(data.init.to_vec());
+            self.module_inst_mut().datas.push(data.init.to_vec());
 
             if matches!(data.mode, DataMode::Active) {
                 // 其实只有一个指令
                 self.exec_instr(&data.offset_expr[0])?;
 
                 // 初始化完成后，此时栈顶就是内存起始地址
-                let addr = self.pop().as_mem_addr();
-                let mut mem = self.mems[data.mem_idx as usize].borrow_mut();
+                let addr = self.pop_addr();
+                let inst = self.module_inst_mut();
+                let mut mem = inst.mems[data.mem_idx as usize].borrow_mut();
 
-                mem.mem_writes(addr, &self.datas[i])?;
+                mem.mem_writes(addr, &inst.datas[i])?;
+                drop(mem);
 
-                self.datas[i].clear();
+                inst.datas[i].clear();
             }
         }
 
         Ok(())
     }
 
-    // 初始化函数段
-    fn init_funcs(&mut self, module: &Module) {
-        // 内部函数
-        for (i, ft_idx) in module.func_sec.iter().enumerate() {
-            let ft = &module.type_sec[*ft_idx as usize];
-            let code = &module.code_sec[i];
-            let func_inst = FuncInst::from_wasm(ft.clone(), i, Rc::new(code.clone()), self.get_name());
-
-            self.funcs.push(Rc::new(RefCell::new(func_inst)));
-        }
-    }
-
     // 初始化全局段
-    fn init_global(&mut self, module: &Module) -> VMState {
+    fn init_global(&mut self, module: &BinaryModule) -> VMState {
         for global in &module.global_sec {
             self.exec_instr(&global.init_expr[0])?;
 
             let global_inst = GlobalInst::new(global.type_.clone(), self.pop())?;
 
-            self.globals.push(Rc::new(RefCell::new(global_inst)));
+            self.module_inst_mut()
+                .globals
+                .push(Rc::new(RefCell::new(global_inst)));
         }
 
         Ok(())
     }
 
     // 初始化表
-    fn init_table_and_elem(&mut self, module: &Module) -> VMState {
+    fn init_table_and_elem(&mut self, module: &BinaryModule) -> VMState {
         for table_type in &module.table_sec {
             let table = TableInst::new(table_type.clone());
 
-            self.tables.push(Rc::new(RefCell::new(table)));
+            self.module_inst_mut().tables.push(Rc::new(RefCell::new(table)));
         }
 
         for elem in &module.elem_sec {
@@ -418,17 +711,16 @@ impl VM {
                     .func_idxs
                     .iter()
                     .map(|idx| {
-                        let func_inst = &self.funcs[*idx as usize];
-                        let ref_inst = Rc::clone(func_inst);
+                        let func_inst = &self.module_inst().funcs[*idx as usize];
 
-                        ValInst::new_ref(elem.type_, Some(ref_inst), Some(*idx))
+                        ValInst::new_func_ref(Rc::clone(func_inst))
                     })
                     .collect::<Vec<_>>(),
             };
 
             let elem_inst = ElemInst::new(elem.type_, refs);
 
-            self.elements.push(elem_inst);
+            self.module_inst_mut().elements.push(elem_inst);
         }
 
         // 初始化元素段
@@ -440,26 +732,139 @@ impl VM {
                 } => {
                     self.exec_instr(&offset[0])?;
 
-                    let offset = self.pop_u32() as usize;
-                    let elem_inst = &mut self.elements[i];
-                    let mut table = self.tables[*table_idx as usize].borrow_mut();
+                    let offset = self.pop_addr();
+                    let inst = self.module_inst_mut();
+                    let elem_inst = &mut inst.elements[i];
+                    let mut table = inst.tables[*table_idx as usize].borrow_mut();
 
                     // 为了区分实例化阶段和运行阶段的 access 错误
-                    if offset + elem_inst.refs.len() > (table.size() as usize) {
+                    if offset.saturating_add(elem_inst.refs.len() as u64) > table.size() {
                         Err(InstError::OutofBoundTable)?;
                     }
 
-                    for (i, ref_val) in elem_inst.refs.iter().enumerate() {
-                        table.set_elem((offset + i) as u32, ref_val.clone())?;
-                    }
+                    table.set_elems(offset, &elem_inst.refs)?;
 
                     elem_inst.drop_();
                 }
-                ElementMode::Declarative => self.elements[i].drop_(),
+                ElementMode::Declarative => self.module_inst_mut().elements[i].drop_(),
                 ElementMode::Passive => continue,
             };
         }
 
         Ok(())
     }
+
+    // 执行入口函数
+    fn call_start(&mut self) -> VMState {
+        if let Some(idx) = self.module_inst().module.start_sec {
+            let func_inst = Rc::clone(&self.module_inst().funcs[idx as usize]);
+            let func_inst = func_inst.borrow();
+
+            self.invoke(&func_inst, Some(vec![]))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::VM;
+    use crate::binary::module::Module;
+    use crate::execution::config::{Config, Engine};
+    use crate::execution::errors::Trap;
+    use crate::execution::value::ValInst;
+
+    /// 1 加到 n
+    const SUM: &str = r#"
+        (module
+          (func (export "spin") (loop $l (br $l)))
+          (func (export "sum") (param $n i32) (result i32) (local $acc i32)
+            (loop $l
+              (local.set $acc (i32.add (local.get $acc) (local.get $n)))
+              (br_if $l (local.tee $n (i32.sub (local.get $n) (i32.const 1)))))
+            (local.get $acc)))
+    "#;
+
+    fn vm(config: Config) -> VM {
+        VM::with_config("test", Module::from_text(SUM).unwrap(), None, config).unwrap()
+    }
+
+    #[test]
+    fn test_fuel_resume() {
+        for engine in [Engine::Stack, Engine::Register] {
+            let mut vm = vm(Config {
+                fuel: Some(20),
+                engine,
+                ..Config::default()
+            });
+            let instance = vm.instance().unwrap();
+            let err = vm
+                .call_export(instance, "sum", vec![ValInst::I32(100)])
+                .unwrap_err();
+
+            assert!(matches!(err.downcast_ref::<Trap>(), Some(Trap::OutOfFuel)));
+            assert!(vm.is_suspended());
+            assert_eq!(vm.fuel_remaining(), Some(0));
+
+            // 燃料不够时可以多次恢复，每次都从停下的指令继续
+            let rets = loop {
+                vm.add_fuel(100);
+
+                match vm.resume() {
+                    Ok(rets) => break rets,
+                    Err(err) => assert!(matches!(err.downcast_ref::<Trap>(), Some(Trap::OutOfFuel))),
+                }
+            };
+
+            assert_eq!(rets, vec![ValInst::I32(5050)]);
+            assert!(!vm.is_suspended());
+            assert!(matches!(
+                vm.resume().unwrap_err().downcast_ref::<Trap>(),
+                Some(Trap::NothingToResume)
+            ));
+
+            vm.set_fuel(1000);
+
+            assert_eq!(
+                vm.call_export(instance, "sum", vec![ValInst::I32(3)]).unwrap(),
+                vec![ValInst::I32(6)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_interrupt() {
+        for engine in [Engine::Stack, Engine::Register] {
+            let mut vm = vm(Config {
+                engine,
+                ..Config::default()
+            });
+            let instance = vm.instance().unwrap();
+            let handle = vm.interrupt_handle();
+
+            // 调用之前发出的中断由下一次调用响应
+            handle.interrupt();
+
+            let err = vm
+                .call_export(instance, "sum", vec![ValInst::I32(3)])
+                .unwrap_err();
+
+            assert!(matches!(err.downcast_ref::<Trap>(), Some(Trap::Interrupted)));
+            assert_eq!(
+                vm.call_export(instance, "sum", vec![ValInst::I32(3)]).unwrap(),
+                vec![ValInst::I32(6)]
+            );
+
+            let timer = std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                handle.interrupt();
+            });
+            let err = vm.call_export(instance, "spin", vec![]).unwrap_err();
+
+            timer.join().unwrap();
+
+            assert!(matches!(err.downcast_ref::<Trap>(), Some(Trap::Interrupted)));
+        }
+    }
 }
@@ -1,9 +1,9 @@
 #![feature(associated_type_defaults)]
-#![feature(cursor_remaining)]
 #![allow(dead_code)]
 #![feature(portable_simd)]
-#![feature(round_ties_even)]
-#![feature(trace_macros)]
 
 pub mod binary;
 pub mod execution;
+pub mod spec;
+pub mod text;
+pub mod wasi;
@@ -1 +1,389 @@
-fn main() {}
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::ops::ControlFlow;
+use std::process::ExitCode;
+use std::rc::Rc;
+
+use wasm::binary;
+use wasm::binary::encode::Encode;
+use wasm::binary::errors::{DecodeErr, DecodeError, ValidateErr};
+use wasm::binary::stream::StreamDecoder;
+use wasm::binary::types::ValType;
+use wasm::execution::backtrace::WasmBacktrace;
+use wasm::execution::config::{Config, Engine};
+use wasm::execution::errors::{CallError, Exception, InstError, LinkError, Trap};
+use wasm::execution::linker::Linker;
+use wasm::execution::module::Module;
+use wasm::execution::store::Instance;
+use wasm::execution::value::{v128, ValInst, ValInsts};
+use wasm::execution::vm::VM;
+use wasm::text::errors::ParseErr;
+use wasm::wasi::{self, Wasi, WasiCtx, WasiError};
+
+const USAGE: &str = "用法：wasm run [选项] <文件> [参数...]
+      wasm inspect <文件>
+
+运行 .wasm 或 .wat 文件。不指定 --invoke 时以 WASI 命令的方式执行 _start，参数传给程序；
+指定时按导出函数的签名解析参数，并逐行打印返回值。
+inspect 逐段列出模块的内容以及它们在文件中的位置，.wat 文件按编码后的二进制格式显示。
+
+选项：
+    --invoke <函数名>        调用指定的导出函数
+    --preload <名字=文件>    预先实例化一个模块，它的导出可以按 名字 导入，可以重复
+    --validate-only         只解码和校验，不实例化
+    --max-steps <次数>      最多执行多少条指令，超出后以 trap 结束
+    --engine <stack|register>
+                            解释器类型，默认 stack
+    --dir <宿主目录[::名字]>  把目录预打开给 WASI 程序，可以重复
+    --env <键=值>           设置 WASI 环境变量，可以重复
+
+退出码：
+    0 成功，1 参数错误，2 解码失败，3 校验失败，4 链接失败，5 trap
+    WASI 程序调用 proc_exit 时以它给出的值退出";
+
+/// 按错误的来源区分退出码
+const EXIT_USAGE: u8 = 1;
+const EXIT_DECODE: u8 = 2;
+const EXIT_VALIDATE: u8 = 3;
+const EXIT_LINK: u8 = 4;
+const EXIT_TRAP: u8 = 5;
+
+#[derive(Default)]
+struct Options {
+    inspect: bool,
+    file: String,
+    args: Vec<String>,
+    invoke: Option<String>,
+    preloads: Vec<(String, String)>,
+    validate_only: bool,
+    max_steps: Option<u64>,
+    engine: Engine,
+    dirs: Vec<(String, String)>,
+    envs: Vec<(String, String)>,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_options(&args) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{}\n\n{}", msg, USAGE);
+
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let mut backtrace = None;
+
+    match run(&options, &mut backtrace) {
+        Ok(code) => ExitCode::from(code),
+        Err(err) => {
+            if let Some(code) = wasi::exit_code(err.as_ref()) {
+                return ExitCode::from(code as u8);
+            }
+
+            let (kind, code) = classify(err.as_ref());
+
+            eprintln!("{}：{}", kind, err);
+
+            if let Some(backtrace) = backtrace {
+                eprintln!("{}", backtrace);
+            }
+
+            ExitCode::from(code)
+        }
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+    let mut file = None;
+
+    match args.next().map(String::as_str) {
+        Some("run") => {}
+        Some("inspect") => options.inspect = true,
+        Some("-h" | "--help") | None => return Err("wasm 命令行".to_string()),
+        Some(cmd) => return Err(format!("未知的命令：{}", cmd)),
+    }
+
+    let mut rest = false;
+
+    while let Some(arg) = args.next() {
+        if rest || !arg.starts_with("--") {
+            match file {
+                None => file = Some(arg.clone()),
+                Some(_) => options.args.push(arg.clone()),
+            }
+
+            continue;
+        }
+
+        match arg.as_str() {
+            // 之后的参数都不再当作选项
+            "--" => rest = true,
+            "--invoke" => options.invoke = Some(next_value(&mut args, arg)?),
+            "--preload" => options.preloads.push(split_pair(&next_value(&mut args, arg)?)?),
+            "--validate-only" => options.validate_only = true,
+            "--max-steps" => {
+                let steps = next_value(&mut args, arg)?;
+
+                options.max_steps = Some(steps.parse().map_err(|_| format!("无效的步数：{}", steps))?);
+            }
+            "--engine" => {
+                options.engine = match next_value(&mut args, arg)?.as_str() {
+                    "stack" => Engine::Stack,
+                    "register" => Engine::Register,
+                    engine => Err(format!("未知的解释器：{}", engine))?,
+                }
+            }
+            "--dir" => {
+                let dir = next_value(&mut args, arg)?;
+
+                options.dirs.push(match dir.split_once("::") {
+                    Some((host, guest)) => (host.to_string(), guest.to_string()),
+                    None => (dir.clone(), dir),
+                });
+            }
+            "--env" => options.envs.push(split_pair(&next_value(&mut args, arg)?)?),
+            "--help" => return Err("wasm 命令行".to_string()),
+            _ => Err(format!("未知的选项：{}", arg))?,
+        }
+    }
+
+    options.file = file.ok_or("缺少要运行的文件")?;
+
+    Ok(options)
+}
+
+fn next_value<'a>(args: &mut impl Iterator<Item = &'a String>, name: &str) -> Result<String, String> {
+    args.next().cloned().ok_or(format!("{} 缺少参数", name))
+}
+
+fn split_pair(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((k, v)) if !k.is_empty() => Ok((k.to_string(), v.to_string())),
+        _ => Err(format!("无效的参数：{}，应为 名字=值", s)),
+    }
+}
+
+/// 返回正常结束时的退出码，以 trap 结束时带回 wasm 调用栈
+fn run(options: &Options, backtrace: &mut Option<WasmBacktrace>) -> Result<u8, Box<dyn Error>> {
+    if options.inspect {
+        return inspect(&options.file);
+    }
+
+    if options.validate_only {
+        validate(&options.file, options.engine)?;
+
+        for (_, file) in &options.preloads {
+            validate(file, options.engine)?;
+        }
+
+        return Ok(0);
+    }
+
+    let module = load(&options.file, options.engine)?;
+
+    let config = Config {
+        fuel: options.max_steps,
+        engine: options.engine,
+        ..Config::default()
+    };
+    let mut vm = VM::empty("main", config);
+    let ret = execute(&mut vm, &module, options);
+
+    *backtrace = vm.backtrace().cloned();
+
+    ret
+}
+
+/// 链接并实例化模块，再调用指定的函数或 _start
+fn execute(vm: &mut VM, module: &Module, options: &Options) -> Result<u8, Box<dyn Error>> {
+    let mut linker = Linker::new();
+    let mut ctx = WasiCtx::new();
+
+    ctx.args(&[std::slice::from_ref(&options.file), &options.args[..]].concat());
+
+    for (key, value) in &options.envs {
+        ctx.env(key, value);
+    }
+
+    for (host, guest) in &options.dirs {
+        ctx.preopen_dir(host, guest)?;
+    }
+
+    linker.importer(wasi::MODULE, Rc::new(RefCell::new(Wasi::new(ctx))));
+
+    for (name, file) in &options.preloads {
+        let instance = linker.instantiate(vm, name, &load(file, options.engine)?)?;
+
+        linker.instance(vm, name, instance);
+    }
+
+    let instance = linker.instantiate(vm, "main", module)?;
+
+    match &options.invoke {
+        Some(name) => invoke(vm, instance, name, &options.args),
+        None => match vm.store.get(instance).get_func("_start") {
+            Some(_) => vm.call_export(instance, "_start", vec![]).map(|_| 0),
+            None => Err("模块没有导出 _start，需要用 --invoke 指定要调用的函数")?,
+        },
+    }
+}
+
+/// .wat 按文本格式解析，其他都按二进制格式解码
+fn load(file: &str, engine: Engine) -> Result<Module, Box<dyn Error>> {
+    Module::new(decode(file)?, engine)
+}
+
+fn decode(file: &str) -> Result<binary::module::Module, Box<dyn Error>> {
+    let data = fs::read(file).map_err(|err| format!("无法读取 {}：{}", file, err))?;
+    let module = match file.ends_with(".wat") {
+        true => binary::module::Module::from_text(&String::from_utf8(data)?)?,
+        false => binary::module::Module::from_data(data)?,
+    };
+
+    Ok(module)
+}
+
+/// 二进制文件边读边校验，不用把整个模块放进内存
+fn validate(file: &str, engine: Engine) -> Result<(), Box<dyn Error>> {
+    if file.ends_with(".wat") {
+        return load(file, engine).map(|_| ());
+    }
+
+    let src = File::open(file).map_err(|err| format!("无法读取 {}：{}", file, err))?;
+
+    StreamDecoder::with_validation(BufReader::new(src)).decode(|_| ControlFlow::Continue(()))
+}
+
+/// 位置只在解码时记录，文本格式先编码再解码一次
+fn inspect(file: &str) -> Result<u8, Box<dyn Error>> {
+    let mut module = decode(file)?;
+
+    if file.ends_with(".wat") {
+        module = binary::module::Module::from_data(module.encode())?;
+    }
+
+    print!("{}", module.dump());
+
+    Ok(0)
+}
+
+fn invoke(vm: &mut VM, instance: Instance, name: &str, args: &[String]) -> Result<u8, Box<dyn Error>> {
+    let func = vm
+        .store
+        .get(instance)
+        .get_func(name)
+        .ok_or(format!("找不到导出函数：{}", name))?;
+    let func_type = func.borrow().get_type().clone();
+
+    if args.len() != func_type.params.len() {
+        Err(format!(
+            "{} 需要 {} 个参数 {:?}，实际传入了 {} 个",
+            name,
+            func_type.params.len(),
+            func_type.params,
+            args.len()
+        ))?;
+    }
+
+    let args = func_type
+        .params
+        .iter()
+        .zip(args)
+        .map(|(val_type, arg)| parse_val(val_type, arg))
+        .collect::<Result<ValInsts, _>>()?;
+
+    for ret in vm.call_export(instance, name, args)? {
+        println!("{}", format_val(&ret));
+    }
+
+    Ok(0)
+}
+
+/// 整数可以是有符号或无符号的十进制，也可以是 0x 开头的十六进制；引用只能是 null
+fn parse_val(val_type: &ValType, arg: &str) -> Result<ValInst, String> {
+    let invalid = || format!("无效的 {:?} 参数：{}", val_type, arg);
+    let hex = arg.strip_prefix("0x");
+    let val = match val_type {
+        ValType::I32 => ValInst::I32(
+            match hex {
+                Some(hex) => u32::from_str_radix(hex, 16).map(|v| v as i32).ok(),
+                None => arg
+                    .parse::<i32>()
+                    .ok()
+                    .or(arg.parse::<u32>().ok().map(|v| v as i32)),
+            }
+            .ok_or_else(invalid)?,
+        ),
+        ValType::I64 => ValInst::I64(
+            match hex {
+                Some(hex) => u64::from_str_radix(hex, 16).map(|v| v as i64).ok(),
+                None => arg
+                    .parse::<i64>()
+                    .ok()
+                    .or(arg.parse::<u64>().ok().map(|v| v as i64)),
+            }
+            .ok_or_else(invalid)?,
+        ),
+        ValType::F32 => ValInst::F32(arg.parse().map_err(|_| invalid())?),
+        ValType::F64 => ValInst::F64(arg.parse().map_err(|_| invalid())?),
+        ValType::V128 => {
+            let v = match hex {
+                Some(hex) => u128::from_str_radix(hex, 16),
+                None => arg.parse::<u128>(),
+            };
+
+            ValInst::V128(v128::new(v.map_err(|_| invalid())?.to_le_bytes()))
+        }
+        ValType::FuncRef | ValType::ExternRef | ValType::ExnRef if arg == "null" => {
+            ValInst::from(val_type)
+        }
+        _ => Err(invalid())?,
+    };
+
+    Ok(val)
+}
+
+fn format_val(val: &ValInst) -> String {
+    match val {
+        ValInst::I32(v) => format!("{}: i32", v),
+        ValInst::I64(v) => format!("{}: i64", v),
+        ValInst::F32(v) => format!("{:?}: f32", v),
+        ValInst::F64(v) => format!("{:?}: f64", v),
+        // 从低位到高位依次是 v.0 到 v.3
+        ValInst::V128(v) => format!("0x{:08x}{:08x}{:08x}{:08x}: v128", v.3, v.2, v.1, v.0),
+        ValInst::FuncRef(None) | ValInst::ExternRef(None) | ValInst::ExnRef(None) | ValInst::NullRef => {
+            format!("null: {:?}", val.get_type())
+        }
+        ValInst::FuncRef(Some(_)) => "funcref".to_string(),
+        ValInst::ExternRef(Some(_)) => "externref".to_string(),
+        ValInst::ExnRef(Some(_)) => "exnref".to_string(),
+    }
+}
+
+/// 错误的种类和对应的退出码
+fn classify(err: &(dyn Error + 'static)) -> (&'static str, u8) {
+    if err.is::<DecodeError>() || err.is::<DecodeErr>() || err.is::<ParseErr>() {
+        ("解码失败", EXIT_DECODE)
+    } else if err.is::<ValidateErr>() {
+        ("校验失败", EXIT_VALIDATE)
+    } else if err.is::<LinkError>() {
+        ("链接失败", EXIT_LINK)
+    } else if let Some(Trap::OutOfFuel) = err.downcast_ref::<Trap>() {
+        ("超出步数上限", EXIT_TRAP)
+    } else if err.is::<Trap>()
+        || err.is::<InstError>()
+        || err.is::<CallError>()
+        || err.is::<WasiError>()
+        || err.is::<Exception>()
+    {
+        ("trap", EXIT_TRAP)
+    } else {
+        ("错误", EXIT_USAGE)
+    }
+}
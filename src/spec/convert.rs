@@ -0,0 +1,98 @@
+use std::rc::Rc;
+use std::simd::{f32x4, f64x2, u16x8, u32x4, u64x2, u8x16};
+use std::str::FromStr;
+
+use super::errors::SpecError;
+use super::models::{Const, LaneType, Simd};
+use super::SpecResult;
+use crate::execution::store::ModuleInst;
+use crate::execution::value::{v128, ExternRef, ToV128, ValInst};
+
+impl Const {
+    /// funcref 常量里的索引指向 inst 的函数空间
+    pub fn as_value(&self, inst: Option<&ModuleInst>) -> SpecResult<ValInst> {
+        let value = match self {
+            Const::I32(s) => ValInst::from(parse::<u32>("i32", s)?),
+            Const::I64(s) => ValInst::from(parse::<u64>("i64", s)?),
+            Const::F32(s) => ValInst::from(str_to_f32(s)?),
+            Const::F64(s) => ValInst::from(str_to_f64(s)?),
+            // (ref.extern n) 以数字 n 作为宿主对象
+            Const::Externref(s) => ValInst::ExternRef(s.parse::<u32>().ok().map(ExternRef::new)),
+            Const::Funcref(s) => match s.parse::<u32>() {
+                Ok(idx) => {
+                    let func_inst = inst.and_then(|inst| inst.funcs.get(idx as usize).map(Rc::clone));
+
+                    ValInst::new_func_ref(func_inst.ok_or(SpecError::FuncRefNotFound(idx))?)
+                }
+                Err(_) => ValInst::FuncRef(None),
+            },
+            Const::Exnref(_) => ValInst::ExnRef(None),
+            Const::V128(simd) => ValInst::from(v128::try_from(simd)?),
+        };
+
+        Ok(value)
+    }
+
+    pub fn as_values(consts: &[Const], inst: Option<&ModuleInst>) -> SpecResult<Vec<ValInst>> {
+        consts.iter().map(|item| item.as_value(inst)).collect()
+    }
+}
+
+impl TryFrom<&Simd> for v128 {
+    type Error = SpecError;
+
+    fn try_from(simd: &Simd) -> Result<Self, Self::Error> {
+        let values = &simd.value;
+
+        let v = match simd.lane_type {
+            LaneType::I8 => u8x16::from_slice(&parse_lanes::<u8>("i8", values)?).v128(),
+            LaneType::I16 => u16x8::from_slice(&parse_lanes::<u16>("i16", values)?).v128(),
+            LaneType::I32 => u32x4::from_slice(&parse_lanes::<u32>("i32", values)?).v128(),
+            LaneType::I64 => u64x2::from_slice(&parse_lanes::<u64>("i64", values)?).v128(),
+            LaneType::F32 => {
+                let lanes = values
+                    .iter()
+                    .map(|s| str_to_f32(s))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                f32x4::from_slice(&lanes).v128()
+            }
+            LaneType::F64 => {
+                let lanes = values
+                    .iter()
+                    .map(|s| str_to_f64(s))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                f64x2::from_slice(&lanes).v128()
+            }
+        };
+
+        Ok(v)
+    }
+}
+
+fn parse<T: FromStr>(kind: &'static str, s: &str) -> Result<T, SpecError> {
+    s.parse::<T>()
+        .map_err(|_| SpecError::InvalidConst(kind, s.to_string()))
+}
+
+fn parse_lanes<T: FromStr>(kind: &'static str, values: &[String]) -> Result<Vec<T>, SpecError> {
+    values.iter().map(|s| parse::<T>(kind, s)).collect()
+}
+
+/// 整数形式给出的是位模式，nan:canonical 和 nan:arithmetic 只用于比较返回值
+fn str_to_f32(s: &str) -> Result<f32, SpecError> {
+    match s {
+        "nan:canonical" => Ok(f32::from_bits(0x7fc0_0000)),
+        "nan:arithmetic" => Ok(f32::NAN),
+        _ => parse::<u32>("f32", s).map(f32::from_bits),
+    }
+}
+
+fn str_to_f64(s: &str) -> Result<f64, SpecError> {
+    match s {
+        "nan:canonical" => Ok(f64::from_bits(0x7ff8_0000_0000_0000)),
+        "nan:arithmetic" => Ok(f64::NAN),
+        _ => parse::<u64>("f64", s).map(f64::from_bits),
+    }
+}
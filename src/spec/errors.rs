@@ -0,0 +1,23 @@
+#[derive(thiserror::Error, Debug)]
+pub enum SpecError {
+    #[error("无效的 {0} 常量：{1}")]
+    InvalidConst(&'static str, String),
+
+    #[error("找不到索引为 {0} 的函数")]
+    FuncRefNotFound(u32),
+
+    #[error("找不到模块：{0}")]
+    ModuleNotFound(String),
+
+    #[error("找不到全局变量：{0}")]
+    GlobalNotFound(String),
+
+    #[error("第 {0} 行：不支持的命令 {1}")]
+    UnknownCommand(usize, String),
+
+    #[error("第 {0} 行：意外的 {1}")]
+    UnexpectedToken(usize, String),
+
+    #[error("脚本意外结束")]
+    UnexpectedEof,
+}
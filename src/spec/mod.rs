@@ -0,0 +1,10 @@
+mod convert;
+mod errors;
+pub mod models;
+pub mod runner;
+pub mod spectest;
+pub mod wast;
+
+use std::error::Error;
+
+pub type SpecResult<T> = Result<T, Box<dyn Error>>;
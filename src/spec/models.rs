@@ -0,0 +1,179 @@
+use serde::Deserialize;
+
+/// wast2json 输出的 json 文件
+/// https://github.com/WebAssembly/wabt/blob/main/docs/wast2json.md
+#[derive(Debug, Deserialize)]
+pub struct WabtJson {
+    pub source_filename: String,
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Command {
+    #[serde(flatten)]
+    pub type_: CommandType,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommandKind {
+    Module,
+    Action,
+    Register,
+    AssertReturn,
+    AssertExhaustion,
+    AssertTrap,
+    AssertException,
+    AssertInvalid,
+    AssertMalformed,
+    AssertUninstantiable,
+    AssertUnlinkable,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum CommandType {
+    Module(Module),
+    Action { action: Action },
+    AssertReturn(AssertReturn),
+    AssertExhaustion(AssertExhaustion),
+    AssertTrap(AssertTrap),
+    AssertException(AssertException),
+    AssertInvalid(AssertModule),
+    AssertMalformed(AssertModule),
+    AssertUninstantiable(AssertModule),
+    AssertUnlinkable(AssertModule),
+    Register(Register),
+}
+
+impl CommandType {
+    pub fn kind(&self) -> CommandKind {
+        match self {
+            Self::Module(_) => CommandKind::Module,
+            Self::Action { .. } => CommandKind::Action,
+            Self::AssertReturn(_) => CommandKind::AssertReturn,
+            Self::AssertExhaustion(_) => CommandKind::AssertExhaustion,
+            Self::AssertTrap(_) => CommandKind::AssertTrap,
+            Self::AssertException(_) => CommandKind::AssertException,
+            Self::AssertInvalid(_) => CommandKind::AssertInvalid,
+            Self::AssertMalformed(_) => CommandKind::AssertMalformed,
+            Self::AssertUninstantiable(_) => CommandKind::AssertUninstantiable,
+            Self::AssertUnlinkable(_) => CommandKind::AssertUnlinkable,
+            Self::Register(_) => CommandKind::Register,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Module {
+    pub name: Option<String>,
+    pub filename: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Get(GetAction),
+    Invoke(InvokeAction),
+}
+
+impl Action {
+    pub fn module(&self) -> &Option<String> {
+        match self {
+            Action::Get(action) => &action.module,
+            Action::Invoke(action) => &action.module,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InvokeAction {
+    pub module: Option<String>,
+    pub field: String,
+    pub args: Vec<Const>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetAction {
+    pub module: Option<String>,
+    pub field: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "lowercase")]
+#[serde(tag = "type", content = "value")]
+pub enum Const {
+    I32(String),
+    I64(String),
+    F32(String),
+    F64(String),
+    Externref(String),
+    Funcref(String),
+    Exnref(String),
+    #[serde(untagged)]
+    V128(Simd),
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Simd {
+    pub lane_type: LaneType,
+    pub value: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum LaneType {
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssertReturn {
+    pub action: Action,
+    pub expected: Vec<Const>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssertExhaustion {
+    pub action: Action,
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssertTrap {
+    pub action: Action,
+    pub text: String,
+}
+
+/// 调用以没有被捕获的异常结束
+#[derive(Debug, Deserialize)]
+pub struct AssertException {
+    pub action: Action,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssertModule {
+    pub filename: String,
+    pub text: String,
+    pub module_type: ModuleType,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ModuleType {
+    Binary,
+    Text,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Register {
+    pub name: Option<String>,
+    #[serde(rename = "as")]
+    pub as_: String,
+}
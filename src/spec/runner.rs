@@ -0,0 +1,678 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::errors::SpecError;
+use super::models::{
+    Action, AssertException, AssertExhaustion, AssertModule, AssertReturn, AssertTrap, Command,
+    CommandKind, CommandType, Const, LaneType, Module, ModuleType, Register, WabtJson,
+};
+use super::spectest::SpecTestModule;
+use super::wast::Wast;
+use super::SpecResult;
+use crate::binary::encode::Encode;
+use crate::binary::errors::{DecodeErr, DecodeError, ValidateErr};
+use crate::binary::validate::Validate;
+use crate::execution::config::Config;
+use crate::execution::errors::{Exception, InstError, LinkError, Trap};
+use crate::execution::importer::Importer;
+use crate::execution::linker::Linker;
+use crate::execution::store::Instance;
+use crate::execution::value::{ValInst, ValInsts};
+use crate::execution::vm::VM;
+use crate::text::errors::ParseErr;
+use crate::{binary, execution};
+
+/// 没有指定模块名时，命令作用于最近一次定义的模块
+const LATEST_NAME: &str = "latest";
+
+/// 单条命令的执行结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Pass,
+    Fail(String),
+    Skip(String),
+    /// 错误的种类符合预期，但对应的文本和期望不一致
+    Mismatch(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandResult {
+    pub line: usize,
+    pub kind: CommandKind,
+    pub outcome: Outcome,
+}
+
+impl CommandResult {
+    pub fn is_fail(&self) -> bool {
+        matches!(self.outcome, Outcome::Fail(_))
+    }
+}
+
+/// 执行 wast2json 生成的命令文件
+///
+/// 所有模块都实例化在同一个虚拟机中，register 之后才能被其他模块导入
+pub struct Runner {
+    root: PathBuf,
+    /// 直接读取 .wast 时内联的模块，优先于 root 下的文件
+    files: HashMap<String, Vec<u8>>,
+    vm: VM,
+    instances: HashMap<String, Instance>,
+    linker: Linker,
+}
+
+impl Runner {
+    /// root 为命令文件中 .wasm/.wat 文件所在的目录
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self::with_config(root, Config::default())
+    }
+
+    pub fn with_config<P: AsRef<Path>>(root: P, config: Config) -> Self {
+        let mut runner = Self {
+            root: root.as_ref().to_path_buf(),
+            files: HashMap::new(),
+            vm: VM::empty("spec", config),
+            instances: HashMap::new(),
+            linker: Linker::new(),
+        };
+        let spec_test_module = SpecTestModule::new();
+
+        spec_test_module.define(&mut runner.linker, "sys");
+        spec_test_module.define(&mut runner.linker, "spectest");
+
+        runner
+    }
+
+    /// 读取并执行一个 json 命令文件
+    pub fn run_file<P: AsRef<Path>>(path: P) -> SpecResult<Vec<CommandResult>> {
+        Self::run_file_with_config(path, Config::default())
+    }
+
+    pub fn run_file_with_config<P: AsRef<Path>>(
+        path: P,
+        config: Config,
+    ) -> SpecResult<Vec<CommandResult>> {
+        let path = path.as_ref();
+        let wabt_json: WabtJson = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let mut runner = Self::with_config(path.parent().unwrap_or(Path::new(".")), config);
+
+        Ok(runner.run(&wabt_json))
+    }
+
+    /// 不经过 wast2json，直接读取并执行 .wast 脚本
+    pub fn run_wast_file_with_config<P: AsRef<Path>>(
+        path: P,
+        config: Config,
+    ) -> SpecResult<Vec<CommandResult>> {
+        let path = path.as_ref();
+        let filename = path.file_name().unwrap_or_default().to_string_lossy();
+        let wast = Wast::parse(&filename, &fs::read_to_string(path)?)?;
+        let mut runner = Self::with_config(path.parent().unwrap_or(Path::new(".")), config);
+
+        runner.files = wast.files;
+
+        Ok(runner.run(&wast.json))
+    }
+
+    /// 注册一个可供后续模块导入的宿主模块
+    pub fn register(&mut self, name: &str, importer: Rc<RefCell<dyn Importer>>) {
+        self.linker.importer(name, importer);
+    }
+
+    /// 在 linker 中定义更多宿主函数等导入项
+    pub fn linker_mut(&mut self) -> &mut Linker {
+        &mut self.linker
+    }
+
+    /// 所有模块都实例化在这个虚拟机中
+    pub fn vm_mut(&mut self) -> &mut VM {
+        &mut self.vm
+    }
+
+    pub fn run(&mut self, wabt_json: &WabtJson) -> Vec<CommandResult> {
+        wabt_json
+            .commands
+            .iter()
+            .map(|command| CommandResult {
+                line: command.line,
+                kind: command.type_.kind(),
+                outcome: self.run_command(command),
+            })
+            .collect()
+    }
+
+    pub fn run_command(&mut self, command: &Command) -> Outcome {
+        let ret = match &command.type_ {
+            CommandType::Module(module) => self.module(module),
+            CommandType::Action { action } => self.action(action).map(|_| Outcome::Pass),
+            CommandType::Register(register) => self.register_module(register),
+            CommandType::AssertReturn(assert) => self.assert_return(assert),
+            CommandType::AssertExhaustion(assert) => self.assert_exhaustion(assert),
+            CommandType::AssertTrap(assert) => self.assert_trap(assert),
+            CommandType::AssertException(assert) => self.assert_exception(assert),
+            CommandType::AssertInvalid(assert) => self.assert_invalid(assert),
+            CommandType::AssertMalformed(assert) => self.assert_malformed(assert),
+            CommandType::AssertUninstantiable(assert) | CommandType::AssertUnlinkable(assert) => {
+                self.assert_instantiate(assert)
+            }
+        };
+
+        ret.unwrap_or_else(|err| Outcome::Fail(err.to_string()))
+    }
+}
+
+/// 模块与动作
+impl Runner {
+    fn module(&mut self, module: &Module) -> SpecResult<Outcome> {
+        let module_type = match module.filename.ends_with(".wat") {
+            true => ModuleType::Text,
+            false => ModuleType::Binary,
+        };
+        let decoded = self.load(&module.filename, &module_type)?;
+        let encoded = decoded.encode();
+
+        // 顺便检查编码前后是否一致
+        if binary::module::Module::from_data(encoded.clone())?.encode() != encoded {
+            return Ok(Outcome::Fail("模块编码前后不一致".to_string()));
+        }
+
+        let name = module.name.clone().unwrap_or(LATEST_NAME.to_string());
+        let instance = self.instantiate(&name, decoded)?;
+
+        self.instances.insert(LATEST_NAME.to_string(), instance);
+        self.instances.insert(name, instance);
+
+        Ok(Outcome::Pass)
+    }
+
+    fn instantiate(&mut self, name: &str, module: binary::module::Module) -> SpecResult<Instance> {
+        let module = execution::module::Module::new(module, self.vm.config.engine)?;
+
+        self.linker.instantiate(&mut self.vm, name, &module)
+    }
+
+    fn register_module(&mut self, register: &Register) -> SpecResult<Outcome> {
+        let instance = self.instance(&register.name)?;
+
+        self.linker.instance(&self.vm, &register.as_, instance);
+
+        Ok(Outcome::Pass)
+    }
+
+    /// 已经实例化的模块，没有给出名字时为最近一次定义的模块
+    pub fn instance(&self, name: &Option<String>) -> SpecResult<Instance> {
+        let name = name.as_deref().unwrap_or(LATEST_NAME);
+
+        match self.instances.get(name) {
+            Some(instance) => Ok(*instance),
+            None => Err(SpecError::ModuleNotFound(name.to_string()))?,
+        }
+    }
+
+    fn action(&mut self, action: &Action) -> SpecResult<ValInsts> {
+        match action {
+            Action::Get(action) => {
+                let instance = self.instance(&action.module)?;
+
+                match self.vm.store.get(instance).get_global(&action.field) {
+                    Some(global) => Ok(vec![global.borrow().value()]),
+                    None => Err(SpecError::GlobalNotFound(action.field.clone()))?,
+                }
+            }
+            Action::Invoke(action) => {
+                let instance = self.instance(&action.module)?;
+                let args = Const::as_values(&action.args, Some(self.vm.store.get(instance)))?;
+
+                self.vm.call_export(instance, &action.field, args)
+            }
+        }
+    }
+
+    fn load_assert_module(&self, assert: &AssertModule) -> SpecResult<binary::module::Module> {
+        self.load(&assert.filename, &assert.module_type)
+    }
+
+    fn load(&self, filename: &str, module_type: &ModuleType) -> SpecResult<binary::module::Module> {
+        let path = self.root.join(filename);
+
+        match (self.files.get(filename), module_type) {
+            (Some(data), ModuleType::Binary) => Ok(binary::module::Module::from_data(data.clone())?),
+            (None, ModuleType::Binary) => Ok(binary::module::Module::from_file(
+                path.to_str().unwrap_or_default(),
+            )?),
+            (Some(data), ModuleType::Text) => {
+                binary::module::Module::from_text(&String::from_utf8(data.clone())?)
+            }
+            (None, ModuleType::Text) => binary::module::Module::from_text(&fs::read_to_string(path)?),
+        }
+    }
+}
+
+/// 断言
+impl Runner {
+    fn assert_return(&mut self, assert: &AssertReturn) -> SpecResult<Outcome> {
+        let rets = self.action(&assert.action)?;
+
+        if rets.len() != assert.expected.len() {
+            return Ok(Outcome::Fail(format!(
+                "返回值个数不匹配：{:?}，期望 {:?}",
+                rets, assert.expected
+            )));
+        }
+
+        for (ret, expected) in rets.iter().zip(&assert.expected) {
+            let matched = match expected {
+                // 返回值里的 (ref.func) 表示任意非空的函数引用，wast2json 会填上索引 0
+                Const::Funcref(s) if s != "null" => matches!(ret, ValInst::FuncRef(Some(_))),
+                _ => match_value(ret, expected, &expected.as_value(None)?),
+            };
+
+            if !matched {
+                return Ok(Outcome::Fail(format!("返回 {:?}，期望 {:?}", ret, expected)));
+            }
+        }
+
+        Ok(Outcome::Pass)
+    }
+
+    fn assert_trap(&mut self, assert: &AssertTrap) -> SpecResult<Outcome> {
+        Ok(match self.action(&assert.action) {
+            Ok(rets) => Outcome::Fail(format!("期望 trap：{}，实际返回 {:?}", assert.text, rets)),
+            Err(err) => match_text(err.as_ref(), &assert.text),
+        })
+    }
+
+    fn assert_exception(&mut self, assert: &AssertException) -> SpecResult<Outcome> {
+        Ok(match self.action(&assert.action) {
+            Ok(rets) => Outcome::Fail(format!("期望抛出异常，实际返回 {:?}", rets)),
+            Err(err) if err.is::<Exception>() => Outcome::Pass,
+            Err(err) => Outcome::Fail(format!("期望抛出异常，实际为 {:?}", trap_text(err.as_ref()))),
+        })
+    }
+
+    fn assert_exhaustion(&mut self, assert: &AssertExhaustion) -> SpecResult<Outcome> {
+        Ok(match self.action(&assert.action) {
+            Ok(rets) => Outcome::Fail(format!("期望调用栈溢出，实际返回 {:?}", rets)),
+            Err(err) => match_text(err.as_ref(), &assert.text),
+        })
+    }
+
+    /// 只有校验错误才算通过，解析失败的模块不算
+    fn assert_invalid(&self, assert: &AssertModule) -> SpecResult<Outcome> {
+        let ret = self
+            .load_assert_module(assert)
+            .and_then(|module| Ok(module.validate()?));
+
+        Ok(match ret {
+            Ok(_) => Outcome::Fail(format!("期望校验失败：{}", assert.text)),
+            Err(err) => match err.downcast_ref::<ValidateErr>() {
+                Some(err) => match_message(&invalid_text(err), &assert.text),
+                // wast2json 不会为用到数据段的文本模块补上 DataCount 段，解码时就会出错
+                None if matches!(malformed_text(err.as_ref()), Some("data count section required")) => {
+                    Outcome::Mismatch(format!("期望 {:?}，实际为 {}", assert.text, err))
+                }
+                None => Outcome::Fail(format!("期望校验失败：{}，实际为 {}", assert.text, err)),
+            },
+        })
+    }
+
+    /// 只有解码或文本解析错误才算通过
+    fn assert_malformed(&self, assert: &AssertModule) -> SpecResult<Outcome> {
+        Ok(match self.load_assert_module(assert) {
+            Ok(_) => Outcome::Fail(format!("期望解析失败：{}", assert.text)),
+            Err(err) => match malformed_text(err.as_ref()) {
+                Some(actual) => match_message(actual, &assert.text),
+                None => Outcome::Fail(format!("期望解析失败：{}，实际为 {}", assert.text, err)),
+            },
+        })
+    }
+
+    /// 实例化失败的模块不会被注册，但对导入项的修改会保留下来
+    fn assert_instantiate(&mut self, assert: &AssertModule) -> SpecResult<Outcome> {
+        let module = self.load_assert_module(assert)?;
+
+        Ok(match self.instantiate(LATEST_NAME, module) {
+            Ok(_) => Outcome::Fail(format!("期望实例化失败：{}", assert.text)),
+            Err(err) => match_text(err.as_ref(), &assert.text),
+        })
+    }
+}
+
+/// 错误转成的文本需要和期望完全一致
+fn match_text(err: &(dyn Error + 'static), text: &str) -> Outcome {
+    let actual = trap_text(err);
+
+    match actual == text {
+        true => Outcome::Pass,
+        false => Outcome::Fail(format!("期望 {:?}，实际为 {:?}", text, actual)),
+    }
+}
+
+/// 将错误转成规范测试中使用的文本
+pub fn trap_text(err: &(dyn Error + 'static)) -> String {
+    let text = if let Some(trap) = err.downcast_ref::<Trap>() {
+        match trap {
+            Trap::Unreachable => "unreachable",
+            Trap::FnNotFound => "unknown function",
+            Trap::NoOpcode => "missing instruction",
+            Trap::GlobalVarConst => "immutable global",
+            Trap::FnNoBody => "function without body",
+            Trap::CallFrameNotFount => "call frame not found",
+            Trap::ArgNotEq => "indirect call type mismatch",
+            Trap::ValTypeNotEq => "type mismatch",
+            Trap::GlobalTypeNotEq => "global type mismatch",
+            Trap::UnInitTableElem => "uninitialized element",
+            Trap::UndefinedElem => "undefined element",
+            Trap::InvalidRef => "invalid reference",
+            Trap::IntegerOverflow => "integer overflow",
+            Trap::DivZero => "integer divide by zero",
+            Trap::InvalidConversionToInteger => "invalid conversion to integer",
+            Trap::CallStackExhausted => "call stack exhausted",
+            Trap::OutOfFuel => "out of fuel",
+            Trap::NothingToResume => "nothing to resume",
+            Trap::Interrupted => "interrupted",
+            Trap::ForeignFunc => "foreign function",
+            Trap::ImporterBusy => "importer busy",
+            Trap::NullExnRef => "null exception reference",
+            Trap::UnalignedAtomic => "unaligned atomic",
+            Trap::ExpectedSharedMemory => "expected shared memory",
+        }
+    } else if let Some(err) = err.downcast_ref::<InstError>() {
+        match err {
+            InstError::OutofBoundMem => "out of bounds memory access",
+            InstError::OutofBoundTable => "out of bounds table access",
+            InstError::Unreachable => "unreachable",
+        }
+    } else if let Some(err) = err.downcast_ref::<LinkError>() {
+        match err {
+            LinkError::ModuleNotFound(_) => "unknown import",
+            LinkError::ItemNotFound(..) => "unknown import",
+            LinkError::ExportNotFound(..) => "unknown import",
+            LinkError::IncompatibleImportType(..) => "incompatible import type",
+            LinkError::ForeignFunc(..) => "incompatible import type",
+            LinkError::ImportCountNotEq(..) => "import count mismatch",
+        }
+    } else {
+        return err.to_string();
+    };
+
+    text.to_string()
+}
+
+/// 和参考解释器一样，错误文本以期望的文本开头即可
+fn match_message(actual: &str, text: &str) -> Outcome {
+    match actual.starts_with(text) {
+        true => Outcome::Pass,
+        false => Outcome::Mismatch(format!("期望 {:?}，实际为 {:?}", text, actual)),
+    }
+}
+
+/// 将校验错误转成规范测试中使用的文本，未知的索引和参考解释器一样带上索引
+fn invalid_text(err: &ValidateErr) -> String {
+    use ValidateErr::*;
+
+    let text = match err {
+        FnTypeNotFound(idx) | UnknownType(_, _, idx) => return format!("unknown type {}", idx),
+        MemNotFound(idx) | UnknownMemory(_, _, idx) => return format!("unknown memory {}", idx),
+        TableNotFound(idx) | UnknownTable(_, _, idx) => return format!("unknown table {}", idx),
+        FnNotFound(idx) | UnknownFunc(_, _, idx) => return format!("unknown function {}", idx),
+        GlobalVarNotFound(idx) | UnknownGlobal(_, _, idx) => return format!("unknown global {}", idx),
+        TagNotFound(idx) | UnknownTag(_, _, idx) => return format!("unknown tag {}", idx),
+        GlobalVarNotConst(_) | InitNotConst(_) => "constant expression required",
+        StartFnNoParam(_) | StartFnNoResult(_) => "start function",
+        DuplicateExport(_) => "duplicate export name",
+        MaxLtMin(..) => "size minimum must not be greater than maximum",
+        MaxTooLarge(..) | MinTooLarge(..) => "memory size must be at most 65536 pages (4GiB)",
+        SharedWithoutMax => "shared memory must have maximum",
+        TagHasResults(_) => "non-empty tag result type",
+        InvalidResultArity(..) => "invalid result arity",
+        UnknownLabel(_, _, idx) => return format!("unknown label {}", idx),
+        UnknownLocal(_, _, idx) => return format!("unknown local {}", idx),
+        UnknownElem(_, _, idx) => return format!("unknown elem segment {}", idx),
+        UnknownData(_, _, idx) => return format!("unknown data segment {}", idx),
+        ImmutableGlobal(..) => "global is immutable",
+        AlignTooLarge(..) => "alignment must not be larger than natural",
+        AtomicAlignMismatch(..) => "alignment must be equal to natural",
+        OffsetTooLarge(..) => "offset out of range",
+        InvalidLaneIdx(..) => "invalid lane index",
+        UndeclaredFuncRef(..) => "undeclared function reference",
+        DataCountRequired(..) => "data count section required",
+        OffsetRetNotEq(..)
+        | InitExprLen(_)
+        | ExprRetNotEq(..)
+        | ElemTypeNotEq(..)
+        | InvalidRefType(..)
+        | InvalidHeapType(..)
+        | OperandTypeMismatch(..)
+        | ElseWithoutIf(..)
+        | UnmatchedEnd(..)
+        | OperandStackEmpty(..)
+        | OperandStackRemain(..)
+        | ExpectRefType(..)
+        | SelectRefType(..)
+        | BrTableArityMismatch(..)
+        | TableTypeMismatch(..)
+        | CatchLabelMismatch(..)
+        | ReturnCallResultMismatch(..) => "type mismatch",
+    };
+
+    text.to_string()
+}
+
+/// 解码和文本解析错误对应的文本，其他错误返回 None
+fn malformed_text(err: &(dyn Error + 'static)) -> Option<&'static str> {
+    if let Some(err) = err.downcast_ref::<DecodeError>() {
+        decode_text(&err.kind)
+    } else if let Some(err) = err.downcast_ref::<DecodeErr>() {
+        decode_text(err)
+    } else {
+        err.downcast_ref::<ParseErr>().map(parse_text)
+    }
+}
+
+/// 读文件失败不是模块本身的问题
+fn decode_text(err: &DecodeErr) -> Option<&'static str> {
+    use DecodeErr::*;
+
+    let text = match err {
+        FileRead(_) => return None,
+        UnexpectedEnd | LEBUnexpectedEnd => "unexpected end",
+        ExprUnexpectedEnd | InvalidBlock | InvalidElseBlock => "unexpected end of section or function",
+        InvalidUtf8 => "malformed UTF-8 encoding",
+        InvalidValType(_) | InvalidBlockType(_) | InvalidType(_) => "malformed value type",
+        LEBDecodeTooLong => "integer representation too long",
+        IntTooLarge => "integer too large",
+        MagicUnMatch(_) => "magic header not detected",
+        VersionUnMatch(_) => "unknown binary version",
+        UnexpectedSection(_) => "malformed section id",
+        InvalidMut(_) => "malformed mutability",
+        UnknownOpcodePrefix(_) | UnknownOpcode(..) => "illegal opcode",
+        InvalidTableElemType(_) | TableElemNotARef => "malformed reference type",
+        InvalidImportKind(_) => "malformed import kind",
+        InvalidExportKind(_) => "malformed export kind",
+        InvalidElemMode(_) => "malformed elements segment kind",
+        InvalidDataMode(_) => "malformed data segment kind",
+        LocalsTooLarge => "too many locals",
+        SectionSizeMismatch => "section size mismatch",
+        InvalidLimitMode(_) => "integer too large",
+        SharedTable => "tables cannot be shared",
+        MultipleSection(..) | SectionOutOfOrder(..) => "unexpected content after last section",
+        FuncAndCodeNotEq(..) => "function and code section have inconsistent lengths",
+        DataAndDataCountNotEq(..) => "data count and data section have inconsistent lengths",
+        LossDataCount(_) => "data count section required",
+        InvalidTagAttribute(_) => "malformed tag attribute",
+        InvalidCatchKind(_) => "malformed catch clause",
+        InvalidFenceFlag(_) => "zero byte expected",
+    };
+
+    Some(text)
+}
+
+fn parse_text(err: &ParseErr) -> &'static str {
+    use ParseErr::*;
+
+    match err {
+        UnexpectedChar(..) | Expected(..) | MisplacedKeyword(..) => "unexpected token",
+        UnclosedString(_) => "unclosed string",
+        UnclosedComment(_) => "unclosed comment",
+        InvalidEscape(..) => "illegal escape",
+        InvalidUtf8(_) => "malformed UTF-8 encoding",
+        UnexpectedEof => "unexpected end of input",
+        UnknownId(_, kind, _) => match *kind {
+            "标签" => "unknown label",
+            "局部变量" => "unknown local",
+            "类型" => "unknown type",
+            "函数" => "unknown function",
+            "表" => "unknown table",
+            "内存" => "unknown memory",
+            "全局变量" => "unknown global",
+            "元素段" => "unknown elem segment",
+            "数据段" => "unknown data segment",
+            _ => "unknown tag",
+        },
+        DuplicateId(_, kind, _) => match *kind {
+            "局部变量" => "duplicate local",
+            "类型" => "duplicate type",
+            "函数" => "duplicate func",
+            "表" => "duplicate table",
+            "内存" => "duplicate memory",
+            "全局变量" => "duplicate global",
+            "元素段" => "duplicate elem",
+            "数据段" => "duplicate data",
+            _ => "duplicate tag",
+        },
+        UnknownInstr(..) => "unknown operator",
+        InvalidNumber(..) => "unknown operator",
+        NumberOutOfRange(..) => "constant out of range",
+        ImportAfterDefinition(_) => "import after function",
+        TypeUseMismatch(..) => "inline function type",
+        LabelMismatch(..) => "mismatching label",
+        InvalidAlign(..) => "alignment must be a power of two",
+    }
+}
+
+fn match_value(ret: &ValInst, expected: &Const, value: &ValInst) -> bool {
+    match expected {
+        // 每次转换都会创建新的宿主对象，只能比较其中的数字
+        Const::Externref(s) if s != "null" => match ret {
+            ValInst::ExternRef(Some(ret)) => ret.downcast_ref::<u32>() == s.parse::<u32>().ok().as_ref(),
+            _ => false,
+        },
+        Const::F32(kind) | Const::F64(kind) => match_float(ret, kind, value),
+        Const::V128(simd) => match (ret, &simd.lane_type) {
+            (ValInst::V128(ret), LaneType::F32) => {
+                let rets = ret.as_f32x4().to_array().map(ValInst::F32);
+                let values = value.as_v128().as_f32x4().to_array().map(ValInst::F32);
+
+                (0..rets.len()).all(|i| match_float(&rets[i], &simd.value[i], &values[i]))
+            }
+            (ValInst::V128(ret), LaneType::F64) => {
+                let rets = ret.as_f64x2().to_array().map(ValInst::F64);
+                let values = value.as_v128().as_f64x2().to_array().map(ValInst::F64);
+
+                (0..rets.len()).all(|i| match_float(&rets[i], &simd.value[i], &values[i]))
+            }
+            _ => ret == value,
+        },
+        _ => ret == value,
+    }
+}
+
+fn match_float(ret: &ValInst, kind: &str, value: &ValInst) -> bool {
+    match (kind, ret) {
+        ("nan:canonical", ValInst::F32(v)) => v.to_bits() & 0x7fff_ffff == 0x7fc0_0000,
+        ("nan:canonical", ValInst::F64(v)) => {
+            v.to_bits() & 0x7fff_ffff_ffff_ffff == 0x7ff8_0000_0000_0000
+        }
+        // 算术 NaN 的指数全为 1，并且尾数的最高位为 1
+        ("nan:arithmetic", ValInst::F32(v)) => v.to_bits() & 0x7fc0_0000 == 0x7fc0_0000,
+        ("nan:arithmetic", ValInst::F64(v)) => {
+            v.to_bits() & 0x7ff8_0000_0000_0000 == 0x7ff8_0000_0000_0000
+        }
+        _ => ret == value,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{match_float, match_text, Outcome, Runner};
+    use crate::execution::errors::{LinkError, Trap};
+    use crate::execution::value::ValInst;
+    use crate::spec::wast::Wast;
+
+    #[test]
+    fn test_match_arithmetic_nan() {
+        let expected = ValInst::F32(f32::NAN);
+
+        assert!(match_float(
+            &ValInst::F32(f32::from_bits(0xffc0_0001)),
+            "nan:arithmetic",
+            &expected
+        ));
+        // 尾数最高位为 0 的是 signaling NaN
+        assert!(!match_float(
+            &ValInst::F32(f32::from_bits(0x7fa0_0000)),
+            "nan:arithmetic",
+            &expected
+        ));
+        assert!(!match_float(
+            &ValInst::F64(f64::from_bits(0x7ff4_0000_0000_0000)),
+            "nan:arithmetic",
+            &ValInst::F64(f64::NAN)
+        ));
+    }
+
+    #[test]
+    fn test_match_text_exact() {
+        assert_eq!(
+            match_text(&Trap::DivZero, "integer divide by zero"),
+            Outcome::Pass
+        );
+        assert!(matches!(match_text(&Trap::DivZero, "integer"), Outcome::Fail(_)));
+
+        let err = LinkError::ImportCountNotEq(1, 0);
+
+        assert!(matches!(match_text(&err, "unknown import"), Outcome::Fail(_)));
+    }
+
+    #[test]
+    fn test_assert_module_kind() {
+        let wast = Wast::parse(
+            "t.wast",
+            r#"
+            (assert_invalid (module (func (result i32) (i64.const 0))) "type mismatch")
+            (assert_invalid (module (func (call 3))) "unknown function")
+            (assert_invalid (module (func (call 3))) "type mismatch")
+            (assert_invalid (module binary "\00asm") "type mismatch")
+            (assert_malformed (module binary "\00asm\02\00\00\00") "unknown binary version")
+            (assert_malformed (module quote "(func (i32.add1))") "unknown operator")
+            (assert_malformed (module binary "\00asm\01\00\00\00\0b\01") "unknown operator")
+            (assert_malformed (module quote "(func (result i32) (i64.const 0))") "type mismatch")
+            "#,
+        )
+        .unwrap();
+        let mut runner = Runner::new(".");
+
+        runner.files = wast.files;
+
+        let outcomes = runner
+            .run(&wast.json)
+            .into_iter()
+            .map(|result| match result.outcome {
+                Outcome::Pass => "pass",
+                Outcome::Fail(_) => "fail",
+                Outcome::Skip(_) => "skip",
+                Outcome::Mismatch(_) => "mismatch",
+            })
+            .collect::<Vec<_>>();
+
+        // 错误的种类不对时失败，种类对但文本不同时单独记为 mismatch
+        assert_eq!(
+            outcomes,
+            ["pass", "pass", "mismatch", "fail", "pass", "pass", "mismatch", "fail"]
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::binary::types::{GlobalType, Limits, RefType, TableType, ValType};
+use crate::execution::inst::global::GlobalInst;
+use crate::execution::inst::memory::MemInst;
+use crate::execution::inst::table::TableInst;
+use crate::execution::inst::{RGlobalInst, RMemInst, RTableInst};
+use crate::execution::linker::Linker;
+use crate::execution::value::ValInst;
+
+/// 测试脚本约定的 spectest 模块
+/// https://github.com/WebAssembly/spec/tree/main/interpreter#spectest-host-module
+///
+/// 表、内存和全局变量只创建一次，定义在多个模块名下时共享同一份实例
+#[derive(Debug, Clone)]
+pub struct SpecTestModule {
+    table: RTableInst,
+    memory: RMemInst,
+    globals: Vec<(&'static str, RGlobalInst)>,
+}
+
+impl SpecTestModule {
+    pub fn new() -> Self {
+        let table = TableInst::new(TableType {
+            elem_type: RefType::FuncRef,
+            limits: Limits {
+                min: 10,
+                max: Some(20),
+                is64: false,
+                shared: false,
+            },
+        });
+        let memory = MemInst::new(Limits {
+            min: 1,
+            max: Some(2),
+            is64: false,
+            shared: false,
+        });
+        let globals = [
+            ("global_i32", ValType::I32, ValInst::I32(666)),
+            ("global_i64", ValType::I64, ValInst::I64(666)),
+            ("global_f32", ValType::F32, ValInst::F32(666.6)),
+            ("global_f64", ValType::F64, ValInst::F64(666.6)),
+        ]
+        .into_iter()
+        .map(|(name, val_type, value)| {
+            let global_inst = GlobalInst::new(GlobalType::new(val_type, false), value).unwrap();
+
+            (name, Rc::new(RefCell::new(global_inst)))
+        })
+        .collect();
+
+        Self {
+            table: Rc::new(RefCell::new(table)),
+            memory: Rc::new(RefCell::new(memory)),
+            globals,
+        }
+    }
+
+    /// 把所有导出项定义在 module 下
+    pub fn define(&self, linker: &mut Linker, module: &str) {
+        linker
+            .table(module, "table", Rc::clone(&self.table))
+            .memory(module, "memory", Rc::clone(&self.memory))
+            .func(module, "print", || println!("spectest.print"))
+            .func(module, "print_i32", |v: i32| {
+                println!("spectest.print_i32：{}", v)
+            })
+            .func(module, "print_i64", |v: i64| {
+                println!("spectest.print_i64：{}", v)
+            })
+            .func(module, "print_f32", |v: f32| {
+                println!("spectest.print_f32：{}", v)
+            })
+            .func(module, "print_f64", |v: f64| {
+                println!("spectest.print_f64：{}", v)
+            })
+            .func(module, "print_i32_f32", |a: i32, b: f32| {
+                println!("spectest.print_i32_f32：{} {}", a, b)
+            })
+            .func(module, "print_f64_f64", |a: f64, b: f64| {
+                println!("spectest.print_f64_f64：{} {}", a, b)
+            });
+
+        for (name, global) in &self.globals {
+            linker.global(module, name, Rc::clone(global));
+        }
+    }
+}
+
+impl Default for SpecTestModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
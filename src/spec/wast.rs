@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+
+use super::errors::SpecError;
+use super::models::{
+    Action, AssertException, AssertExhaustion, AssertModule, AssertReturn, AssertTrap, Command,
+    CommandType, Const, GetAction, InvokeAction, LaneType, Module, ModuleType, Register, Simd, WabtJson,
+};
+use super::SpecResult;
+use crate::text::errors::Pos;
+use crate::text::lexer::{Lexer, Token};
+use crate::text::number::{self, NumResult};
+
+/// 直接读取 .wast 脚本，得到和 wast2json 相同的命令
+///
+/// 脚本中的模块原样保存成 .wat 文本，交给文本格式解析器处理
+pub struct Wast {
+    pub json: WabtJson,
+    /// 以文件名为键的模块文本
+    pub files: HashMap<String, Vec<u8>>,
+}
+
+impl Wast {
+    pub fn parse(source_filename: &str, text: &str) -> SpecResult<Self> {
+        let mut reader = WastReader {
+            text,
+            lines: line_starts(text),
+            tokens: Lexer::tokenize(text)?,
+            cursor: 0,
+            stem: source_filename.trim_end_matches(".wast").to_string(),
+            files: HashMap::new(),
+        };
+        let mut commands = vec![];
+
+        while reader.cursor < reader.tokens.len() {
+            commands.push(reader.command()?);
+        }
+
+        Ok(Self {
+            json: WabtJson {
+                source_filename: source_filename.to_string(),
+                commands,
+            },
+            files: reader.files,
+        })
+    }
+}
+
+struct WastReader<'a> {
+    text: &'a str,
+    /// 每一行在文本中的起始字节
+    lines: Vec<usize>,
+    tokens: Vec<(Token, Pos)>,
+    cursor: usize,
+    stem: String,
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl WastReader<'_> {
+    fn command(&mut self) -> SpecResult<Command> {
+        let line = self.pos().line;
+
+        self.expect(Token::LParen)?;
+
+        let type_ = match self.keyword()?.as_str() {
+            "module" => {
+                let (name, filename) = self.module()?;
+
+                return Ok(Command {
+                    type_: CommandType::Module(Module { name, filename }),
+                    line,
+                });
+            }
+            "register" => {
+                let as_ = self.string()?;
+                let name = self.opt_id();
+
+                CommandType::Register(Register { name, as_ })
+            }
+            kw @ ("invoke" | "get") => CommandType::Action {
+                action: self.action_body(kw)?,
+            },
+            "assert_return" => {
+                let action = self.action()?;
+                let mut expected = vec![];
+
+                while self.peek() == Some(&Token::LParen) {
+                    expected.push(self.const_()?);
+                }
+
+                CommandType::AssertReturn(AssertReturn { action, expected })
+            }
+            "assert_trap" if self.peek_module() => {
+                CommandType::AssertUninstantiable(self.assert_module()?)
+            }
+            "assert_trap" => {
+                let action = self.action()?;
+                let text = self.string()?;
+
+                CommandType::AssertTrap(AssertTrap { action, text })
+            }
+            "assert_exception" => CommandType::AssertException(AssertException {
+                action: self.action()?,
+            }),
+            "assert_exhaustion" => {
+                let action = self.action()?;
+                let text = self.string()?;
+
+                CommandType::AssertExhaustion(AssertExhaustion { action, text })
+            }
+            "assert_invalid" => CommandType::AssertInvalid(self.assert_module()?),
+            "assert_malformed" => CommandType::AssertMalformed(self.assert_module()?),
+            "assert_unlinkable" => CommandType::AssertUnlinkable(self.assert_module()?),
+            kw => Err(SpecError::UnknownCommand(line, kw.to_string()))?,
+        };
+
+        self.expect(Token::RParen)?;
+
+        Ok(Command { type_, line })
+    }
+
+    /// 从 module 关键字之后读到模块结束，保存模块的完整文本
+    fn module(&mut self) -> SpecResult<(Option<String>, String)> {
+        let start = self.cursor - 2;
+        let name = self.opt_id();
+        let mut depth = 1;
+
+        while depth > 0 {
+            match self.next()? {
+                (Token::LParen, _) => depth += 1,
+                (Token::RParen, _) => depth -= 1,
+                _ => {}
+            }
+        }
+
+        let begin = self.offset(self.tokens[start].1);
+        let end = self.offset(self.tokens[self.cursor - 1].1) + 1;
+        let filename = format!("{}.{}.wat", self.stem, self.files.len());
+
+        self.files
+            .insert(filename.clone(), self.text.as_bytes()[begin..end].to_vec());
+
+        Ok((name, filename))
+    }
+
+    fn assert_module(&mut self) -> SpecResult<AssertModule> {
+        self.expect(Token::LParen)?;
+        self.expect_keyword("module")?;
+
+        let (_, filename) = self.module()?;
+        let text = self.string()?;
+
+        Ok(AssertModule {
+            filename,
+            text,
+            module_type: ModuleType::Text,
+        })
+    }
+
+    fn action(&mut self) -> SpecResult<Action> {
+        self.expect(Token::LParen)?;
+
+        let kw = self.keyword()?;
+        let action = self.action_body(&kw)?;
+
+        self.expect(Token::RParen)?;
+
+        Ok(action)
+    }
+
+    fn action_body(&mut self, kw: &str) -> SpecResult<Action> {
+        let module = self.opt_id();
+        let field = self.string()?;
+
+        match kw {
+            "invoke" => {
+                let mut args = vec![];
+
+                while self.peek() == Some(&Token::LParen) {
+                    args.push(self.const_()?);
+                }
+
+                Ok(Action::Invoke(InvokeAction { module, field, args }))
+            }
+            "get" => Ok(Action::Get(GetAction { module, field })),
+            _ => Err(self.unexpected(kw))?,
+        }
+    }
+
+    /// 和 wast2json 一样，整数和浮点数都转成十进制的位模式
+    fn const_(&mut self) -> SpecResult<Const> {
+        self.expect(Token::LParen)?;
+
+        let kw = self.keyword()?;
+        let value = match kw.as_str() {
+            "i32.const" => Const::I32(self.num("i32", |s| number::parse_int(s, 32))?),
+            "i64.const" => Const::I64(self.num("i64", |s| number::parse_int(s, 64))?),
+            "f32.const" => Const::F32(self.float(LaneType::F32)?),
+            "f64.const" => Const::F64(self.float(LaneType::F64)?),
+            "v128.const" => Const::V128(self.simd()?),
+            "ref.null" => {
+                let type_ = self.keyword()?;
+
+                match type_.as_str() {
+                    "func" | "funcref" => Const::Funcref("null".to_string()),
+                    "extern" | "externref" => Const::Externref("null".to_string()),
+                    "exn" | "exnref" => Const::Exnref("null".to_string()),
+                    _ => Err(self.unexpected(&type_))?,
+                }
+            }
+            "ref.extern" => Const::Externref(self.num("externref", number::parse_u64)?),
+            // 返回值里的 (ref.func) 表示任意非空的函数引用
+            "ref.func" => {
+                self.opt_id();
+
+                Const::Funcref("0".to_string())
+            }
+            _ => Err(self.unexpected(&kw))?,
+        };
+
+        self.expect(Token::RParen)?;
+
+        Ok(value)
+    }
+
+    fn simd(&mut self) -> SpecResult<Simd> {
+        let shape = self.keyword()?;
+        let (lane_type, lanes) = match shape.as_str() {
+            "i8x16" => (LaneType::I8, 16),
+            "i16x8" => (LaneType::I16, 8),
+            "i32x4" => (LaneType::I32, 4),
+            "i64x2" => (LaneType::I64, 2),
+            "f32x4" => (LaneType::F32, 4),
+            "f64x2" => (LaneType::F64, 2),
+            _ => Err(self.unexpected(&shape))?,
+        };
+        let value = (0..lanes)
+            .map(|_| match lane_type {
+                LaneType::I8 => self.num("i8", |s| number::parse_int(s, 8)),
+                LaneType::I16 => self.num("i16", |s| number::parse_int(s, 16)),
+                LaneType::I32 => self.num("i32", |s| number::parse_int(s, 32)),
+                LaneType::I64 => self.num("i64", |s| number::parse_int(s, 64)),
+                LaneType::F32 | LaneType::F64 => self.float(lane_type.clone()),
+            })
+            .collect::<SpecResult<_>>()?;
+
+        Ok(Simd { lane_type, value })
+    }
+
+    fn float(&mut self, lane_type: LaneType) -> SpecResult<String> {
+        match self.peek() {
+            Some(Token::Keyword(kw)) if kw == "nan:canonical" || kw == "nan:arithmetic" => {
+                self.keyword()
+            }
+            _ => match lane_type {
+                LaneType::F64 => self.num("f64", |s| number::parse_f64(s).map(f64::to_bits)),
+                _ => self.num("f32", |s| number::parse_f32(s).map(|v| v.to_bits() as u64)),
+            },
+        }
+    }
+
+    fn num(&mut self, kind: &'static str, parse: impl Fn(&str) -> NumResult<u64>) -> SpecResult<String> {
+        let s = self.keyword()?;
+
+        match parse(&s) {
+            Ok(v) => Ok(v.to_string()),
+            Err(_) => Err(SpecError::InvalidConst(kind, s))?,
+        }
+    }
+}
+
+/// 词法单元
+impl WastReader<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.cursor).map(|(token, _)| token)
+    }
+
+    fn peek_module(&self) -> bool {
+        self.peek() == Some(&Token::LParen)
+            && matches!(self.tokens.get(self.cursor + 1), Some((Token::Keyword(kw), _)) if kw == "module")
+    }
+
+    fn pos(&self) -> Pos {
+        self.tokens
+            .get(self.cursor)
+            .or(self.tokens.last())
+            .map(|(_, pos)| *pos)
+            .unwrap_or_default()
+    }
+
+    fn next(&mut self) -> SpecResult<(Token, Pos)> {
+        let token = self.tokens.get(self.cursor).cloned();
+
+        self.cursor += 1;
+
+        token.ok_or_else(|| SpecError::UnexpectedEof.into())
+    }
+
+    fn unexpected(&self, found: &str) -> SpecError {
+        SpecError::UnexpectedToken(self.pos().line, found.to_string())
+    }
+
+    fn expect(&mut self, expected: Token) -> SpecResult<()> {
+        let (token, _) = self.next()?;
+
+        match token == expected {
+            true => Ok(()),
+            false => Err(self.unexpected(&token.describe()))?,
+        }
+    }
+
+    fn expect_keyword(&mut self, kw: &str) -> SpecResult<()> {
+        self.expect(Token::Keyword(kw.to_string()))
+    }
+
+    fn keyword(&mut self) -> SpecResult<String> {
+        match self.next()? {
+            (Token::Keyword(kw), _) => Ok(kw),
+            (token, _) => Err(self.unexpected(&token.describe()))?,
+        }
+    }
+
+    fn string(&mut self) -> SpecResult<String> {
+        match self.next()? {
+            (Token::Str(s), _) => Ok(String::from_utf8_lossy(&s).into_owned()),
+            (token, _) => Err(self.unexpected(&token.describe()))?,
+        }
+    }
+
+    /// 模块名保留 $，和 wast2json 一致
+    fn opt_id(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(Token::Id(id)) => {
+                let id = format!("${}", id);
+
+                self.cursor += 1;
+
+                Some(id)
+            }
+            _ => None,
+        }
+    }
+
+    /// 行列位置转成字节位置，列按字符计数
+    fn offset(&self, pos: Pos) -> usize {
+        let start = self.lines[pos.line - 1];
+
+        self.text[start..]
+            .char_indices()
+            .nth(pos.col - 1)
+            .map_or(self.text.len(), |(i, _)| start + i)
+    }
+}
+
+fn line_starts(text: &str) -> Vec<usize> {
+    let mut lines = vec![0];
+
+    lines.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+
+    lines
+}
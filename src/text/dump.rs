@@ -0,0 +1,389 @@
+use super::printer::{
+    block_type, catch, const_expr, global, instr_text, limits, signature, string, table, val_type,
+};
+use crate::binary::instruction::Instruction;
+use crate::binary::module::Module;
+use crate::binary::section::{DataMode, ElementMode, ExportDesc, Expr, ImportDesc, Section};
+
+/// 类似 objdump，逐段列出模块的内容以及它们在文件中的位置，位置都是十六进制
+///
+/// 只有从二进制格式解码的模块才有位置信息，从文本格式解析的模块不显示位置
+pub struct Dumper<'a> {
+    module: &'a Module,
+    out: String,
+}
+
+impl<'a> Dumper<'a> {
+    pub fn dump(module: &Module) -> String {
+        let mut dumper = Dumper {
+            module,
+            out: String::new(),
+        };
+
+        dumper.sections();
+        dumper.types();
+        dumper.imports();
+        dumper.funcs();
+        dumper.tables();
+        dumper.tags();
+        dumper.globals();
+        dumper.exports();
+        dumper.elems();
+        dumper.datas();
+        dumper.customs();
+        dumper.codes();
+        dumper.out
+    }
+
+    fn line(&mut self, text: &str) {
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// 段标题，带上项的数量
+    fn title(&mut self, name: &str, count: usize) {
+        if count > 0 {
+            self.line(&format!("\n{}[{}]:", name, count));
+        }
+    }
+
+    fn sections(&mut self) {
+        let module = self.module;
+        let mut customs = module.custom_sec.iter();
+
+        if module.sections.is_empty() {
+            return;
+        }
+
+        self.line("sections:");
+
+        for sec in &module.sections {
+            let name = match sec.id {
+                Section::Custom => format!("custom {}", string(customs.next().unwrap().name.as_bytes())),
+                _ => format!("{:?}", sec.id).to_lowercase(),
+            };
+
+            self.line(&format!(
+                "  {:06x} {:<10} offset={:06x} size={}",
+                sec.start, name, sec.offset, sec.size
+            ));
+        }
+    }
+
+    fn types(&mut self) {
+        let module = self.module;
+
+        self.title("type", module.type_sec.len());
+
+        for (i, func_type) in module.type_sec.iter().enumerate() {
+            self.line(&format!("  - type[{}] (func{})", i, signature(func_type)));
+        }
+    }
+
+    fn imports(&mut self) {
+        let module = self.module;
+        let mut counts = [0; 5];
+
+        self.title("import", module.import_sec.len());
+
+        for import in &module.import_sec {
+            let (kind, desc) = match &import.desc {
+                ImportDesc::Func(type_idx) => {
+                    (0, format!("func[{}] {}", counts[0], self.type_use(*type_idx)))
+                }
+                ImportDesc::Table(table_type) => {
+                    (1, format!("table[{}] {}", counts[1], table(table_type)))
+                }
+                ImportDesc::Mem(mem_type) => (2, format!("memory[{}] {}", counts[2], limits(mem_type))),
+                ImportDesc::Global(global_type) => {
+                    (3, format!("global[{}] {}", counts[3], global(global_type)))
+                }
+                ImportDesc::Tag(tag_type) => (
+                    4,
+                    format!("tag[{}] {}", counts[4], self.type_use(tag_type.type_idx)),
+                ),
+            };
+
+            counts[kind] += 1;
+            self.line(&format!(
+                "  - {} <- {}.{}",
+                desc,
+                string(import.module.as_bytes()),
+                string(import.name.as_bytes())
+            ));
+        }
+    }
+
+    fn funcs(&mut self) {
+        let module = self.module;
+        let base = self.imported(0);
+
+        self.title("function", module.func_sec.len());
+
+        for (i, type_idx) in module.func_sec.iter().enumerate() {
+            self.line(&format!(
+                "  - func[{}]{} {}",
+                base + i,
+                self.func_name(base + i),
+                self.type_use(*type_idx)
+            ));
+        }
+    }
+
+    fn tables(&mut self) {
+        let module = self.module;
+        let (table_base, mem_base) = (self.imported(1), self.imported(2));
+
+        self.title("table", module.table_sec.len());
+
+        for (i, table_type) in module.table_sec.iter().enumerate() {
+            self.line(&format!("  - table[{}] {}", table_base + i, table(table_type)));
+        }
+
+        self.title("memory", module.mem_sec.len());
+
+        for (i, mem_type) in module.mem_sec.iter().enumerate() {
+            self.line(&format!("  - memory[{}] {}", mem_base + i, limits(mem_type)));
+        }
+    }
+
+    fn tags(&mut self) {
+        let module = self.module;
+        let base = self.imported(4);
+
+        self.title("tag", module.tag_sec.len());
+
+        for (i, tag_type) in module.tag_sec.iter().enumerate() {
+            self.line(&format!(
+                "  - tag[{}] {}",
+                base + i,
+                self.type_use(tag_type.type_idx)
+            ));
+        }
+    }
+
+    fn globals(&mut self) {
+        let module = self.module;
+        let base = self.imported(3);
+
+        self.title("global", module.global_sec.len());
+
+        for (i, seg) in module.global_sec.iter().enumerate() {
+            self.line(&format!(
+                "  - global[{}] {} {}",
+                base + i,
+                global(&seg.type_),
+                const_expr(&seg.init_expr, "")
+            ));
+        }
+    }
+
+    fn exports(&mut self) {
+        let module = self.module;
+
+        self.title("export", module.export_sec.len());
+
+        for export in &module.export_sec {
+            let desc = match &export.desc {
+                ExportDesc::Func(idx) => format!("func[{}]", idx),
+                ExportDesc::Table(idx) => format!("table[{}]", idx),
+                ExportDesc::Mem(idx) => format!("memory[{}]", idx),
+                ExportDesc::Global(idx) => format!("global[{}]", idx),
+                ExportDesc::Tag(idx) => format!("tag[{}]", idx),
+            };
+
+            self.line(&format!("  - {} -> {}", desc, string(export.name.as_bytes())));
+        }
+
+        if let Some(func_idx) = module.start_sec {
+            self.line(&format!("\nstart:\n  - func[{}]", func_idx));
+        }
+    }
+
+    fn elems(&mut self) {
+        let module = self.module;
+
+        self.title("elem", module.elem_sec.len());
+
+        for (i, elem) in module.elem_sec.iter().enumerate() {
+            let mode = match &elem.mode {
+                ElementMode::Passive => "passive".to_string(),
+                ElementMode::Declarative => "declarative".to_string(),
+                ElementMode::Active {
+                    table_idx,
+                    offset_expr,
+                } => format!(
+                    "active table={} offset={}",
+                    table_idx,
+                    const_expr(offset_expr, "")
+                ),
+            };
+            let items = match elem.init_is_expr() {
+                true => elem
+                    .init_expr
+                    .iter()
+                    .map(|expr| const_expr(expr, ""))
+                    .collect::<Vec<_>>(),
+                false => elem.func_idxs.iter().map(|idx| idx.to_string()).collect(),
+            };
+
+            self.line(&format!(
+                "  - elem[{}] {} {} count={}: {}",
+                i,
+                mode,
+                val_type(&elem.type_),
+                items.len(),
+                items.join(" ")
+            ));
+        }
+    }
+
+    fn datas(&mut self) {
+        let module = self.module;
+
+        self.title("data", module.data_sec.len());
+
+        for (i, data) in module.data_sec.iter().enumerate() {
+            let mode = match data.mode {
+                DataMode::Passive => "passive".to_string(),
+                DataMode::Active => format!(
+                    "active memory={} offset={}",
+                    data.mem_idx,
+                    const_expr(&data.offset_expr, "")
+                ),
+            };
+
+            self.line(&format!("  - data[{}] {} size={}", i, mode, data.init.len()));
+            self.line(&format!("    {}", string(&data.init[..data.init.len().min(64)])));
+        }
+    }
+
+    /// 自定义段只列出名字和大小
+    fn customs(&mut self) {
+        let module = self.module;
+
+        self.title("custom", module.custom_sec.len());
+
+        for custom in &module.custom_sec {
+            self.line(&format!(
+                "  - {} size={}",
+                string(custom.name.as_bytes()),
+                custom.data.len()
+            ));
+        }
+    }
+
+    fn codes(&mut self) {
+        let module = self.module;
+        let base = self.imported(0);
+
+        self.title("code", module.code_sec.len());
+
+        for (i, code) in module.code_sec.iter().enumerate() {
+            let locals = code
+                .locals
+                .iter()
+                .map(|locals| format!("{}x{}", val_type(&locals.value_type), locals.n))
+                .collect::<Vec<_>>();
+
+            self.line(&format!(
+                "  - func[{}]{} offset={:06x} size={} locals=[{}]",
+                base + i,
+                self.func_name(base + i),
+                code.offset,
+                code.size,
+                locals.join(" ")
+            ));
+
+            let mut offsets = code.instr_offsets.iter().copied();
+
+            self.instrs(&code.body, &mut offsets, 0);
+            self.instr(offsets.next(), 0, "end");
+        }
+    }
+
+    /// 位置和解码时的顺序一致，结构化指令后面要补上 else 和 end
+    fn instrs(&mut self, expr: &Expr, offsets: &mut impl Iterator<Item = usize>, depth: usize) {
+        for instr in expr {
+            let offset = offsets.next();
+
+            match instr {
+                Instruction::Block(block) | Instruction::Loop(block) => {
+                    self.instr(
+                        offset,
+                        depth,
+                        &format!("{}{}", instr.name(), block_type(&block.type_)),
+                    );
+                    self.instrs(&block.expr, offsets, depth + 1);
+                    self.instr(offsets.next(), depth, "end");
+                }
+                Instruction::TryTable(block) => {
+                    let catches = block.catches.iter().map(catch).collect::<String>();
+
+                    self.instr(
+                        offset,
+                        depth,
+                        &format!("try_table{}{}", block_type(&block.type_), catches),
+                    );
+                    self.instrs(&block.expr, offsets, depth + 1);
+                    self.instr(offsets.next(), depth, "end");
+                }
+                Instruction::If(block) => {
+                    self.instr(offset, depth, &format!("if{}", block_type(&block.type_)));
+                    self.instrs(&block.if_expr, offsets, depth + 1);
+
+                    if !block.else_expr.is_empty() {
+                        self.instr(offsets.next(), depth, "else");
+                        self.instrs(&block.else_expr, offsets, depth + 1);
+                    }
+
+                    self.instr(offsets.next(), depth, "end");
+                }
+                _ => self.instr(offset, depth, &instr_text(instr)),
+            }
+        }
+    }
+
+    fn instr(&mut self, offset: Option<usize>, depth: usize, text: &str) {
+        let offset = match offset {
+            Some(offset) => format!("{:06x}:", offset),
+            None => " ".repeat(7),
+        };
+
+        self.line(&format!("    {} {}{}", offset, "  ".repeat(depth), text));
+    }
+
+    /// 某类导入项的数量，定义在模块内的项从这里开始编号
+    fn imported(&self, kind: usize) -> usize {
+        self.module
+            .import_sec
+            .iter()
+            .filter(|import| {
+                let i = match import.desc {
+                    ImportDesc::Func(_) => 0,
+                    ImportDesc::Table(_) => 1,
+                    ImportDesc::Mem(_) => 2,
+                    ImportDesc::Global(_) => 3,
+                    ImportDesc::Tag(_) => 4,
+                };
+
+                i == kind
+            })
+            .count()
+    }
+
+    /// 名字段中有函数名时显示在索引后面
+    fn func_name(&self, func_idx: usize) -> String {
+        match self.module.names.funcs.get(&(func_idx as u32)) {
+            Some(name) => format!(" <{}>", name),
+            None => String::new(),
+        }
+    }
+
+    fn type_use(&self, type_idx: u32) -> String {
+        match self.module.type_sec.get(type_idx as usize) {
+            Some(func_type) => format!("(type {}){}", type_idx, signature(func_type)),
+            None => format!("(type {})", type_idx),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::fmt;
+
+/// 源码位置，行列均从 1 开始
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Pos {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParseErr {
+    #[error("{0}：非法字符 {1:?}")]
+    UnexpectedChar(Pos, char),
+
+    #[error("{0}：字符串没有闭合")]
+    UnclosedString(Pos),
+
+    #[error("{0}：块注释没有闭合")]
+    UnclosedComment(Pos),
+
+    #[error("{0}：无效的转义字符 {1}")]
+    InvalidEscape(Pos, String),
+
+    #[error("{0}：名称不是合法的 UTF-8 字符串")]
+    InvalidUtf8(Pos),
+
+    #[error("文本意外结束")]
+    UnexpectedEof,
+
+    #[error("{0}：期望 {1}，实际为 {2}")]
+    Expected(Pos, String, String),
+
+    #[error("{0}：未知的{1} ${2}")]
+    UnknownId(Pos, &'static str, String),
+
+    #[error("{0}：重复定义的{1} ${2}")]
+    DuplicateId(Pos, &'static str, String),
+
+    #[error("{0}：未知的指令 {1}")]
+    UnknownInstr(Pos, String),
+
+    #[error("{0}：{1} 只能出现在 if 或块的结构中")]
+    MisplacedKeyword(Pos, String),
+
+    #[error("{0}：无效的数字 {1}")]
+    InvalidNumber(Pos, String),
+
+    #[error("{0}：数字 {1} 超出范围")]
+    NumberOutOfRange(Pos, String),
+
+    #[error("{0}：导入必须在函数、表、内存、全局变量的定义之前")]
+    ImportAfterDefinition(Pos),
+
+    #[error("{0}：内联的函数签名和类型 {1} 不一致")]
+    TypeUseMismatch(Pos, u32),
+
+    #[error("{0}：结束标签 ${1} 和块标签不一致")]
+    LabelMismatch(Pos, String),
+
+    #[error("{0}：对齐 {1} 必须是 2 的幂")]
+    InvalidAlign(Pos, u32),
+}
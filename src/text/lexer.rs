@@ -0,0 +1,246 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use super::errors::{ParseErr, Pos};
+use super::parser::ParseResult;
+
+/// https://webassembly.github.io/spec/core/text/lexical.html#tokens
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    LParen,
+    RParen,
+    /// 关键字、数字以及 offset=、align= 这类保留字
+    Keyword(String),
+    /// $name，不含 $
+    Id(String),
+    /// 字符串是字节序列，不一定是合法的 UTF-8
+    Str(Vec<u8>),
+}
+
+impl Token {
+    pub fn describe(&self) -> String {
+        match self {
+            Token::LParen => "(".to_string(),
+            Token::RParen => ")".to_string(),
+            Token::Keyword(keyword) => keyword.clone(),
+            Token::Id(id) => format!("${}", id),
+            Token::Str(s) => format!("{:?}", String::from_utf8_lossy(s)),
+        }
+    }
+}
+
+pub struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    pos: Pos,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self {
+            chars: text.chars().peekable(),
+            pos: Pos { line: 1, col: 1 },
+        }
+    }
+
+    pub fn tokenize(text: &'a str) -> ParseResult<Vec<(Token, Pos)>> {
+        let mut lexer = Self::new(text);
+        let mut tokens = vec![];
+
+        while let Some(token) = lexer.next_token()? {
+            tokens.push(token);
+        }
+
+        Ok(tokens)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+
+        match c {
+            '\n' => {
+                self.pos.line += 1;
+                self.pos.col = 1;
+            }
+            _ => self.pos.col += 1,
+        }
+
+        Some(c)
+    }
+
+    fn next_token(&mut self) -> ParseResult<Option<(Token, Pos)>> {
+        self.skip_blank()?;
+
+        let pos = self.pos;
+        let token = match self.peek() {
+            None => return Ok(None),
+            Some('(') => {
+                self.bump();
+
+                Token::LParen
+            }
+            Some(')') => {
+                self.bump();
+
+                Token::RParen
+            }
+            Some('"') => Token::Str(self.string()?),
+            Some(c) if is_idchar(c) => {
+                let mut atom = String::new();
+
+                while let Some(c) = self.peek().filter(|c| is_idchar(*c)) {
+                    atom.push(c);
+                    self.bump();
+                }
+
+                match atom.strip_prefix('$') {
+                    Some(id) if !id.is_empty() => Token::Id(id.to_string()),
+                    _ => Token::Keyword(atom),
+                }
+            }
+            Some(c) => Err(ParseErr::UnexpectedChar(pos, c))?,
+        };
+
+        Ok(Some((token, pos)))
+    }
+
+    /// 空白、行注释 ;; 和可嵌套的块注释 (; ;)
+    fn skip_blank(&mut self) -> ParseResult<()> {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\n' | '\r') => {
+                    self.bump();
+                }
+                Some(';') => {
+                    let pos = self.pos;
+                    let mut ahead = self.chars.clone();
+
+                    ahead.next();
+
+                    match ahead.next() {
+                        // 行注释在 \n 或 \r 处结束
+                        Some(';') => {
+                            while self.peek().is_some_and(|c| c != '\n' && c != '\r') {
+                                self.bump();
+                            }
+                        }
+                        _ => Err(ParseErr::UnexpectedChar(pos, ';'))?,
+                    }
+                }
+                Some('(') => {
+                    let mut ahead = self.chars.clone();
+
+                    ahead.next();
+
+                    match ahead.next() {
+                        Some(';') => self.block_comment()?,
+                        _ => return Ok(()),
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn block_comment(&mut self) -> ParseResult<()> {
+        let pos = self.pos;
+        let mut depth = 0;
+
+        loop {
+            match (self.bump(), self.peek()) {
+                (Some('('), Some(';')) => {
+                    self.bump();
+                    depth += 1;
+                }
+                (Some(';'), Some(')')) => {
+                    self.bump();
+                    depth -= 1;
+
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                (Some(_), _) => (),
+                (None, _) => Err(ParseErr::UnclosedComment(pos))?,
+            }
+        }
+    }
+
+    fn string(&mut self) -> ParseResult<Vec<u8>> {
+        let pos = self.pos;
+        let mut bytes = vec![];
+
+        self.bump();
+
+        loop {
+            match self.bump() {
+                None | Some('\n') => Err(ParseErr::UnclosedString(pos))?,
+                Some('"') => return Ok(bytes),
+                Some('\\') => self.escape(&mut bytes)?,
+                Some(c) => {
+                    let mut buf = [0; 4];
+
+                    bytes.extend(c.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+        }
+    }
+
+    fn escape(&mut self, bytes: &mut Vec<u8>) -> ParseResult<()> {
+        let pos = self.pos;
+
+        match self.bump() {
+            Some('t') => bytes.push(b'\t'),
+            Some('n') => bytes.push(b'\n'),
+            Some('r') => bytes.push(b'\r'),
+            Some('"') => bytes.push(b'"'),
+            Some('\'') => bytes.push(b'\''),
+            Some('\\') => bytes.push(b'\\'),
+            Some('u') => {
+                let mut hex = String::new();
+
+                if self.bump() != Some('{') {
+                    Err(ParseErr::InvalidEscape(pos, "\\u".to_string()))?;
+                }
+
+                loop {
+                    match self.bump() {
+                        Some('}') => break,
+                        Some(c) if c.is_ascii_hexdigit() || c == '_' => hex.push(c),
+                        _ => Err(ParseErr::InvalidEscape(pos, format!("\\u{{{}", hex)))?,
+                    }
+                }
+
+                let c = u32::from_str_radix(&hex.replace('_', ""), 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| ParseErr::InvalidEscape(pos, format!("\\u{{{}}}", hex)))?;
+                let mut buf = [0; 4];
+
+                bytes.extend(c.encode_utf8(&mut buf).as_bytes());
+            }
+            Some(h) if h.is_ascii_hexdigit() => match self.bump() {
+                Some(l) if l.is_ascii_hexdigit() => {
+                    let byte = (h.to_digit(16).unwrap() << 4) | l.to_digit(16).unwrap();
+
+                    bytes.push(byte as u8);
+                }
+                l => Err(ParseErr::InvalidEscape(
+                    pos,
+                    format!("\\{}{}", h, l.unwrap_or(' ')),
+                ))?,
+            },
+            c => Err(ParseErr::InvalidEscape(pos, format!("\\{}", c.unwrap_or(' '))))?,
+        }
+
+        Ok(())
+    }
+}
+
+/// https://webassembly.github.io/spec/core/text/values.html#text-idchar
+fn is_idchar(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-./:<=>?@\\^_`|~".contains(c)
+}
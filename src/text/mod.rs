@@ -0,0 +1,29 @@
+pub mod dump;
+pub mod errors;
+pub mod lexer;
+pub mod names;
+pub(crate) mod number;
+pub mod parser;
+pub mod printer;
+
+use self::dump::Dumper;
+use self::parser::{ParseResult, Parser};
+use self::printer::Printer;
+use crate::binary::module::Module;
+
+impl Module {
+    /// 从 WebAssembly 文本格式（.wat）解析模块
+    pub fn from_text(text: &str) -> ParseResult<Self> {
+        Parser::parse(text)
+    }
+
+    /// 输出成文本格式，用于反汇编
+    pub fn to_text(&self) -> String {
+        Printer::print(self)
+    }
+
+    /// 逐段列出模块的内容和位置，用于查看二进制文件
+    pub fn dump(&self) -> String {
+        Dumper::dump(self)
+    }
+}
@@ -0,0 +1,560 @@
+use crate::binary::instruction::Instruction;
+
+/// 指令码和文本格式里的指令名
+/// https://webassembly.github.io/spec/core/text/instructions.html
+pub const INSTR_NAMES: &[(u16, &str)] = &[
+    (0x00, "unreachable"),
+    (0x01, "nop"),
+    (0x02, "block"),
+    (0x03, "loop"),
+    (0x04, "if"),
+    (0x05, "else"),
+    (0x08, "throw"),
+    (0x0a, "throw_ref"),
+    (0x0b, "end"),
+    (0x0c, "br"),
+    (0x0d, "br_if"),
+    (0x0e, "br_table"),
+    (0x0f, "return"),
+    (0x10, "call"),
+    (0x11, "call_indirect"),
+    (0x12, "return_call"),
+    (0x13, "return_call_indirect"),
+    (0x1a, "drop"),
+    (0x1b, "select"),
+    (0x1c, "select"),
+    (0x1f, "try_table"),
+    (0x20, "local.get"),
+    (0x21, "local.set"),
+    (0x22, "local.tee"),
+    (0x23, "global.get"),
+    (0x24, "global.set"),
+    (0x25, "table.get"),
+    (0x26, "table.set"),
+    (0x28, "i32.load"),
+    (0x29, "i64.load"),
+    (0x2a, "f32.load"),
+    (0x2b, "f64.load"),
+    (0x2c, "i32.load8_s"),
+    (0x2d, "i32.load8_u"),
+    (0x2e, "i32.load16_s"),
+    (0x2f, "i32.load16_u"),
+    (0x30, "i64.load8_s"),
+    (0x31, "i64.load8_u"),
+    (0x32, "i64.load16_s"),
+    (0x33, "i64.load16_u"),
+    (0x34, "i64.load32_s"),
+    (0x35, "i64.load32_u"),
+    (0x36, "i32.store"),
+    (0x37, "i64.store"),
+    (0x38, "f32.store"),
+    (0x39, "f64.store"),
+    (0x3a, "i32.store8"),
+    (0x3b, "i32.store16"),
+    (0x3c, "i64.store8"),
+    (0x3d, "i64.store16"),
+    (0x3e, "i64.store32"),
+    (0x3f, "memory.size"),
+    (0x40, "memory.grow"),
+    (0x41, "i32.const"),
+    (0x42, "i64.const"),
+    (0x43, "f32.const"),
+    (0x44, "f64.const"),
+    (0x45, "i32.eqz"),
+    (0x46, "i32.eq"),
+    (0x47, "i32.ne"),
+    (0x48, "i32.lt_s"),
+    (0x49, "i32.lt_u"),
+    (0x4a, "i32.gt_s"),
+    (0x4b, "i32.gt_u"),
+    (0x4c, "i32.le_s"),
+    (0x4d, "i32.le_u"),
+    (0x4e, "i32.ge_s"),
+    (0x4f, "i32.ge_u"),
+    (0x50, "i64.eqz"),
+    (0x51, "i64.eq"),
+    (0x52, "i64.ne"),
+    (0x53, "i64.lt_s"),
+    (0x54, "i64.lt_u"),
+    (0x55, "i64.gt_s"),
+    (0x56, "i64.gt_u"),
+    (0x57, "i64.le_s"),
+    (0x58, "i64.le_u"),
+    (0x59, "i64.ge_s"),
+    (0x5a, "i64.ge_u"),
+    (0x5b, "f32.eq"),
+    (0x5c, "f32.ne"),
+    (0x5d, "f32.lt"),
+    (0x5e, "f32.gt"),
+    (0x5f, "f32.le"),
+    (0x60, "f32.ge"),
+    (0x61, "f64.eq"),
+    (0x62, "f64.ne"),
+    (0x63, "f64.lt"),
+    (0x64, "f64.gt"),
+    (0x65, "f64.le"),
+    (0x66, "f64.ge"),
+    (0x67, "i32.clz"),
+    (0x68, "i32.ctz"),
+    (0x69, "i32.popcnt"),
+    (0x6a, "i32.add"),
+    (0x6b, "i32.sub"),
+    (0x6c, "i32.mul"),
+    (0x6d, "i32.div_s"),
+    (0x6e, "i32.div_u"),
+    (0x6f, "i32.rem_s"),
+    (0x70, "i32.rem_u"),
+    (0x71, "i32.and"),
+    (0x72, "i32.or"),
+    (0x73, "i32.xor"),
+    (0x74, "i32.shl"),
+    (0x75, "i32.shr_s"),
+    (0x76, "i32.shr_u"),
+    (0x77, "i32.rotl"),
+    (0x78, "i32.rotr"),
+    (0x79, "i64.clz"),
+    (0x7a, "i64.ctz"),
+    (0x7b, "i64.popcnt"),
+    (0x7c, "i64.add"),
+    (0x7d, "i64.sub"),
+    (0x7e, "i64.mul"),
+    (0x7f, "i64.div_s"),
+    (0x80, "i64.div_u"),
+    (0x81, "i64.rem_s"),
+    (0x82, "i64.rem_u"),
+    (0x83, "i64.and"),
+    (0x84, "i64.or"),
+    (0x85, "i64.xor"),
+    (0x86, "i64.shl"),
+    (0x87, "i64.shr_s"),
+    (0x88, "i64.shr_u"),
+    (0x89, "i64.rotl"),
+    (0x8a, "i64.rotr"),
+    (0x8b, "f32.abs"),
+    (0x8c, "f32.neg"),
+    (0x8d, "f32.ceil"),
+    (0x8e, "f32.floor"),
+    (0x8f, "f32.trunc"),
+    (0x90, "f32.nearest"),
+    (0x91, "f32.sqrt"),
+    (0x92, "f32.add"),
+    (0x93, "f32.sub"),
+    (0x94, "f32.mul"),
+    (0x95, "f32.div"),
+    (0x96, "f32.min"),
+    (0x97, "f32.max"),
+    (0x98, "f32.copysign"),
+    (0x99, "f64.abs"),
+    (0x9a, "f64.neg"),
+    (0x9b, "f64.ceil"),
+    (0x9c, "f64.floor"),
+    (0x9d, "f64.trunc"),
+    (0x9e, "f64.nearest"),
+    (0x9f, "f64.sqrt"),
+    (0xa0, "f64.add"),
+    (0xa1, "f64.sub"),
+    (0xa2, "f64.mul"),
+    (0xa3, "f64.div"),
+    (0xa4, "f64.min"),
+    (0xa5, "f64.max"),
+    (0xa6, "f64.copysign"),
+    (0xa7, "i32.wrap_i64"),
+    (0xa8, "i32.trunc_f32_s"),
+    (0xa9, "i32.trunc_f32_u"),
+    (0xaa, "i32.trunc_f64_s"),
+    (0xab, "i32.trunc_f64_u"),
+    (0xac, "i64.extend_i32_s"),
+    (0xad, "i64.extend_i32_u"),
+    (0xae, "i64.trunc_f32_s"),
+    (0xaf, "i64.trunc_f32_u"),
+    (0xb0, "i64.trunc_f64_s"),
+    (0xb1, "i64.trunc_f64_u"),
+    (0xb2, "f32.convert_i32_s"),
+    (0xb3, "f32.convert_i32_u"),
+    (0xb4, "f32.convert_i64_s"),
+    (0xb5, "f32.convert_i64_u"),
+    (0xb6, "f32.demote_f64"),
+    (0xb7, "f64.convert_i32_s"),
+    (0xb8, "f64.convert_i32_u"),
+    (0xb9, "f64.convert_i64_s"),
+    (0xba, "f64.convert_i64_u"),
+    (0xbb, "f64.promote_f32"),
+    (0xbc, "i32.reinterpret_f32"),
+    (0xbd, "i64.reinterpret_f64"),
+    (0xbe, "f32.reinterpret_i32"),
+    (0xbf, "f64.reinterpret_i64"),
+    (0xc0, "i32.extend8_s"),
+    (0xc1, "i32.extend16_s"),
+    (0xc2, "i64.extend8_s"),
+    (0xc3, "i64.extend16_s"),
+    (0xc4, "i64.extend32_s"),
+    (0xd0, "ref.null"),
+    (0xd1, "ref.is_null"),
+    (0xd2, "ref.func"),
+    (0xfc00, "i32.trunc_sat_f32_s"),
+    (0xfc01, "i32.trunc_sat_f32_u"),
+    (0xfc02, "i32.trunc_sat_f64_s"),
+    (0xfc03, "i32.trunc_sat_f64_u"),
+    (0xfc04, "i64.trunc_sat_f32_s"),
+    (0xfc05, "i64.trunc_sat_f32_u"),
+    (0xfc06, "i64.trunc_sat_f64_s"),
+    (0xfc07, "i64.trunc_sat_f64_u"),
+    (0xfc08, "memory.init"),
+    (0xfc09, "data.drop"),
+    (0xfc0a, "memory.copy"),
+    (0xfc0b, "memory.fill"),
+    (0xfc0c, "table.init"),
+    (0xfc0d, "elem.drop"),
+    (0xfc0e, "table.copy"),
+    (0xfc0f, "table.grow"),
+    (0xfc10, "table.size"),
+    (0xfc11, "table.fill"),
+    (0xfd00, "v128.load"),
+    (0xfd01, "v128.load8x8_s"),
+    (0xfd02, "v128.load8x8_u"),
+    (0xfd03, "v128.load16x4_s"),
+    (0xfd04, "v128.load16x4_u"),
+    (0xfd05, "v128.load32x2_s"),
+    (0xfd06, "v128.load32x2_u"),
+    (0xfd07, "v128.load8_splat"),
+    (0xfd08, "v128.load16_splat"),
+    (0xfd09, "v128.load32_splat"),
+    (0xfd0a, "v128.load64_splat"),
+    (0xfd0b, "v128.store"),
+    (0xfd0c, "v128.const"),
+    (0xfd0d, "i8x16.shuffle"),
+    (0xfd0e, "i8x16.swizzle"),
+    (0xfd0f, "i8x16.splat"),
+    (0xfd10, "i16x8.splat"),
+    (0xfd11, "i32x4.splat"),
+    (0xfd12, "i64x2.splat"),
+    (0xfd13, "f32x4.splat"),
+    (0xfd14, "f64x2.splat"),
+    (0xfd15, "i8x16.extract_lane_s"),
+    (0xfd16, "i8x16.extract_lane_u"),
+    (0xfd17, "i8x16.replace_lane"),
+    (0xfd18, "i16x8.extract_lane_s"),
+    (0xfd19, "i16x8.extract_lane_u"),
+    (0xfd1a, "i16x8.replace_lane"),
+    (0xfd1b, "i32x4.extract_lane"),
+    (0xfd1c, "i32x4.replace_lane"),
+    (0xfd1d, "i64x2.extract_lane"),
+    (0xfd1e, "i64x2.replace_lane"),
+    (0xfd1f, "f32x4.extract_lane"),
+    (0xfd20, "f32x4.replace_lane"),
+    (0xfd21, "f64x2.extract_lane"),
+    (0xfd22, "f64x2.replace_lane"),
+    (0xfd23, "i8x16.eq"),
+    (0xfd24, "i8x16.ne"),
+    (0xfd25, "i8x16.lt_s"),
+    (0xfd26, "i8x16.lt_u"),
+    (0xfd27, "i8x16.gt_s"),
+    (0xfd28, "i8x16.gt_u"),
+    (0xfd29, "i8x16.le_s"),
+    (0xfd2a, "i8x16.le_u"),
+    (0xfd2b, "i8x16.ge_s"),
+    (0xfd2c, "i8x16.ge_u"),
+    (0xfd2d, "i16x8.eq"),
+    (0xfd2e, "i16x8.ne"),
+    (0xfd2f, "i16x8.lt_s"),
+    (0xfd30, "i16x8.lt_u"),
+    (0xfd31, "i16x8.gt_s"),
+    (0xfd32, "i16x8.gt_u"),
+    (0xfd33, "i16x8.le_s"),
+    (0xfd34, "i16x8.le_u"),
+    (0xfd35, "i16x8.ge_s"),
+    (0xfd36, "i16x8.ge_u"),
+    (0xfd37, "i32x4.eq"),
+    (0xfd38, "i32x4.ne"),
+    (0xfd39, "i32x4.lt_s"),
+    (0xfd3a, "i32x4.lt_u"),
+    (0xfd3b, "i32x4.gt_s"),
+    (0xfd3c, "i32x4.gt_u"),
+    (0xfd3d, "i32x4.le_s"),
+    (0xfd3e, "i32x4.le_u"),
+    (0xfd3f, "i32x4.ge_s"),
+    (0xfd40, "i32x4.ge_u"),
+    (0xfd41, "f32x4.eq"),
+    (0xfd42, "f32x4.ne"),
+    (0xfd43, "f32x4.lt"),
+    (0xfd44, "f32x4.gt"),
+    (0xfd45, "f32x4.le"),
+    (0xfd46, "f32x4.ge"),
+    (0xfd47, "f64x2.eq"),
+    (0xfd48, "f64x2.ne"),
+    (0xfd49, "f64x2.lt"),
+    (0xfd4a, "f64x2.gt"),
+    (0xfd4b, "f64x2.le"),
+    (0xfd4c, "f64x2.ge"),
+    (0xfd4d, "v128.not"),
+    (0xfd4e, "v128.and"),
+    (0xfd4f, "v128.andnot"),
+    (0xfd50, "v128.or"),
+    (0xfd51, "v128.xor"),
+    (0xfd52, "v128.bitselect"),
+    (0xfd53, "v128.any_true"),
+    (0xfd54, "v128.load8_lane"),
+    (0xfd55, "v128.load16_lane"),
+    (0xfd56, "v128.load32_lane"),
+    (0xfd57, "v128.load64_lane"),
+    (0xfd58, "v128.store8_lane"),
+    (0xfd59, "v128.store16_lane"),
+    (0xfd5a, "v128.store32_lane"),
+    (0xfd5b, "v128.store64_lane"),
+    (0xfd5c, "v128.load32_zero"),
+    (0xfd5d, "v128.load64_zero"),
+    (0xfd5e, "f32x4.demote_f64x2_zero"),
+    (0xfd5f, "f64x2.promote_low_f32x4"),
+    (0xfd60, "i8x16.abs"),
+    (0xfd61, "i8x16.neg"),
+    (0xfd62, "i8x16.popcnt"),
+    (0xfd63, "i8x16.all_true"),
+    (0xfd64, "i8x16.bitmask"),
+    (0xfd65, "i8x16.narrow_i16x8_s"),
+    (0xfd66, "i8x16.narrow_i16x8_u"),
+    (0xfd67, "f32x4.ceil"),
+    (0xfd68, "f32x4.floor"),
+    (0xfd69, "f32x4.trunc"),
+    (0xfd6a, "f32x4.nearest"),
+    (0xfd6b, "i8x16.shl"),
+    (0xfd6c, "i8x16.shr_s"),
+    (0xfd6d, "i8x16.shr_u"),
+    (0xfd6e, "i8x16.add"),
+    (0xfd6f, "i8x16.add_sat_s"),
+    (0xfd70, "i8x16.add_sat_u"),
+    (0xfd71, "i8x16.sub"),
+    (0xfd72, "i8x16.sub_sat_s"),
+    (0xfd73, "i8x16.sub_sat_u"),
+    (0xfd74, "f64x2.ceil"),
+    (0xfd75, "f64x2.floor"),
+    (0xfd76, "i8x16.min_s"),
+    (0xfd77, "i8x16.min_u"),
+    (0xfd78, "i8x16.max_s"),
+    (0xfd79, "i8x16.max_u"),
+    (0xfd7a, "f64x2.trunc"),
+    (0xfd7b, "i8x16.avgr_u"),
+    (0xfd7c, "i16x8.extadd_pairwise_i8x16_s"),
+    (0xfd7d, "i16x8.extadd_pairwise_i8x16_u"),
+    (0xfd7e, "i32x4.extadd_pairwise_i16x8_s"),
+    (0xfd7f, "i32x4.extadd_pairwise_i16x8_u"),
+    (0xfd80, "i16x8.abs"),
+    (0xfd81, "i16x8.neg"),
+    (0xfd82, "i16x8.q15mulr_sat_s"),
+    (0xfd83, "i16x8.all_true"),
+    (0xfd84, "i16x8.bitmask"),
+    (0xfd85, "i16x8.narrow_i32x4_s"),
+    (0xfd86, "i16x8.narrow_i32x4_u"),
+    (0xfd87, "i16x8.extend_low_i8x16_s"),
+    (0xfd88, "i16x8.extend_high_i8x16_s"),
+    (0xfd89, "i16x8.extend_low_i8x16_u"),
+    (0xfd8a, "i16x8.extend_high_i8x16_u"),
+    (0xfd8b, "i16x8.shl"),
+    (0xfd8c, "i16x8.shr_s"),
+    (0xfd8d, "i16x8.shr_u"),
+    (0xfd8e, "i16x8.add"),
+    (0xfd8f, "i16x8.add_sat_s"),
+    (0xfd90, "i16x8.add_sat_u"),
+    (0xfd91, "i16x8.sub"),
+    (0xfd92, "i16x8.sub_sat_s"),
+    (0xfd93, "i16x8.sub_sat_u"),
+    (0xfd94, "f64x2.nearest"),
+    (0xfd95, "i16x8.mul"),
+    (0xfd96, "i16x8.min_s"),
+    (0xfd97, "i16x8.min_u"),
+    (0xfd98, "i16x8.max_s"),
+    (0xfd99, "i16x8.max_u"),
+    (0xfd9b, "i16x8.avgr_u"),
+    (0xfd9c, "i16x8.extmul_low_i8x16_s"),
+    (0xfd9d, "i16x8.extmul_high_i8x16_s"),
+    (0xfd9e, "i16x8.extmul_low_i8x16_u"),
+    (0xfd9f, "i16x8.extmul_high_i8x16_u"),
+    (0xfda0, "i32x4.abs"),
+    (0xfda1, "i32x4.neg"),
+    (0xfda3, "i32x4.all_true"),
+    (0xfda4, "i32x4.bitmask"),
+    (0xfda7, "i32x4.extend_low_i16x8_s"),
+    (0xfda8, "i32x4.extend_high_i16x8_s"),
+    (0xfda9, "i32x4.extend_low_i16x8_u"),
+    (0xfdaa, "i32x4.extend_high_i16x8_u"),
+    (0xfdab, "i32x4.shl"),
+    (0xfdac, "i32x4.shr_s"),
+    (0xfdad, "i32x4.shr_u"),
+    (0xfdae, "i32x4.add"),
+    (0xfdb1, "i32x4.sub"),
+    (0xfdb5, "i32x4.mul"),
+    (0xfdb6, "i32x4.min_s"),
+    (0xfdb7, "i32x4.min_u"),
+    (0xfdb8, "i32x4.max_s"),
+    (0xfdb9, "i32x4.max_u"),
+    (0xfdba, "i32x4.dot_i16x8_s"),
+    (0xfdbc, "i32x4.extmul_low_i16x8_s"),
+    (0xfdbd, "i32x4.extmul_high_i16x8_s"),
+    (0xfdbe, "i32x4.extmul_low_i16x8_u"),
+    (0xfdbf, "i32x4.extmul_high_i16x8_u"),
+    (0xfdc0, "i64x2.abs"),
+    (0xfdc1, "i64x2.neg"),
+    (0xfdc3, "i64x2.all_true"),
+    (0xfdc4, "i64x2.bitmask"),
+    (0xfdc7, "i64x2.extend_low_i32x4_s"),
+    (0xfdc8, "i64x2.extend_high_i32x4_s"),
+    (0xfdc9, "i64x2.extend_low_i32x4_u"),
+    (0xfdca, "i64x2.extend_high_i32x4_u"),
+    (0xfdcb, "i64x2.shl"),
+    (0xfdcc, "i64x2.shr_s"),
+    (0xfdcd, "i64x2.shr_u"),
+    (0xfdce, "i64x2.add"),
+    (0xfdd1, "i64x2.sub"),
+    (0xfdd5, "i64x2.mul"),
+    (0xfdd6, "i64x2.eq"),
+    (0xfdd7, "i64x2.ne"),
+    (0xfdd8, "i64x2.lt_s"),
+    (0xfdd9, "i64x2.gt_s"),
+    (0xfdda, "i64x2.le_s"),
+    (0xfddb, "i64x2.ge_s"),
+    (0xfddc, "i64x2.extmul_low_i32x4_s"),
+    (0xfddd, "i64x2.extmul_high_i32x4_s"),
+    (0xfdde, "i64x2.extmul_low_i32x4_u"),
+    (0xfddf, "i64x2.extmul_high_i32x4_u"),
+    (0xfde0, "f32x4.abs"),
+    (0xfde1, "f32x4.neg"),
+    (0xfde3, "f32x4.sqrt"),
+    (0xfde4, "f32x4.add"),
+    (0xfde5, "f32x4.sub"),
+    (0xfde6, "f32x4.mul"),
+    (0xfde7, "f32x4.div"),
+    (0xfde8, "f32x4.min"),
+    (0xfde9, "f32x4.max"),
+    (0xfdea, "f32x4.pmin"),
+    (0xfdeb, "f32x4.pmax"),
+    (0xfdec, "f64x2.abs"),
+    (0xfded, "f64x2.neg"),
+    (0xfdef, "f64x2.sqrt"),
+    (0xfdf0, "f64x2.add"),
+    (0xfdf1, "f64x2.sub"),
+    (0xfdf2, "f64x2.mul"),
+    (0xfdf3, "f64x2.div"),
+    (0xfdf4, "f64x2.min"),
+    (0xfdf5, "f64x2.max"),
+    (0xfdf6, "f64x2.pmin"),
+    (0xfdf7, "f64x2.pmax"),
+    (0xfdf8, "i32x4.trunc_sat_f32x4_s"),
+    (0xfdf9, "i32x4.trunc_sat_f32x4_u"),
+    (0xfdfa, "f32x4.convert_i32x4_s"),
+    (0xfdfb, "f32x4.convert_i32x4_u"),
+    (0xfdfc, "i32x4.trunc_sat_f64x2_s_zero"),
+    (0xfdfd, "i32x4.trunc_sat_f64x2_u_zero"),
+    (0xfdfe, "f64x2.convert_low_i32x4_s"),
+    (0xfdff, "f64x2.convert_low_i32x4_u"),
+    (0xfe00, "memory.atomic.notify"),
+    (0xfe01, "memory.atomic.wait32"),
+    (0xfe02, "memory.atomic.wait64"),
+    (0xfe03, "atomic.fence"),
+    (0xfe10, "i32.atomic.load"),
+    (0xfe11, "i64.atomic.load"),
+    (0xfe12, "i32.atomic.load8_u"),
+    (0xfe13, "i32.atomic.load16_u"),
+    (0xfe14, "i64.atomic.load8_u"),
+    (0xfe15, "i64.atomic.load16_u"),
+    (0xfe16, "i64.atomic.load32_u"),
+    (0xfe17, "i32.atomic.store"),
+    (0xfe18, "i64.atomic.store"),
+    (0xfe19, "i32.atomic.store8"),
+    (0xfe1a, "i32.atomic.store16"),
+    (0xfe1b, "i64.atomic.store8"),
+    (0xfe1c, "i64.atomic.store16"),
+    (0xfe1d, "i64.atomic.store32"),
+    (0xfe1e, "i32.atomic.rmw.add"),
+    (0xfe1f, "i64.atomic.rmw.add"),
+    (0xfe20, "i32.atomic.rmw8.add_u"),
+    (0xfe21, "i32.atomic.rmw16.add_u"),
+    (0xfe22, "i64.atomic.rmw8.add_u"),
+    (0xfe23, "i64.atomic.rmw16.add_u"),
+    (0xfe24, "i64.atomic.rmw32.add_u"),
+    (0xfe25, "i32.atomic.rmw.sub"),
+    (0xfe26, "i64.atomic.rmw.sub"),
+    (0xfe27, "i32.atomic.rmw8.sub_u"),
+    (0xfe28, "i32.atomic.rmw16.sub_u"),
+    (0xfe29, "i64.atomic.rmw8.sub_u"),
+    (0xfe2a, "i64.atomic.rmw16.sub_u"),
+    (0xfe2b, "i64.atomic.rmw32.sub_u"),
+    (0xfe2c, "i32.atomic.rmw.and"),
+    (0xfe2d, "i64.atomic.rmw.and"),
+    (0xfe2e, "i32.atomic.rmw8.and_u"),
+    (0xfe2f, "i32.atomic.rmw16.and_u"),
+    (0xfe30, "i64.atomic.rmw8.and_u"),
+    (0xfe31, "i64.atomic.rmw16.and_u"),
+    (0xfe32, "i64.atomic.rmw32.and_u"),
+    (0xfe33, "i32.atomic.rmw.or"),
+    (0xfe34, "i64.atomic.rmw.or"),
+    (0xfe35, "i32.atomic.rmw8.or_u"),
+    (0xfe36, "i32.atomic.rmw16.or_u"),
+    (0xfe37, "i64.atomic.rmw8.or_u"),
+    (0xfe38, "i64.atomic.rmw16.or_u"),
+    (0xfe39, "i64.atomic.rmw32.or_u"),
+    (0xfe3a, "i32.atomic.rmw.xor"),
+    (0xfe3b, "i64.atomic.rmw.xor"),
+    (0xfe3c, "i32.atomic.rmw8.xor_u"),
+    (0xfe3d, "i32.atomic.rmw16.xor_u"),
+    (0xfe3e, "i64.atomic.rmw8.xor_u"),
+    (0xfe3f, "i64.atomic.rmw16.xor_u"),
+    (0xfe40, "i64.atomic.rmw32.xor_u"),
+    (0xfe41, "i32.atomic.rmw.xchg"),
+    (0xfe42, "i64.atomic.rmw.xchg"),
+    (0xfe43, "i32.atomic.rmw8.xchg_u"),
+    (0xfe44, "i32.atomic.rmw16.xchg_u"),
+    (0xfe45, "i64.atomic.rmw8.xchg_u"),
+    (0xfe46, "i64.atomic.rmw16.xchg_u"),
+    (0xfe47, "i64.atomic.rmw32.xchg_u"),
+    (0xfe48, "i32.atomic.rmw.cmpxchg"),
+    (0xfe49, "i64.atomic.rmw.cmpxchg"),
+    (0xfe4a, "i32.atomic.rmw8.cmpxchg_u"),
+    (0xfe4b, "i32.atomic.rmw16.cmpxchg_u"),
+    (0xfe4c, "i64.atomic.rmw8.cmpxchg_u"),
+    (0xfe4d, "i64.atomic.rmw16.cmpxchg_u"),
+    (0xfe4e, "i64.atomic.rmw32.cmpxchg_u"),
+];
+
+/// 指令名对应的指令码，select 带类型的形式需要单独处理
+/// else 和 end 属于块的结构，不能作为单独的指令
+pub fn opcode(name: &str) -> Option<u16> {
+    INSTR_NAMES
+        .iter()
+        .filter(|(opcode, _)| *opcode != 0x05 && *opcode != 0x0b)
+        .find(|(_, instr_name)| *instr_name == name)
+        .map(|(opcode, _)| *opcode)
+}
+
+/// 内存指令的默认对齐，即访问的字节数
+pub fn natural_align(name: &str) -> u32 {
+    let (type_, op) = name.split_once('.').unwrap_or_default();
+    // 原子指令的宽度在 atomic. 之后，比如 i32.atomic.rmw8.add_u、memory.atomic.wait64
+    let op = op.strip_prefix("atomic.").unwrap_or(op);
+    let width = op.trim_start_matches(char::is_alphabetic);
+    let bits = width
+        .split(|c: char| !c.is_ascii_digit())
+        .next()
+        .unwrap_or_default();
+
+    match bits.parse::<u32>() {
+        // v128.load8x8_s 这类一次读 64 位
+        Ok(_) if width[bits.len()..].starts_with('x') => 8,
+        Ok(bits) => bits / 8,
+        Err(_) => match type_ {
+            "i64" | "f64" => 8,
+            "v128" => 16,
+            _ => 4,
+        },
+    }
+}
+
+impl Instruction {
+    pub fn name(&self) -> &'static str {
+        let opcode = self.discriminant();
+
+        INSTR_NAMES
+            .iter()
+            .find(|(code, _)| *code == opcode)
+            .map(|(_, name)| *name)
+            .unwrap()
+    }
+}
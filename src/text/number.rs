@@ -0,0 +1,277 @@
+/// https://webassembly.github.io/spec/core/text/values.html#integers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumErr {
+    Invalid,
+    OutOfRange,
+}
+
+pub type NumResult<T> = Result<T, NumErr>;
+
+/// 去掉下划线，下划线只能出现在两个数字之间
+fn strip_underscore(s: &str, radix: u32) -> NumResult<String> {
+    if s.is_empty() || s.split('_').any(|part| part.is_empty()) {
+        return Err(NumErr::Invalid);
+    }
+
+    let digits = s.replace('_', "");
+
+    match digits.chars().all(|c| c.is_digit(radix)) {
+        true => Ok(digits),
+        false => Err(NumErr::Invalid),
+    }
+}
+
+fn split_sign(s: &str) -> (bool, bool, &str) {
+    match s.as_bytes().first() {
+        Some(b'-') => (true, true, &s[1..]),
+        Some(b'+') => (true, false, &s[1..]),
+        _ => (false, false, s),
+    }
+}
+
+fn parse_magnitude(s: &str) -> NumResult<u64> {
+    let (digits, radix) = match s.strip_prefix("0x") {
+        Some(hex) => (strip_underscore(hex, 16)?, 16),
+        None => (strip_underscore(s, 10)?, 10),
+    };
+
+    u64::from_str_radix(&digits, radix).map_err(|_| NumErr::OutOfRange)
+}
+
+/// 索引、对齐、偏移等无符号数
+pub fn parse_u32(s: &str) -> NumResult<u32> {
+    if s.starts_with(['+', '-']) {
+        return Err(NumErr::Invalid);
+    }
+
+    parse_magnitude(s)?.try_into().map_err(|_| NumErr::OutOfRange)
+}
+
+/// memory64 的偏移和大小
+pub fn parse_u64(s: &str) -> NumResult<u64> {
+    if s.starts_with(['+', '-']) {
+        return Err(NumErr::Invalid);
+    }
+
+    parse_magnitude(s)
+}
+
+/// 位宽为 bits 的整数，有符号和无符号写法都接受，返回补码
+pub fn parse_int(s: &str, bits: u32) -> NumResult<u64> {
+    let (signed, negative, s) = split_sign(s);
+    let magnitude = parse_magnitude(s)?;
+    let limit = match signed {
+        true if negative => 1u128 << (bits - 1),
+        true => (1u128 << (bits - 1)) - 1,
+        false => (1u128 << bits) - 1,
+    };
+
+    if magnitude as u128 > limit {
+        return Err(NumErr::OutOfRange);
+    }
+
+    let mask = ((1u128 << bits) - 1) as u64;
+
+    match negative {
+        true => Ok(magnitude.wrapping_neg() & mask),
+        false => Ok(magnitude),
+    }
+}
+
+pub fn parse_f32(s: &str) -> NumResult<f32> {
+    parse_float(s, 23, 8).map(|bits| f32::from_bits(bits as u32))
+}
+
+pub fn parse_f64(s: &str) -> NumResult<f64> {
+    parse_float(s, 52, 11).map(f64::from_bits)
+}
+
+/// https://webassembly.github.io/spec/core/text/values.html#floating-point
+fn parse_float(s: &str, mant_bits: u32, exp_bits: u32) -> NumResult<u64> {
+    let (_, negative, s) = split_sign(s);
+    let sign = (negative as u64) << (mant_bits + exp_bits);
+    let exp_mask = ((1u64 << exp_bits) - 1) << mant_bits;
+
+    let bits = if s == "inf" {
+        exp_mask
+    } else if s == "nan" {
+        exp_mask | 1 << (mant_bits - 1)
+    } else if let Some(payload) = s.strip_prefix("nan:0x") {
+        let payload =
+            u64::from_str_radix(&strip_underscore(payload, 16)?, 16).map_err(|_| NumErr::OutOfRange)?;
+
+        if payload == 0 || payload >> mant_bits != 0 {
+            return Err(NumErr::OutOfRange);
+        }
+
+        exp_mask | payload
+    } else if let Some(hex) = s.strip_prefix("0x") {
+        parse_hex_float(hex, mant_bits, exp_bits)?
+    } else {
+        parse_dec_float(s, mant_bits)?
+    };
+
+    Ok(sign | bits)
+}
+
+/// 十进制交给标准库处理，它同样按就近舍入
+fn parse_dec_float(s: &str, mant_bits: u32) -> NumResult<u64> {
+    let (mantissa, exp) = match s.find(['e', 'E']) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+    let (int, frac) = match mantissa.split_once('.') {
+        Some((int, frac)) => (int, Some(frac)),
+        None => (mantissa, None),
+    };
+    let mut text = strip_underscore(int, 10)?;
+
+    if let Some(frac) = frac.filter(|frac| !frac.is_empty()) {
+        text.push('.');
+        text.push_str(&strip_underscore(frac, 10)?);
+    }
+
+    if let Some(exp) = exp {
+        let (_, negative, exp) = split_sign(exp);
+
+        text.push('e');
+        text.push_str(if negative { "-" } else { "" });
+        text.push_str(&strip_underscore(exp, 10)?);
+    }
+
+    let bits = match mant_bits {
+        23 => text.parse::<f32>().map(|v| (v.is_finite(), v.to_bits() as u64)),
+        _ => text.parse::<f64>().map(|v| (v.is_finite(), v.to_bits())),
+    };
+
+    match bits {
+        Ok((true, bits)) => Ok(bits),
+        Ok((false, _)) => Err(NumErr::OutOfRange),
+        Err(_) => Err(NumErr::Invalid),
+    }
+}
+
+/// 十六进制浮点数，手动按就近舍入（相等时取偶数）
+fn parse_hex_float(s: &str, mant_bits: u32, exp_bits: u32) -> NumResult<u64> {
+    let (mantissa, exp) = match s.find(['p', 'P']) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+    let (int, frac) = match mantissa.split_once('.') {
+        Some((int, frac)) => (int, frac),
+        None => (mantissa, ""),
+    };
+    let int = strip_underscore(int, 16)?;
+    let frac = match frac.is_empty() {
+        true => String::new(),
+        false => strip_underscore(frac, 16)?,
+    };
+
+    // 值为 m * 2^e，放不下的低位只记录是否非零
+    let mut m = 0u64;
+    let mut e = 0i64;
+    let mut sticky = false;
+
+    for (i, c) in int.chars().chain(frac.chars()).enumerate() {
+        let digit = c.to_digit(16).unwrap() as u64;
+        let is_frac = i >= int.len();
+
+        if m >> 60 == 0 {
+            m = m << 4 | digit;
+            e -= if is_frac { 4 } else { 0 };
+        } else {
+            e += if is_frac { 0 } else { 4 };
+            sticky |= digit != 0;
+        }
+    }
+
+    if let Some(exp) = exp {
+        let (_, negative, exp) = split_sign(exp);
+        let exp = strip_underscore(exp, 10)?;
+        let exp = exp.parse::<i64>().unwrap_or(i64::MAX / 2).min(i64::MAX / 2);
+
+        e += if negative { -exp } else { exp };
+    }
+
+    if m == 0 {
+        return Ok(0);
+    }
+
+    let bias = (1i64 << (exp_bits - 1)) - 1;
+    let emin = 1 - bias;
+    let top = 63 - m.leading_zeros() as i64 + e;
+    let mut lsb = (top - mant_bits as i64).max(emin - mant_bits as i64);
+    let shift = lsb - e;
+    let mut r = if shift <= 0 {
+        (m as u128) << -shift
+    } else if shift >= 128 {
+        0
+    } else {
+        let m = m as u128;
+        let rem = m & ((1 << shift) - 1);
+        let half = 1 << (shift - 1);
+        let r = m >> shift;
+
+        match rem > half || (rem == half && (sticky || r & 1 == 1)) {
+            true => r + 1,
+            false => r,
+        }
+    };
+
+    if r >> (mant_bits + 1) != 0 {
+        r >>= 1;
+        lsb += 1;
+    }
+
+    if r >> mant_bits == 0 {
+        return Ok(r as u64);
+    }
+
+    let biased = lsb + mant_bits as i64 + bias;
+
+    if biased >= (1 << exp_bits) - 1 {
+        return Err(NumErr::OutOfRange);
+    }
+
+    Ok((biased as u64) << mant_bits | (r as u64 & ((1 << mant_bits) - 1)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_f32, parse_f64, parse_int, parse_u32, NumErr};
+
+    #[test]
+    fn test_parse_int() {
+        assert_eq!(parse_int("-1", 32), Ok(0xffff_ffff));
+        assert_eq!(parse_int("0xffff_ffff", 32), Ok(0xffff_ffff));
+        assert_eq!(parse_int("-0x8000_0000", 32), Ok(0x8000_0000));
+        assert_eq!(parse_int("+0x8000_0000", 32), Err(NumErr::OutOfRange));
+        assert_eq!(parse_int("4294967296", 32), Err(NumErr::OutOfRange));
+        assert_eq!(parse_int("1__0", 32), Err(NumErr::Invalid));
+        assert_eq!(parse_int("_1", 32), Err(NumErr::Invalid));
+        assert_eq!(parse_int("0x", 64), Err(NumErr::Invalid));
+        assert_eq!(parse_u32("-1"), Err(NumErr::Invalid));
+    }
+
+    #[test]
+    fn test_parse_float() {
+        assert_eq!(parse_f32("0x1.fffffep127"), Ok(f32::MAX));
+        assert_eq!(parse_f32("0x1p128"), Err(NumErr::OutOfRange));
+        assert_eq!(parse_f32("0x1.ffffffp127"), Err(NumErr::OutOfRange));
+        assert_eq!(parse_f32("0x1p-149").map(f32::to_bits), Ok(1));
+        // 恰好一半时舍入到偶数
+        assert_eq!(parse_f32("0x1p-150").map(f32::to_bits), Ok(0));
+        assert_eq!(parse_f32("0x1.000002p-150").map(f32::to_bits), Ok(1));
+        assert_eq!(parse_f32("0x1.000001p0").map(f32::to_bits), Ok(0x3f80_0000));
+        assert_eq!(parse_f32("0x1.000003p0").map(f32::to_bits), Ok(0x3f80_0002));
+        assert_eq!(parse_f64("0x1.fffffffffffffp-1023"), Ok(f64::MIN_POSITIVE));
+        assert_eq!(parse_f64("-0x0p0").map(f64::to_bits), Ok(1 << 63));
+        assert_eq!(parse_f32("1_000.5e-1"), Ok(100.05));
+        assert_eq!(parse_f32("1e39"), Err(NumErr::OutOfRange));
+        assert_eq!(parse_f32("-inf"), Ok(f32::NEG_INFINITY));
+        assert_eq!(parse_f32("nan:0x200000").map(f32::to_bits), Ok(0x7fa0_0000));
+        assert_eq!(parse_f64("-nan").map(f64::to_bits), Ok(0xfff8_0000_0000_0000));
+        assert_eq!(parse_f32("nan:0x0"), Err(NumErr::OutOfRange));
+        assert_eq!(parse_f32(".5"), Err(NumErr::Invalid));
+    }
+}
@@ -0,0 +1,1773 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use super::errors::{ParseErr, Pos};
+use super::lexer::{Lexer, Token};
+use super::names::{natural_align, opcode};
+use super::number::{self, NumErr, NumResult};
+use crate::binary::decode::Decode;
+use crate::binary::encode::{Encode, Encodes};
+use crate::binary::instruction::{
+    Block, BlockType, BrTableArg, Catch, IfBlock, Instruction, MemoryArg, TryTable,
+};
+use crate::binary::module::Module;
+use crate::binary::reader::Reader;
+use crate::binary::section::{
+    CodeSeg, DataMode, DataSeg, ElementMode, ElementSeg, ExportDesc, ExportSeg, Expr, FuncIdx,
+    GlobalSeg, ImportDesc, ImportSeg, LabelIdx, LocalIdx, Locals, TagType, TypeIdx,
+};
+use crate::binary::types::{FuncType, GlobalType, Limits, RefType, TableType, ValType};
+use crate::execution::value::v128;
+
+pub type ParseResult<T> = Result<T, Box<dyn Error>>;
+
+/// 参数类型、参数名、结果类型
+type Signature = (Vec<ValType>, Vec<Option<String>>, Vec<ValType>);
+
+/// 索引空间
+/// https://webassembly.github.io/spec/core/text/modules.html#indices
+#[derive(Debug, Clone, Copy)]
+enum Space {
+    Type,
+    Func,
+    Table,
+    Mem,
+    Global,
+    Elem,
+    Data,
+    Tag,
+}
+
+impl Space {
+    fn from_kind(kind: &str) -> Option<Self> {
+        match kind {
+            "func" => Some(Self::Func),
+            "table" => Some(Self::Table),
+            "memory" => Some(Self::Mem),
+            "global" => Some(Self::Global),
+            "tag" => Some(Self::Tag),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::Type => "类型",
+            Self::Func => "函数",
+            Self::Table => "表",
+            Self::Mem => "内存",
+            Self::Global => "全局变量",
+            Self::Elem => "元素段",
+            Self::Data => "数据段",
+            Self::Tag => "异常标签",
+        }
+    }
+}
+
+/// 文本格式解析，分两遍：
+/// 第一遍收集各索引空间的 $id 和类型定义，第二遍按字段生成 Module
+pub struct Parser {
+    tokens: Vec<(Token, Pos)>,
+    cursor: usize,
+    module: Module,
+    ids: [HashMap<String, u32>; 8],
+    counts: [u32; 8],
+    /// 当前函数的参数和局部变量
+    locals: HashMap<String, LocalIdx>,
+    /// 当前的块标签，由外到内
+    labels: Vec<Option<String>>,
+    /// 已经出现过函数、表、内存或全局变量的定义
+    defined: bool,
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<(Token, Pos)>) -> Self {
+        Self {
+            tokens,
+            cursor: 0,
+            module: Module::new(),
+            ids: Default::default(),
+            counts: Default::default(),
+            locals: HashMap::new(),
+            labels: vec![],
+            defined: false,
+        }
+    }
+
+    /// 解析一个模块，支持 (module ...)、(module binary ...)、(module quote ...) 以及省略 module 的写法
+    pub fn parse(text: &str) -> ParseResult<Module> {
+        let mut parser = Self::new(Lexer::tokenize(text)?);
+
+        parser.module()
+    }
+
+    fn module(&mut self) -> ParseResult<Module> {
+        let wrapped = self.eat_sexpr("module");
+
+        if wrapped {
+            self.module.names.module = self.opt_id();
+
+            if self.eat_keyword("binary") {
+                let data = self.strings()?;
+
+                self.rparen()?;
+                self.eof()?;
+
+                return Module::from_data(data);
+            }
+
+            if self.eat_keyword("quote") {
+                let pos = self.pos();
+                let text = String::from_utf8(self.strings()?).map_err(|_| ParseErr::InvalidUtf8(pos))?;
+
+                self.rparen()?;
+                self.eof()?;
+
+                return Self::parse(&text);
+            }
+        }
+
+        self.fields()?;
+
+        if wrapped {
+            self.rparen()?;
+        }
+
+        self.eof()?;
+
+        Ok(std::mem::replace(&mut self.module, Module::new()))
+    }
+
+    fn fields(&mut self) -> ParseResult<()> {
+        self.collect_ids()?;
+        self.counts = Default::default();
+
+        while self.peek() == Some(&Token::LParen) {
+            self.cursor += 1;
+
+            let pos = self.pos();
+
+            match self.keyword()?.as_str() {
+                "type" => self.skip_to_rparen()?,
+                "import" => self.import(pos)?,
+                "func" => self.func(pos)?,
+                "table" => self.table(pos)?,
+                "memory" => self.memory(pos)?,
+                "global" => self.global(pos)?,
+                "tag" => self.tag(pos)?,
+                "export" => self.export()?,
+                "start" => self.module.start_sec = Some(self.index(Space::Func)?),
+                "elem" => self.elem()?,
+                "data" => self.data()?,
+                kw => Err(ParseErr::Expected(pos, "模块字段".to_string(), kw.to_string()))?,
+            }
+
+            self.rparen()?;
+        }
+
+        if self.module.code_sec.iter().any(|code| uses_data_idx(&code.body)) {
+            self.module.data_counat_sec = Some(self.module.data_sec.len() as u32);
+        }
+
+        // 函数的 $id 作为名字，出错时可以显示在调用栈中
+        for (id, idx) in &self.ids[Space::Func as usize] {
+            self.module.names.funcs.insert(*idx, id.clone());
+        }
+
+        Ok(())
+    }
+
+    /// 第一遍：分配索引、登记 $id，并解析全部类型定义
+    fn collect_ids(&mut self) -> ParseResult<()> {
+        let start = self.cursor;
+
+        while self.peek() == Some(&Token::LParen) {
+            self.cursor += 1;
+
+            let pos = self.pos();
+            let kw = self.keyword()?;
+
+            match kw.as_str() {
+                "type" => {
+                    let id = self.opt_id();
+
+                    self.define(Space::Type, id, pos)?;
+                    self.expect_sexpr("func")?;
+
+                    let (params, _, results) = self.signature()?;
+
+                    self.rparen()?;
+                    self.module.type_sec.push(FuncType { params, results });
+                }
+                "import" => {
+                    self.name()?;
+                    self.name()?;
+                    self.lparen()?;
+
+                    let pos = self.pos();
+                    let kind = self.keyword()?;
+                    let space = Space::from_kind(&kind)
+                        .ok_or_else(|| ParseErr::Expected(pos, "导入类型".to_string(), kind.clone()))?;
+                    let id = self.opt_id();
+
+                    self.define(space, id, pos)?;
+                    self.skip_to_rparen()?;
+                    self.rparen()?;
+                }
+                "func" | "table" | "memory" | "global" | "tag" => {
+                    let id = self.opt_id();
+
+                    self.define(Space::from_kind(&kw).unwrap(), id, pos)?;
+
+                    if kw == "table" && self.has_child("elem") {
+                        self.define(Space::Elem, None, pos)?;
+                    }
+
+                    if kw == "memory" && self.has_child("data") {
+                        self.define(Space::Data, None, pos)?;
+                    }
+                }
+                "elem" => {
+                    let id = self.opt_id();
+
+                    self.define(Space::Elem, id, pos)?;
+                }
+                "data" => {
+                    let id = self.opt_id();
+
+                    self.define(Space::Data, id, pos)?;
+                }
+                _ => (),
+            }
+
+            self.skip_to_rparen()?;
+            self.rparen()?;
+        }
+
+        self.cursor = start;
+
+        Ok(())
+    }
+
+    fn define(&mut self, space: Space, id: Option<String>, pos: Pos) -> ParseResult<()> {
+        let idx = self.next_index(space);
+
+        if let Some(id) = id {
+            if self.ids[space as usize].insert(id.clone(), idx).is_some() {
+                Err(ParseErr::DuplicateId(pos, space.describe(), id))?
+            }
+        }
+
+        Ok(())
+    }
+
+    fn next_index(&mut self, space: Space) -> u32 {
+        let idx = self.counts[space as usize];
+
+        self.counts[space as usize] += 1;
+
+        idx
+    }
+
+    /// https://webassembly.github.io/spec/core/text/modules.html#imports
+    fn import(&mut self, pos: Pos) -> ParseResult<()> {
+        let module = self.name()?;
+        let name = self.name()?;
+
+        self.lparen()?;
+
+        let kind_pos = self.pos();
+        let kind = self.keyword()?;
+        let space = Space::from_kind(&kind)
+            .ok_or_else(|| ParseErr::Expected(kind_pos, "导入类型".to_string(), kind.clone()))?;
+
+        self.opt_id();
+        self.next_index(space);
+
+        let desc = match space {
+            Space::Func => ImportDesc::Func(self.type_use()?.0),
+            Space::Table => ImportDesc::Table(self.table_type()?),
+            Space::Mem => ImportDesc::Mem(self.mem_type()?),
+            Space::Tag => ImportDesc::Tag(TagType {
+                type_idx: self.type_use()?.0,
+            }),
+            _ => ImportDesc::Global(self.global_type()?),
+        };
+
+        self.rparen()?;
+        self.push_import(pos, module, name, desc)
+    }
+
+    fn push_import(
+        &mut self,
+        pos: Pos,
+        module: String,
+        name: String,
+        desc: ImportDesc,
+    ) -> ParseResult<()> {
+        if self.defined {
+            Err(ParseErr::ImportAfterDefinition(pos))?
+        }
+
+        self.module.import_sec.push(ImportSeg { module, name, desc });
+
+        Ok(())
+    }
+
+    /// 内联的 (import "m" "n")
+    fn inline_import(&mut self) -> ParseResult<Option<(String, String)>> {
+        if !self.eat_sexpr("import") {
+            return Ok(None);
+        }
+
+        let module = self.name()?;
+        let name = self.name()?;
+
+        self.rparen()?;
+
+        Ok(Some((module, name)))
+    }
+
+    /// 内联的 (export "n")*
+    fn inline_exports(&mut self, desc: ExportDesc) -> ParseResult<()> {
+        while self.eat_sexpr("export") {
+            let name = self.name()?;
+
+            self.rparen()?;
+            self.module.export_sec.push(ExportSeg {
+                name,
+                desc: desc.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// https://webassembly.github.io/spec/core/text/modules.html#functions
+    fn func(&mut self, pos: Pos) -> ParseResult<()> {
+        self.opt_id();
+
+        let idx = self.next_index(Space::Func);
+
+        self.inline_exports(ExportDesc::Func(idx))?;
+
+        let import = self.inline_import()?;
+        let (type_idx, param_names) = self.type_use()?;
+
+        if let Some((module, name)) = import {
+            return self.push_import(pos, module, name, ImportDesc::Func(type_idx));
+        }
+
+        self.defined = true;
+        self.locals.clear();
+
+        let mut local_idx = param_names.len() as LocalIdx;
+
+        for (i, id) in param_names.into_iter().enumerate() {
+            if let Some(id) = id {
+                self.bind_local(id, i as LocalIdx, pos)?;
+            }
+        }
+
+        let mut locals: Vec<Locals> = vec![];
+
+        while self.eat_sexpr("local") {
+            let pos = self.pos();
+            let types = match self.opt_id() {
+                Some(id) => {
+                    self.bind_local(id, local_idx, pos)?;
+
+                    vec![self.val_type()?]
+                }
+                None => self.val_types()?,
+            };
+
+            for value_type in types {
+                local_idx += 1;
+
+                match locals.last_mut() {
+                    Some(last) if last.value_type == value_type => last.n += 1,
+                    _ => locals.push(Locals { n: 1, value_type }),
+                }
+            }
+
+            self.rparen()?;
+        }
+
+        let body = self.instrs()?;
+        let size = (locals.encodes(false).len() + body.encode().len()) as u32;
+
+        self.module.func_sec.push(type_idx);
+        self.module.code_sec.push(CodeSeg {
+            size,
+            locals,
+            body,
+            offset: 0,
+            instr_offsets: vec![],
+        });
+
+        Ok(())
+    }
+
+    fn bind_local(&mut self, id: String, idx: LocalIdx, pos: Pos) -> ParseResult<()> {
+        if self.locals.insert(id.clone(), idx).is_some() {
+            Err(ParseErr::DuplicateId(pos, "局部变量", id))?
+        }
+
+        Ok(())
+    }
+
+    /// https://webassembly.github.io/spec/core/text/modules.html#tables
+    fn table(&mut self, pos: Pos) -> ParseResult<()> {
+        self.opt_id();
+
+        let idx = self.next_index(Space::Table);
+
+        self.inline_exports(ExportDesc::Table(idx))?;
+
+        if let Some((module, name)) = self.inline_import()? {
+            let table_type = self.table_type()?;
+
+            return self.push_import(pos, module, name, ImportDesc::Table(table_type));
+        }
+
+        self.defined = true;
+
+        let is64 = self.is64();
+
+        if self.is_index_next() {
+            let limits = self.sized_limits(is64)?;
+            let elem_type = self.ref_type()?;
+
+            self.module.table_sec.push(TableType { elem_type, limits });
+
+            return Ok(());
+        }
+
+        // reftype (elem ...)
+        let elem_type = self.ref_type()?;
+
+        self.expect_sexpr("elem")?;
+
+        let (func_idxs, init_expr) = match self.peek() == Some(&Token::LParen) {
+            true => (vec![], self.elem_exprs()?),
+            false => (self.func_idxs()?, vec![]),
+        };
+        let n = func_idxs.len().max(init_expr.len()) as u64;
+        let explicit = idx != 0 || elem_type != RefType::FuncRef;
+        let flag = match (init_expr.is_empty(), explicit) {
+            (true, false) => 0,
+            (true, true) => 2,
+            (false, false) => 4,
+            (false, true) => 6,
+        };
+
+        self.rparen()?;
+        self.next_index(Space::Elem);
+        self.module.table_sec.push(TableType {
+            elem_type,
+            limits: Limits {
+                min: n,
+                max: Some(n),
+                is64,
+                shared: false,
+            },
+        });
+        self.module.elem_sec.push(ElementSeg {
+            flag,
+            mode: ElementMode::Active {
+                table_idx: idx,
+                offset_expr: vec![addr_zero(is64)],
+            },
+            type_: elem_type.as_val_type(),
+            elem_kind: 0,
+            func_idxs,
+            init_expr,
+        });
+
+        Ok(())
+    }
+
+    /// https://webassembly.github.io/spec/core/text/modules.html#memories
+    fn memory(&mut self, pos: Pos) -> ParseResult<()> {
+        self.opt_id();
+
+        let idx = self.next_index(Space::Mem);
+
+        self.inline_exports(ExportDesc::Mem(idx))?;
+
+        if let Some((module, name)) = self.inline_import()? {
+            let limits = self.mem_type()?;
+
+            return self.push_import(pos, module, name, ImportDesc::Mem(limits));
+        }
+
+        self.defined = true;
+
+        let is64 = self.is64();
+
+        if !self.eat_sexpr("data") {
+            let limits = self.mem_limits(is64)?;
+
+            self.module.mem_sec.push(limits);
+
+            return Ok(());
+        }
+
+        let init = self.strings()?;
+        let pages = init.len().div_ceil(65536) as u64;
+
+        self.rparen()?;
+        self.next_index(Space::Data);
+        self.module.mem_sec.push(Limits {
+            min: pages,
+            max: Some(pages),
+            is64,
+            shared: false,
+        });
+        self.module.data_sec.push(DataSeg {
+            flag: if idx == 0 { 0 } else { 2 },
+            mode: DataMode::Active,
+            init,
+            mem_idx: idx,
+            offset_expr: vec![addr_zero(is64)],
+        });
+
+        Ok(())
+    }
+
+    /// https://webassembly.github.io/spec/core/text/modules.html#globals
+    fn global(&mut self, pos: Pos) -> ParseResult<()> {
+        self.opt_id();
+
+        let idx = self.next_index(Space::Global);
+
+        self.inline_exports(ExportDesc::Global(idx))?;
+
+        let import = self.inline_import()?;
+        let type_ = self.global_type()?;
+
+        if let Some((module, name)) = import {
+            return self.push_import(pos, module, name, ImportDesc::Global(type_));
+        }
+
+        self.defined = true;
+
+        let init_expr = self.instrs()?;
+
+        self.module.global_sec.push(GlobalSeg { type_, init_expr });
+
+        Ok(())
+    }
+
+    /// (tag $id? (export "n")* (import "m" "n")? typeuse)
+    fn tag(&mut self, pos: Pos) -> ParseResult<()> {
+        self.opt_id();
+
+        let idx = self.next_index(Space::Tag);
+
+        self.inline_exports(ExportDesc::Tag(idx))?;
+
+        let import = self.inline_import()?;
+        let type_ = TagType {
+            type_idx: self.type_use()?.0,
+        };
+
+        if let Some((module, name)) = import {
+            return self.push_import(pos, module, name, ImportDesc::Tag(type_));
+        }
+
+        self.defined = true;
+        self.module.tag_sec.push(type_);
+
+        Ok(())
+    }
+
+    /// https://webassembly.github.io/spec/core/text/modules.html#exports
+    fn export(&mut self) -> ParseResult<()> {
+        let name = self.name()?;
+
+        self.lparen()?;
+
+        let pos = self.pos();
+        let kind = self.keyword()?;
+        let desc = match Space::from_kind(&kind) {
+            Some(Space::Func) => ExportDesc::Func(self.index(Space::Func)?),
+            Some(Space::Table) => ExportDesc::Table(self.index(Space::Table)?),
+            Some(Space::Mem) => ExportDesc::Mem(self.index(Space::Mem)?),
+            Some(Space::Global) => ExportDesc::Global(self.index(Space::Global)?),
+            Some(Space::Tag) => ExportDesc::Tag(self.index(Space::Tag)?),
+            _ => Err(ParseErr::Expected(pos, "导出类型".to_string(), kind))?,
+        };
+
+        self.rparen()?;
+        self.module.export_sec.push(ExportSeg { name, desc });
+
+        Ok(())
+    }
+
+    /// https://webassembly.github.io/spec/core/text/modules.html#element-segments
+    fn elem(&mut self) -> ParseResult<()> {
+        self.opt_id();
+        self.next_index(Space::Elem);
+
+        let declare = self.eat_keyword("declare");
+        let mut table_idx = None;
+
+        if self.eat_sexpr("table") {
+            table_idx = Some(self.index(Space::Table)?);
+            self.rparen()?;
+        }
+
+        let offset_expr = self.offset()?;
+        // 写成 reftype 加表达式列表时用 4~7 的编码
+        let (type_, is_expr, func_idxs, init_expr) = if self.eat_keyword("func") {
+            (RefType::FuncRef, false, self.func_idxs()?, vec![])
+        } else if self.peek_keyword().and_then(ref_type).is_some() {
+            (self.ref_type()?, true, vec![], self.elem_exprs()?)
+        } else {
+            (RefType::FuncRef, false, self.func_idxs()?, vec![])
+        };
+        let (flag, mode) = match offset_expr {
+            _ if declare => (3, ElementMode::Declarative),
+            None => (1, ElementMode::Passive),
+            Some(offset_expr) => {
+                let explicit = table_idx.is_some() || type_ != RefType::FuncRef;
+
+                (
+                    explicit as u32 * 2,
+                    ElementMode::Active {
+                        table_idx: table_idx.unwrap_or(0),
+                        offset_expr,
+                    },
+                )
+            }
+        };
+
+        self.module.elem_sec.push(ElementSeg {
+            flag: flag + is_expr as u32 * 4,
+            mode,
+            type_: type_.as_val_type(),
+            elem_kind: 0,
+            func_idxs,
+            init_expr,
+        });
+
+        Ok(())
+    }
+
+    /// https://webassembly.github.io/spec/core/text/modules.html#data-segments
+    fn data(&mut self) -> ParseResult<()> {
+        self.opt_id();
+        self.next_index(Space::Data);
+
+        let mut mem_idx = None;
+
+        if self.eat_sexpr("memory") {
+            mem_idx = Some(self.index(Space::Mem)?);
+            self.rparen()?;
+        }
+
+        let offset_expr = self.offset()?;
+        let init = self.strings()?;
+        let data = match offset_expr {
+            Some(offset_expr) => DataSeg {
+                flag: if mem_idx.is_some() { 2 } else { 0 },
+                mode: DataMode::Active,
+                init,
+                mem_idx: mem_idx.unwrap_or(0),
+                offset_expr,
+            },
+            None => DataSeg {
+                flag: 1,
+                mode: DataMode::Passive,
+                init,
+                mem_idx: 0,
+                offset_expr: vec![],
+            },
+        };
+
+        self.module.data_sec.push(data);
+
+        Ok(())
+    }
+
+    /// (offset instr*) 或者单条折叠指令
+    fn offset(&mut self) -> ParseResult<Option<Expr>> {
+        if self.eat_sexpr("offset") {
+            let expr = self.instrs()?;
+
+            self.rparen()?;
+
+            return Ok(Some(expr));
+        }
+
+        if self.peek() != Some(&Token::LParen) || self.peek_sexpr("item") {
+            return Ok(None);
+        }
+
+        let mut expr = vec![];
+
+        self.folded_instr(&mut expr)?;
+
+        Ok(Some(expr))
+    }
+
+    /// (item instr*) 或者单条折叠指令
+    fn elem_exprs(&mut self) -> ParseResult<Vec<Expr>> {
+        let mut exprs = vec![];
+
+        while self.peek() == Some(&Token::LParen) {
+            if self.eat_sexpr("item") {
+                exprs.push(self.instrs()?);
+                self.rparen()?;
+            } else {
+                let mut expr = vec![];
+
+                self.folded_instr(&mut expr)?;
+                exprs.push(expr);
+            }
+        }
+
+        Ok(exprs)
+    }
+
+    fn func_idxs(&mut self) -> ParseResult<Vec<FuncIdx>> {
+        let mut idxs = vec![];
+
+        while let Some(idx) = self.opt_index(Space::Func)? {
+            idxs.push(idx);
+        }
+
+        Ok(idxs)
+    }
+
+    /// https://webassembly.github.io/spec/core/text/modules.html#type-uses
+    fn type_use(&mut self) -> ParseResult<(TypeIdx, Vec<Option<String>>)> {
+        let pos = self.pos();
+        let mut explicit = None;
+
+        if self.eat_sexpr("type") {
+            explicit = Some(self.index(Space::Type)?);
+            self.rparen()?;
+        }
+
+        let (params, mut names, results) = self.signature()?;
+        let func_type = FuncType { params, results };
+
+        let Some(idx) = explicit else {
+            return Ok((self.type_idx(func_type), names));
+        };
+
+        let expect = self
+            .module
+            .type_sec
+            .get(idx as usize)
+            .ok_or_else(|| ParseErr::UnknownId(pos, Space::Type.describe(), idx.to_string()))?;
+
+        if func_type.params.is_empty() && func_type.results.is_empty() {
+            names = vec![None; expect.params.len()];
+        } else if *expect != func_type {
+            Err(ParseErr::TypeUseMismatch(pos, idx))?
+        }
+
+        Ok((idx, names))
+    }
+
+    /// 隐式的类型追加到类型段末尾
+    fn type_idx(&mut self, func_type: FuncType) -> TypeIdx {
+        let type_sec = &mut self.module.type_sec;
+
+        match type_sec.iter().position(|t| *t == func_type) {
+            Some(idx) => idx as TypeIdx,
+            None => {
+                type_sec.push(func_type);
+
+                (type_sec.len() - 1) as TypeIdx
+            }
+        }
+    }
+
+    /// (param $id? t*)* (result t*)*
+    fn signature(&mut self) -> ParseResult<Signature> {
+        let mut params = vec![];
+        let mut names = vec![];
+        let mut results = vec![];
+
+        while self.eat_sexpr("param") {
+            match self.opt_id() {
+                Some(id) => {
+                    params.push(self.val_type()?);
+                    names.push(Some(id));
+                }
+                None => {
+                    let types = self.val_types()?;
+
+                    names.extend(types.iter().map(|_| None));
+                    params.extend(types);
+                }
+            }
+
+            self.rparen()?;
+        }
+
+        while self.eat_sexpr("result") {
+            results.extend(self.val_types()?);
+            self.rparen()?;
+        }
+
+        Ok((params, names, results))
+    }
+
+    /// https://webassembly.github.io/spec/core/text/instructions.html#control-instructions
+    fn block_type(&mut self) -> ParseResult<BlockType> {
+        if self.peek_sexpr("type") {
+            return Ok(BlockType::TypeIdx(self.type_use()?.0 as i32));
+        }
+
+        let (params, _, results) = self.signature()?;
+        let block_type = match (params.is_empty(), results.as_slice()) {
+            (true, []) => BlockType::Empty,
+            (true, [ValType::I32]) => BlockType::I32,
+            (true, [ValType::I64]) => BlockType::I64,
+            (true, [ValType::F32]) => BlockType::F32,
+            (true, [ValType::F64]) => BlockType::F64,
+            (true, [ValType::V128]) => BlockType::V128,
+            (true, [ValType::FuncRef]) => BlockType::FuncRef,
+            (true, [ValType::ExternRef]) => BlockType::ExternRef,
+            (true, [ValType::ExnRef]) => BlockType::ExnRef,
+            _ => BlockType::TypeIdx(self.type_idx(FuncType { params, results }) as i32),
+        };
+
+        Ok(block_type)
+    }
+
+    fn table_type(&mut self) -> ParseResult<TableType> {
+        let limits = self.limits()?;
+        let elem_type = self.ref_type()?;
+
+        Ok(TableType { elem_type, limits })
+    }
+
+    fn limits(&mut self) -> ParseResult<Limits> {
+        let is64 = self.is64();
+
+        self.sized_limits(is64)
+    }
+
+    /// 地址类型已经读过，64 位的大小按 u64 解析
+    fn sized_limits(&mut self, is64: bool) -> ParseResult<Limits> {
+        let size = |p: &mut Self| match is64 {
+            true => p.number(number::parse_u64),
+            false => p.u32().map(u64::from),
+        };
+        let min = size(self)?;
+        let max = match self.is_index_next() {
+            true => Some(size(self)?),
+            false => None,
+        };
+
+        Ok(Limits {
+            min,
+            max,
+            is64,
+            shared: false,
+        })
+    }
+
+    fn mem_type(&mut self) -> ParseResult<Limits> {
+        let is64 = self.is64();
+
+        self.mem_limits(is64)
+    }
+
+    /// 内存的大小范围后面可以跟 shared
+    fn mem_limits(&mut self, is64: bool) -> ParseResult<Limits> {
+        let mut limits = self.sized_limits(is64)?;
+
+        limits.shared = self.eat_keyword("shared");
+
+        Ok(limits)
+    }
+
+    /// 内存和表的类型前面可以写地址类型，默认为 i32
+    fn is64(&mut self) -> bool {
+        if self.eat_keyword("i64") {
+            return true;
+        }
+
+        self.eat_keyword("i32");
+
+        false
+    }
+
+    fn global_type(&mut self) -> ParseResult<GlobalType> {
+        if self.eat_sexpr("mut") {
+            let val_type = self.val_type()?;
+
+            self.rparen()?;
+
+            return Ok(GlobalType::new(val_type, true));
+        }
+
+        Ok(GlobalType::new(self.val_type()?, false))
+    }
+
+    fn val_type(&mut self) -> ParseResult<ValType> {
+        match self.peek_keyword().and_then(val_type) {
+            Some(val_type) => {
+                self.cursor += 1;
+
+                Ok(val_type)
+            }
+            None => self.expected("值类型"),
+        }
+    }
+
+    fn val_types(&mut self) -> ParseResult<Vec<ValType>> {
+        let mut types = vec![];
+
+        while self.peek() != Some(&Token::RParen) {
+            types.push(self.val_type()?);
+        }
+
+        Ok(types)
+    }
+
+    fn ref_type(&mut self) -> ParseResult<RefType> {
+        match self.peek_keyword().and_then(ref_type) {
+            Some(ref_type) => {
+                self.cursor += 1;
+
+                Ok(ref_type)
+            }
+            None => self.expected("引用类型"),
+        }
+    }
+
+    /// 指令序列，遇到 end、else 或 ) 结束
+    /// https://webassembly.github.io/spec/core/text/instructions.html
+    fn instrs(&mut self) -> ParseResult<Expr> {
+        let mut expr = vec![];
+
+        loop {
+            match self.peek() {
+                Some(Token::LParen) => self.folded_instr(&mut expr)?,
+                Some(Token::Keyword(kw)) if kw != "end" && kw != "else" => {
+                    self.plain_instr(&mut expr)?
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    fn plain_instr(&mut self, expr: &mut Expr) -> ParseResult<()> {
+        let pos = self.pos();
+        let kw = self.keyword()?;
+        let instr = match kw.as_str() {
+            "block" | "loop" => {
+                let label = self.opt_id();
+                let type_ = self.block_type()?;
+
+                self.labels.push(label.clone());
+
+                let body = self.instrs()?;
+
+                self.labels.pop();
+                self.expect_keyword("end")?;
+                self.end_label(&label)?;
+
+                match kw.as_str() {
+                    "block" => Instruction::Block(Block::new(type_, body)),
+                    _ => Instruction::Loop(Block::new(type_, body)),
+                }
+            }
+            "if" => {
+                let label = self.opt_id();
+                let type_ = self.block_type()?;
+
+                self.labels.push(label.clone());
+
+                let if_expr = self.instrs()?;
+                let else_expr = match self.eat_keyword("else") {
+                    true => {
+                        self.end_label(&label)?;
+                        self.instrs()?
+                    }
+                    false => vec![],
+                };
+
+                self.labels.pop();
+                self.expect_keyword("end")?;
+                self.end_label(&label)?;
+
+                Instruction::If(IfBlock {
+                    type_,
+                    if_expr,
+                    else_expr,
+                })
+            }
+            "try_table" => {
+                let label = self.opt_id();
+                let type_ = self.block_type()?;
+                let catches = self.catches()?;
+
+                self.labels.push(label.clone());
+
+                let expr = self.instrs()?;
+
+                self.labels.pop();
+                self.expect_keyword("end")?;
+                self.end_label(&label)?;
+
+                Instruction::TryTable(TryTable { type_, catches, expr })
+            }
+            _ => self.instr(&kw, pos)?,
+        };
+
+        expr.push(instr);
+
+        Ok(())
+    }
+
+    /// 折叠形式：(op imm* folded*)，操作数先于指令本身
+    /// https://webassembly.github.io/spec/core/text/instructions.html#folded-instructions
+    fn folded_instr(&mut self, expr: &mut Expr) -> ParseResult<()> {
+        self.lparen()?;
+
+        let pos = self.pos();
+        let kw = self.keyword()?;
+        let instr = match kw.as_str() {
+            "block" | "loop" => {
+                let label = self.opt_id();
+                let type_ = self.block_type()?;
+
+                self.labels.push(label);
+
+                let body = self.instrs()?;
+
+                self.labels.pop();
+
+                match kw.as_str() {
+                    "block" => Instruction::Block(Block::new(type_, body)),
+                    _ => Instruction::Loop(Block::new(type_, body)),
+                }
+            }
+            "if" => {
+                let label = self.opt_id();
+                let type_ = self.block_type()?;
+
+                while self.peek() == Some(&Token::LParen) && !self.peek_sexpr("then") {
+                    self.folded_instr(expr)?;
+                }
+
+                self.expect_sexpr("then")?;
+                self.labels.push(label);
+
+                let if_expr = self.instrs()?;
+
+                self.rparen()?;
+
+                let else_expr = match self.eat_sexpr("else") {
+                    true => {
+                        let else_expr = self.instrs()?;
+
+                        self.rparen()?;
+
+                        else_expr
+                    }
+                    false => vec![],
+                };
+
+                self.labels.pop();
+
+                Instruction::If(IfBlock {
+                    type_,
+                    if_expr,
+                    else_expr,
+                })
+            }
+            "try_table" => {
+                let label = self.opt_id();
+                let type_ = self.block_type()?;
+                let catches = self.catches()?;
+
+                self.labels.push(label);
+
+                let expr = self.instrs()?;
+
+                self.labels.pop();
+
+                Instruction::TryTable(TryTable { type_, catches, expr })
+            }
+            _ => {
+                let instr = self.instr(&kw, pos)?;
+
+                while self.peek() == Some(&Token::LParen) {
+                    self.folded_instr(expr)?;
+                }
+
+                instr
+            }
+        };
+
+        self.rparen()?;
+        expr.push(instr);
+
+        Ok(())
+    }
+
+    /// try_table 的 catch 子句，标签在块外解析
+    fn catches(&mut self) -> ParseResult<Vec<Catch>> {
+        let mut catches = vec![];
+
+        loop {
+            let catch = if self.eat_sexpr("catch") {
+                Catch::Tag(self.index(Space::Tag)?, self.label()?)
+            } else if self.eat_sexpr("catch_ref") {
+                Catch::TagRef(self.index(Space::Tag)?, self.label()?)
+            } else if self.eat_sexpr("catch_all") {
+                Catch::All(self.label()?)
+            } else if self.eat_sexpr("catch_all_ref") {
+                Catch::AllRef(self.label()?)
+            } else {
+                return Ok(catches);
+            };
+
+            self.rparen()?;
+            catches.push(catch);
+        }
+    }
+
+    /// 非结构化指令及其立即数
+    fn instr(&mut self, kw: &str, pos: Pos) -> ParseResult<Instruction> {
+        let instr = match kw {
+            "br" => Instruction::Br(self.label()?),
+            "br_if" => Instruction::BrIf(self.label()?),
+            "br_table" => {
+                let mut labels = vec![self.label()?];
+
+                while self.is_index_next() {
+                    labels.push(self.label()?);
+                }
+
+                let default = labels.pop().unwrap();
+
+                Instruction::BrTable(BrTableArg { labels, default })
+            }
+            "call" => Instruction::Call(self.index(Space::Func)?),
+            "call_indirect" => {
+                let table_idx = self.opt_index(Space::Table)?.unwrap_or(0);
+                let (type_idx, _) = self.type_use()?;
+
+                Instruction::CallIndirect(type_idx, table_idx)
+            }
+            "return_call" => Instruction::ReturnCall(self.index(Space::Func)?),
+            "return_call_indirect" => {
+                let table_idx = self.opt_index(Space::Table)?.unwrap_or(0);
+                let (type_idx, _) = self.type_use()?;
+
+                Instruction::ReturnCallIndirect(type_idx, table_idx)
+            }
+            "select" if self.peek_sexpr("result") => {
+                let mut results = vec![];
+
+                while self.eat_sexpr("result") {
+                    results.extend(self.val_types()?);
+                    self.rparen()?;
+                }
+
+                match results.first() {
+                    Some(val_type) => Instruction::Select2(results.len() as u8, *val_type),
+                    None => Err(ParseErr::Expected(pos, "结果类型".to_string(), ")".to_string()))?,
+                }
+            }
+            "local.get" => Instruction::LocalGet(self.local()?),
+            "local.set" => Instruction::LocalSet(self.local()?),
+            "local.tee" => Instruction::LocalTee(self.local()?),
+            "global.get" => Instruction::GlobalGet(self.index(Space::Global)?),
+            "global.set" => Instruction::GlobalSet(self.index(Space::Global)?),
+            "table.get" => Instruction::TableGet(self.table_idx()?),
+            "table.set" => Instruction::TableSet(self.table_idx()?),
+            "table.size" => Instruction::TableSize(self.table_idx()?),
+            "table.grow" => Instruction::TableGrow(self.table_idx()?),
+            "table.fill" => Instruction::TableFill(self.table_idx()?),
+            "table.copy" => {
+                let dst = self.table_idx()?;
+                let src = self.table_idx()?;
+
+                Instruction::TableCopy(dst, src)
+            }
+            "table.init" => {
+                // 两个索引时第一个是表
+                let table_idx = match self.is_index_at(1) {
+                    true => self.index(Space::Table)?,
+                    false => 0,
+                };
+
+                Instruction::TableInit(self.index(Space::Elem)?, table_idx)
+            }
+            "elem.drop" => Instruction::ElemDrop(self.index(Space::Elem)?),
+            "memory.size" => Instruction::MemorySize(self.mem_idx()?),
+            "memory.grow" => Instruction::MemoryGrow(self.mem_idx()?),
+            "memory.init" => {
+                let mem_idx = match self.is_index_at(1) {
+                    true => self.index(Space::Mem)?,
+                    false => 0,
+                };
+
+                Instruction::MemoryInit(self.index(Space::Data)?, mem_idx)
+            }
+            "data.drop" => Instruction::DataDrop(self.index(Space::Data)?),
+            "memory.copy" => {
+                let dst = self.mem_idx()?;
+                let src = self.mem_idx()?;
+
+                Instruction::MemoryCopy(dst, src)
+            }
+            "memory.fill" => Instruction::MemoryFill(self.mem_idx()?),
+            "i32.const" => Instruction::I32Const(self.number(|s| number::parse_int(s, 32))? as i32),
+            "i64.const" => Instruction::I64Const(self.number(|s| number::parse_int(s, 64))? as i64),
+            "f32.const" => Instruction::F32Const(self.number(number::parse_f32)?),
+            "f64.const" => Instruction::F64Const(self.number(number::parse_f64)?),
+            "v128.const" => Instruction::V128Const(self.v128_const()?),
+            "i8x16.shuffle" => {
+                let mut lanes = [0; 16];
+
+                for lane in lanes.iter_mut() {
+                    *lane = self.lane_idx()?;
+                }
+
+                Instruction::I8x16Shuffle(lanes)
+            }
+            "ref.null" => {
+                let heap_type = match self.keyword()?.as_str() {
+                    "func" => RefType::FuncRef,
+                    "extern" => RefType::ExternRef,
+                    "exn" => RefType::ExnRef,
+                    heap_type => Err(ParseErr::Expected(
+                        pos,
+                        "func、extern 或 exn".to_string(),
+                        heap_type.to_string(),
+                    ))?,
+                };
+
+                Instruction::RefNull(heap_type as u64)
+            }
+            "ref.func" => Instruction::RefFunc(self.index(Space::Func)?),
+            "throw" => Instruction::Throw(self.index(Space::Tag)?),
+            "else" | "end" => Err(ParseErr::MisplacedKeyword(pos, kw.to_string()))?,
+            _ => {
+                let opcode = opcode(kw).ok_or_else(|| ParseErr::UnknownInstr(pos, kw.to_string()))?;
+
+                self.instr_with_opcode(kw, opcode)?
+            }
+        };
+
+        Ok(instr)
+    }
+
+    /// 剩下的指令只有内存参数、lane 索引两类立即数，拼出二进制后交给解码器
+    fn instr_with_opcode(&mut self, kw: &str, opcode: u16) -> ParseResult<Instruction> {
+        let mut bytes = match opcode < 0xfc {
+            true => vec![opcode as u8],
+            false => opcode.to_be_bytes().to_vec(),
+        };
+        let (_, op) = kw.split_once('.').unwrap_or_default();
+
+        // 原子指令除了 atomic.fence 都有 memarg
+        if op.starts_with("load") || op.starts_with("store") || op.starts_with("atomic.") {
+            bytes.extend(self.mem_arg(natural_align(kw), kw.contains("_lane"))?.encode());
+        }
+
+        // atomic.fence 的保留字节
+        if kw == "atomic.fence" {
+            bytes.push(0x00);
+        }
+
+        if kw.contains("_lane") {
+            bytes.push(self.lane_idx()?);
+        }
+
+        // 0xfd 0x80 及以上的指令码占两个字节
+        if (0xfd80..0xfe00).contains(&opcode) {
+            bytes.push(0x01);
+        }
+
+        Instruction::decode(&mut Reader::new(&bytes, None))
+    }
+
+    /// https://webassembly.github.io/spec/core/text/instructions.html#memory-instructions
+    ///
+    /// lane 指令后面还有 lane 索引，只有一个数字时它是 lane 索引而不是内存索引
+    fn mem_arg(&mut self, natural: u32, lane: bool) -> ParseResult<MemoryArg> {
+        let mut offset = 0;
+        let mut align = natural;
+        let mem_idx = match !lane || self.is_index_at(self.mem_arg_len(1) + 1) {
+            true => self.mem_idx()?,
+            false => 0,
+        };
+
+        if let Some(value) = self.peek_keyword().and_then(|kw| kw.strip_prefix("offset=")) {
+            let pos = self.pos();
+
+            offset = number::parse_u64(value).map_err(|err| num_err(pos, value, err))?;
+            self.cursor += 1;
+        }
+
+        if let Some(value) = self.peek_keyword().and_then(|kw| kw.strip_prefix("align=")) {
+            let pos = self.pos();
+
+            align = number::parse_u32(value).map_err(|err| num_err(pos, value, err))?;
+            self.cursor += 1;
+
+            if !align.is_power_of_two() {
+                Err(ParseErr::InvalidAlign(pos, align))?
+            }
+        }
+
+        Ok(MemoryArg {
+            align: align.trailing_zeros(),
+            offset,
+            mem_idx,
+        })
+    }
+
+    /// 从第 n 个 token 开始 offset= 和 align= 的个数
+    fn mem_arg_len(&self, n: usize) -> usize {
+        self.tokens
+            .iter()
+            .skip(self.cursor + n)
+            .take_while(|(token, _)| {
+                matches!(token, Token::Keyword(kw) if kw.starts_with("offset=") || kw.starts_with("align="))
+            })
+            .count()
+    }
+
+    fn v128_const(&mut self) -> ParseResult<v128> {
+        let pos = self.pos();
+        let shape = self.keyword()?;
+        let mut bytes = vec![];
+
+        match shape.as_str() {
+            "i8x16" | "i16x8" | "i32x4" | "i64x2" => {
+                let bits = shape[1..].split('x').next().unwrap().parse::<u32>().unwrap();
+
+                for _ in 0..128 / bits {
+                    let lane = self.number(|s| number::parse_int(s, bits))?;
+
+                    bytes.extend(&lane.to_le_bytes()[..bits as usize / 8]);
+                }
+            }
+            "f32x4" => {
+                for _ in 0..4 {
+                    bytes.extend(self.number(number::parse_f32)?.to_le_bytes());
+                }
+            }
+            "f64x2" => {
+                for _ in 0..2 {
+                    bytes.extend(self.number(number::parse_f64)?.to_le_bytes());
+                }
+            }
+            _ => Err(ParseErr::Expected(pos, "v128 形状".to_string(), shape))?,
+        }
+
+        Ok(v128::new(bytes.try_into().unwrap()))
+    }
+
+    fn label(&mut self) -> ParseResult<LabelIdx> {
+        let pos = self.pos();
+
+        match self.peek() {
+            Some(Token::Id(id)) => {
+                let id = id.clone();
+                let depth = self
+                    .labels
+                    .iter()
+                    .rposition(|label| label.as_ref() == Some(&id))
+                    .ok_or(ParseErr::UnknownId(pos, "标签", id))?;
+
+                self.cursor += 1;
+
+                Ok((self.labels.len() - 1 - depth) as LabelIdx)
+            }
+            _ => self.u32(),
+        }
+    }
+
+    /// 结束处可选的 $label 必须和块标签一致
+    fn end_label(&mut self, label: &Option<String>) -> ParseResult<()> {
+        let pos = self.pos();
+
+        if let Some(Token::Id(id)) = self.peek() {
+            if label.as_ref() != Some(id) {
+                Err(ParseErr::LabelMismatch(pos, id.clone()))?
+            }
+
+            self.cursor += 1;
+        }
+
+        Ok(())
+    }
+
+    fn local(&mut self) -> ParseResult<LocalIdx> {
+        let pos = self.pos();
+
+        match self.peek() {
+            Some(Token::Id(id)) => {
+                let id = id.clone();
+                let idx = *self
+                    .locals
+                    .get(&id)
+                    .ok_or(ParseErr::UnknownId(pos, "局部变量", id))?;
+
+                self.cursor += 1;
+
+                Ok(idx)
+            }
+            _ => self.u32(),
+        }
+    }
+
+    fn table_idx(&mut self) -> ParseResult<u32> {
+        Ok(self.opt_index(Space::Table)?.unwrap_or(0))
+    }
+
+    fn mem_idx(&mut self) -> ParseResult<u32> {
+        Ok(self.opt_index(Space::Mem)?.unwrap_or(0))
+    }
+
+    fn lane_idx(&mut self) -> ParseResult<u8> {
+        self.number(|s| {
+            number::parse_u32(s).and_then(|v| u8::try_from(v).map_err(|_| NumErr::OutOfRange))
+        })
+    }
+
+    fn index(&mut self, space: Space) -> ParseResult<u32> {
+        let pos = self.pos();
+
+        match self.peek() {
+            Some(Token::Id(id)) => {
+                let id = id.clone();
+                let idx = *self.ids[space as usize]
+                    .get(&id)
+                    .ok_or_else(|| ParseErr::UnknownId(pos, space.describe(), id))?;
+
+                self.cursor += 1;
+
+                Ok(idx)
+            }
+            _ => self.u32(),
+        }
+    }
+
+    fn opt_index(&mut self, space: Space) -> ParseResult<Option<u32>> {
+        match self.is_index_next() {
+            true => Ok(Some(self.index(space)?)),
+            false => Ok(None),
+        }
+    }
+
+    fn is_index_next(&self) -> bool {
+        self.is_index_at(0)
+    }
+
+    fn is_index_at(&self, n: usize) -> bool {
+        match self.tokens.get(self.cursor + n) {
+            Some((Token::Id(_), _)) => true,
+            Some((Token::Keyword(kw), _)) => kw.starts_with(|c: char| c.is_ascii_digit()),
+            _ => false,
+        }
+    }
+
+    fn u32(&mut self) -> ParseResult<u32> {
+        self.number(number::parse_u32)
+    }
+
+    fn number<T>(&mut self, parse: impl Fn(&str) -> NumResult<T>) -> ParseResult<T> {
+        let pos = self.pos();
+
+        match self.peek() {
+            Some(Token::Keyword(kw)) => {
+                let value = parse(kw).map_err(|err| num_err(pos, kw, err))?;
+
+                self.cursor += 1;
+
+                Ok(value)
+            }
+            _ => self.expected("数字"),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.cursor).map(|(token, _)| token)
+    }
+
+    fn peek_keyword(&self) -> Option<&str> {
+        match self.peek() {
+            Some(Token::Keyword(kw)) => Some(kw),
+            _ => None,
+        }
+    }
+
+    /// 接下来是否是 (kw
+    fn peek_sexpr(&self, kw: &str) -> bool {
+        self.peek() == Some(&Token::LParen)
+            && matches!(self.tokens.get(self.cursor + 1), Some((Token::Keyword(k), _)) if k == kw)
+    }
+
+    fn pos(&self) -> Pos {
+        self.tokens
+            .get(self.cursor)
+            .or(self.tokens.last())
+            .map(|(_, pos)| *pos)
+            .unwrap_or_default()
+    }
+
+    fn expected<T>(&self, what: &str) -> ParseResult<T> {
+        let actual = self.peek().map_or("文本结尾".to_string(), Token::describe);
+
+        Err(ParseErr::Expected(self.pos(), what.to_string(), actual))?
+    }
+
+    fn expect(&mut self, token: Token) -> ParseResult<()> {
+        match self.peek() == Some(&token) {
+            true => {
+                self.cursor += 1;
+
+                Ok(())
+            }
+            false => self.expected(&token.describe()),
+        }
+    }
+
+    fn lparen(&mut self) -> ParseResult<()> {
+        self.expect(Token::LParen)
+    }
+
+    fn rparen(&mut self) -> ParseResult<()> {
+        self.expect(Token::RParen)
+    }
+
+    fn eof(&self) -> ParseResult<()> {
+        match self.peek() {
+            Some(_) => self.expected("文本结尾"),
+            None => Ok(()),
+        }
+    }
+
+    fn keyword(&mut self) -> ParseResult<String> {
+        match self.peek_keyword() {
+            Some(kw) => {
+                let kw = kw.to_string();
+
+                self.cursor += 1;
+
+                Ok(kw)
+            }
+            None => self.expected("关键字"),
+        }
+    }
+
+    fn eat_keyword(&mut self, kw: &str) -> bool {
+        let matched = self.peek_keyword() == Some(kw);
+
+        if matched {
+            self.cursor += 1;
+        }
+
+        matched
+    }
+
+    fn expect_keyword(&mut self, kw: &str) -> ParseResult<()> {
+        match self.eat_keyword(kw) {
+            true => Ok(()),
+            false => self.expected(kw),
+        }
+    }
+
+    fn eat_sexpr(&mut self, kw: &str) -> bool {
+        let matched = self.peek_sexpr(kw);
+
+        if matched {
+            self.cursor += 2;
+        }
+
+        matched
+    }
+
+    fn expect_sexpr(&mut self, kw: &str) -> ParseResult<()> {
+        match self.eat_sexpr(kw) {
+            true => Ok(()),
+            false => self.expected(&format!("({}", kw)),
+        }
+    }
+
+    fn opt_id(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(Token::Id(id)) => {
+                let id = id.clone();
+
+                self.cursor += 1;
+
+                Some(id)
+            }
+            _ => None,
+        }
+    }
+
+    fn string(&mut self) -> ParseResult<Vec<u8>> {
+        match self.peek() {
+            Some(Token::Str(bytes)) => {
+                let bytes = bytes.clone();
+
+                self.cursor += 1;
+
+                Ok(bytes)
+            }
+            _ => self.expected("字符串"),
+        }
+    }
+
+    /// 连续的字符串拼接成一段字节
+    fn strings(&mut self) -> ParseResult<Vec<u8>> {
+        let mut bytes = vec![];
+
+        while matches!(self.peek(), Some(Token::Str(_))) {
+            bytes.extend(self.string()?);
+        }
+
+        Ok(bytes)
+    }
+
+    fn name(&mut self) -> ParseResult<String> {
+        let pos = self.pos();
+
+        String::from_utf8(self.string()?).map_err(|_| ParseErr::InvalidUtf8(pos).into())
+    }
+
+    /// 跳到当前 s 表达式的 ) 之前
+    fn skip_to_rparen(&mut self) -> ParseResult<()> {
+        let mut depth = 0;
+
+        loop {
+            match self.peek() {
+                None => Err(ParseErr::UnexpectedEof)?,
+                Some(Token::RParen) if depth == 0 => return Ok(()),
+                Some(Token::RParen) => depth -= 1,
+                Some(Token::LParen) => depth += 1,
+                _ => (),
+            }
+
+            self.cursor += 1;
+        }
+    }
+
+    /// 当前 s 表达式是否直接包含 (kw ...)
+    fn has_child(&self, kw: &str) -> bool {
+        let mut depth = 0;
+
+        for (i, (token, _)) in self.tokens[self.cursor..].iter().enumerate() {
+            match token {
+                Token::RParen if depth == 0 => return false,
+                Token::RParen => depth -= 1,
+                Token::LParen => {
+                    let next = self.tokens.get(self.cursor + i + 1).map(|(token, _)| token);
+
+                    if depth == 0 && matches!(next, Some(Token::Keyword(k)) if k == kw) {
+                        return true;
+                    }
+
+                    depth += 1;
+                }
+                _ => (),
+            }
+        }
+
+        false
+    }
+}
+
+fn val_type(kw: &str) -> Option<ValType> {
+    match kw {
+        "i32" => Some(ValType::I32),
+        "i64" => Some(ValType::I64),
+        "f32" => Some(ValType::F32),
+        "f64" => Some(ValType::F64),
+        "v128" => Some(ValType::V128),
+        "funcref" => Some(ValType::FuncRef),
+        "externref" => Some(ValType::ExternRef),
+        "exnref" => Some(ValType::ExnRef),
+        _ => None,
+    }
+}
+
+fn ref_type(kw: &str) -> Option<RefType> {
+    match kw {
+        "funcref" => Some(RefType::FuncRef),
+        "externref" => Some(RefType::ExternRef),
+        "exnref" => Some(RefType::ExnRef),
+        _ => None,
+    }
+}
+
+fn num_err(pos: Pos, text: &str, err: NumErr) -> Box<dyn Error> {
+    match err {
+        NumErr::Invalid => ParseErr::InvalidNumber(pos, text.to_string()).into(),
+        NumErr::OutOfRange => ParseErr::NumberOutOfRange(pos, text.to_string()).into(),
+    }
+}
+
+/// 内联的元素段和数据段从地址 0 开始
+fn addr_zero(is64: bool) -> Instruction {
+    match is64 {
+        true => Instruction::I64Const(0),
+        false => Instruction::I32Const(0),
+    }
+}
+
+/// memory.init 和 data.drop 需要数据段计数段
+fn uses_data_idx(expr: &Expr) -> bool {
+    expr.iter().any(|instr| match instr {
+        Instruction::MemoryInit(..) | Instruction::DataDrop(_) => true,
+        Instruction::Block(block) | Instruction::Loop(block) => uses_data_idx(&block.expr),
+        Instruction::TryTable(block) => uses_data_idx(&block.expr),
+        Instruction::If(block) => uses_data_idx(&block.if_expr) || uses_data_idx(&block.else_expr),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::Parser;
+    use crate::text::errors::ParseErr;
+
+    fn parse_err(text: &str) -> ParseErr {
+        *Parser::parse(text).unwrap_err().downcast::<ParseErr>().unwrap()
+    }
+
+    #[test]
+    fn test_stray_else_end() {
+        assert!(
+            matches!(parse_err("(module (func (else)))"), ParseErr::MisplacedKeyword(_, kw) if kw == "else")
+        );
+        assert!(
+            matches!(parse_err("(module (func (end)))"), ParseErr::MisplacedKeyword(_, kw) if kw == "end")
+        );
+        assert!(matches!(
+            parse_err("(module (func else))"),
+            ParseErr::Expected(..)
+        ));
+        assert!(matches!(parse_err("(module (func end))"), ParseErr::Expected(..)));
+
+        assert!(Parser::parse("(module (func (if (i32.const 1) (then) (else))))").is_ok());
+        assert!(Parser::parse("(module (func i32.const 1 if else end))").is_ok());
+    }
+
+    #[test]
+    fn test_line_comment_cr() {
+        let module = Parser::parse("(module (func (result i32) ;; comment\r i32.const 1))").unwrap();
+
+        assert_eq!(module.code_sec[0].body.len(), 1);
+    }
+}
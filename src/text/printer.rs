@@ -0,0 +1,648 @@
+use super::names::natural_align;
+use crate::binary::instruction::{BlockType, Catch, Instruction, MemoryArg};
+use crate::binary::module::Module;
+use crate::binary::section::{
+    DataMode, ElementMode, ExportDesc, Expr, ImportDesc, TypeIdx, ACTIVE_2, ACTIVE_6,
+};
+use crate::binary::types::{FuncType, GlobalType, Limits, Mut, RefType, TableType, ValType};
+
+/// 把 Module 输出成文本格式，结构化指令用折叠形式并缩进，
+/// 没有名字的项用 (;索引;) 注释标出，输出可以再被 Parser 解析回同样的模块
+pub struct Printer<'a> {
+    module: &'a Module,
+    out: String,
+    indent: usize,
+}
+
+impl<'a> Printer<'a> {
+    pub fn new(module: &'a Module) -> Self {
+        Self {
+            module,
+            out: String::new(),
+            indent: 0,
+        }
+    }
+
+    pub fn print(module: &Module) -> String {
+        let mut printer = Printer::new(module);
+
+        printer.module();
+        printer.out
+    }
+
+    fn line(&mut self, text: &str) {
+        self.out.push_str(&"  ".repeat(self.indent));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// 右括号接在上一行末尾
+    fn close(&mut self) {
+        self.out.pop();
+        self.out.push_str(")\n");
+    }
+
+    fn module(&mut self) {
+        let module = self.module;
+
+        self.line("(module");
+        self.indent += 1;
+
+        for (i, func_type) in module.type_sec.iter().enumerate() {
+            self.line(&format!("(type (;{};) (func{}))", i, signature(func_type)));
+        }
+
+        let mut counts = [0; 5];
+
+        for import in &module.import_sec {
+            let (kind, desc) = match &import.desc {
+                ImportDesc::Func(type_idx) => {
+                    (0, format!("func (;{};) {}", counts[0], self.type_use(*type_idx)))
+                }
+                ImportDesc::Table(table_type) => {
+                    (1, format!("table (;{};) {}", counts[1], table(table_type)))
+                }
+                ImportDesc::Mem(limits) => {
+                    (2, format!("memory (;{};) {}", counts[2], self::limits(limits)))
+                }
+                ImportDesc::Global(global_type) => {
+                    (3, format!("global (;{};) {}", counts[3], global(global_type)))
+                }
+                ImportDesc::Tag(tag_type) => (
+                    4,
+                    format!("tag (;{};) {}", counts[4], self.type_use(tag_type.type_idx)),
+                ),
+            };
+
+            counts[kind] += 1;
+            self.line(&format!(
+                "(import {} {} ({}))",
+                string(import.module.as_bytes()),
+                string(import.name.as_bytes()),
+                desc
+            ));
+        }
+
+        for (i, (type_idx, code)) in module.func_sec.iter().zip(&module.code_sec).enumerate() {
+            self.line(&format!(
+                "(func (;{};) {}",
+                counts[0] + i as u32,
+                self.type_use(*type_idx)
+            ));
+            self.indent += 1;
+
+            for locals in &code.locals {
+                let types = vec![val_type(&locals.value_type); locals.n as usize];
+
+                self.line(&format!("(local {})", types.join(" ")));
+            }
+
+            self.instrs(&code.body);
+            self.indent -= 1;
+            self.close();
+        }
+
+        for (i, table_type) in module.table_sec.iter().enumerate() {
+            self.line(&format!(
+                "(table (;{};) {})",
+                counts[1] + i as u32,
+                table(table_type)
+            ));
+        }
+
+        for (i, limits) in module.mem_sec.iter().enumerate() {
+            self.line(&format!(
+                "(memory (;{};) {})",
+                counts[2] + i as u32,
+                self::limits(limits)
+            ));
+        }
+
+        for (i, tag_type) in module.tag_sec.iter().enumerate() {
+            self.line(&format!(
+                "(tag (;{};) {})",
+                counts[4] + i as u32,
+                self.type_use(tag_type.type_idx)
+            ));
+        }
+
+        for (i, global) in module.global_sec.iter().enumerate() {
+            self.line(&format!(
+                "(global (;{};) {} {})",
+                counts[3] + i as u32,
+                self::global(&global.type_),
+                const_expr(&global.init_expr, "")
+            ));
+        }
+
+        for export in &module.export_sec {
+            let desc = match &export.desc {
+                ExportDesc::Func(idx) => format!("func {}", idx),
+                ExportDesc::Table(idx) => format!("table {}", idx),
+                ExportDesc::Mem(idx) => format!("memory {}", idx),
+                ExportDesc::Global(idx) => format!("global {}", idx),
+                ExportDesc::Tag(idx) => format!("tag {}", idx),
+            };
+
+            self.line(&format!("(export {} ({}))", string(export.name.as_bytes()), desc));
+        }
+
+        if let Some(func_idx) = module.start_sec {
+            self.line(&format!("(start {})", func_idx));
+        }
+
+        for (i, elem) in module.elem_sec.iter().enumerate() {
+            let mut text = format!("(elem (;{};)", i);
+
+            match &elem.mode {
+                ElementMode::Passive => (),
+                ElementMode::Declarative => text.push_str(" declare"),
+                ElementMode::Active {
+                    table_idx,
+                    offset_expr,
+                } => {
+                    if elem.flag == ACTIVE_2 || elem.flag == ACTIVE_6 {
+                        text.push_str(&format!(" (table {})", table_idx));
+                    }
+
+                    text.push_str(&format!(" {}", const_expr(offset_expr, "offset")));
+                }
+            }
+
+            match elem.flag & 0b100 == 0 {
+                true => {
+                    text.push_str(" func");
+                    elem.func_idxs
+                        .iter()
+                        .for_each(|idx| text.push_str(&format!(" {}", idx)));
+                }
+                false => {
+                    text.push_str(&format!(" {}", val_type(&elem.type_)));
+                    elem.init_expr
+                        .iter()
+                        .for_each(|expr| text.push_str(&format!(" {}", const_expr(expr, "item"))));
+                }
+            }
+
+            self.line(&format!("{})", text));
+        }
+
+        for (i, data) in module.data_sec.iter().enumerate() {
+            let mut text = format!("(data (;{};)", i);
+
+            if let DataMode::Active = data.mode {
+                if data.flag == 2 {
+                    text.push_str(&format!(" (memory {})", data.mem_idx));
+                }
+
+                text.push_str(&format!(" {}", const_expr(&data.offset_expr, "offset")));
+            }
+
+            self.line(&format!("{} {})", text, string(&data.init)));
+        }
+
+        self.indent -= 1;
+        self.close();
+    }
+
+    /// (type x) 后面附上签名方便阅读
+    fn type_use(&self, type_idx: TypeIdx) -> String {
+        match self.module.type_sec.get(type_idx as usize) {
+            Some(func_type) => format!("(type {}){}", type_idx, signature(func_type)),
+            None => format!("(type {})", type_idx),
+        }
+    }
+
+    fn instrs(&mut self, expr: &Expr) {
+        for instr in expr {
+            match instr {
+                Instruction::Block(block) | Instruction::Loop(block) => {
+                    self.line(&format!("({}{}", instr.name(), block_type(&block.type_)));
+                    self.indent += 1;
+                    self.instrs(&block.expr);
+                    self.indent -= 1;
+                    self.close();
+                }
+                Instruction::If(block) => {
+                    self.line(&format!("(if{}", block_type(&block.type_)));
+                    self.indent += 1;
+                    self.line("(then");
+                    self.indent += 1;
+                    self.instrs(&block.if_expr);
+                    self.indent -= 1;
+                    self.close();
+
+                    if !block.else_expr.is_empty() {
+                        self.line("(else");
+                        self.indent += 1;
+                        self.instrs(&block.else_expr);
+                        self.indent -= 1;
+                        self.close();
+                    }
+
+                    self.indent -= 1;
+                    self.close();
+                }
+                Instruction::TryTable(block) => {
+                    let catches = block.catches.iter().map(catch).collect::<String>();
+
+                    self.line(&format!("(try_table{}{}", block_type(&block.type_), catches));
+                    self.indent += 1;
+                    self.instrs(&block.expr);
+                    self.indent -= 1;
+                    self.close();
+                }
+                _ => self.line(&instr_text(instr)),
+            }
+        }
+    }
+}
+
+/// 非结构化指令
+pub(super) fn instr_text(instr: &Instruction) -> String {
+    let name = instr.name();
+    let immediates = match instr {
+        Instruction::Br(idx)
+        | Instruction::BrIf(idx)
+        | Instruction::Call(idx)
+        | Instruction::ReturnCall(idx)
+        | Instruction::Throw(idx)
+        | Instruction::LocalGet(idx)
+        | Instruction::LocalSet(idx)
+        | Instruction::LocalTee(idx)
+        | Instruction::GlobalGet(idx)
+        | Instruction::GlobalSet(idx)
+        | Instruction::TableGet(idx)
+        | Instruction::TableSet(idx)
+        | Instruction::RefFunc(idx)
+        | Instruction::DataDrop(idx)
+        | Instruction::ElemDrop(idx)
+        | Instruction::TableGrow(idx)
+        | Instruction::TableSize(idx)
+        | Instruction::TableFill(idx) => format!(" {}", idx),
+        Instruction::BrTable(arg) => arg
+            .labels
+            .iter()
+            .chain([&arg.default])
+            .map(|label| format!(" {}", label))
+            .collect(),
+        Instruction::CallIndirect(type_idx, table_idx)
+        | Instruction::ReturnCallIndirect(type_idx, table_idx) => {
+            format!(" {} (type {})", table_idx, type_idx)
+        }
+        Instruction::Select2(_, type_) => format!(" (result {})", val_type(type_)),
+        Instruction::MemorySize(mem_idx) | Instruction::MemoryGrow(mem_idx) => opt_idx(*mem_idx),
+        Instruction::MemoryFill(mem_idx) => opt_idx(*mem_idx),
+        Instruction::MemoryCopy(dst, src) => match (dst, src) {
+            (0, 0) => String::new(),
+            _ => format!(" {} {}", dst, src),
+        },
+        Instruction::MemoryInit(data_idx, mem_idx) => format!("{} {}", opt_idx(*mem_idx), data_idx),
+        Instruction::TableInit(elem_idx, table_idx) => format!(" {} {}", table_idx, elem_idx),
+        Instruction::TableCopy(dst, src) => format!(" {} {}", dst, src),
+        Instruction::I32Const(v) => format!(" {}", v),
+        Instruction::I64Const(v) => format!(" {}", v),
+        Instruction::F32Const(v) => format!(" {}", f32_text(*v)),
+        Instruction::F64Const(v) => format!(" {}", f64_text(*v)),
+        Instruction::V128Const(v) => format!(
+            " i32x4 0x{:08x} 0x{:08x} 0x{:08x} 0x{:08x}",
+            v.0 as u32, v.1 as u32, v.2 as u32, v.3 as u32
+        ),
+        Instruction::I8x16Shuffle(lanes) => lanes.iter().map(|lane| format!(" {}", lane)).collect(),
+        Instruction::RefNull(heap_type) => match RefType::from_heap_type(*heap_type) {
+            Some(RefType::ExternRef) => " extern".to_string(),
+            Some(RefType::ExnRef) => " exn".to_string(),
+            _ => " func".to_string(),
+        },
+        Instruction::I32Load(arg)
+        | Instruction::I64Load(arg)
+        | Instruction::F32Load(arg)
+        | Instruction::F64Load(arg)
+        | Instruction::I32Load8S(arg)
+        | Instruction::I32Load8U(arg)
+        | Instruction::I32Load16S(arg)
+        | Instruction::I32Load16U(arg)
+        | Instruction::I64Load8S(arg)
+        | Instruction::I64Load8U(arg)
+        | Instruction::I64Load16S(arg)
+        | Instruction::I64Load16U(arg)
+        | Instruction::I64Load32S(arg)
+        | Instruction::I64Load32U(arg)
+        | Instruction::I32Store(arg)
+        | Instruction::I64Store(arg)
+        | Instruction::F32Store(arg)
+        | Instruction::F64Store(arg)
+        | Instruction::I32Store8(arg)
+        | Instruction::I32Store16(arg)
+        | Instruction::I64Store8(arg)
+        | Instruction::I64Store16(arg)
+        | Instruction::I64Store32(arg)
+        | Instruction::V128Load(arg)
+        | Instruction::V128Load8x8S(arg)
+        | Instruction::V128Load8x8U(arg)
+        | Instruction::V128Load16x4S(arg)
+        | Instruction::V128Load16x4U(arg)
+        | Instruction::V128Load32x2S(arg)
+        | Instruction::V128Load32x2U(arg)
+        | Instruction::V128Load8Splat(arg)
+        | Instruction::V128Load16Splat(arg)
+        | Instruction::V128Load32Splat(arg)
+        | Instruction::V128Load64Splat(arg)
+        | Instruction::V128Store(arg)
+        | Instruction::V128Load32Zero(arg)
+        | Instruction::V128Load64Zero(arg)
+        | Instruction::MemoryAtomicNotify(arg)
+        | Instruction::MemoryAtomicWait32(arg)
+        | Instruction::MemoryAtomicWait64(arg)
+        | Instruction::I32AtomicLoad(arg)
+        | Instruction::I64AtomicLoad(arg)
+        | Instruction::I32AtomicLoad8U(arg)
+        | Instruction::I32AtomicLoad16U(arg)
+        | Instruction::I64AtomicLoad8U(arg)
+        | Instruction::I64AtomicLoad16U(arg)
+        | Instruction::I64AtomicLoad32U(arg)
+        | Instruction::I32AtomicStore(arg)
+        | Instruction::I64AtomicStore(arg)
+        | Instruction::I32AtomicStore8(arg)
+        | Instruction::I32AtomicStore16(arg)
+        | Instruction::I64AtomicStore8(arg)
+        | Instruction::I64AtomicStore16(arg)
+        | Instruction::I64AtomicStore32(arg)
+        | Instruction::I32AtomicRmwAdd(arg)
+        | Instruction::I64AtomicRmwAdd(arg)
+        | Instruction::I32AtomicRmw8AddU(arg)
+        | Instruction::I32AtomicRmw16AddU(arg)
+        | Instruction::I64AtomicRmw8AddU(arg)
+        | Instruction::I64AtomicRmw16AddU(arg)
+        | Instruction::I64AtomicRmw32AddU(arg)
+        | Instruction::I32AtomicRmwSub(arg)
+        | Instruction::I64AtomicRmwSub(arg)
+        | Instruction::I32AtomicRmw8SubU(arg)
+        | Instruction::I32AtomicRmw16SubU(arg)
+        | Instruction::I64AtomicRmw8SubU(arg)
+        | Instruction::I64AtomicRmw16SubU(arg)
+        | Instruction::I64AtomicRmw32SubU(arg)
+        | Instruction::I32AtomicRmwAnd(arg)
+        | Instruction::I64AtomicRmwAnd(arg)
+        | Instruction::I32AtomicRmw8AndU(arg)
+        | Instruction::I32AtomicRmw16AndU(arg)
+        | Instruction::I64AtomicRmw8AndU(arg)
+        | Instruction::I64AtomicRmw16AndU(arg)
+        | Instruction::I64AtomicRmw32AndU(arg)
+        | Instruction::I32AtomicRmwOr(arg)
+        | Instruction::I64AtomicRmwOr(arg)
+        | Instruction::I32AtomicRmw8OrU(arg)
+        | Instruction::I32AtomicRmw16OrU(arg)
+        | Instruction::I64AtomicRmw8OrU(arg)
+        | Instruction::I64AtomicRmw16OrU(arg)
+        | Instruction::I64AtomicRmw32OrU(arg)
+        | Instruction::I32AtomicRmwXor(arg)
+        | Instruction::I64AtomicRmwXor(arg)
+        | Instruction::I32AtomicRmw8XorU(arg)
+        | Instruction::I32AtomicRmw16XorU(arg)
+        | Instruction::I64AtomicRmw8XorU(arg)
+        | Instruction::I64AtomicRmw16XorU(arg)
+        | Instruction::I64AtomicRmw32XorU(arg)
+        | Instruction::I32AtomicRmwXchg(arg)
+        | Instruction::I64AtomicRmwXchg(arg)
+        | Instruction::I32AtomicRmw8XchgU(arg)
+        | Instruction::I32AtomicRmw16XchgU(arg)
+        | Instruction::I64AtomicRmw8XchgU(arg)
+        | Instruction::I64AtomicRmw16XchgU(arg)
+        | Instruction::I64AtomicRmw32XchgU(arg)
+        | Instruction::I32AtomicRmwCmpxchg(arg)
+        | Instruction::I64AtomicRmwCmpxchg(arg)
+        | Instruction::I32AtomicRmw8CmpxchgU(arg)
+        | Instruction::I32AtomicRmw16CmpxchgU(arg)
+        | Instruction::I64AtomicRmw8CmpxchgU(arg)
+        | Instruction::I64AtomicRmw16CmpxchgU(arg)
+        | Instruction::I64AtomicRmw32CmpxchgU(arg) => mem_arg(name, arg),
+        Instruction::V128Load8Lane(arg, lane)
+        | Instruction::V128Load16Lane(arg, lane)
+        | Instruction::V128Load32Lane(arg, lane)
+        | Instruction::V128Load64Lane(arg, lane)
+        | Instruction::V128Store8Lane(arg, lane)
+        | Instruction::V128Store16Lane(arg, lane)
+        | Instruction::V128Store32Lane(arg, lane)
+        | Instruction::V128Store64Lane(arg, lane) => format!("{} {}", mem_arg(name, arg), lane),
+        Instruction::I8x16ExtractLaneS(lane)
+        | Instruction::I8x16ExtractLaneU(lane)
+        | Instruction::I8x16ReplaceLane(lane)
+        | Instruction::I16x8ExtractLaneS(lane)
+        | Instruction::I16x8ExtractLaneU(lane)
+        | Instruction::I16x8ReplaceLane(lane)
+        | Instruction::I32x4ExtractLane(lane)
+        | Instruction::I32x4ReplaceLane(lane)
+        | Instruction::I64x2ExtractLane(lane)
+        | Instruction::I64x2ReplaceLane(lane)
+        | Instruction::F32x4ExtractLane(lane)
+        | Instruction::F32x4ReplaceLane(lane)
+        | Instruction::F64x2ExtractLane(lane)
+        | Instruction::F64x2ReplaceLane(lane) => format!(" {}", lane),
+        _ => String::new(),
+    };
+
+    format!("{}{}", name, immediates)
+}
+
+pub(super) fn catch(catch: &Catch) -> String {
+    match catch {
+        Catch::Tag(tag, label) => format!(" (catch {} {})", tag, label),
+        Catch::TagRef(tag, label) => format!(" (catch_ref {} {})", tag, label),
+        Catch::All(label) => format!(" (catch_all {})", label),
+        Catch::AllRef(label) => format!(" (catch_all_ref {})", label),
+    }
+}
+
+/// 内存索引、偏移为 0，对齐为默认值时省略
+fn mem_arg(name: &str, arg: &MemoryArg) -> String {
+    let mut text = opt_idx(arg.mem_idx);
+
+    if arg.offset != 0 {
+        text.push_str(&format!(" offset={}", arg.offset));
+    }
+
+    if 1u64.checked_shl(arg.align) != Some(natural_align(name) as u64) {
+        text.push_str(&format!(" align={}", 1u64 << arg.align.min(63)));
+    }
+
+    text
+}
+
+fn opt_idx(idx: u32) -> String {
+    match idx {
+        0 => String::new(),
+        _ => format!(" {}", idx),
+    }
+}
+
+/// 常量表达式：单条指令用折叠形式，多条用 (keyword instr*)
+pub(super) fn const_expr(expr: &Expr, keyword: &str) -> String {
+    let instrs = expr.iter().map(instr_text).collect::<Vec<_>>();
+
+    match (instrs.as_slice(), keyword) {
+        ([instr], _) => format!("({})", instr),
+        (_, "") => instrs.join(" "),
+        _ => format!("({} {})", keyword, instrs.join(" ")).replace(" )", ")"),
+    }
+}
+
+pub(super) fn signature(func_type: &FuncType) -> String {
+    let mut text = String::new();
+
+    if !func_type.params.is_empty() {
+        text.push_str(&format!(" (param {})", val_types(&func_type.params)));
+    }
+
+    if !func_type.results.is_empty() {
+        text.push_str(&format!(" (result {})", val_types(&func_type.results)));
+    }
+
+    text
+}
+
+pub(super) fn block_type(block_type: &BlockType) -> String {
+    match block_type {
+        BlockType::Empty => String::new(),
+        BlockType::TypeIdx(idx) => format!(" (type {})", idx),
+        _ => signature(&FuncType::from(block_type)),
+    }
+}
+
+pub(super) fn val_types(types: &[ValType]) -> String {
+    types.iter().map(val_type).collect::<Vec<_>>().join(" ")
+}
+
+pub(super) fn val_type(val_type: &ValType) -> &'static str {
+    match val_type {
+        ValType::I32 => "i32",
+        ValType::I64 => "i64",
+        ValType::F32 => "f32",
+        ValType::F64 => "f64",
+        ValType::V128 => "v128",
+        ValType::FuncRef => "funcref",
+        ValType::ExternRef | ValType::NullRef => "externref",
+        ValType::ExnRef => "exnref",
+    }
+}
+
+pub(super) fn limits(limits: &Limits) -> String {
+    let addr = if limits.is64 { "i64 " } else { "" };
+    let shared = if limits.shared { " shared" } else { "" };
+
+    match limits.max {
+        Some(max) => format!("{}{} {}{}", addr, limits.min, max, shared),
+        None => format!("{}{}{}", addr, limits.min, shared),
+    }
+}
+
+pub(super) fn table(table_type: &TableType) -> String {
+    format!(
+        "{} {}",
+        limits(&table_type.limits),
+        val_type(&table_type.elem_type.as_val_type())
+    )
+}
+
+pub(super) fn global(global_type: &GlobalType) -> String {
+    match global_type.mut_ {
+        Mut::Var => format!("(mut {})", val_type(&global_type.val_type)),
+        Mut::Const => val_type(&global_type.val_type).to_string(),
+    }
+}
+
+/// 可打印的 ASCII 原样输出，其余字节转义成 \hh
+pub(super) fn string(bytes: &[u8]) -> String {
+    let mut text = String::from("\"");
+
+    for byte in bytes {
+        match byte {
+            b'"' => text.push_str("\\\""),
+            b'\\' => text.push_str("\\\\"),
+            0x20..=0x7e => text.push(*byte as char),
+            _ => text.push_str(&format!("\\{:02x}", byte)),
+        }
+    }
+
+    text.push('"');
+    text
+}
+
+/// 有限值用最短的十进制表示，nan 保留负载
+fn f32_text(v: f32) -> String {
+    float_text(v.to_bits() as u64, 23, 8, format!("{:?}", v))
+}
+
+fn f64_text(v: f64) -> String {
+    float_text(v.to_bits(), 52, 11, format!("{:?}", v))
+}
+
+fn float_text(bits: u64, mant_bits: u32, exp_bits: u32, finite: String) -> String {
+    let sign = if bits >> (mant_bits + exp_bits) == 1 {
+        "-"
+    } else {
+        ""
+    };
+    let exp = bits >> mant_bits & ((1 << exp_bits) - 1);
+    let payload = bits & ((1 << mant_bits) - 1);
+
+    match (exp == (1 << exp_bits) - 1, payload) {
+        (true, 0) => format!("{}inf", sign),
+        (true, payload) if payload == 1 << (mant_bits - 1) => format!("{}nan", sign),
+        (true, payload) => format!("{}nan:0x{:x}", sign, payload),
+        _ => finite,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::binary::encode::Encode;
+    use crate::binary::module::Module;
+    use crate::spec::models::CommandType;
+    use crate::spec::wast::Wast;
+
+    /// 编码后输出成文本，再解析、编码，前后的二进制一致
+    fn round_trip(text: &str) {
+        let encoded = Module::from_text(text).unwrap().encode();
+        let printed = Module::from_data(encoded.clone()).unwrap().to_text();
+        let reparsed = Module::from_text(&printed).unwrap_or_else(|err| panic!("{}\n{}", err, printed));
+
+        assert_eq!(reparsed.encode(), encoded, "{}", printed);
+    }
+
+    #[test]
+    fn test_round_trip_proposals() {
+        for entry in fs::read_dir("./tests/proposals").unwrap() {
+            let path = entry.unwrap().path();
+            let filename = path.file_name().unwrap().to_string_lossy();
+            let wast = Wast::parse(&filename, &fs::read_to_string(&path).unwrap()).unwrap();
+
+            for command in &wast.json.commands {
+                if let CommandType::Module(module) = &command.type_ {
+                    round_trip(&String::from_utf8(wast.files[&module.filename].clone()).unwrap());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_literals() {
+        round_trip(
+            r#"(module
+                 (table $t0 1 funcref)
+                 (table $t1 2 funcref)
+                 (memory $m0 1)
+                 (memory $m1 1)
+                 (func $f (result f32 f64 v128)
+                   (f32.const nan:0x200001)
+                   (f64.const -nan:0x8000000000001)
+                   (v128.const f32x4 nan:0x1 -nan inf -0))
+                 (elem (table $t1) (i32.const 1) func $f)
+                 (elem (table 0) (i32.const 0) funcref (ref.func $f))
+                 (data (memory $m1) (i32.const 0) "\00\01\ff\"\\\n\t\u{263a}")
+                 (data (memory 0) (i32.const 8) "a" "\7f"))"#,
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
+
+use super::errors::WasiError;
+use super::types::{Errno, WasiResult};
+use crate::execution::errors::VMState;
+
+/// 符号链接最多嵌套的层数
+const MAX_LINKS: usize = 40;
+
+/// 文件描述符指向的对象
+pub(super) enum Desc {
+    Reader(Box<dyn Read>),
+    Writer(Box<dyn Write>),
+    File(File),
+    /// root 为所属预打开目录在宿主中的路径，通过它打开的路径都不能离开 root
+    Dir {
+        path: PathBuf,
+        root: PathBuf,
+        preopen: Option<String>,
+    },
+}
+
+/// WASI 进程的运行环境：参数、环境变量、标准输入输出和预打开的目录
+///
+/// 模块只能访问预打开的目录及其子目录，不能通过绝对路径或 .. 访问其他位置
+pub struct WasiCtx {
+    pub(super) args: Vec<String>,
+    pub(super) envs: Vec<(String, String)>,
+    pub(super) fds: BTreeMap<u32, Desc>,
+    pub(super) start: Instant,
+}
+
+impl WasiCtx {
+    /// 默认继承宿主的标准输入输出，没有参数、环境变量和预打开的目录
+    pub fn new() -> Self {
+        let mut fds = BTreeMap::new();
+
+        fds.insert(0, Desc::Reader(Box::new(io::stdin())));
+        fds.insert(1, Desc::Writer(Box::new(io::stdout())));
+        fds.insert(2, Desc::Writer(Box::new(io::stderr())));
+
+        Self {
+            args: vec![],
+            envs: vec![],
+            fds,
+            start: Instant::now(),
+        }
+    }
+
+    /// 第一个参数一般是程序名
+    pub fn args<S: AsRef<str>>(&mut self, args: &[S]) -> &mut Self {
+        self.args = args.iter().map(|arg| arg.as_ref().to_string()).collect();
+
+        self
+    }
+
+    pub fn env(&mut self, key: &str, value: &str) -> &mut Self {
+        self.envs.push((key.to_string(), value.to_string()));
+
+        self
+    }
+
+    pub fn inherit_env(&mut self) -> &mut Self {
+        self.envs.extend(std::env::vars());
+
+        self
+    }
+
+    pub fn stdin(&mut self, stdin: Box<dyn Read>) -> &mut Self {
+        self.fds.insert(0, Desc::Reader(stdin));
+
+        self
+    }
+
+    pub fn stdout(&mut self, stdout: Box<dyn Write>) -> &mut Self {
+        self.fds.insert(1, Desc::Writer(stdout));
+
+        self
+    }
+
+    pub fn stderr(&mut self, stderr: Box<dyn Write>) -> &mut Self {
+        self.fds.insert(2, Desc::Writer(stderr));
+
+        self
+    }
+
+    /// 把宿主的 host_path 目录以 guest_path 的名字交给模块，按调用顺序从 3 开始分配描述符
+    pub fn preopen_dir<P: AsRef<Path>>(&mut self, host_path: P, guest_path: &str) -> VMState<&mut Self> {
+        let path = fs::canonicalize(&host_path)
+            .map_err(|err| WasiError::Preopen(host_path.as_ref().display().to_string(), err))?;
+
+        if !path.is_dir() {
+            Err(WasiError::Preopen(
+                path.display().to_string(),
+                io::Error::from(io::ErrorKind::NotADirectory),
+            ))?;
+        }
+
+        self.alloc_fd(Desc::Dir {
+            root: path.clone(),
+            path,
+            preopen: Some(guest_path.to_string()),
+        });
+
+        Ok(self)
+    }
+
+    /// 分配最小的空闲描述符
+    pub(super) fn alloc_fd(&mut self, desc: Desc) -> u32 {
+        let fd = (0..).find(|fd| !self.fds.contains_key(fd)).unwrap();
+
+        self.fds.insert(fd, desc);
+
+        fd
+    }
+
+    pub(super) fn get_fd(&mut self, fd: u32) -> WasiResult<&mut Desc> {
+        self.fds.get_mut(&fd).ok_or(Errno::Badf)
+    }
+
+    pub(super) fn get_file(&mut self, fd: u32) -> WasiResult<&mut File> {
+        match self.get_fd(fd)? {
+            Desc::File(file) => Ok(file),
+            Desc::Dir { .. } => Err(Errno::Isdir),
+            _ => Err(Errno::Spipe),
+        }
+    }
+
+    /// 把模块给出的相对路径解析成宿主路径，fd 必须是目录
+    ///
+    /// 路径只按字面处理 . 和 ..，中间各级的符号链接替换成它指向的位置，最后一级保持原样，
+    /// 以便删除或读取链接本身；所有链接，包括最后一级，都必须指向预打开的目录之内
+    pub(super) fn resolve(&mut self, fd: u32, path: &str) -> WasiResult<PathBuf> {
+        let (root, literal) = self.literal(fd, path)?;
+
+        expand(&root, &literal, 0)?;
+
+        match (literal.parent(), literal.file_name()) {
+            (Some(parent), Some(name)) if literal != root => Ok(expand(&root, parent, 0)?.join(name)),
+            _ => Ok(literal),
+        }
+    }
+
+    /// 和 resolve 相同，但最后一级的符号链接也会被替换，得到的路径中不再有符号链接
+    pub(super) fn resolve_follow(&mut self, fd: u32, path: &str) -> WasiResult<PathBuf> {
+        let (root, literal) = self.literal(fd, path)?;
+
+        expand(&root, &literal, 0)
+    }
+
+    /// 按字面拼接出的路径和它所属的预打开目录
+    fn literal(&mut self, fd: u32, path: &str) -> WasiResult<(PathBuf, PathBuf)> {
+        let (base, root) = match self.get_fd(fd)? {
+            Desc::Dir { path, root, .. } => (path.clone(), root.clone()),
+            _ => Err(Errno::Notdir)?,
+        };
+        let relative = base.strip_prefix(&root).map_err(|_| Errno::Notcapable)?;
+        let mut parts: Vec<_> = relative.components().collect();
+
+        for component in Path::new(path).components() {
+            match component {
+                Component::Normal(_) => parts.push(component),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    parts.pop().ok_or(Errno::Notcapable)?;
+                }
+                Component::RootDir | Component::Prefix(_) => Err(Errno::Notcapable)?,
+            }
+        }
+
+        let literal = parts.iter().fold(root.clone(), |path, part| path.join(part));
+
+        Ok((root, literal))
+    }
+
+    /// 目录描述符所属的预打开目录
+    pub(super) fn root_of(&mut self, fd: u32) -> WasiResult<PathBuf> {
+        match self.get_fd(fd)? {
+            Desc::Dir { root, .. } => Ok(root.clone()),
+            _ => Err(Errno::Notdir),
+        }
+    }
+}
+
+/// 逐级替换 path 中的符号链接，每个链接都必须指向 root 之内，包括指向不存在的文件的链接
+///
+/// 最后一级不能用 exists 判断，它会跟随链接，悬空的链接在 O_CREAT 时会在 root 之外创建文件
+fn expand(root: &Path, path: &Path, depth: usize) -> WasiResult<PathBuf> {
+    if depth > MAX_LINKS {
+        Err(Errno::Loop)?;
+    }
+
+    let relative = path.strip_prefix(root).map_err(|_| Errno::Notcapable)?;
+    let mut current = root.to_path_buf();
+    let mut components = relative.components();
+
+    while let Some(component) = components.next() {
+        current.push(component);
+
+        match fs::symlink_metadata(&current) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                // current 之前的部分已经没有链接，相对链接可以直接按字面拼接
+                let target = current.parent().unwrap_or(root).join(fs::read_link(&current)?);
+
+                return expand(root, &normalize(&target).join(components.as_path()), depth + 1);
+            }
+            Ok(_) => {}
+            // 不存在的部分之后不会再有符号链接
+            Err(_) => {
+                current.extend(components);
+                break;
+            }
+        }
+    }
+
+    Ok(current)
+}
+
+/// 从 root 开始逐级 openat，每一级都不跟随符号链接
+///
+/// path 由 resolve_follow 得到，其中已经没有符号链接；检查之后某一级被换成链接时打开失败，而不是离开 root
+#[cfg(unix)]
+pub(super) fn open_beneath(root: &Path, path: &Path, flags: i32) -> WasiResult<File> {
+    use std::ffi::CString;
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::os::unix::ffi::OsStrExt;
+
+    let relative = path.strip_prefix(root).map_err(|_| Errno::Notcapable)?;
+    let mut file = File::open(root)?;
+    let mut components = relative.components().peekable();
+
+    while let Some(component) = components.next() {
+        let name = CString::new(component.as_os_str().as_bytes()).map_err(|_| Errno::Inval)?;
+        let (flags, mode) = match components.peek() {
+            Some(_) => (libc::O_RDONLY | libc::O_DIRECTORY, 0),
+            None => (flags, 0o666),
+        };
+        // SAFETY: name 以 0 结尾，file 在调用期间一直打开
+        let fd = unsafe {
+            libc::openat(
+                file.as_raw_fd(),
+                name.as_ptr(),
+                flags | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+                mode as libc::c_uint,
+            )
+        };
+
+        if fd < 0 {
+            let err = io::Error::last_os_error();
+
+            Err(match err.raw_os_error() {
+                Some(libc::ELOOP) => Errno::Loop,
+                _ => Errno::from(err),
+            })?;
+        }
+
+        // SAFETY: fd 是刚打开的描述符，只归这里所有
+        file = unsafe { File::from_raw_fd(fd) };
+    }
+
+    Ok(file)
+}
+
+/// 按字面去掉 . 和 ..，path 是绝对路径
+fn normalize(path: &Path) -> PathBuf {
+    path.components().fold(PathBuf::new(), |mut path, component| {
+        match component {
+            Component::ParentDir => {
+                path.pop();
+            }
+            Component::CurDir => {}
+            _ => path.push(component),
+        }
+
+        path
+    })
+}
+
+impl Default for WasiCtx {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,23 @@
+use std::error::Error;
+use std::io;
+
+#[derive(thiserror::Error, Debug)]
+pub enum WasiError {
+    /// proc_exit，不是 trap，宿主可以用 exit_code 取出退出码
+    #[error("进程退出，退出码：{0}")]
+    Exit(i32),
+
+    #[error("调用方没有导出内存")]
+    NoMemory,
+
+    #[error("无法预打开目录 {0}：{1}")]
+    Preopen(String, io::Error),
+}
+
+/// 调用因为 proc_exit 结束时返回退出码
+pub fn exit_code(err: &(dyn Error + 'static)) -> Option<i32> {
+    match err.downcast_ref::<WasiError>() {
+        Some(WasiError::Exit(code)) => Some(*code),
+        _ => None,
+    }
+}
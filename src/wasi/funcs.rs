@@ -0,0 +1,810 @@
+use std::fs::{self, File, FileTimes};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rand::RngCore;
+
+#[cfg(unix)]
+use super::ctx::open_beneath;
+use super::ctx::{Desc, WasiCtx};
+use super::memory::{self, GuestMem};
+use super::types::*;
+
+/// 系统调用的实现，指针和长度都是调用方内存中的位置，结果写回调用方内存
+/// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md
+impl WasiCtx {
+    pub(super) fn args_get(&mut self, mem: &mut GuestMem, argv: u32, argv_buf: u32) -> WasiResult {
+        write_strs(
+            mem,
+            self.args.iter().map(|arg| arg.as_bytes().to_vec()),
+            argv,
+            argv_buf,
+        )
+    }
+
+    pub(super) fn args_sizes_get(&mut self, mem: &mut GuestMem, argc: u32, buf_size: u32) -> WasiResult {
+        let size = self.args.iter().map(|arg| arg.len() + 1).sum::<usize>();
+
+        mem.write_u32(argc, self.args.len() as u32)?;
+        mem.write_u32(buf_size, size as u32)
+    }
+
+    pub(super) fn environ_get(
+        &mut self,
+        mem: &mut GuestMem,
+        environ: u32,
+        environ_buf: u32,
+    ) -> WasiResult {
+        let envs = self.envs.iter().map(|(k, v)| format!("{}={}", k, v).into_bytes());
+
+        write_strs(mem, envs, environ, environ_buf)
+    }
+
+    pub(super) fn environ_sizes_get(
+        &mut self,
+        mem: &mut GuestMem,
+        count: u32,
+        buf_size: u32,
+    ) -> WasiResult {
+        let size = self
+            .envs
+            .iter()
+            .map(|(k, v)| k.len() + v.len() + 2)
+            .sum::<usize>();
+
+        mem.write_u32(count, self.envs.len() as u32)?;
+        mem.write_u32(buf_size, size as u32)
+    }
+
+    pub(super) fn clock_res_get(&mut self, mem: &mut GuestMem, id: u32, resolution: u32) -> WasiResult {
+        match id {
+            clockid::REALTIME..=clockid::THREAD_CPUTIME_ID => mem.write_u64(resolution, 1),
+            _ => Err(Errno::Inval),
+        }
+    }
+
+    /// CPU 时间以进程启动后经过的时间代替
+    pub(super) fn clock_time_get(
+        &mut self,
+        mem: &mut GuestMem,
+        id: u32,
+        _precision: u64,
+        time: u32,
+    ) -> WasiResult {
+        let now = match id {
+            clockid::REALTIME => to_nanos(SystemTime::now()),
+            clockid::MONOTONIC | clockid::PROCESS_CPUTIME_ID | clockid::THREAD_CPUTIME_ID => {
+                self.start.elapsed().as_nanos() as u64
+            }
+            _ => Err(Errno::Inval)?,
+        };
+
+        mem.write_u64(time, now)
+    }
+
+    pub(super) fn fd_advise(&mut self, _: &mut GuestMem, fd: u32, _: u64, _: u64, _: u32) -> WasiResult {
+        self.get_file(fd).map(|_| ())
+    }
+
+    pub(super) fn fd_allocate(
+        &mut self,
+        _: &mut GuestMem,
+        fd: u32,
+        offset: u64,
+        len: u64,
+    ) -> WasiResult {
+        let file = self.get_file(fd)?;
+        let size = offset.checked_add(len).ok_or(Errno::TooBig)?;
+
+        if size > file.metadata()?.len() {
+            file.set_len(size)?;
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn fd_close(&mut self, _: &mut GuestMem, fd: u32) -> WasiResult {
+        self.fds.remove(&fd).map(|_| ()).ok_or(Errno::Badf)
+    }
+
+    pub(super) fn fd_datasync(&mut self, _: &mut GuestMem, fd: u32) -> WasiResult {
+        Ok(self.get_file(fd)?.sync_data()?)
+    }
+
+    pub(super) fn fd_fdstat_get(&mut self, mem: &mut GuestMem, fd: u32, buf: u32) -> WasiResult {
+        let filetype = match self.get_fd(fd)? {
+            Desc::Reader(_) | Desc::Writer(_) => filetype::CHARACTER_DEVICE,
+            Desc::File(_) => filetype::REGULAR_FILE,
+            Desc::Dir { .. } => filetype::DIRECTORY,
+        };
+
+        mem.write_bytes(buf, &[0; FDSTAT_SIZE as usize])?;
+        mem.write_u8(buf, filetype)?;
+        mem.write_u64(memory::offset(buf, 8)?, rights::ALL)?;
+        mem.write_u64(memory::offset(buf, 16)?, rights::ALL)
+    }
+
+    pub(super) fn fd_fdstat_set_rights(
+        &mut self,
+        _: &mut GuestMem,
+        fd: u32,
+        _: u64,
+        _: u64,
+    ) -> WasiResult {
+        self.get_fd(fd).map(|_| ())
+    }
+
+    pub(super) fn fd_filestat_get(&mut self, mem: &mut GuestMem, fd: u32, buf: u32) -> WasiResult {
+        let meta = match self.get_fd(fd)? {
+            Desc::File(file) => file.metadata()?,
+            Desc::Dir { path, .. } => fs::metadata(path)?,
+            Desc::Reader(_) | Desc::Writer(_) => {
+                mem.write_bytes(buf, &[0; FILESTAT_SIZE as usize])?;
+
+                return mem.write_u8(memory::offset(buf, 16)?, filetype::CHARACTER_DEVICE);
+            }
+        };
+
+        write_filestat(mem, buf, &meta)
+    }
+
+    pub(super) fn fd_filestat_set_size(&mut self, _: &mut GuestMem, fd: u32, size: u64) -> WasiResult {
+        Ok(self.get_file(fd)?.set_len(size)?)
+    }
+
+    pub(super) fn fd_filestat_set_times(
+        &mut self,
+        _: &mut GuestMem,
+        fd: u32,
+        atim: u64,
+        mtim: u64,
+        flags: u32,
+    ) -> WasiResult {
+        let times = file_times(atim, mtim, flags)?;
+
+        Ok(self.get_file(fd)?.set_times(times)?)
+    }
+
+    /// 读完之后恢复原来的位置
+    pub(super) fn fd_pread(
+        &mut self,
+        mem: &mut GuestMem,
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        offset: u64,
+        nread: u32,
+    ) -> WasiResult {
+        let iovs = mem.read_iovs(iovs, iovs_len)?;
+        let file = self.get_file(fd)?;
+        let pos = file.stream_position()?;
+
+        file.seek(SeekFrom::Start(offset))?;
+
+        let data = read_once(file, mem, &iovs);
+
+        file.seek(SeekFrom::Start(pos))?;
+
+        let data = data?;
+
+        mem.scatter(&iovs, &data)?;
+        mem.write_u32(nread, data.len() as u32)
+    }
+
+    pub(super) fn fd_prestat_get(&mut self, mem: &mut GuestMem, fd: u32, buf: u32) -> WasiResult {
+        match self.get_fd(fd)? {
+            Desc::Dir {
+                preopen: Some(name), ..
+            } => {
+                let len = name.len() as u32;
+
+                mem.write_u32(buf, 0)?;
+                mem.write_u32(memory::offset(buf, 4)?, len)
+            }
+            _ => Err(Errno::Badf),
+        }
+    }
+
+    pub(super) fn fd_prestat_dir_name(
+        &mut self,
+        mem: &mut GuestMem,
+        fd: u32,
+        path: u32,
+        len: u32,
+    ) -> WasiResult {
+        match self.get_fd(fd)? {
+            Desc::Dir {
+                preopen: Some(name), ..
+            } => {
+                let name = name.as_bytes();
+
+                mem.write_bytes(path, &name[..name.len().min(len as usize)])
+            }
+            _ => Err(Errno::Badf),
+        }
+    }
+
+    pub(super) fn fd_pwrite(
+        &mut self,
+        mem: &mut GuestMem,
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        offset: u64,
+        nwritten: u32,
+    ) -> WasiResult {
+        let data = mem.gather(&mem.read_iovs(iovs, iovs_len)?)?;
+        let file = self.get_file(fd)?;
+        let pos = file.stream_position()?;
+
+        file.seek(SeekFrom::Start(offset))?;
+
+        let ret = file.write_all(&data);
+
+        file.seek(SeekFrom::Start(pos))?;
+        ret?;
+
+        mem.write_u32(nwritten, data.len() as u32)
+    }
+
+    pub(super) fn fd_read(
+        &mut self,
+        mem: &mut GuestMem,
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        nread: u32,
+    ) -> WasiResult {
+        let iovs = mem.read_iovs(iovs, iovs_len)?;
+        let data = match self.get_fd(fd)? {
+            Desc::Reader(reader) => read_once(reader, mem, &iovs)?,
+            Desc::File(file) => read_once(file, mem, &iovs)?,
+            Desc::Writer(_) => Err(Errno::Badf)?,
+            Desc::Dir { .. } => Err(Errno::Isdir)?,
+        };
+
+        mem.scatter(&iovs, &data)?;
+        mem.write_u32(nread, data.len() as u32)
+    }
+
+    /// cookie 为下一项的序号，缓冲区放不下时最后一项会被截断，由调用方换更大的缓冲区重读
+    pub(super) fn fd_readdir(
+        &mut self,
+        mem: &mut GuestMem,
+        fd: u32,
+        buf: u32,
+        buf_len: u32,
+        cookie: u64,
+        bufused: u32,
+    ) -> WasiResult {
+        let path = match self.get_fd(fd)? {
+            Desc::Dir { path, .. } => path.clone(),
+            _ => Err(Errno::Notdir)?,
+        };
+        let mut entries = vec![
+            (".".to_string(), filetype::DIRECTORY),
+            ("..".to_string(), filetype::DIRECTORY),
+        ];
+        let mut rest = fs::read_dir(path)?
+            .map(|entry| {
+                let entry = entry?;
+
+                Ok((
+                    entry.file_name().to_string_lossy().to_string(),
+                    to_filetype(&entry.file_type()?),
+                ))
+            })
+            .collect::<Result<Vec<_>, std::io::Error>>()?;
+
+        rest.sort();
+        entries.extend(rest);
+
+        let mut data = vec![];
+
+        for (i, (name, filetype)) in entries.iter().enumerate().skip(cookie as usize) {
+            if data.len() >= buf_len as usize {
+                break;
+            }
+
+            data.extend((i as u64 + 1).to_le_bytes());
+            data.extend(0u64.to_le_bytes());
+            data.extend((name.len() as u32).to_le_bytes());
+            data.extend([*filetype, 0, 0, 0]);
+            data.extend(name.as_bytes());
+        }
+
+        data.truncate(buf_len as usize);
+        mem.write_bytes(buf, &data)?;
+        mem.write_u32(bufused, data.len() as u32)
+    }
+
+    /// to 原来指向的对象会被关闭
+    pub(super) fn fd_renumber(&mut self, _: &mut GuestMem, fd: u32, to: u32) -> WasiResult {
+        self.get_fd(to)?;
+
+        let desc = self.fds.remove(&fd).ok_or(Errno::Badf)?;
+
+        self.fds.insert(to, desc);
+
+        Ok(())
+    }
+
+    pub(super) fn fd_seek(
+        &mut self,
+        mem: &mut GuestMem,
+        fd: u32,
+        offset: i64,
+        whence: u32,
+        newoffset: u32,
+    ) -> WasiResult {
+        let pos = match whence {
+            whence::SET => SeekFrom::Start(u64::try_from(offset).map_err(|_| Errno::Inval)?),
+            whence::CUR => SeekFrom::Current(offset),
+            whence::END => SeekFrom::End(offset),
+            _ => Err(Errno::Inval)?,
+        };
+        let pos = self.get_file(fd)?.seek(pos)?;
+
+        mem.write_u64(newoffset, pos)
+    }
+
+    pub(super) fn fd_sync(&mut self, _: &mut GuestMem, fd: u32) -> WasiResult {
+        match self.get_fd(fd)? {
+            Desc::File(file) => Ok(file.sync_all()?),
+            Desc::Writer(writer) => Ok(writer.flush()?),
+            _ => Ok(()),
+        }
+    }
+
+    pub(super) fn fd_tell(&mut self, mem: &mut GuestMem, fd: u32, offset: u32) -> WasiResult {
+        let pos = self.get_file(fd)?.stream_position()?;
+
+        mem.write_u64(offset, pos)
+    }
+
+    pub(super) fn fd_write(
+        &mut self,
+        mem: &mut GuestMem,
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        nwritten: u32,
+    ) -> WasiResult {
+        let data = mem.gather(&mem.read_iovs(iovs, iovs_len)?)?;
+
+        match self.get_fd(fd)? {
+            Desc::Writer(writer) => {
+                writer.write_all(&data)?;
+                writer.flush()?;
+            }
+            Desc::File(file) => file.write_all(&data)?,
+            Desc::Reader(_) => Err(Errno::Badf)?,
+            Desc::Dir { .. } => Err(Errno::Isdir)?,
+        };
+
+        mem.write_u32(nwritten, data.len() as u32)
+    }
+
+    pub(super) fn path_create_directory(
+        &mut self,
+        mem: &mut GuestMem,
+        fd: u32,
+        path: u32,
+        len: u32,
+    ) -> WasiResult {
+        let path = self.resolve(fd, &mem.read_str(path, len)?)?;
+
+        Ok(fs::create_dir(path)?)
+    }
+
+    pub(super) fn path_filestat_get(
+        &mut self,
+        mem: &mut GuestMem,
+        fd: u32,
+        flags: u32,
+        path: u32,
+        len: u32,
+        buf: u32,
+    ) -> WasiResult {
+        let path = self.resolve(fd, &mem.read_str(path, len)?)?;
+        let meta = match flags & lookupflags::SYMLINK_FOLLOW {
+            0 => fs::symlink_metadata(path)?,
+            _ => fs::metadata(path)?,
+        };
+
+        write_filestat(mem, buf, &meta)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn path_filestat_set_times(
+        &mut self,
+        mem: &mut GuestMem,
+        fd: u32,
+        _flags: u32,
+        path: u32,
+        len: u32,
+        atim: u64,
+        mtim: u64,
+        fst_flags: u32,
+    ) -> WasiResult {
+        let path = self.resolve(fd, &mem.read_str(path, len)?)?;
+        let times = file_times(atim, mtim, fst_flags)?;
+
+        Ok(fs::File::open(path)?.set_times(times)?)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn path_link(
+        &mut self,
+        mem: &mut GuestMem,
+        old_fd: u32,
+        _flags: u32,
+        old_path: u32,
+        old_len: u32,
+        new_fd: u32,
+        new_path: u32,
+        new_len: u32,
+    ) -> WasiResult {
+        let old_path = self.resolve(old_fd, &mem.read_str(old_path, old_len)?)?;
+        let new_path = self.resolve(new_fd, &mem.read_str(new_path, new_len)?)?;
+
+        Ok(fs::hard_link(old_path, new_path)?)
+    }
+
+    /// 读写方式由 rights 决定，目录总是以目录描述符打开
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn path_open(
+        &mut self,
+        mem: &mut GuestMem,
+        fd: u32,
+        _dirflags: u32,
+        path: u32,
+        len: u32,
+        oflags: u32,
+        rights_base: u64,
+        _rights_inheriting: u64,
+        fdflags: u32,
+        opened_fd: u32,
+    ) -> WasiResult {
+        let root = self.root_of(fd)?;
+        let path = self.resolve_follow(fd, &mem.read_str(path, len)?)?;
+        let append = fdflags & fdflags::APPEND != 0;
+        let write = rights_base & rights::FD_WRITE != 0 || oflags & (oflags::CREAT | oflags::TRUNC) != 0;
+        let read = rights_base & rights::FD_READ != 0 || !(write || append);
+
+        let desc = if path.is_dir() {
+            if oflags & (oflags::CREAT | oflags::EXCL) == oflags::CREAT | oflags::EXCL {
+                Err(Errno::Exist)?;
+            }
+
+            if write || append {
+                Err(Errno::Isdir)?;
+            }
+
+            Desc::Dir {
+                path,
+                root,
+                preopen: None,
+            }
+        } else {
+            if oflags & oflags::DIRECTORY != 0 {
+                Err(match path.exists() {
+                    true => Errno::Notdir,
+                    false => Errno::Noent,
+                })?;
+            }
+
+            Desc::File(open_file(&root, &path, read, write && !append, append, oflags)?)
+        };
+
+        let fd = self.alloc_fd(desc);
+
+        mem.write_u32(opened_fd, fd)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn path_readlink(
+        &mut self,
+        mem: &mut GuestMem,
+        fd: u32,
+        path: u32,
+        len: u32,
+        buf: u32,
+        buf_len: u32,
+        bufused: u32,
+    ) -> WasiResult {
+        let path = self.resolve(fd, &mem.read_str(path, len)?)?;
+        let target = fs::read_link(path)?;
+        let target = target.to_string_lossy();
+        let target = &target.as_bytes()[..target.len().min(buf_len as usize)];
+
+        mem.write_bytes(buf, target)?;
+        mem.write_u32(bufused, target.len() as u32)
+    }
+
+    pub(super) fn path_remove_directory(
+        &mut self,
+        mem: &mut GuestMem,
+        fd: u32,
+        path: u32,
+        len: u32,
+    ) -> WasiResult {
+        let path = self.resolve(fd, &mem.read_str(path, len)?)?;
+
+        Ok(fs::remove_dir(path)?)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn path_rename(
+        &mut self,
+        mem: &mut GuestMem,
+        fd: u32,
+        old_path: u32,
+        old_len: u32,
+        new_fd: u32,
+        new_path: u32,
+        new_len: u32,
+    ) -> WasiResult {
+        let old_path = self.resolve(fd, &mem.read_str(old_path, old_len)?)?;
+        let new_path = self.resolve(new_fd, &mem.read_str(new_path, new_len)?)?;
+
+        Ok(fs::rename(old_path, new_path)?)
+    }
+
+    pub(super) fn path_unlink_file(
+        &mut self,
+        mem: &mut GuestMem,
+        fd: u32,
+        path: u32,
+        len: u32,
+    ) -> WasiResult {
+        let path = self.resolve(fd, &mem.read_str(path, len)?)?;
+
+        if fs::symlink_metadata(&path)?.is_dir() {
+            Err(Errno::Isdir)?;
+        }
+
+        Ok(fs::remove_file(path)?)
+    }
+
+    /// 读写事件总是立即就绪；只有时钟事件时，等到最早的一个到期
+    pub(super) fn poll_oneoff(
+        &mut self,
+        mem: &mut GuestMem,
+        in_: u32,
+        out: u32,
+        nsubscriptions: u32,
+        nevents: u32,
+    ) -> WasiResult {
+        if nsubscriptions == 0 {
+            Err(Errno::Inval)?;
+        }
+
+        let mut clocks = vec![];
+        let mut fds = vec![];
+
+        for i in 0..nsubscriptions as usize {
+            let sub = memory::element(in_, i, SUBSCRIPTION_SIZE)?;
+            let userdata = mem.read_u64(sub)?;
+            let tag = mem.read_bytes(memory::offset(sub, 8)?, 1)?[0];
+
+            match tag {
+                eventtype::CLOCK => {
+                    let id = mem.read_u32(memory::offset(sub, 16)?)?;
+                    let timeout = mem.read_u64(memory::offset(sub, 24)?)?;
+                    let abstime = mem.read_bytes(memory::offset(sub, 40)?, 1)?[0] & 1 != 0;
+                    let timeout = match (abstime, id) {
+                        (false, _) => timeout,
+                        (true, clockid::REALTIME) => timeout.saturating_sub(to_nanos(SystemTime::now())),
+                        (true, _) => timeout.saturating_sub(self.start.elapsed().as_nanos() as u64),
+                    };
+
+                    clocks.push((userdata, timeout));
+                }
+                eventtype::FD_READ | eventtype::FD_WRITE => fds.push((userdata, tag)),
+                _ => Err(Errno::Inval)?,
+            }
+        }
+
+        let events: Vec<_> = match fds.is_empty() {
+            true => {
+                let min = clocks.iter().map(|(_, timeout)| *timeout).min().unwrap_or(0);
+
+                thread::sleep(Duration::from_nanos(min));
+
+                clocks
+                    .iter()
+                    .filter(|(_, timeout)| *timeout == min)
+                    .map(|(userdata, _)| (*userdata, eventtype::CLOCK))
+                    .collect()
+            }
+            false => fds,
+        };
+
+        for (i, (userdata, type_)) in events.iter().enumerate() {
+            let event = memory::element(out, i, EVENT_SIZE)?;
+
+            mem.write_bytes(event, &[0; EVENT_SIZE as usize])?;
+            mem.write_u64(event, *userdata)?;
+            mem.write_u8(memory::offset(event, 10)?, *type_)?;
+        }
+
+        mem.write_u32(nevents, events.len() as u32)
+    }
+
+    pub(super) fn sched_yield(&mut self, _: &mut GuestMem) -> WasiResult {
+        thread::yield_now();
+
+        Ok(())
+    }
+
+    /// 先检查范围再分块填充，len 由调用方给出，不能按它一次分配内存
+    pub(super) fn random_get(&mut self, mem: &mut GuestMem, buf: u32, len: u32) -> WasiResult {
+        let mut chunk = [0; 4096];
+        let mut rng = rand::thread_rng();
+
+        mem.check(buf, len)?;
+
+        for start in (0..len).step_by(chunk.len()) {
+            let n = (len - start).min(chunk.len() as u32) as usize;
+
+            rng.fill_bytes(&mut chunk[..n]);
+            mem.write_bytes(memory::offset(buf, start)?, &chunk[..n])?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 打开普通文件，unix 上逐级打开，不跟随检查之后才出现的符号链接
+#[cfg(unix)]
+fn open_file(
+    root: &Path,
+    path: &Path,
+    read: bool,
+    write: bool,
+    append: bool,
+    oflags: u32,
+) -> WasiResult<File> {
+    let access = match (read, write || append) {
+        (true, true) => libc::O_RDWR,
+        (false, true) => libc::O_WRONLY,
+        _ => libc::O_RDONLY,
+    };
+    let flag = |set: bool, flag: i32| if set { flag } else { 0 };
+    let create = oflags & oflags::CREAT != 0;
+    let flags = access
+        | flag(append, libc::O_APPEND)
+        | flag(create, libc::O_CREAT)
+        | flag(create && oflags & oflags::EXCL != 0, libc::O_EXCL)
+        | flag(oflags & oflags::TRUNC != 0, libc::O_TRUNC);
+
+    open_beneath(root, path, flags)
+}
+
+#[cfg(not(unix))]
+fn open_file(
+    _: &Path,
+    path: &Path,
+    read: bool,
+    write: bool,
+    append: bool,
+    oflags: u32,
+) -> WasiResult<File> {
+    let file = fs::OpenOptions::new()
+        .read(read)
+        .write(write)
+        .append(append)
+        .create(oflags & oflags::CREAT != 0)
+        .create_new(oflags & (oflags::CREAT | oflags::EXCL) == oflags::CREAT | oflags::EXCL)
+        .truncate(oflags & oflags::TRUNC != 0)
+        .open(path)?;
+
+    Ok(file)
+}
+
+/// 依次写入字符串的指针和以 0 结尾的内容
+fn write_strs(
+    mem: &mut GuestMem,
+    strs: impl Iterator<Item = Vec<u8>>,
+    ptrs: u32,
+    mut buf: u32,
+) -> WasiResult {
+    for (i, mut s) in strs.enumerate() {
+        s.push(0);
+
+        mem.write_u32(memory::element(ptrs, i, 4)?, buf)?;
+        mem.write_bytes(buf, &s)?;
+        buf = memory::offset(buf, u32::try_from(s.len()).map_err(|_| Errno::Overflow)?)?;
+    }
+
+    Ok(())
+}
+
+/// 只读一次，和 readv 一样可能读不满
+///
+/// 读之前检查所有的 iovec 都在内存中，越界时不会消耗输入；
+/// 每个 iovec 都不超过内存的大小，一次最多也只读内存大小的数据
+fn read_once(reader: &mut impl Read, mem: &GuestMem, iovs: &[(u32, u32)]) -> WasiResult<Vec<u8>> {
+    let mut total = 0;
+
+    for (buf, len) in iovs {
+        mem.check(*buf, *len)?;
+        total += *len as u64;
+    }
+
+    let mut data = vec![0; total.min(mem.byte_len()) as usize];
+    let n = reader.read(&mut data)?;
+
+    data.truncate(n);
+
+    Ok(data)
+}
+
+fn write_filestat(mem: &mut GuestMem, buf: u32, meta: &fs::Metadata) -> WasiResult {
+    let (dev, ino, nlink) = inode(meta);
+    let time = |time: std::io::Result<SystemTime>| time.map(to_nanos).unwrap_or(0);
+
+    let field = |offset| memory::offset(buf, offset);
+
+    mem.write_u64(buf, dev)?;
+    mem.write_u64(field(8)?, ino)?;
+    mem.write_bytes(field(16)?, &[0; 8])?;
+    mem.write_u8(field(16)?, to_filetype(&meta.file_type()))?;
+    mem.write_u64(field(24)?, nlink)?;
+    mem.write_u64(field(32)?, meta.len())?;
+    mem.write_u64(field(40)?, time(meta.accessed()))?;
+    mem.write_u64(field(48)?, time(meta.modified()))?;
+    mem.write_u64(field(56)?, time(meta.created()))
+}
+
+#[cfg(unix)]
+fn inode(meta: &fs::Metadata) -> (u64, u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+
+    (meta.dev(), meta.ino(), meta.nlink())
+}
+
+#[cfg(not(unix))]
+fn inode(_: &fs::Metadata) -> (u64, u64, u64) {
+    (0, 0, 1)
+}
+
+fn to_filetype(file_type: &fs::FileType) -> u8 {
+    if file_type.is_dir() {
+        filetype::DIRECTORY
+    } else if file_type.is_file() {
+        filetype::REGULAR_FILE
+    } else if file_type.is_symlink() {
+        filetype::SYMBOLIC_LINK
+    } else {
+        filetype::UNKNOWN
+    }
+}
+
+fn to_nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// 同时给出时间和 NOW 是无效的
+fn file_times(atim: u64, mtim: u64, flags: u32) -> WasiResult<FileTimes> {
+    let pick = |time: u64, set: u32, now: u32| match (flags & set != 0, flags & now != 0) {
+        (true, true) => Err(Errno::Inval),
+        (true, false) => Ok(Some(UNIX_EPOCH + Duration::from_nanos(time))),
+        (false, true) => Ok(Some(SystemTime::now())),
+        (false, false) => Ok(None),
+    };
+    let mut times = FileTimes::new();
+
+    if let Some(atim) = pick(atim, fstflags::ATIM, fstflags::ATIM_NOW)? {
+        times = times.set_accessed(atim);
+    }
+
+    if let Some(mtim) = pick(mtim, fstflags::MTIM, fstflags::MTIM_NOW)? {
+        times = times.set_modified(mtim);
+    }
+
+    Ok(times)
+}
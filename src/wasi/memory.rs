@@ -0,0 +1,106 @@
+use super::types::{Errno, WasiResult};
+use crate::execution::inst::memory::{MemInst, Memory, PAGE_SIZE};
+
+/// 调用方的内存，越界访问返回 Fault
+pub struct GuestMem<'a>(pub &'a mut MemInst);
+
+/// 指针加上偏移，越过 u32 时返回 Fault，而不是回绕到内存的开头
+pub fn offset(ptr: u32, offset: u32) -> WasiResult<u32> {
+    ptr.checked_add(offset).ok_or(Errno::Fault)
+}
+
+/// 从 base 开始、每项 size 字节的数组中第 i 项的位置
+pub fn element(base: u32, i: usize, size: u32) -> WasiResult<u32> {
+    let i = u32::try_from(i).map_err(|_| Errno::Fault)?;
+
+    offset(base, i.checked_mul(size).ok_or(Errno::Fault)?)
+}
+
+impl GuestMem<'_> {
+    /// 内存的字节数
+    pub fn byte_len(&self) -> u64 {
+        self.0.mem_size() * PAGE_SIZE as u64
+    }
+
+    /// ptr 开始的 len 个字节都在内存中
+    pub fn check(&self, ptr: u32, len: u32) -> WasiResult {
+        match ptr as u64 + len as u64 <= self.byte_len() {
+            true => Ok(()),
+            false => Err(Errno::Fault),
+        }
+    }
+
+    pub fn read_bytes(&self, ptr: u32, len: u32) -> WasiResult<Vec<u8>> {
+        self.0.mem_reads(ptr as u64, len as u64).map_err(|_| Errno::Fault)
+    }
+
+    pub fn write_bytes(&mut self, ptr: u32, bytes: &[u8]) -> WasiResult {
+        self.0.mem_writes(ptr as u64, bytes).map_err(|_| Errno::Fault)
+    }
+
+    pub fn read_u32(&self, ptr: u32) -> WasiResult<u32> {
+        let bytes = self.read_bytes(ptr, 4)?;
+
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&self, ptr: u32) -> WasiResult<u64> {
+        let bytes = self.read_bytes(ptr, 8)?;
+
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub fn write_u8(&mut self, ptr: u32, v: u8) -> WasiResult {
+        self.write_bytes(ptr, &[v])
+    }
+
+    pub fn write_u16(&mut self, ptr: u32, v: u16) -> WasiResult {
+        self.write_bytes(ptr, &v.to_le_bytes())
+    }
+
+    pub fn write_u32(&mut self, ptr: u32, v: u32) -> WasiResult {
+        self.write_bytes(ptr, &v.to_le_bytes())
+    }
+
+    pub fn write_u64(&mut self, ptr: u32, v: u64) -> WasiResult {
+        self.write_bytes(ptr, &v.to_le_bytes())
+    }
+
+    pub fn read_str(&self, ptr: u32, len: u32) -> WasiResult<String> {
+        String::from_utf8(self.read_bytes(ptr, len)?).map_err(|_| Errno::Inval)
+    }
+
+    /// iovec 和 ciovec 的布局相同：buf u32，buf_len u32
+    pub fn read_iovs(&self, ptr: u32, len: u32) -> WasiResult<Vec<(u32, u32)>> {
+        (0..len as usize)
+            .map(|i| {
+                let iov = element(ptr, i, 8)?;
+
+                Ok((self.read_u32(iov)?, self.read_u32(offset(iov, 4)?)?))
+            })
+            .collect()
+    }
+
+    /// 把 iovs 指向的数据拼在一起
+    pub fn gather(&self, iovs: &[(u32, u32)]) -> WasiResult<Vec<u8>> {
+        let mut data = vec![];
+
+        for (buf, len) in iovs {
+            data.extend(self.read_bytes(*buf, *len)?);
+        }
+
+        Ok(data)
+    }
+
+    /// 把 data 依次写进 iovs 指向的缓冲区，data 不会超过缓冲区的总长度
+    pub fn scatter(&mut self, iovs: &[(u32, u32)], mut data: &[u8]) -> WasiResult {
+        for (buf, len) in iovs {
+            let n = data.len().min(*len as usize);
+
+            self.write_bytes(*buf, &data[..n])?;
+            data = &data[n..];
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,392 @@
+mod ctx;
+mod errors;
+mod funcs;
+mod memory;
+mod types;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub use ctx::WasiCtx;
+pub use errors::{exit_code, WasiError};
+pub use types::Errno;
+
+use self::memory::GuestMem;
+use self::types::WasiResult;
+use crate::execution::errors::{Trap, VMState};
+use crate::execution::host::{Caller, IntoFunc};
+use crate::execution::importer::Importer;
+use crate::execution::inst::function::{FuncInst, FuncInstKind};
+use crate::execution::inst::{ExportInst, RFuncInst};
+use crate::execution::value::ValInsts;
+
+/// WASI 导入项所在的模块名
+pub const MODULE: &str = "wasi_snapshot_preview1";
+
+/// WASI preview1 的宿主实现，以导入方的形式提供给 Linker
+/// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md
+///
+/// 函数通过调用方导出的 memory 读写参数，proc_exit 以 WasiError::Exit 结束调用
+pub struct Wasi {
+    ctx: Rc<RefCell<WasiCtx>>,
+    funcs: HashMap<&'static str, RFuncInst>,
+}
+
+/// 注册系统调用，参数按 wasm 的类型声明，传给实现时转成对应的无符号类型
+macro_rules! wasi_funcs {
+    ($wasi:ident, $($name:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            let ctx = Rc::clone(&$wasi.ctx);
+
+            $wasi.func(stringify!($name), move |caller: Caller, $($arg: $ty),*| {
+                syscall(&ctx, caller, |ctx, mem| ctx.$name(mem, $($arg as _),*))
+            });
+        )*
+    };
+}
+
+/// 没有实现的系统调用，总是返回 Nosys
+macro_rules! nosys_funcs {
+    ($wasi:ident, $($name:ident($($ty:ty),*);)*) => {
+        $(
+            $wasi.func(stringify!($name), |$(_: $ty),*| Errno::Nosys as i32);
+        )*
+    };
+}
+
+impl Wasi {
+    pub fn new(ctx: WasiCtx) -> Self {
+        let mut wasi = Self {
+            ctx: Rc::new(RefCell::new(ctx)),
+            funcs: HashMap::new(),
+        };
+
+        wasi_funcs!(wasi,
+            args_get(argv: i32, argv_buf: i32);
+            args_sizes_get(argc: i32, buf_size: i32);
+            environ_get(environ: i32, environ_buf: i32);
+            environ_sizes_get(count: i32, buf_size: i32);
+            clock_res_get(id: i32, resolution: i32);
+            clock_time_get(id: i32, precision: i64, time: i32);
+            fd_advise(fd: i32, offset: i64, len: i64, advice: i32);
+            fd_allocate(fd: i32, offset: i64, len: i64);
+            fd_close(fd: i32);
+            fd_datasync(fd: i32);
+            fd_fdstat_get(fd: i32, buf: i32);
+            fd_fdstat_set_rights(fd: i32, base: i64, inheriting: i64);
+            fd_filestat_get(fd: i32, buf: i32);
+            fd_filestat_set_size(fd: i32, size: i64);
+            fd_filestat_set_times(fd: i32, atim: i64, mtim: i64, flags: i32);
+            fd_pread(fd: i32, iovs: i32, iovs_len: i32, offset: i64, nread: i32);
+            fd_prestat_get(fd: i32, buf: i32);
+            fd_prestat_dir_name(fd: i32, path: i32, len: i32);
+            fd_pwrite(fd: i32, iovs: i32, iovs_len: i32, offset: i64, nwritten: i32);
+            fd_read(fd: i32, iovs: i32, iovs_len: i32, nread: i32);
+            fd_readdir(fd: i32, buf: i32, buf_len: i32, cookie: i64, bufused: i32);
+            fd_renumber(fd: i32, to: i32);
+            fd_seek(fd: i32, offset: i64, whence: i32, newoffset: i32);
+            fd_sync(fd: i32);
+            fd_tell(fd: i32, offset: i32);
+            fd_write(fd: i32, iovs: i32, iovs_len: i32, nwritten: i32);
+            path_create_directory(fd: i32, path: i32, len: i32);
+            path_filestat_get(fd: i32, flags: i32, path: i32, len: i32, buf: i32);
+            path_filestat_set_times(
+                fd: i32, flags: i32, path: i32, len: i32, atim: i64, mtim: i64, fst_flags: i32
+            );
+            path_link(
+                old_fd: i32, flags: i32, old_path: i32, old_len: i32,
+                new_fd: i32, new_path: i32, new_len: i32
+            );
+            path_open(
+                fd: i32, dirflags: i32, path: i32, len: i32, oflags: i32,
+                base: i64, inheriting: i64, fdflags: i32, opened: i32
+            );
+            path_readlink(fd: i32, path: i32, len: i32, buf: i32, buf_len: i32, bufused: i32);
+            path_remove_directory(fd: i32, path: i32, len: i32);
+            path_rename(fd: i32, old_path: i32, old_len: i32, new_fd: i32, new_path: i32, new_len: i32);
+            path_unlink_file(fd: i32, path: i32, len: i32);
+            poll_oneoff(in_: i32, out: i32, nsubscriptions: i32, nevents: i32);
+            sched_yield();
+            random_get(buf: i32, len: i32);
+        );
+
+        // 符号链接可能指向预打开目录之外，不支持创建
+        nosys_funcs!(wasi,
+            fd_fdstat_set_flags(i32, i32);
+            path_symlink(i32, i32, i32, i32, i32);
+            proc_raise(i32);
+            sock_accept(i32, i32, i32);
+            sock_recv(i32, i32, i32, i32, i32, i32);
+            sock_send(i32, i32, i32, i32, i32);
+            sock_shutdown(i32, i32);
+        );
+
+        wasi.func("proc_exit", |code: i32| -> VMState<()> {
+            Err(WasiError::Exit(code))?
+        });
+
+        wasi
+    }
+
+    fn func<Params, Results>(&mut self, name: &'static str, func: impl IntoFunc<Params, Results>) {
+        let (func_type, func) = func.into_func();
+        let func_inst = FuncInst::from_host(func_type, func, &format!("{}.{}", MODULE, name));
+
+        self.funcs.insert(name, Rc::new(RefCell::new(func_inst)));
+    }
+
+    /// 调用结束后可以从这里取出运行环境，比如换掉标准输出
+    pub fn ctx(&self) -> &Rc<RefCell<WasiCtx>> {
+        &self.ctx
+    }
+}
+
+/// 取出调用方的内存执行系统调用，错误码作为返回值
+fn syscall(
+    ctx: &Rc<RefCell<WasiCtx>>,
+    caller: Caller,
+    f: impl FnOnce(&mut WasiCtx, &mut GuestMem) -> WasiResult,
+) -> VMState<i32> {
+    let mem = match caller.get_export("memory") {
+        Some(ExportInst::Mem(mem)) => mem,
+        _ => Err(WasiError::NoMemory)?,
+    };
+    let mut mem = mem.borrow_mut();
+    let errno = match f(&mut ctx.borrow_mut(), &mut GuestMem(&mut mem)) {
+        Ok(_) => Errno::Success,
+        Err(errno) => errno,
+    };
+
+    Ok(errno as i32)
+}
+
+impl Importer for Wasi {
+    fn get_name(&self) -> &str {
+        MODULE
+    }
+
+    fn resolve_func(&self, name: &str) -> Option<RFuncInst> {
+        self.funcs.get(name).map(Rc::clone)
+    }
+
+    /// 不经过虚拟机直接调用时没有调用方，用到内存的函数都会失败
+    fn call_by_name(&mut self, name: &str, args: ValInsts) -> VMState<ValInsts> {
+        let func_inst = self.funcs.get(name).ok_or(Trap::FnNotFound)?.borrow();
+
+        match &func_inst.kind {
+            FuncInstKind::Host(_, func) => func(Caller::new(None), args),
+            _ => Err(Trap::FnNotFound)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::io::{self, Cursor, Write};
+    use std::rc::Rc;
+
+    use super::{exit_code, Errno, Wasi, WasiCtx, MODULE};
+    use crate::binary::module::Module;
+    use crate::execution::value::ValInst;
+    use crate::execution::vm::VM;
+
+    /// 和测试共享的输出缓冲区
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const WAT: &str = r#"
+        (module
+          (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+          (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+          (import "wasi_snapshot_preview1" "path_open"
+            (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+          (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+          (import "wasi_snapshot_preview1" "random_get" (func $random_get (param i32 i32) (result i32)))
+          (import "wasi_snapshot_preview1" "args_get" (func $args_get (param i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "\10\00\00\00\05\00\00\00")
+          (data (i32.const 16) "hello")
+          (func (export "write") (param $iovs i32) (param $len i32) (result i32)
+            (call $fd_write (i32.const 1) (local.get $iovs) (local.get $len) (i32.const 8)))
+          (func (export "read") (result i32)
+            (drop (call $fd_read (i32.const 0) (i32.const 0) (i32.const 1) (i32.const 8)))
+            (i32.load (i32.const 8)))
+          (func (export "readv") (param $iovs i32) (param $len i32) (result i32)
+            (call $fd_read (i32.const 0) (local.get $iovs) (local.get $len) (i32.const 8)))
+          (func (export "random") (param $buf i32) (param $len i32) (result i32)
+            (call $random_get (local.get $buf) (local.get $len)))
+          (func (export "args") (param $argv i32) (param $buf i32) (result i32)
+            (call $args_get (local.get $argv) (local.get $buf)))
+          (func (export "load") (param i32) (result i32) (i32.load8_u (local.get 0)))
+          (func (export "create") (param $path i32) (param $len i32) (result i32)
+            (call $path_open (i32.const 3) (i32.const 0) (local.get $path) (local.get $len)
+              (i32.const 1) (i64.const 64) (i64.const 0) (i32.const 0) (i32.const 12)))
+          (func (export "exit") (call $proc_exit (i32.const 3)))
+          (data (i32.const 32) "escape")
+          (data (i32.const 48) "inside")
+          ;; 第二个 iovec 越界
+          (data (i32.const 64) "\10\00\00\00\04\00\00\00\00\00\ff\ff\04\00\00\00"))
+    "#;
+
+    fn vm(ctx: WasiCtx) -> VM {
+        let mut importers: HashMap<_, Rc<RefCell<dyn crate::execution::importer::Importer>>> = HashMap::new();
+
+        importers.insert(MODULE.to_string(), Rc::new(RefCell::new(Wasi::new(ctx))));
+
+        VM::new("test", Module::from_text(WAT).unwrap(), Some(importers)).unwrap()
+    }
+
+    fn call(vm: &mut VM, name: &str, args: Vec<ValInst>) -> i32 {
+        let instance = vm.instance().unwrap();
+
+        vm.call_export(instance, name, args).unwrap()[0].as_i32()
+    }
+
+    #[test]
+    fn test_fd_write_read() {
+        let out = Output::default();
+        let mut ctx = WasiCtx::new();
+
+        ctx.stdout(Box::new(out.clone()));
+        ctx.stdin(Box::new(Cursor::new(b"wasi".to_vec())));
+
+        let mut vm = vm(ctx);
+
+        assert_eq!(call(&mut vm, "write", vec![ValInst::I32(0), ValInst::I32(1)]), 0);
+        assert_eq!(out.0.borrow().as_slice(), b"hello");
+        assert_eq!(call(&mut vm, "load", vec![ValInst::I32(8)]), 5);
+
+        // 读到的数据写进 iovec 指向的 16 开始的缓冲区
+        assert_eq!(call(&mut vm, "read", vec![]), 4);
+        assert_eq!(call(&mut vm, "load", vec![ValInst::I32(16)]), b'w' as i32);
+
+        // iovec 的地址溢出时返回 Fault 而不是回绕
+        let args = vec![ValInst::I32(0xffff_fff8u32 as i32), ValInst::I32(2)];
+
+        assert_eq!(call(&mut vm, "write", args), Errno::Fault as i32);
+    }
+
+    #[test]
+    fn test_proc_exit() {
+        let mut vm = vm(WasiCtx::new());
+        let instance = vm.instance().unwrap();
+        let err = vm.call_export(instance, "exit", vec![]).unwrap_err();
+
+        assert_eq!(exit_code(err.as_ref()), Some(3));
+    }
+
+    #[test]
+    fn test_guest_pointers() {
+        let mut ctx = WasiCtx::new();
+
+        ctx.stdin(Box::new(Cursor::new(b"wasi".to_vec())));
+        ctx.args(&["a".to_string(), "bc".to_string()]);
+
+        let mut vm = vm(ctx);
+        let fault = Errno::Fault as i32;
+
+        // 长度来自调用方，越界时先返回 Fault，而不是按长度分配内存
+        assert_eq!(call(&mut vm, "random", vec![ValInst::I32(0), ValInst::I32(-1)]), fault);
+        assert_eq!(call(&mut vm, "random", vec![ValInst::I32(65535), ValInst::I32(2)]), fault);
+        assert_eq!(call(&mut vm, "random", vec![ValInst::I32(128), ValInst::I32(5000)]), 0);
+
+        // 有一个 iovec 越界时不读取输入，之后还能读到全部数据
+        assert_eq!(call(&mut vm, "readv", vec![ValInst::I32(64), ValInst::I32(2)]), fault);
+        assert_eq!(call(&mut vm, "readv", vec![ValInst::I32(64), ValInst::I32(1)]), 0);
+        assert_eq!(call(&mut vm, "load", vec![ValInst::I32(8)]), 4);
+        assert_eq!(call(&mut vm, "load", vec![ValInst::I32(16)]), b'w' as i32);
+
+        // 指针加上偏移越过 u32 时返回 Fault，不会回绕到内存开头
+        let args = vec![ValInst::I32(0), ValInst::I32(-2)];
+
+        assert_eq!(call(&mut vm, "args", args), fault);
+        assert_eq!(call(&mut vm, "args", vec![ValInst::I32(256), ValInst::I32(512)]), 0);
+        assert_eq!(call(&mut vm, "load", vec![ValInst::I32(514)]), b'b' as i32);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_escape() {
+        use std::fs;
+        use std::os::unix::fs::symlink;
+
+        let dir = std::env::temp_dir().join(format!("wasi-sandbox-{}", std::process::id()));
+        let root = dir.join("root");
+
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(dir.join("outside")).unwrap();
+        symlink("../outside/created", root.join("escape")).unwrap();
+        symlink("created", root.join("inside")).unwrap();
+
+        let mut ctx = WasiCtx::new();
+
+        ctx.preopen_dir(&root, "/").unwrap();
+
+        let mut vm = vm(ctx);
+
+        // 悬空的链接指向预打开目录之外，O_CREAT 不能通过它创建文件
+        let escape = call(&mut vm, "create", vec![ValInst::I32(32), ValInst::I32(6)]);
+        let inside = call(&mut vm, "create", vec![ValInst::I32(48), ValInst::I32(6)]);
+        let escaped = dir.join("outside/created").exists();
+        let created = root.join("created").exists();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(escape, Errno::Notcapable as i32);
+        assert!(!escaped);
+        assert_eq!(inside, 0);
+        assert!(created);
+    }
+
+    /// 中间目录被换成符号链接后，逐级打开会失败，不会在预打开目录之外创建文件
+    #[cfg(unix)]
+    #[test]
+    fn test_open_beneath() {
+        use std::fs;
+        use std::os::unix::fs::symlink;
+
+        use super::ctx::open_beneath;
+
+        let dir = std::env::temp_dir().join(format!("wasi-beneath-{}", std::process::id()));
+        let root = dir.join("root");
+
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir_all(dir.join("outside")).unwrap();
+
+        let flags = libc::O_WRONLY | libc::O_CREAT;
+        let created = open_beneath(&root, &root.join("sub/a"), flags).is_ok();
+
+        fs::remove_dir_all(root.join("sub")).unwrap();
+        symlink("../outside", root.join("sub")).unwrap();
+
+        let swapped = open_beneath(&root, &root.join("sub/b"), flags).unwrap_err();
+        let escaped = dir.join("outside/b").exists();
+
+        // 最后一级是符号链接时同样不跟随
+        symlink("../outside/c", root.join("c")).unwrap();
+
+        let last = open_beneath(&root, &root.join("c"), flags).unwrap_err();
+        let escaped_last = dir.join("outside/c").exists();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(created);
+        assert!(matches!(swapped, Errno::Loop | Errno::Notdir));
+        assert!(!escaped);
+        assert_eq!(last, Errno::Loop);
+        assert!(!escaped_last);
+    }
+}
@@ -0,0 +1,107 @@
+use std::io;
+
+/// 错误码，只列出用到的
+/// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-errno-variant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum Errno {
+    Success = 0,
+    TooBig = 1,
+    Acces = 2,
+    Badf = 8,
+    Exist = 20,
+    Fault = 21,
+    Inval = 28,
+    Io = 29,
+    Isdir = 31,
+    Loop = 32,
+    Noent = 44,
+    Nosys = 52,
+    Notdir = 54,
+    Notempty = 55,
+    Overflow = 61,
+    Perm = 63,
+    Spipe = 70,
+    Notcapable = 76,
+}
+
+impl From<io::Error> for Errno {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::Noent,
+            io::ErrorKind::PermissionDenied => Self::Acces,
+            io::ErrorKind::AlreadyExists => Self::Exist,
+            io::ErrorKind::InvalidInput => Self::Inval,
+            io::ErrorKind::NotADirectory => Self::Notdir,
+            io::ErrorKind::IsADirectory => Self::Isdir,
+            io::ErrorKind::DirectoryNotEmpty => Self::Notempty,
+            _ => Self::Io,
+        }
+    }
+}
+
+pub type WasiResult<T = ()> = Result<T, Errno>;
+
+/// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-filetype-variant
+pub mod filetype {
+    pub const UNKNOWN: u8 = 0;
+    pub const CHARACTER_DEVICE: u8 = 2;
+    pub const DIRECTORY: u8 = 3;
+    pub const REGULAR_FILE: u8 = 4;
+    pub const SYMBOLIC_LINK: u8 = 7;
+}
+
+pub mod clockid {
+    pub const REALTIME: u32 = 0;
+    pub const MONOTONIC: u32 = 1;
+    pub const PROCESS_CPUTIME_ID: u32 = 2;
+    pub const THREAD_CPUTIME_ID: u32 = 3;
+}
+
+pub mod whence {
+    pub const SET: u32 = 0;
+    pub const CUR: u32 = 1;
+    pub const END: u32 = 2;
+}
+
+pub mod oflags {
+    pub const CREAT: u32 = 1 << 0;
+    pub const DIRECTORY: u32 = 1 << 1;
+    pub const EXCL: u32 = 1 << 2;
+    pub const TRUNC: u32 = 1 << 3;
+}
+
+pub mod fdflags {
+    pub const APPEND: u32 = 1 << 0;
+}
+
+pub mod lookupflags {
+    pub const SYMLINK_FOLLOW: u32 = 1 << 0;
+}
+
+pub mod fstflags {
+    pub const ATIM: u32 = 1 << 0;
+    pub const ATIM_NOW: u32 = 1 << 1;
+    pub const MTIM: u32 = 1 << 2;
+    pub const MTIM_NOW: u32 = 1 << 3;
+}
+
+pub mod rights {
+    pub const FD_READ: u64 = 1 << 1;
+    pub const FD_WRITE: u64 = 1 << 6;
+    /// 不做权限检查，所有描述符都拥有全部权限
+    pub const ALL: u64 = (1 << 30) - 1;
+}
+
+pub mod eventtype {
+    pub const CLOCK: u8 = 0;
+    pub const FD_READ: u8 = 1;
+    pub const FD_WRITE: u8 = 2;
+}
+
+/// 结构体在内存中的大小
+pub const FDSTAT_SIZE: u32 = 24;
+pub const FILESTAT_SIZE: u32 = 64;
+pub const DIRENT_SIZE: u32 = 24;
+pub const SUBSCRIPTION_SIZE: u32 = 48;
+pub const EVENT_SIZE: u32 = 32;
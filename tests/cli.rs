@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process::{Command, Output};
+
+    /// 把模块写进临时目录，返回文件路径
+    fn fixture(name: &str, data: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wasm-cli-{}", std::process::id()));
+
+        fs::create_dir_all(&dir).unwrap();
+
+        let file = dir.join(name);
+
+        fs::write(&file, data).unwrap();
+
+        file
+    }
+
+    fn wasm(args: &[&str], file: &PathBuf) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_wasm"))
+            .args(args)
+            .arg(file)
+            .output()
+            .unwrap()
+    }
+
+    fn exit_code(args: &[&str], file: &PathBuf) -> i32 {
+        wasm(args, file).status.code().unwrap()
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let ok = fixture(
+            "ok.wat",
+            b"(module (func (export \"f\") (result i32) (i32.const 7)))",
+        );
+        let malformed = fixture("malformed.wasm", b"\0asm\x02\0\0\0");
+        let invalid = fixture("invalid.wat", b"(module (func (result i32) (i64.const 0)))");
+        let unlinkable = fixture("unlinkable.wat", b"(module (import \"env\" \"f\" (func)))");
+        let trap = fixture("trap.wat", b"(module (func (export \"f\") (unreachable)))");
+        let exit = fixture(
+            "exit.wat",
+            br#"(module
+                  (import "wasi_snapshot_preview1" "proc_exit" (func $exit (param i32)))
+                  (memory (export "memory") 1)
+                  (func (export "_start") (call $exit (i32.const 42))))"#,
+        );
+
+        let output = wasm(&["run", "--invoke", "f"], &ok);
+
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "7: i32\n");
+
+        assert_eq!(exit_code(&["run", "--unknown"], &ok), 1);
+        assert_eq!(exit_code(&["run"], &malformed), 2);
+        assert_eq!(exit_code(&["run", "--validate-only"], &invalid), 3);
+        assert_eq!(exit_code(&["run"], &unlinkable), 4);
+        assert_eq!(exit_code(&["run", "--invoke", "f"], &trap), 5);
+        assert_eq!(exit_code(&["run"], &exit), 42);
+    }
+
+    #[test]
+    fn test_inspect() {
+        let file = fixture(
+            "inspect.wat",
+            br#"(module
+                  (memory 1)
+                  (func (export "f") (param i32) (result i32) (local.get 0))
+                  (data (i32.const 0) "hi"))"#,
+        );
+        let output = wasm(&["inspect"], &file);
+        let expected = "\
+sections:
+  000008 type       offset=00000a size=6
+  000010 function   offset=000012 size=2
+  000014 memory     offset=000016 size=3
+  000019 export     offset=00001b size=5
+  000020 code       offset=000022 size=6
+  000028 data       offset=00002a size=8
+
+type[1]:
+  - type[0] (func (param i32) (result i32))
+
+function[1]:
+  - func[0] (type 0) (param i32) (result i32)
+
+memory[1]:
+  - memory[0] 1
+
+export[1]:
+  - func[0] -> \"f\"
+
+data[1]:
+  - data[0] active memory=0 offset=(i32.const 0) size=2
+    \"hi\"
+
+code[1]:
+  - func[0] offset=000024 size=4 locals=[]
+    000025: local.get 0
+    000027: end
+";
+
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    }
+}
@@ -0,0 +1,46 @@
+#[cfg(test)]
+mod test {
+    use paste::paste;
+    use wasm::execution::config::{Config, Engine};
+    use wasm::spec::runner::Runner;
+
+    /// 提案的测试脚本随仓库提交，不依赖 wast2json，两种解释器各跑一遍
+    macro_rules! load {
+        ($name:ident) => {
+            paste! {
+                #[test]
+                fn [<test_ $name>]() {
+                    run_test(stringify!($name), Engine::Stack);
+                }
+
+                #[test]
+                fn [<test_ $name _register>]() {
+                    run_test(stringify!($name), Engine::Register);
+                }
+            }
+        };
+    }
+
+    fn run_test(name: &str, engine: Engine) {
+        let config = Config {
+            engine,
+            ..Config::default()
+        };
+        let results =
+            Runner::run_wast_file_with_config(format!("./tests/proposals/{name}.wast"), config)
+                .expect("测试文件读取失败");
+        let failed = results
+            .iter()
+            .filter(|result| result.is_fail())
+            .collect::<Vec<_>>();
+
+        assert!(!results.is_empty());
+        assert!(failed.is_empty(), "{:#?}", failed);
+    }
+
+    load!(tail_call);
+    load!(multi_memory);
+    load!(memory64);
+    load!(exceptions);
+    load!(threads);
+}
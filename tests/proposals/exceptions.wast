@@ -0,0 +1,60 @@
+;; 异常处理：throw、throw_ref 和 try_table 的四种 catch，标签可以导出给其他模块，未捕获的异常交给嵌入方
+(module $M
+  (tag $e (export "e") (param i32))
+  (tag $e0)
+  (func $thr (param i32) (throw $e (local.get 0)))
+  (func (export "throw") (param i32) (throw $e (local.get 0)))
+  (func (export "catch") (param i32) (result i32)
+    (block $h (result i32)
+      (try_table (catch $e $h) (call $thr (local.get 0)))
+      (i32.const -1)))
+  (func (export "catch_ref") (result i32)
+    (block $h (result i32 exnref)
+      (try_table (catch_ref $e $h) (throw $e (i32.const 5)))
+      (unreachable))
+    (drop))
+  (func (export "catch_all") (result i32)
+    (block $h
+      (try_table (catch $e 1) (catch_all $h) (throw $e0))
+      (return (i32.const 0)))
+    (i32.const 1))
+  (func (export "rethrow") (result i32)
+    (block $h (result i32)
+      (try_table (catch $e $h)
+        (block $h2 (result exnref)
+          (try_table (catch_all_ref $h2) (throw $e (i32.const 7)))
+          (unreachable))
+        (throw_ref))
+      (i32.const 0)))
+  (func (export "no-throw") (result i32)
+    (block $h (result i32)
+      (try_table (result i32) (catch $e $h) (i32.const 3))))
+  (func (export "null") (throw_ref (ref.null exn)))
+  (func (export "uncaught") (throw $e0))
+)
+(register "M" $M)
+(assert_return (invoke "catch" (i32.const 3)) (i32.const 3))
+(assert_return (invoke "catch_ref") (i32.const 5))
+(assert_return (invoke "catch_all") (i32.const 1))
+(assert_return (invoke "rethrow") (i32.const 7))
+(assert_return (invoke "no-throw") (i32.const 3))
+(assert_trap (invoke "null") "null exception reference")
+(assert_exception (invoke "uncaught"))
+(assert_exception (invoke "throw" (i32.const 1)))
+
+;; 导入的标签和导出它的模块是同一个，可以捕获对方抛出的异常
+(module
+  (import "M" "e" (tag $e (param i32)))
+  (import "M" "throw" (func $throw (param i32)))
+  (func (export "catch-import") (result i32)
+    (block $h (result i32)
+      (try_table (catch $e $h) (call $throw (i32.const 9)))
+      (i32.const -1))))
+(assert_return (invoke "catch-import") (i32.const 9))
+
+(assert_unlinkable (module (import "M" "e" (tag (param i64)))) "incompatible import type")
+(assert_invalid (module (tag $e (param i32)) (func (throw $e))) "type mismatch")
+(assert_invalid (module (func (throw 0))) "unknown tag")
+(assert_invalid
+  (module (tag $e (param i32)) (func (block $h (try_table (catch $e $h) (nop)))))
+  "type mismatch")
@@ -0,0 +1,66 @@
+;; memory64 和 table64：i64 地址的内存和表，64 位的偏移、size/grow，以及导入时地址类型必须一致
+(module
+  (memory $m i64 1 3)
+  (memory $n 1)
+  (data (memory $m) (i64.const 16) "abcd")
+  (func (export "load") (param i64) (result i32) (i32.load8_u (local.get 0)))
+  (func (export "store") (param i64 i32) (i32.store (local.get 0) (local.get 1)))
+  (func (export "size") (result i64) (memory.size))
+  (func (export "grow") (param i64) (result i64) (memory.grow (local.get 0)))
+  (func (export "fill") (param i64 i32 i64) (memory.fill (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "copy64to32") (param i32 i64 i32) (memory.copy $n $m (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "load32") (param i32) (result i32) (i32.load8_u $n (local.get 0)))
+  (func (export "bigoff") (result i32) (i32.load offset=0x100000000 (i64.const 0)))
+)
+(assert_return (invoke "load" (i64.const 16)) (i32.const 97))
+(assert_return (invoke "size") (i64.const 1))
+(assert_return (invoke "grow" (i64.const 1)) (i64.const 1))
+(assert_return (invoke "grow" (i64.const 5)) (i64.const -1))
+(assert_return (invoke "grow" (i64.const 0xffffffffffff)) (i64.const -1))
+(assert_return (invoke "size") (i64.const 2))
+(invoke "store" (i64.const 70000) (i32.const 0x42))
+(assert_return (invoke "load" (i64.const 70000)) (i32.const 0x42))
+(assert_trap (invoke "load" (i64.const 0x100000010)) "out of bounds memory access")
+(assert_trap (invoke "load" (i64.const -1)) "out of bounds memory access")
+(assert_trap (invoke "bigoff") "out of bounds memory access")
+(invoke "fill" (i64.const 100) (i32.const 7) (i64.const 3))
+(assert_return (invoke "load" (i64.const 102)) (i32.const 7))
+(assert_trap (invoke "fill" (i64.const 100) (i32.const 7) (i64.const -1)) "out of bounds memory access")
+(invoke "copy64to32" (i32.const 5) (i64.const 16) (i32.const 4))
+(assert_return (invoke "load32" (i32.const 8)) (i32.const 100))
+(module (memory (data "xy")) (func (export "a") (result i32) (i32.load8_u (i32.const 1))))
+(assert_return (invoke "a") (i32.const 0x79))
+(module (memory i64 (data "xy")) (func (export "a") (result i32) (i32.load8_u (i64.const 1))))
+(assert_return (invoke "a") (i32.const 0x79))
+(assert_invalid (module (memory i64 1) (func (drop (i32.load (i32.const 0))))) "type mismatch")
+(assert_invalid (module (memory 1) (func (drop (i32.load offset=0x100000000 (i32.const 0))))) "offset out of range")
+(assert_invalid (module (memory 1) (data (i64.const 0) "")) "type mismatch")
+
+(module
+  (type $t (func (result i32)))
+  (table $t64 i64 2 funcref)
+  (elem (table $t64) (i64.const 1) func $f)
+  (func $f (result i32) (i32.const 42))
+  (func (export "call") (param i64) (result i32) (call_indirect $t64 (type $t) (local.get 0)))
+  (func (export "size") (result i64) (table.size $t64))
+  (func (export "grow") (param i64) (result i64) (table.grow $t64 (ref.null func) (local.get 0)))
+  (func (export "fill") (param i64 i64) (table.fill $t64 (local.get 0) (ref.func $f) (local.get 1)))
+  (func (export "is-null") (param i64) (result i32) (ref.is_null (table.get $t64 (local.get 0))))
+)
+(assert_return (invoke "call" (i64.const 1)) (i32.const 42))
+(assert_trap (invoke "call" (i64.const 0)) "uninitialized element")
+(assert_trap (invoke "call" (i64.const 0x100000001)) "undefined element")
+(assert_return (invoke "size") (i64.const 2))
+(assert_return (invoke "grow" (i64.const 2)) (i64.const 2))
+(assert_return (invoke "grow" (i64.const 0xffffffffffff)) (i64.const -1))
+(assert_return (invoke "is-null" (i64.const 3)) (i32.const 1))
+(invoke "fill" (i64.const 2) (i64.const 2))
+(assert_return (invoke "call" (i64.const 3)) (i32.const 42))
+(assert_trap (invoke "fill" (i64.const 3) (i64.const 2)) "out of bounds table access")
+(assert_invalid (module (table i64 1 funcref) (func (drop (table.get 0 (i32.const 0))))) "type mismatch")
+
+(module $A (memory (export "m") i64 1) (table (export "t") i64 1 funcref))
+(register "a" $A)
+(module (import "a" "m" (memory i64 1)) (import "a" "t" (table i64 1 funcref)))
+(assert_unlinkable (module (import "a" "m" (memory 1))) "incompatible import type")
+(assert_unlinkable (module (import "a" "t" (table 1 funcref))) "incompatible import type")
@@ -0,0 +1,59 @@
+;; 多内存：导入和定义的内存共存，访存、memory.* 和 SIMD 指令按索引选择内存
+(module $M
+  (memory (export "mem") 1)
+  (data (i32.const 0) "\01\02\03\04"))
+(register "M" $M)
+(module
+  (import "M" "mem" (memory $m0 1))
+  (memory $m1 1 2)
+  (memory $m2 0)
+  (data (memory $m1) (i32.const 8) "\aa\bb\cc\dd")
+  (func (export "load0") (param i32) (result i32) (i32.load8_u $m0 (local.get 0)))
+  (func (export "load1") (param i32) (result i32) (i32.load8_u $m1 (local.get 0)))
+  (func (export "load1w") (param i32) (result i32) (i32.load 1 offset=4 (local.get 0)))
+  (func (export "store1") (param i32 i32) (i32.store8 $m1 offset=1 (local.get 0) (local.get 1)))
+  (func (export "size") (result i32 i32 i32) (memory.size $m0) (memory.size $m1) (memory.size 2))
+  (func (export "grow2") (param i32) (result i32) (memory.grow $m2 (local.get 0)))
+  (func (export "grow1") (param i32) (result i32) (memory.grow $m1 (local.get 0)))
+  (func (export "copy") (memory.copy $m1 $m0 (i32.const 100) (i32.const 0) (i32.const 4)))
+  (func (export "copy-back") (memory.copy $m0 $m1 (i32.const 200) (i32.const 8) (i32.const 4)))
+  (func (export "fill2") (memory.fill $m2 (i32.const 0) (i32.const 7) (i32.const 3)))
+  (func (export "load2") (param i32) (result i32) (i32.load8_u $m2 (local.get 0)))
+  (func (export "lane") (param i32) (result i32)
+    (i8x16.extract_lane_u 3 (v128.load8_lane $m1 offset=8 3 (local.get 0) (v128.const i64x2 0 0))))
+  (func (export "lane0") (param i32) (result i32)
+    (i8x16.extract_lane_u 1 (v128.load8_lane 1 (local.get 0) (v128.const i64x2 0 0))))
+  (func (export "v128") (result i32)
+    (v128.store $m2 (i32.const 16) (v128.load $m1 (i32.const 8)))
+    (i32.load $m2 (i32.const 16)))
+  (data $p "xyz")
+  (func (export "init") (memory.init $m2 $p (i32.const 1) (i32.const 0) (i32.const 3)))
+)
+(assert_return (invoke "load0" (i32.const 1)) (i32.const 2))
+(assert_return (invoke "load1" (i32.const 9)) (i32.const 0xbb))
+(assert_return (invoke "load1w" (i32.const 4)) (i32.const 0xddccbbaa))
+(assert_return (invoke "size") (i32.const 1) (i32.const 1) (i32.const 0))
+(assert_trap (invoke "load2" (i32.const 0)) "out of bounds memory access")
+(assert_return (invoke "grow2" (i32.const 1)) (i32.const 0))
+(assert_return (invoke "size") (i32.const 1) (i32.const 1) (i32.const 1))
+(assert_return (invoke "grow1" (i32.const 2)) (i32.const -1))
+(assert_return (invoke "grow1" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "store1" (i32.const 0) (i32.const 0x55)))
+(assert_return (invoke "load1" (i32.const 1)) (i32.const 0x55))
+(assert_return (invoke "load0" (i32.const 1)) (i32.const 2))
+(assert_return (invoke "copy"))
+(assert_return (invoke "load1" (i32.const 103)) (i32.const 4))
+(assert_return (invoke "copy-back"))
+(assert_return (invoke "load0" (i32.const 202)) (i32.const 0xcc))
+(assert_return (invoke "fill2"))
+(assert_return (invoke "load2" (i32.const 2)) (i32.const 7))
+(assert_return (invoke "load2" (i32.const 3)) (i32.const 0))
+(assert_return (invoke "lane" (i32.const 3)) (i32.const 0xdd))
+(assert_return (invoke "lane0" (i32.const 10)) (i32.const 0))
+(assert_return (invoke "v128") (i32.const 0xddccbbaa))
+(assert_return (invoke "init"))
+(assert_return (invoke "load2" (i32.const 1)) (i32.const 0x78))
+(assert_invalid (module (memory 1) (func (drop (i32.load 1 (i32.const 0))))) "unknown memory 1")
+(assert_invalid (module (memory 1) (func (drop (memory.size 1)))) "unknown memory 1")
+(assert_invalid (module (memory 1) (func (memory.copy 0 1 (i32.const 0) (i32.const 0) (i32.const 0)))) "unknown memory 1")
+(module (memory 0) (memory 0) (memory 0))
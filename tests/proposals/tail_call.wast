@@ -0,0 +1,40 @@
+;; return_call 和 return_call_indirect：深度递归不会耗尽调用栈，尾调用宿主函数和多返回值
+(module
+  (import "spectest" "print_i32" (func $print (param i32)))
+  (type $ii (func (param i64) (result i64)))
+  (table funcref (elem $fac-i $even $odd $host $pair))
+  (func $fac-acc (export "fac-acc") (param i64 i64) (result i64)
+    (if (result i64) (i64.eqz (local.get 0))
+      (then (local.get 1))
+      (else (return_call $fac-acc (i64.sub (local.get 0) (i64.const 1)) (i64.mul (local.get 0) (local.get 1))))))
+  (func $fac-i (param i64) (result i64) (return_call $fac-acc (local.get 0) (i64.const 1)))
+  (func $even (export "even") (param i64) (result i64)
+    (if (result i64) (i64.eqz (local.get 0)) (then (i64.const 44))
+      (else (return_call_indirect (type $ii) (i64.sub (local.get 0) (i64.const 1)) (i32.const 2)))))
+  (func $odd (export "odd") (param i64) (result i64)
+    (if (result i64) (i64.eqz (local.get 0)) (then (i64.const 99))
+      (else (return_call_indirect (type $ii) (i64.sub (local.get 0) (i64.const 1)) (i32.const 1)))))
+  (func $host (param i64) (result i64) (local.get 0))
+  (func (export "fac-ind") (param i64) (result i64) (return_call_indirect (type $ii) (local.get 0) (i32.const 0)))
+  (func (export "bad-type") (result i64) (return_call_indirect (type $ii) (i64.const 0) (i32.const 4)))
+  (func (export "oob") (result i64) (return_call_indirect (type $ii) (i64.const 0) (i32.const 9)))
+  (func (export "print") (param i32) (return_call $print (local.get 0)))
+  (func (export "mixed") (param i32) (result i32 i32) (local f64)
+    (i32.const 7) (local.get 0) (return_call $pair))
+  (func $pair (param i32) (result i32 i32) (local.get 0) (i32.const 3))
+)
+(assert_return (invoke "fac-acc" (i64.const 25) (i64.const 1)) (i64.const 7034535277573963776))
+(assert_return (invoke "fac-ind" (i64.const 5)) (i64.const 120))
+(assert_return (invoke "even" (i64.const 10001)) (i64.const 99))
+(assert_return (invoke "odd" (i64.const 10000)) (i64.const 99))
+(assert_return (invoke "even" (i64.const 10000)) (i64.const 44))
+(assert_trap (invoke "oob") "undefined element")
+(assert_trap (invoke "bad-type") "indirect call type mismatch")
+(assert_return (invoke "print" (i32.const 5)))
+(assert_return (invoke "mixed" (i32.const 1)) (i32.const 1) (i32.const 3))
+(assert_invalid (module (func $f (result i64) (i64.const 0)) (func (result i32) (return_call $f))) "type mismatch")
+(assert_invalid (module (func $f (param i32)) (func (return_call $f (i64.const 0)))) "type mismatch")
+(assert_invalid (module (func (return_call 1))) "unknown function")
+(assert_invalid (module (type $t (func)) (func (return_call_indirect (type $t) (i32.const 0)))) "unknown table")
+(assert_invalid (module (table 1 funcref) (type $t (func (result i32))) (func (return_call_indirect (type $t) (i32.const 0)))) "type mismatch")
+(module (func $f (result i32) (i32.const 1)) (func (result i32) (return_call $f) (i64.const 1) (drop) (i32.const 1)))
@@ -0,0 +1,49 @@
+;; 线程：共享内存上的原子读写、读改写和比较交换，wait/notify 的返回值，以及共享属性的校验和导入检查
+(module
+  (memory (export "mem") 1 1 shared)
+  (func (export "init") (param i64) (i64.store (i32.const 0) (local.get 0)))
+  (func (export "i32.atomic.load8_u") (param $addr i32) (result i32) (i32.atomic.load8_u (local.get $addr)))
+  (func (export "i64.atomic.load32_u") (param $addr i32) (result i64) (i64.atomic.load32_u (local.get $addr)))
+  (func (export "i32.atomic.load") (param $addr i32) (result i32) (i32.atomic.load (local.get $addr)))
+  (func (export "i64.atomic.rmw16.xor_u") (param $addr i32) (param $v i64) (result i64) (i64.atomic.rmw16.xor_u (local.get $addr) (local.get $v)))
+  (func (export "i64.atomic.rmw.xchg") (param $addr i32) (param $v i64) (result i64) (i64.atomic.rmw.xchg (local.get $addr) (local.get $v)))
+  (func (export "i32.atomic.rmw8.cmpxchg_u") (param $addr i32) (param $e i32) (param $v i32) (result i32) (i32.atomic.rmw8.cmpxchg_u (local.get $addr) (local.get $e) (local.get $v)))
+  (func (export "i64.atomic.rmw.cmpxchg") (param $addr i32) (param $e i64) (param $v i64) (result i64) (i64.atomic.rmw.cmpxchg (local.get $addr) (local.get $e) (local.get $v)))
+  (func (export "notify") (param i32 i32) (result i32) (memory.atomic.notify (local.get 0) (local.get 1)))
+  (func (export "wait32") (param i32 i32 i64) (result i32) (memory.atomic.wait32 (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "wait64") (param i32 i64 i64) (result i32) (memory.atomic.wait64 (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "fence") atomic.fence)
+)
+(invoke "init" (i64.const 0x1111111111111111))
+(assert_return (invoke "i32.atomic.load8_u" (i32.const 0)) (i32.const 0x11))
+(assert_return (invoke "i64.atomic.load32_u" (i32.const 4)) (i64.const 0x11111111))
+(assert_return (invoke "i64.atomic.rmw16.xor_u" (i32.const 0) (i64.const 0x1010)) (i64.const 0x1111))
+(assert_return (invoke "i64.atomic.rmw.xchg" (i32.const 0) (i64.const 0x42)) (i64.const 0x1111111111110101))
+(assert_return (invoke "i32.atomic.rmw8.cmpxchg_u" (i32.const 0) (i32.const 0x11111142) (i32.const 0x7)) (i32.const 0x42))
+(assert_return (invoke "i64.atomic.rmw.cmpxchg" (i32.const 0) (i64.const 7) (i64.const 9)) (i64.const 7))
+(assert_return (invoke "i32.atomic.load" (i32.const 0)) (i32.const 9))
+(assert_trap (invoke "i32.atomic.load" (i32.const 1)) "unaligned atomic")
+(assert_trap (invoke "i32.atomic.load" (i32.const 65536)) "out of bounds memory access")
+(assert_trap (invoke "notify" (i32.const 65536) (i32.const 0)) "out of bounds memory access")
+(assert_trap (invoke "notify" (i32.const 2) (i32.const 0)) "unaligned atomic")
+(assert_return (invoke "notify" (i32.const 0) (i32.const 10)) (i32.const 0))
+(assert_return (invoke "wait32" (i32.const 0) (i32.const 0) (i64.const 0)) (i32.const 1))
+(assert_return (invoke "wait64" (i32.const 0) (i64.const 9) (i64.const 10)) (i32.const 2))
+(assert_return (invoke "fence"))
+(register "shared")
+
+(module (memory (import "shared" "mem") 1 1 shared))
+(assert_unlinkable (module (memory (import "shared" "mem") 1 1)) "incompatible import type")
+
+(module
+  (memory 1)
+  (func (export "wait") (result i32) (memory.atomic.wait32 (i32.const 0) (i32.const 0) (i64.const 0)))
+  (func (export "notify") (result i32) (memory.atomic.notify (i32.const 0) (i32.const 1))))
+(assert_trap (invoke "wait") "expected shared memory")
+(assert_return (invoke "notify") (i32.const 0))
+
+(assert_invalid (module (memory 1 1 shared) (func (drop (i32.atomic.load align=2 (i32.const 0))))) "alignment")
+(assert_invalid (module (memory 1 1 shared) (func (drop (i64.atomic.rmw32.add_u align=8 (i32.const 0) (i64.const 0))))) "alignment")
+(assert_invalid (module (memory 1 shared)) "shared memory must have maximum")
+(assert_malformed (module binary "\00asm\01\00\00\00\04\04\01\70\03\01") "integer too large")
+(assert_malformed (module binary "\00asm\01\00\00\00\05\03\01\08\01") "integer too large")